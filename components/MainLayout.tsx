@@ -290,6 +290,7 @@ export default function MainLayout() {
             timestamp: msg.date * 1000,
             unread: !msg.seen,
             folder: folder,
+            sourceFolder: msg.folder,
             tags: [],
             starred: msg.flagged,
             body: msg.snippet || '<p>Message body not fetched in this milestone.</p>',
@@ -373,8 +374,9 @@ export default function MainLayout() {
         try {
             const lastEmail = currentEmails[currentEmails.length - 1];
             const beforeUid = lastEmail.uid;
+            const beforeFolder = lastEmail.sourceFolder ?? null;
 
-            const nextBatch: any[] = await invoke('get_folder_messages', { folder: currentFolder, beforeUid, limit: 50 });
+            const nextBatch: any[] = await invoke('get_folder_messages', { folder: currentFolder, beforeUid, beforeFolder, limit: 50 });
             if (nextBatch.length === 0) {
                 setHasMore(false);
             } else {
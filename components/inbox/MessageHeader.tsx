@@ -4,7 +4,7 @@ import React, { memo, useState } from 'react';
 import { motion, AnimatePresence } from 'framer-motion';
 import { File, Download, FileText, Image as ImageIcon, FileArchive, FileCode, Video, Music, Presentation, Table, FileSpreadsheet, FileAudio, FileVideo, FileType, Check, Copy } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
-import { save } from '@tauri-apps/plugin-dialog';
+import { save, ask } from '@tauri-apps/plugin-dialog';
 import { cn } from '@/lib/utils';
 import { Email, Attachment } from '@/lib/types';
 import { useDownloads } from '@/components/DownloadContext';
@@ -323,6 +323,14 @@ const getTypeColor = (mime: string | undefined): string => {
     return "primary";
 };
 
+interface AttachmentRisk {
+    level: 'safe' | 'caution' | 'dangerous';
+    reasons: string[];
+    sanitizedName: string;
+    detectedType: string | null;
+    requiresAcknowledgement: boolean;
+}
+
 export const AttachmentCard = memo(({ uid, folder, attachment }: { uid: number, folder: string, attachment: Attachment }) => {
     const [isDownloading, setIsDownloading] = React.useState(false);
     const { addDownload, updateDownloadStatus } = useDownloads();
@@ -330,9 +338,29 @@ export const AttachmentCard = memo(({ uid, folder, attachment }: { uid: number,
     const handleDownload = async () => {
         setIsDownloading(true);
         try {
+            const attachmentFolder = folder === "sent" ? "sent" : "INBOX";
+
+            // Check the attachment before offering to save it
+            const risk = await invoke<AttachmentRisk>('assess_attachment', {
+                folder: attachmentFolder,
+                uid,
+                partId: attachment.partId,
+            });
+
+            if (risk.requiresAcknowledgement) {
+                const proceed = await ask(
+                    `${risk.reasons.join('\n')}\n\nOnly save this file if you trust the sender.`,
+                    { title: risk.level === 'dangerous' ? "Dangerous attachment" : "Check this attachment", kind: 'warning' }
+                );
+                if (!proceed) {
+                    setIsDownloading(false);
+                    return;
+                }
+            }
+
             // Ask user for save location
             const savePath = await save({
-                defaultPath: attachment.name,
+                defaultPath: risk.sanitizedName,
                 title: "Save Attachment",
             });
 
@@ -349,10 +377,11 @@ export const AttachmentCard = memo(({ uid, folder, attachment }: { uid: number,
             // Since imap fetch blocks, we don't have true byte-level progress in NextJS easily.
             // The context will show "downloading" spinner until the IPC call returns.
             const resultPath = await invoke<string>('download_attachment', {
-                folder: folder === "sent" ? "sent" : "INBOX",
+                folder: attachmentFolder,
                 uid,
                 partId: attachment.partId,
-                savePath: savePath
+                savePath: savePath,
+                acknowledgeRisk: risk.requiresAcknowledgement,
             });
             console.log(`Downloaded to ${resultPath}`);
             updateDownloadStatus(downloadId, 'completed', resultPath);
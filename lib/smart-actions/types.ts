@@ -66,6 +66,7 @@ export enum EntityType {
     TransactionReference = 'TransactionReference',
     SubscriptionReference = 'SubscriptionReference',
     SchemaOrgObject = 'SchemaOrgObject',
+    SecurityWarning = 'SecurityWarning',
 }
 
 export interface Provenance {
@@ -20,6 +20,7 @@ export interface Email {
     unread: boolean;
     starred: boolean;
     folder: "inbox" | "sent" | "drafts" | "trash" | "starred";
+    sourceFolder?: string;
     avatar?: string;
     body?: string;
     tags: string[];
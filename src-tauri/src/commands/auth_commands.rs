@@ -85,9 +85,9 @@ pub async fn sync_mail_folder(app_handle: AppHandle, folder: String) -> Result<u
 }
 
 #[command]
-pub fn get_folder_messages(app_handle: AppHandle, folder: String, before_uid: Option<u32>, limit: u32) -> Result<Vec<crate::mail::message_list::MessageHeader>, String> {
+pub fn get_folder_messages(app_handle: AppHandle, folder: String, before_uid: Option<u32>, before_folder: Option<String>, limit: u32) -> Result<Vec<crate::mail::message_list::MessageHeader>, String> {
     let folder = folder.to_lowercase();
-    crate::mail::database::load_messages_page(&app_handle, &folder, before_uid, limit)
+    crate::mail::database::load_messages_page(&app_handle, &folder, before_uid, before_folder.as_deref(), limit, None)
 }
 
 #[command]
@@ -113,7 +113,8 @@ pub async fn get_message_body(app_handle: AppHandle, folder: String, uid: u32) -
                 Vec::new()
             };
             let extracted_data = extracted_data_json.and_then(|json| serde_json::from_str(&json).ok());
-            return Ok(crate::mail::message_body::MessageDetail { body: cached_body, attachments, extracted_data });
+            let auth_results = crate::mail::database::get_message_auth_results(&app_handle, &folder, uid).unwrap_or(None);
+            return Ok(crate::mail::message_body::MessageDetail { body: cached_body, attachments, extracted_data, auth_results });
         }
     }
 
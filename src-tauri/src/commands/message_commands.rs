@@ -1,5 +1,4 @@
 use crate::mail::database;
-use crate::mail::imap_session::{execute_with_session, SessionKind};
 use tauri::AppHandle;
 use crate::BootError;
 
@@ -9,7 +8,7 @@ pub async fn mark_as_read(app_handle: AppHandle, uid: u32, folder: Option<String
     let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
     let folder_str = folder.map(|f| f.to_lowercase()).unwrap_or_else(|| "inbox".to_string());
 
-    // Idempotency Check: Don't hit IMAP if already updated locally
+    // Idempotency Check: Don't queue an IMAP update if already updated locally
     let is_already_seen = tokio::task::spawn_blocking({
         let app = app_handle.clone();
         let folder_str = folder_str.clone();
@@ -25,37 +24,12 @@ pub async fn mark_as_read(app_handle: AppHandle, uid: u32, folder: Option<String
         return Ok(());
     }
 
-    if folder_str != "inbox" {
-        let app_handle_clone = app_handle.clone();
-        let _ = tokio::task::spawn_blocking(move || {
-            database::set_message_seen(&app_handle_clone, &folder_str, uid, true)
-        }).await;
-        crate::tray_state::refresh_unread_count_from_db(&app_handle);
-        return Ok(());
-    }
-
-    log::info!("Updating IMAP seen flag for UID {}", uid);
-    // Update IMAP (Silent Flag to avoid untagged responses)
-    let imap_res = execute_with_session(&account, SessionKind::Primary, move |session| {
-        log::info!("Executing uid_store +FLAGS.SILENT (\\Seen) for UID {} on IMAP", uid);
-        session.uid_store(uid.to_string(), "+FLAGS.SILENT (\\Seen)")
-            .map_err(|e| format!("IMAP Error marking read: {}", e))?;
-        log::info!("IMAP uid_store success for UID {}", uid);
-        Ok::<(), String>(())
-    }).await;
-
-    if let Err(e) = &imap_res {
-        log::error!("IMAP uid_store failed for UID {}: {}", uid, e);
-        return Err(e.clone());
-    }
-
-    log::info!("Updating SQLite seen flag to true for UID {}", uid);
-    // Update SQLite
+    // Applied locally now; the server is updated by the pending operations queue
     let app_handle_clone = app_handle.clone();
-    let _ = tokio::task::spawn_blocking(move || {
-        database::set_message_seen(&app_handle_clone, "inbox", uid, true)
-    }).await;
-    
+    tokio::task::spawn_blocking(move || {
+        crate::mail::pending_ops::apply_flag(&app_handle_clone, &account, &folder_str, uid, crate::mail::mailbox_actions::MessageFlag::Seen, true)
+    }).await.map_err(|e| e.to_string())??;
+
     crate::tray_state::refresh_unread_count_from_db(&app_handle);
 
     log::info!("mark_as_read completed successfully for UID {}", uid);
@@ -66,35 +40,16 @@ pub async fn mark_as_read(app_handle: AppHandle, uid: u32, folder: Option<String
 pub async fn toggle_read(app_handle: AppHandle, uid: u32, should_read: bool, folder: Option<String>) -> Result<(), String> {
     let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
     let folder_str = folder.map(|f| f.to_lowercase()).unwrap_or_else(|| "inbox".to_string());
+    let kind = if should_read { crate::mail::undo::ActionKind::MarkRead } else { crate::mail::undo::ActionKind::MarkUnread };
+    let on_server = crate::mail::mailbox_actions::source_mailbox(&account, &folder_str).is_ok();
+    let undo = crate::mail::undo::flag_step(&app_handle, &folder_str, &[uid], crate::mail::mailbox_actions::MessageFlag::Seen, on_server);
 
     let app_handle_clone = app_handle.clone();
-    if folder_str != "inbox" {
-        let _ = tokio::task::spawn_blocking(move || {
-            database::set_message_seen(&app_handle_clone, &folder_str, uid, should_read)
-        }).await;
-        crate::tray_state::refresh_unread_count_from_db(&app_handle);
-        return Ok(());
-    }
-
-    // Update IMAP
-    let flag_cmd = if should_read {
-        "+FLAGS.SILENT (\\Seen)"
-    } else {
-        "-FLAGS.SILENT (\\Seen)"
-    };
+    tokio::task::spawn_blocking(move || {
+        crate::mail::pending_ops::apply_flag(&app_handle_clone, &account, &folder_str, uid, crate::mail::mailbox_actions::MessageFlag::Seen, should_read)
+    }).await.map_err(|e| e.to_string())??;
+    crate::mail::undo::journal(&app_handle, kind, undo.into_iter().collect());
 
-    execute_with_session(&account, SessionKind::Primary, move |session| {
-        session.uid_store(uid.to_string(), flag_cmd)
-            .map_err(|e| format!("IMAP Error toggling read: {}", e))?;
-        Ok::<(), String>(())
-    }).await?;
-
-    let app_handle_clone2 = app_handle.clone();
-    // Update SQLite
-    let _ = tokio::task::spawn_blocking(move || {
-        database::set_message_seen(&app_handle_clone2, "inbox", uid, should_read)
-    }).await;
-    
     crate::tray_state::refresh_unread_count_from_db(&app_handle);
 
     Ok(())
@@ -104,81 +59,274 @@ pub async fn toggle_read(app_handle: AppHandle, uid: u32, should_read: bool, fol
 pub async fn toggle_star(app_handle: AppHandle, uid: u32, should_star: bool, folder: Option<String>) -> Result<(), String> {
     let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
     let folder_str = folder.map(|f| f.to_lowercase()).unwrap_or_else(|| "inbox".to_string());
+    let kind = if should_star { crate::mail::undo::ActionKind::Star } else { crate::mail::undo::ActionKind::Unstar };
+    let on_server = crate::mail::mailbox_actions::source_mailbox(&account, &folder_str).is_ok();
+    let undo = crate::mail::undo::flag_step(&app_handle, &folder_str, &[uid], crate::mail::mailbox_actions::MessageFlag::Flagged, on_server);
 
-    if folder_str != "inbox" {
-        let _ = tokio::task::spawn_blocking(move || {
-            database::set_message_flagged(&app_handle, &folder_str, uid, should_star)
-        }).await;
-        return Ok(());
-    }
-
-    // Update IMAP
-    let flag_cmd = if should_star {
-        "+FLAGS.SILENT (\\Flagged)"
-    } else {
-        "-FLAGS.SILENT (\\Flagged)"
-    };
-
-    execute_with_session(&account, SessionKind::Primary, move |session| {
-        session.uid_store(uid.to_string(), flag_cmd)
-            .map_err(|e| format!("IMAP Error toggling star: {}", e))?;
-        Ok::<(), String>(())
-    }).await?;
-
-    // Update SQLite
-    let _ = tokio::task::spawn_blocking(move || {
-        database::set_message_flagged(&app_handle, "inbox", uid, should_star)
-    }).await;
+    let app_handle_clone = app_handle.clone();
+    tokio::task::spawn_blocking(move || {
+        crate::mail::pending_ops::apply_flag(&app_handle_clone, &account, &folder_str, uid, crate::mail::mailbox_actions::MessageFlag::Flagged, should_star)
+    }).await.map_err(|e| e.to_string())??;
+    crate::mail::undo::journal(&app_handle, kind, undo.into_iter().collect());
 
     Ok(())
 }
 
+/// Removes the message locally and queues its move to Trash. It is journaled at once, so
+/// undoing before the move reaches the server simply cancels it.
 #[tauri::command]
 pub async fn delete_message(app_handle: AppHandle, uid: u32, folder: Option<String>) -> Result<(), String> {
     let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
     let folder_str = folder.map(|f| f.to_lowercase()).unwrap_or_else(|| "inbox".to_string());
 
     let app_handle_clone = app_handle.clone();
-    if folder_str != "inbox" {
-        let _ = tokio::task::spawn_blocking(move || {
-            database::delete_message_local(&app_handle_clone, &folder_str, uid)
-        }).await;
-        crate::tray_state::refresh_unread_count_from_db(&app_handle);
-        return Ok(());
-    }
+    tokio::task::spawn_blocking(move || {
+        crate::mail::pending_ops::apply_delete(&app_handle_clone, &account, &folder_str, uid)
+    }).await.map_err(|e| e.to_string())??;
 
-    let provider_clone = account.provider.clone();
-    // IMAP Action: Try MOVE, fallback to Label + Deleted Flag
-    execute_with_session(&account, SessionKind::Primary, move |session| {
-        let trash_folder = match provider_clone {
-            crate::auth::account::MailProvider::Google => "[Gmail]/Trash",
-            crate::auth::account::MailProvider::Outlook => "Deleted Items",
-            crate::auth::account::MailProvider::Custom { .. } => "Trash",
-        };
-        // Attempt standard IMAP MOVE to provider's trash
-        let move_result = session.uid_mv(uid.to_string(), trash_folder);
-        
-        if let Err(e) = move_result {
-            log::warn!("MOVE to Trash failed, attempting fallback: {}", e);
-            // Fallback: Gmail Labels extension (if Google) + \Deleted
-            if matches!(provider_clone, crate::auth::account::MailProvider::Google) {
-                let _ = session.uid_store(uid.to_string(), "+X-GM-LABELS (\\Trash)");
-            }
-            let _ = session.uid_store(uid.to_string(), "+FLAGS.SILENT (\\Deleted)");
+    crate::tray_state::refresh_unread_count_from_db(&app_handle);
+    Ok(())
+}
+
+/// Gmail: removes the Inbox label. Other providers: moves to the `\Archive` mailbox.
+#[tauri::command]
+pub async fn archive_message(app_handle: AppHandle, folder: String, uid: u32) -> Result<crate::mail::mailbox_actions::MoveResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let mut moved = crate::mail::mailbox_actions::archive_message(&app_handle, &account, &folder.to_lowercase(), uid).await?;
+    crate::mail::undo::journal_move(&app_handle, crate::mail::undo::ActionKind::Archive, &mut moved);
+    Ok(moved)
+}
+
+/// Moves a message to `dest`, a folder id such as `inbox` or any IMAP mailbox name.
+#[tauri::command]
+pub async fn move_message(app_handle: AppHandle, folder: String, uid: u32, dest: String) -> Result<crate::mail::mailbox_actions::MoveResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let target = crate::mail::mailbox_actions::MoveTarget::parse(&dest, &account.provider);
+    let mut moved = crate::mail::mailbox_actions::move_message(&app_handle, &account, &folder.to_lowercase(), uid, target).await?;
+    crate::mail::undo::journal_move(&app_handle, crate::mail::undo::ActionKind::Move, &mut moved);
+    Ok(moved)
+}
+
+#[tauri::command]
+pub async fn bulk_set_read(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>, read: bool) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let mut result = crate::mail::mailbox_actions::set_flag(&app_handle, &account, &messages, crate::mail::mailbox_actions::MessageFlag::Seen, read).await;
+    let kind = if read { crate::mail::undo::ActionKind::MarkRead } else { crate::mail::undo::ActionKind::MarkUnread };
+    crate::mail::undo::journal_bulk(&app_handle, kind, &mut result);
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn bulk_set_starred(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>, starred: bool) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let mut result = crate::mail::mailbox_actions::set_flag(&app_handle, &account, &messages, crate::mail::mailbox_actions::MessageFlag::Flagged, starred).await;
+    let kind = if starred { crate::mail::undo::ActionKind::Star } else { crate::mail::undo::ActionKind::Unstar };
+    crate::mail::undo::journal_bulk(&app_handle, kind, &mut result);
+    Ok(result)
+}
+
+/// Moves messages to Trash; messages listed under a virtual folder are only removed locally.
+#[tauri::command]
+pub async fn bulk_delete(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let (on_server, local_only): (Vec<_>, Vec<_>) = messages.into_iter().partition(|m| {
+        m.folder.to_lowercase().parse::<crate::mail::folder::MailFolder>().ok().and_then(|f| f.to_imap_mailbox(&account.provider)).is_some()
+    });
+
+    let mut result = crate::mail::mailbox_actions::trash_messages(&app_handle, &account, &on_server).await;
+    crate::mail::undo::journal_bulk(&app_handle, crate::mail::undo::ActionKind::Delete, &mut result);
+    for m in local_only {
+        let app = app_handle.clone();
+        let folder = m.folder.to_lowercase();
+        let deleted = tokio::task::spawn_blocking(move || database::delete_message_local(&app, &folder, m.uid))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r);
+        match deleted {
+            Ok(()) => result.succeeded.push(m),
+            Err(error) => result.failed.push(crate::mail::mailbox_actions::BulkFailure { folder: m.folder, uid: m.uid, error }),
         }
-        
-        Ok::<(), String>(())
-    }).await?;
-
-    let app_handle_clone2 = app_handle.clone();
-    // Delete locally
-    let _ = tokio::task::spawn_blocking(move || {
-        database::delete_message_local(&app_handle_clone2, "inbox", uid)
-    }).await;
-    
+    }
     crate::tray_state::refresh_unread_count_from_db(&app_handle);
+    Ok(result)
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn bulk_archive(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let mut result = crate::mail::mailbox_actions::archive_messages(&app_handle, &account, &messages).await;
+    crate::mail::undo::journal_bulk(&app_handle, crate::mail::undo::ActionKind::Archive, &mut result);
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn bulk_move(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>, dest: String) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let target = crate::mail::mailbox_actions::MoveTarget::parse(&dest, &account.provider);
+    let mut result = crate::mail::mailbox_actions::move_messages(&app_handle, &account, &messages, target).await;
+    crate::mail::undo::journal_bulk(&app_handle, crate::mail::undo::ActionKind::Move, &mut result);
+    Ok(result)
+}
+
+/// Reverses the most recent delete, archive, move or mark action within the undo window.
+#[tauri::command]
+pub async fn undo_last_action(app_handle: AppHandle) -> Result<crate::mail::undo::UndoResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::undo::undo_last(&app_handle, &account).await
+}
+
+/// Operations applied locally but not yet confirmed by the server, oldest first.
+#[tauri::command]
+pub async fn list_pending_operations(app_handle: AppHandle) -> Result<Vec<crate::mail::pending_ops::PendingOperation>, String> {
+    tokio::task::spawn_blocking(move || crate::mail::pending_ops::list_pending(&app_handle))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn retry_pending_operations() {
+    crate::mail::pending_ops::wake();
+}
+
+#[tauri::command]
+pub fn get_undo_window(app_handle: AppHandle) -> i64 {
+    crate::mail::undo::get_window_secs(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_undo_window(app_handle: AppHandle, seconds: i64) -> Result<(), String> {
+    crate::mail::undo::set_window_secs(&app_handle, seconds)
+}
+
+/// Adds and removes Gmail labels; messages stay in their folders unless `\Inbox` is removed.
+#[tauri::command]
+pub async fn modify_labels(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>, add: Vec<String>, remove: Vec<String>) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::gmail_labels::modify_labels(&app_handle, &account, &messages, &add, &remove).await
+}
+
+#[tauri::command]
+pub async fn get_message_labels(app_handle: AppHandle, folder: String, uid: u32) -> Result<Vec<String>, String> {
+    tokio::task::spawn_blocking(move || crate::mail::gmail_labels::get_message_labels(&app_handle, &folder.to_lowercase(), uid))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Labels with total and unread counts; each is browsable as the `label:<name>` folder.
+#[tauri::command]
+pub async fn list_labels(app_handle: AppHandle) -> Result<Vec<crate::mail::gmail_labels::LabelSummary>, String> {
+    tokio::task::spawn_blocking(move || crate::mail::gmail_labels::list_labels(&app_handle))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn list_tags(app_handle: AppHandle) -> Result<Vec<crate::mail::tags::Tag>, String> {
+    tokio::task::spawn_blocking(move || crate::mail::tags::list_tags(&app_handle))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Creates a tag; `keyword` defaults to the name reduced to IMAP atom characters.
+#[tauri::command]
+pub fn create_tag(app_handle: AppHandle, name: String, color: Option<String>, keyword: Option<String>) -> Result<crate::mail::tags::Tag, String> {
+    crate::mail::tags::create_tag(&app_handle, &name, color.as_deref(), keyword.as_deref())
+}
+
+/// Renames and/or recolors a tag; an empty `color` clears it.
+#[tauri::command]
+pub async fn update_tag(app_handle: AppHandle, name: String, new_name: Option<String>, color: Option<String>) -> Result<(), String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::tags::update_tag(&app_handle, &account, &name, new_name.as_deref(), color.as_deref())
+}
+
+#[tauri::command]
+pub async fn delete_tag(app_handle: AppHandle, name: String) -> Result<crate::mail::tags::TagUpdate, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::tags::delete_tag(&app_handle, &account, &name).await
+}
+
+#[tauri::command]
+pub async fn tag_messages(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>, add: Vec<String>, remove: Vec<String>) -> Result<crate::mail::tags::TagUpdate, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::tags::tag_messages(&app_handle, &account, &messages, &add, &remove).await
+}
+
+/// Moves messages to Junk; `block_sender` also blocks their senders for future syncs.
+#[tauri::command]
+pub async fn report_spam(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>, block_sender: Option<bool>) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let mut result = crate::mail::spam::report_spam(&app_handle, &account, &messages, block_sender.unwrap_or(false)).await;
+    crate::mail::undo::journal_bulk(&app_handle, crate::mail::undo::ActionKind::Move, &mut result);
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn not_spam(app_handle: AppHandle, messages: Vec<crate::mail::mailbox_actions::MessageRef>) -> Result<crate::mail::mailbox_actions::BulkResult, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let mut result = crate::mail::spam::not_spam(&app_handle, &account, &messages).await;
+    crate::mail::undo::journal_bulk(&app_handle, crate::mail::undo::ActionKind::Move, &mut result);
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn list_sender_lists(app_handle: AppHandle, list: Option<crate::contacts::sender_lists::SenderList>) -> Result<Vec<crate::contacts::sender_lists::SenderListEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::contacts::sender_lists::list_entries(&app_handle, list)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Adds an address or domain to the block or allow list, moving it off the other one.
+#[tauri::command]
+pub async fn add_to_sender_list(app_handle: AppHandle, list: crate::contacts::sender_lists::SenderList, entry: String) -> Result<crate::contacts::sender_lists::SenderListEntry, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::contacts::sender_lists::add_entry(&app_handle, list, &entry)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn remove_from_sender_list(app_handle: AppHandle, list: crate::contacts::sender_lists::SenderList, entry: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        crate::contacts::sender_lists::remove_entry(&app_handle, list, &entry)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn import_sender_list(app_handle: AppHandle, list: crate::contacts::sender_lists::SenderList, text: String) -> Result<crate::contacts::sender_lists::SenderListImport, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::contacts::sender_lists::import_list(&app_handle, list, &text)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn export_sender_list(app_handle: AppHandle, list: crate::contacts::sender_lists::SenderList) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::contacts::sender_lists::export_list(&app_handle, list)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_blocked_sender_destination(app_handle: AppHandle) -> crate::mail::spam::BlockedDestination {
+    crate::mail::spam::get_blocked_destination(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_blocked_sender_destination(app_handle: AppHandle, destination: crate::mail::spam::BlockedDestination) -> Result<(), String> {
+    crate::mail::spam::set_blocked_destination(&app_handle, destination)
+}
+
+/// Unsubscribes from the mailing list a message came from and records the outcome.
+#[tauri::command]
+pub async fn unsubscribe(app_handle: AppHandle, folder: String, uid: u32) -> Result<crate::mail::unsubscribe::UnsubscribeOutcome, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::unsubscribe::unsubscribe(&app_handle, account, &folder.to_lowercase(), uid).await
+}
+
+#[tauri::command]
+pub async fn list_unsubscribes(app_handle: AppHandle, limit: Option<u32>) -> Result<Vec<crate::mail::unsubscribe::UnsubscribeOutcome>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::unsubscribe::list_unsubscribes(&app_handle, limit.unwrap_or(100))
+    }).await.map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
@@ -186,7 +334,9 @@ pub async fn get_messages_page(
     app_handle: AppHandle,
     folder: String,
     before_uid: Option<u32>,
+    before_folder: Option<String>,
     limit: u32,
+    tag: Option<String>,
 ) -> Result<Vec<crate::mail::message_list::MessageHeader>, String> {
     let safe_limit = limit.min(100);
     let folder = folder.to_lowercase();
@@ -194,7 +344,7 @@ pub async fn get_messages_page(
     let app_handle_clone = app_handle.clone();
     let folder_clone = folder.clone();
     let pages = tokio::task::spawn_blocking(move || {
-        database::load_messages_page(&app_handle_clone, &folder_clone, before_uid, safe_limit)
+        database::load_messages_page(&app_handle_clone, &folder_clone, before_uid, before_folder.as_deref(), safe_limit, tag.as_deref())
     })
     .await
     .map_err(|e| e.to_string())??;
@@ -254,16 +404,124 @@ pub async fn download_attachment(
     uid: u32,
     part_id: String,
     save_path: String,
+    acknowledge_risk: Option<bool>,
 ) -> Result<String, String> {
     let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
     let folder = folder.to_lowercase();
-    
-    let bytes = crate::mail::message_body::fetch_attachment_part(&account, &folder, uid, &part_id).await?;
-    
+
+    crate::mail::attachment_safety::validate_save_path(&save_path)?;
+
+    let bytes = match crate::mail::attachment_safety::take_staged(&account.email, &folder, uid, &part_id) {
+        Some(bytes) => bytes,
+        None => crate::mail::message_body::fetch_attachment_part(&account, &folder, uid, &part_id).await?,
+    };
+
+    // Re-assess on the bytes we are about to write so a skipped or stale check cannot bypass the prompt.
+    // Without cached metadata the chosen file name and the content still get checked.
+    let (name, mime) = match crate::mail::attachment_preview::attachment_metadata(&app_handle, &account, &folder, uid, &part_id).await {
+        Ok(attachment) => (attachment.name, attachment.type_mime),
+        Err(e) => {
+            log::warn!("Assessing attachment {} of message {} without its metadata: {}", part_id, uid, e);
+            let file_name = std::path::Path::new(&save_path).file_name().map(|n| n.to_string_lossy().into_owned());
+            (file_name.unwrap_or_default(), String::new())
+        }
+    };
+    let risk = crate::mail::attachment_safety::assess_attachment(&name, &mime, &bytes);
+    if risk.requires_acknowledgement && !acknowledge_risk.unwrap_or(false) {
+        return Err(format!("Attachment requires confirmation before saving: {}", risk.reasons.join("; ")));
+    }
+
     std::fs::write(&save_path, bytes).map_err(|e| e.to_string())?;
     Ok(save_path)
 }
 
+#[tauri::command]
+pub async fn assess_attachment(
+    app_handle: tauri::AppHandle,
+    folder: String,
+    uid: u32,
+    part_id: String,
+) -> Result<crate::mail::attachment_safety::AttachmentRisk, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let folder = folder.to_lowercase();
+
+    let attachment = crate::mail::attachment_preview::attachment_metadata(&app_handle, &account, &folder, uid, &part_id).await?;
+    let bytes = crate::mail::message_body::fetch_attachment_part(&account, &folder, uid, &part_id).await?;
+    let risk = crate::mail::attachment_safety::assess_attachment(&attachment.name, &attachment.type_mime, &bytes);
+
+    // The save that follows the prompt reuses these bytes instead of downloading them again
+    crate::mail::attachment_safety::stage(&account.email, &folder, uid, &part_id, bytes);
+    Ok(risk)
+}
+
+#[tauri::command]
+pub async fn get_attachment_preview(
+    app_handle: tauri::AppHandle,
+    folder: String,
+    uid: u32,
+    part_id: String,
+) -> Result<crate::mail::attachment_preview::AttachmentPreview, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let folder = folder.to_lowercase();
+
+    crate::mail::attachment_preview::get_attachment_preview(&app_handle, &account, &folder, uid, &part_id).await
+}
+
+#[tauri::command]
+pub async fn get_raw_message(
+    app_handle: tauri::AppHandle,
+    folder: String,
+    uid: u32,
+) -> Result<crate::mail::raw_message::RawMessage, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let folder = folder.to_lowercase();
+
+    let raw = crate::mail::raw_message::fetch_raw_message(&account, &folder, uid).await?;
+    Ok(crate::mail::raw_message::parse_raw_message(&raw))
+}
+
+#[tauri::command]
+pub async fn export_eml(
+    app_handle: tauri::AppHandle,
+    folder: String,
+    uid: u32,
+    save_path: String,
+) -> Result<String, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let folder = folder.to_lowercase();
+
+    let raw = crate::mail::raw_message::fetch_raw_message(&account, &folder, uid).await?;
+
+    tokio::fs::write(&save_path, raw).await.map_err(|e| format!("Failed to write .eml file: {}", e))?;
+    Ok(save_path)
+}
+
+#[tauri::command]
+pub async fn verify_message_dkim(
+    app_handle: tauri::AppHandle,
+    folder: String,
+    uid: u32,
+) -> Result<crate::mail::auth_results::AuthVerdict, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    let folder = folder.to_lowercase();
+
+    let raw = crate::mail::raw_message::fetch_raw_message(&account, &folder, uid).await?;
+    let local_dkim = crate::mail::dkim::verify_dkim(&raw, &crate::mail::dkim::SystemKeyResolver).await;
+
+    // Keep the provider's verdict if we already have it, otherwise derive it from the raw headers
+    tokio::task::spawn_blocking(move || {
+        let mut verdict = database::get_message_auth_results(&app_handle, &folder, uid)
+            .unwrap_or(None)
+            .unwrap_or_else(|| crate::mail::auth_results::parse_auth_headers(&raw));
+        verdict.local_dkim = Some(local_dkim);
+
+        database::update_message_auth_results(&app_handle, &folder, uid, &verdict)?;
+        Ok(verdict)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 #[derive(serde::Serialize)]
 pub struct AttachmentMetadata {
     pub path: String,
@@ -367,7 +625,7 @@ pub async fn get_unread_counts(app_handle: tauri::AppHandle) -> Result<std::coll
 
         let mut counts = std::collections::HashMap::new();
 
-        let mut stmt = conn.prepare("SELECT folder, COUNT(*) FROM messages WHERE seen = 0 GROUP BY folder").unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT folder, COUNT(*) FROM messages WHERE seen = 0 AND {} GROUP BY folder", crate::mail::snooze::not_snoozed_sql("messages"))).unwrap();
         let rows = stmt.query_map([], |row| {
             let folder: String = row.get(0)?;
             let count: u32 = row.get(1)?;
@@ -380,11 +638,15 @@ pub async fn get_unread_counts(app_handle: tauri::AppHandle) -> Result<std::coll
             }
         }
 
-        let mut stmt = conn.prepare("SELECT COUNT(*) FROM messages WHERE seen = 0 AND flagged = 1").unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT COUNT(*) FROM messages WHERE seen = 0 AND flagged = 1 AND {}", crate::mail::snooze::not_snoozed_sql("messages"))).unwrap();
         if let Ok(count) = stmt.query_row([], |row| row.get(0)) {
             counts.insert("starred".to_string(), count);
         }
 
+        for (folder, count) in crate::mail::saved_searches::unread_counts(&conn)? {
+            counts.insert(folder, count);
+        }
+
         Ok(counts)
     }).await.map_err(|e| e.to_string())?
 }
@@ -430,14 +692,42 @@ pub fn get_boot_error(state: tauri::State<'_, BootError>) -> Option<String> {
 #[derive(serde::Serialize)]
 pub struct SearchResponse {
     pub search_id: String,
-    pub local_results: Vec<crate::mail::message_list::MessageHeader>,
+    pub local_results: Vec<crate::mail::search_query::SearchHit>,
     pub remote_search_state: crate::mail::search::RemoteSearchState,
 }
 
 #[tauri::command]
-pub async fn search_messages(app_handle: tauri::AppHandle, folder: String, query: String) -> Result<SearchResponse, String> {
+pub async fn search_messages(
+    app_handle: tauri::AppHandle,
+    folder: String,
+    query: String,
+    sort: Option<crate::mail::search_query::SearchSort>,
+    scope: Option<crate::mail::search::SearchScope>,
+) -> Result<SearchResponse, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    if let Err(e) = crate::mail::search_history::record_search(&app_handle, &query) {
+        log::warn!("Failed to record search history: {}", e);
+    }
+    let (search_id, local_results, remote_search_state) = crate::mail::search::start_search(app_handle, account, folder, query, sort.unwrap_or_default(), scope.unwrap_or_default()).await?;
+    Ok(SearchResponse {
+        search_id,
+        local_results,
+        remote_search_state,
+    })
+}
+
+/// Searches every local folder and every signed-in account's server at once.
+#[tauri::command]
+pub async fn search_everywhere(
+    app_handle: tauri::AppHandle,
+    query: String,
+    sort: Option<crate::mail::search_query::SearchSort>,
+) -> Result<SearchResponse, String> {
     let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
-    let (search_id, local_results, remote_search_state) = crate::mail::search::start_search(app_handle, account, folder, query).await?;
+    if let Err(e) = crate::mail::search_history::record_search(&app_handle, &query) {
+        log::warn!("Failed to record search history: {}", e);
+    }
+    let (search_id, local_results, remote_search_state) = crate::mail::search::start_global_search(app_handle, account, query, sort.unwrap_or_default()).await?;
     Ok(SearchResponse {
         search_id,
         local_results,
@@ -454,3 +744,216 @@ pub async fn load_more_results(app_handle: tauri::AppHandle, search_id: String)
 pub async fn clear_search(app_handle: tauri::AppHandle, search_id: String) -> Result<(), String> {
     crate::mail::search::clear_search(app_handle, search_id).await
 }
+
+#[tauri::command]
+pub async fn suggest_search(app_handle: tauri::AppHandle, prefix: String) -> Result<Vec<crate::mail::search_history::SearchSuggestion>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::search_history::suggest_search(&app_handle, &prefix)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn list_search_history(app_handle: tauri::AppHandle, limit: Option<u32>) -> Result<Vec<crate::mail::search_history::SearchHistoryEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::search_history::list_search_history(&app_handle, limit.unwrap_or(20))
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn clear_search_history(app_handle: tauri::AppHandle) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::search_history::clear_search_history(&app_handle)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_semantic_search_status(app_handle: tauri::AppHandle) -> Result<crate::mail::semantic_index::SemanticIndexStatus, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::semantic_index::get_status(&app_handle)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn set_semantic_search_enabled(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    crate::mail::semantic_index::set_enabled(&app_handle, enabled)
+}
+
+#[tauri::command]
+pub async fn list_saved_searches(app_handle: tauri::AppHandle) -> Result<Vec<crate::mail::saved_searches::SavedSearch>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::saved_searches::list_saved_searches(&app_handle)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn create_saved_search(app_handle: tauri::AppHandle, name: String, query: String, notify: bool) -> Result<crate::mail::saved_searches::SavedSearch, String> {
+    if name.trim().is_empty() {
+        return Err("Saved search name cannot be empty".to_string());
+    }
+    tokio::task::spawn_blocking(move || {
+        crate::mail::saved_searches::create_saved_search(&app_handle, &name, &query, notify)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn update_saved_search(app_handle: tauri::AppHandle, id: String, name: String, query: String, notify: bool) -> Result<crate::mail::saved_searches::SavedSearch, String> {
+    if name.trim().is_empty() {
+        return Err("Saved search name cannot be empty".to_string());
+    }
+    tokio::task::spawn_blocking(move || {
+        crate::mail::saved_searches::update_saved_search(&app_handle, &id, &name, &query, notify)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn delete_saved_search(app_handle: tauri::AppHandle, id: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::saved_searches::delete_saved_search(&app_handle, &id)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn list_rules(app_handle: tauri::AppHandle) -> Result<Vec<crate::mail::rules::Rule>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::rules::list_rules(&app_handle)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Creates the rule when its id is empty or unknown, otherwise updates it in place.
+#[tauri::command]
+pub async fn save_rule(app_handle: tauri::AppHandle, rule: crate::mail::rules::Rule) -> Result<crate::mail::rules::Rule, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::rules::save_rule(&app_handle, rule)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn delete_rule(app_handle: tauri::AppHandle, id: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::rules::delete_rule(&app_handle, &id)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn reorder_rules(app_handle: tauri::AppHandle, ids: Vec<String>) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::rules::reorder_rules(&app_handle, &ids)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Runs all enabled rules, or just `rule_id`, over the newest cached messages of a folder.
+#[tauri::command]
+pub async fn run_rules(app_handle: tauri::AppHandle, folder: String, rule_id: Option<String>, limit: Option<u32>) -> Result<crate::mail::rules::RuleRunSummary, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::rules::run_rules_on_folder(&app_handle, &account, &folder, rule_id.as_deref(), limit.unwrap_or(200)).await
+}
+
+#[tauri::command]
+pub async fn get_rule_log(app_handle: tauri::AppHandle, limit: Option<u32>) -> Result<Vec<crate::mail::rules::RuleLogEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::rules::get_rule_log(&app_handle, limit.unwrap_or(200))
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn clear_rule_log(app_handle: tauri::AppHandle) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::rules::clear_rule_log(&app_handle)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn list_sieve_scripts(app_handle: tauri::AppHandle) -> Result<Vec<crate::mail::managesieve::SieveScript>, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::list_scripts(&account)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_sieve_script(app_handle: tauri::AppHandle, name: String) -> Result<String, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::get_script(&account, &name)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Asks the server to validate a script without storing it; returns any warnings.
+#[tauri::command]
+pub async fn check_sieve_script(app_handle: tauri::AppHandle, script: String) -> Result<Option<String>, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::check_script(&account, &script)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn upload_sieve_script(app_handle: tauri::AppHandle, name: String, script: String, activate: bool) -> Result<Option<String>, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::upload_script(&app_handle, &account, &name, &script, activate)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Activates a script; an empty name turns server-side filtering off.
+#[tauri::command]
+pub async fn activate_sieve_script(app_handle: tauri::AppHandle, name: String) -> Result<(), String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::activate_script(&app_handle, &account, &name)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn delete_sieve_script(app_handle: tauri::AppHandle, name: String) -> Result<(), String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::delete_script(&app_handle, &account, &name)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn export_rules_to_sieve(app_handle: tauri::AppHandle) -> Result<crate::mail::sieve::SieveExport, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::export_rules(&app_handle, &account)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Uploads the local rules as the app's server-side script so they run while the app is closed.
+#[tauri::command]
+pub async fn push_rules_to_server(app_handle: tauri::AppHandle, activate: bool) -> Result<crate::mail::sieve::SieveExport, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::push_rules(&app_handle, &account, activate)
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn import_sieve_rules(app_handle: tauri::AppHandle, script: String) -> Result<crate::mail::sieve::SieveImport, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::mail::sieve::import_rules(&app_handle, &account, &script)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Hides a message until `until` (Unix seconds), when it comes back unread. `server_side`
+/// moves it to the server's Snoozed mailbox so the snooze applies on every device.
+#[tauri::command]
+pub async fn snooze_message(app_handle: tauri::AppHandle, folder: String, uid: u32, until: i64, server_side: Option<bool>) -> Result<crate::mail::snooze::SnoozedMessage, String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::snooze::snooze_message(&app_handle, &account, &folder, uid, until, server_side.unwrap_or(false)).await
+}
+
+#[tauri::command]
+pub async fn unsnooze_message(app_handle: tauri::AppHandle, id: String) -> Result<(), String> {
+    let account = crate::auth::bootstrap::ensure_active_account(&app_handle).await?;
+    crate::mail::snooze::unsnooze_message(&app_handle, &account, &id).await
+}
+
+#[tauri::command]
+pub async fn list_snoozed(app_handle: tauri::AppHandle) -> Result<Vec<crate::mail::snooze::SnoozedMessage>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::mail::snooze::list_snoozed(&app_handle)
+    }).await.map_err(|e| e.to_string())?
+}
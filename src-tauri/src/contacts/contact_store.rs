@@ -27,6 +27,7 @@ pub fn init_contacts_db(app_handle: &AppHandle) -> Result<(), String> {
         CREATE INDEX IF NOT EXISTS idx_contacts_display_name ON contacts(display_name);
         "
     ).map_err(|e| e.to_string())?;
+    crate::contacts::sender_lists::init_sender_lists_tables(&conn)?;
 
     Ok(())
 }
@@ -97,3 +98,62 @@ pub fn record_usage(app_handle: &AppHandle, emails: &[String]) -> Result<(), Str
     
     Ok(())
 }
+
+/// A sender counts as known once we have written to them or received another message from
+/// them besides the one at `folder`/`uid` (or its copies, matched by Message-ID).
+pub fn is_known_sender(app_handle: &AppHandle, email: &str, folder: &str, uid: u32) -> Result<bool, String> {
+    let email = email.trim().to_lowercase();
+    if email.is_empty() { return Ok(false); }
+
+    let db_path = crate::mail::database::get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let contact: Option<(i64, Option<String>)> = conn.query_row(
+        "SELECT usage_count, source FROM contacts WHERE email = ?1",
+        rusqlite::params![email],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).ok();
+
+    if let Some((usage_count, source)) = contact {
+        if usage_count > 1 || source.as_deref() == Some("SMTP") {
+            return Ok(true);
+        }
+    }
+
+    let received: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM messages
+         WHERE sender_address = ?1 AND NOT (folder = ?2 AND uid = ?3)
+           AND (message_id IS NULL OR message_id IS NOT (SELECT message_id FROM messages WHERE folder = ?2 AND uid = ?3))",
+        rusqlite::params![email, folder, uid],
+        |row| row.get(0),
+    ).unwrap_or(0);
+
+    Ok(received > 0)
+}
+
+/// Domains of people the user actually corresponds with, most frequent first.
+pub fn get_known_contact_domains(app_handle: &AppHandle, limit: u32) -> Result<Vec<String>, String> {
+    let db_path = crate::mail::database::get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare(
+        "SELECT LOWER(SUBSTR(email, INSTR(email, '@') + 1)) AS domain, SUM(usage_count) AS total
+         FROM contacts
+         WHERE usage_count > 1 OR source = 'SMTP'
+         GROUP BY domain
+         ORDER BY total DESC
+         LIMIT ?1"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(rusqlite::params![limit], |row| row.get::<_, String>(0)).map_err(|e| e.to_string())?;
+
+    let mut domains = Vec::new();
+    for row in rows {
+        if let Ok(domain) = row {
+            if !domain.is_empty() {
+                domains.push(domain);
+            }
+        }
+    }
+    Ok(domains)
+}
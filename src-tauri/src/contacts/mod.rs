@@ -1,3 +1,4 @@
 pub mod contact_store;
 pub mod contact_indexer;
 pub mod contact_search;
+pub mod sender_lists;
@@ -0,0 +1,280 @@
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+use tauri::AppHandle;
+use chrono::Utc;
+
+/// Which list an entry belongs to. An entry is on at most one list at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SenderList {
+    Block,
+    Allow,
+}
+
+impl SenderList {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SenderList::Block => "block",
+            SenderList::Allow => "allow",
+        }
+    }
+
+    fn from_db(value: &str) -> Option<SenderList> {
+        match value {
+            "block" => Some(SenderList::Block),
+            "allow" => Some(SenderList::Allow),
+            _ => None,
+        }
+    }
+}
+
+/// An address such as `jane@example.com`, or a domain such as `example.com` that also
+/// covers its subdomains.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderListEntry {
+    pub entry: String,
+    pub list: SenderList,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderListImport {
+    pub added: u32,
+    /// Items that are neither an address nor a domain.
+    pub invalid: Vec<String>,
+}
+
+pub fn init_sender_lists_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sender_lists (
+            entry TEXT PRIMARY KEY,
+            list TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+
+    // Addresses blocked before domains and the allow list existed
+    let has_legacy: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'blocked_senders'",
+        [],
+        |row| row.get(0),
+    ).map_err(|e| e.to_string())?;
+    if has_legacy > 0 {
+        conn.execute_batch(
+            "INSERT OR IGNORE INTO sender_lists (entry, list, created_at) SELECT address, 'block', created_at FROM blocked_senders;
+             DROP TABLE blocked_senders;"
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Normalizes user input to an address or a bare domain; `@example.com` and
+/// `<jane@example.com>` are accepted too.
+fn normalize_entry(input: &str) -> Result<String, String> {
+    let value = input.trim().trim_start_matches('<').trim_end_matches('>').trim().to_lowercase();
+    let value = value.strip_prefix('@').unwrap_or(&value);
+    let (local, domain) = match value.rsplit_once('@') {
+        Some((local, domain)) => (Some(local), domain),
+        None => (None, value),
+    };
+    let valid_domain = domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-');
+    let valid_local = local.map_or(true, |l| !l.is_empty() && !l.chars().any(char::is_whitespace));
+    if !valid_domain || !valid_local {
+        return Err(format!("\"{}\" is not an email address or domain", input.trim()));
+    }
+    Ok(value.to_string())
+}
+
+fn entry_matches(entry: &str, address: &str) -> bool {
+    if entry.contains('@') {
+        return entry == address;
+    }
+    let Some((_, domain)) = address.rsplit_once('@') else { return false };
+    domain == entry || domain.strip_suffix(entry).is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// Both lists, loaded once per sync rather than queried per message.
+#[derive(Debug, Default)]
+pub struct SenderLists {
+    blocked: Vec<String>,
+    allowed: Vec<String>,
+}
+
+impl SenderLists {
+    /// The list `address` falls under. Allowing wins, so an allowed address is never
+    /// caught by a blocked domain.
+    pub fn verdict(&self, address: &str) -> Option<SenderList> {
+        let address = address.trim().to_lowercase();
+        if self.allowed.iter().any(|e| entry_matches(e, &address)) {
+            Some(SenderList::Allow)
+        } else if self.blocked.iter().any(|e| entry_matches(e, &address)) {
+            Some(SenderList::Block)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocked.is_empty() && self.allowed.is_empty()
+    }
+}
+
+fn open(app_handle: &AppHandle) -> Result<Connection, String> {
+    let db_path = crate::mail::database::get_db_path(app_handle)?;
+    Connection::open(db_path).map_err(|e| e.to_string())
+}
+
+pub fn load_sender_lists(app_handle: &AppHandle) -> Result<SenderLists, String> {
+    read_sender_lists(&open(app_handle)?)
+}
+
+fn read_sender_lists(conn: &Connection) -> Result<SenderLists, String> {
+    let mut stmt = conn.prepare("SELECT entry, list FROM sender_lists").map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))).map_err(|e| e.to_string())?;
+    let mut lists = SenderLists::default();
+    for (entry, list) in rows.filter_map(|r| r.ok()) {
+        match SenderList::from_db(&list) {
+            Some(SenderList::Block) => lists.blocked.push(entry),
+            Some(SenderList::Allow) => lists.allowed.push(entry),
+            None => {}
+        }
+    }
+    Ok(lists)
+}
+
+/// Adds an entry to `list`, taking it off the other list if it was there.
+pub fn add_entry(app_handle: &AppHandle, list: SenderList, entry: &str) -> Result<SenderListEntry, String> {
+    let entry = normalize_entry(entry)?;
+    let created_at = Utc::now().timestamp();
+    let conn = open(app_handle)?;
+    conn.execute(
+        "INSERT INTO sender_lists (entry, list, created_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(entry) DO UPDATE SET list = excluded.list, created_at = excluded.created_at",
+        rusqlite::params![entry, list.as_str(), created_at],
+    ).map_err(|e| e.to_string())?;
+    Ok(SenderListEntry { entry, list, created_at })
+}
+
+/// Removes an entry from `list`; the same entry on the other list is left alone.
+pub fn remove_entry(app_handle: &AppHandle, list: SenderList, entry: &str) -> Result<(), String> {
+    let entry = normalize_entry(entry)?;
+    let conn = open(app_handle)?;
+    conn.execute("DELETE FROM sender_lists WHERE entry = ?1 AND list = ?2", rusqlite::params![entry, list.as_str()]).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn list_entries(app_handle: &AppHandle, list: Option<SenderList>) -> Result<Vec<SenderListEntry>, String> {
+    let conn = open(app_handle)?;
+    let mut stmt = conn.prepare("SELECT entry, list, created_at FROM sender_lists ORDER BY entry").map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))).map_err(|e| e.to_string())?;
+    Ok(rows
+        .filter_map(|r| r.ok())
+        .filter_map(|(entry, l, created_at)| Some(SenderListEntry { entry, list: SenderList::from_db(&l)?, created_at }))
+        .filter(|e| list.map_or(true, |l| e.list == l))
+        .collect())
+}
+
+/// One entry per line, the format `import_list` reads.
+pub fn export_list(app_handle: &AppHandle, list: SenderList) -> Result<String, String> {
+    let entries = list_entries(app_handle, Some(list))?;
+    Ok(entries.into_iter().map(|e| e.entry + "\n").collect())
+}
+
+/// Adds entries from text with one address or domain per line, or separated by commas.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn import_list(app_handle: &AppHandle, list: SenderList, text: &str) -> Result<SenderListImport, String> {
+    let mut conn = open(app_handle)?;
+    let created_at = Utc::now().timestamp();
+    let mut summary = SenderListImport::default();
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO sender_lists (entry, list, created_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(entry) DO UPDATE SET list = excluded.list, created_at = excluded.created_at
+             WHERE sender_lists.list != excluded.list"
+        ).map_err(|e| e.to_string())?;
+        let items = text.lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|item| !item.is_empty());
+        for item in items {
+            match normalize_entry(item) {
+                Ok(entry) => summary.added += insert.execute(rusqlite::params![entry, list.as_str(), created_at]).map_err(|e| e.to_string())? as u32,
+                Err(_) => summary.invalid.push(item.to_string()),
+            }
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_entry() {
+        assert_eq!(normalize_entry(" Jane@Example.com ").as_deref(), Ok("jane@example.com"));
+        assert_eq!(normalize_entry("<jane@example.com>").as_deref(), Ok("jane@example.com"));
+        assert_eq!(normalize_entry("@News.Example.com").as_deref(), Ok("news.example.com"));
+        assert!(normalize_entry("localhost").is_err());
+        assert!(normalize_entry("@").is_err());
+        assert!(normalize_entry("jane doe@example.com").is_err());
+        assert!(normalize_entry("example.com.").is_err());
+    }
+
+    #[test]
+    fn test_verdict() {
+        let lists = SenderLists {
+            blocked: vec!["example.com".to_string(), "spam@other.org".to_string()],
+            allowed: vec!["boss@example.com".to_string()],
+        };
+        assert_eq!(lists.verdict("anyone@example.com"), Some(SenderList::Block));
+        assert_eq!(lists.verdict("news@mail.example.com"), Some(SenderList::Block));
+        assert_eq!(lists.verdict("Boss@Example.com"), Some(SenderList::Allow));
+        assert_eq!(lists.verdict("spam@other.org"), Some(SenderList::Block));
+        assert_eq!(lists.verdict("friend@other.org"), None);
+        assert_eq!(lists.verdict("someone@notexample.com"), None);
+    }
+
+    #[test]
+    fn test_sender_address() {
+        use crate::mail::database::parse_sender_address;
+        assert_eq!(parse_sender_address("Jane Doe <Jane@Example.com>").as_deref(), Some("jane@example.com"));
+        assert_eq!(parse_sender_address("news@example.com").as_deref(), Some("news@example.com"));
+        assert_eq!(parse_sender_address("\"Doe, Jane\" <jane@example.com>, bob@example.com").as_deref(), Some("jane@example.com"));
+        assert_eq!(parse_sender_address("Undisclosed recipients:;"), None);
+        assert_eq!(parse_sender_address("MAILER-DAEMON"), None);
+    }
+
+    #[test]
+    fn test_legacy_blocked_senders_migrate() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE blocked_senders (address TEXT PRIMARY KEY, created_at INTEGER NOT NULL);
+             INSERT INTO blocked_senders VALUES ('spam@example.com', 100), ('ads@other.org', 200);"
+        ).unwrap();
+
+        init_sender_lists_tables(&conn).unwrap();
+        // Runs on every start, after the legacy table is gone
+        init_sender_lists_tables(&conn).unwrap();
+
+        let legacy: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'blocked_senders'", [], |row| row.get(0)).unwrap();
+        assert_eq!(legacy, 0);
+        let created_at: i64 = conn.query_row("SELECT created_at FROM sender_lists WHERE entry = 'ads@other.org'", [], |row| row.get(0)).unwrap();
+        assert_eq!(created_at, 200);
+
+        let lists = read_sender_lists(&conn).unwrap();
+        assert_eq!(lists.verdict("spam@example.com"), Some(SenderList::Block));
+        assert_eq!(lists.verdict("ADS@other.org"), Some(SenderList::Block));
+        assert_eq!(lists.verdict("friend@example.com"), None);
+    }
+}
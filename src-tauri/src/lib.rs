@@ -151,6 +151,8 @@ pub fn run() {
       if let Ok(cache_dir) = app.handle().path().app_cache_dir() {
         let inline_dir = cache_dir.join("orbitmail_inline");
         let _ = std::fs::remove_dir_all(&inline_dir);
+        // Cached previews regenerate thumbnails that are missing
+        let _ = std::fs::remove_dir_all(cache_dir.join("orbitmail_previews"));
       }
 
       let boot_err = match crate::mail::database::init_db(app.handle()) {
@@ -163,6 +165,9 @@ pub fn run() {
       app.manage(BootError(Mutex::new(boot_err)));
 
       crate::tray_state::spawn_tray_update_loop(app.handle().clone());
+      crate::mail::semantic_index::spawn_indexer(app.handle().clone());
+      crate::mail::snooze::spawn_scheduler(app.handle().clone());
+      crate::mail::pending_ops::spawn_replayer(app.handle().clone());
 
       Ok(())
     })
@@ -187,6 +192,11 @@ pub fn run() {
       toggle_star,
       delete_message,
       download_attachment,
+      assess_attachment,
+      get_raw_message,
+      export_eml,
+      verify_message_dkim,
+      get_attachment_preview,
       show_in_folder,
       show_main_window,
       send_message,
@@ -200,8 +210,68 @@ pub fn run() {
       open_url,
       clear_local_cache,
       search_messages,
+      search_everywhere,
+      suggest_search,
+      list_search_history,
+      clear_search_history,
+      get_semantic_search_status,
+      set_semantic_search_enabled,
       load_more_results,
       clear_search,
+      list_saved_searches,
+      create_saved_search,
+      update_saved_search,
+      delete_saved_search,
+      list_rules,
+      save_rule,
+      delete_rule,
+      reorder_rules,
+      run_rules,
+      get_rule_log,
+      clear_rule_log,
+      list_sieve_scripts,
+      get_sieve_script,
+      check_sieve_script,
+      upload_sieve_script,
+      activate_sieve_script,
+      delete_sieve_script,
+      export_rules_to_sieve,
+      push_rules_to_server,
+      import_sieve_rules,
+      snooze_message,
+      unsnooze_message,
+      list_snoozed,
+      archive_message,
+      move_message,
+      bulk_set_read,
+      bulk_set_starred,
+      bulk_delete,
+      bulk_archive,
+      bulk_move,
+      undo_last_action,
+      get_undo_window,
+      set_undo_window,
+      list_pending_operations,
+      retry_pending_operations,
+      modify_labels,
+      get_message_labels,
+      list_labels,
+      list_tags,
+      create_tag,
+      update_tag,
+      delete_tag,
+      tag_messages,
+      report_spam,
+      not_spam,
+      list_sender_lists,
+      add_to_sender_list,
+      remove_from_sender_list,
+      import_sender_list,
+      export_sender_list,
+      get_blocked_sender_destination,
+      set_blocked_sender_destination,
+      unsubscribe,
+      list_unsubscribes,
       crate::auth::hello::check_hello_availability,
       crate::auth::hello::authenticate_hello
     ])
@@ -0,0 +1,278 @@
+use crate::auth::account::Account;
+use crate::mail::database;
+use crate::mail::message_body::{self, MessageAttachment};
+use serde::{Serialize, Deserialize};
+use std::fs;
+use tauri::{AppHandle, Manager};
+
+/// Attachments larger than this are never decoded for preview.
+const MAX_PREVIEW_BYTES: usize = 20 * 1024 * 1024;
+/// Upper bound on text kept for display and indexing per attachment.
+const MAX_TEXT_CHARS: usize = 64 * 1024;
+const MAX_CSV_ROWS: usize = 50;
+const THUMBNAIL_SIZE: u32 = 320;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewKind {
+    Image,
+    Pdf,
+    Text,
+    Csv,
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentPreview {
+    pub part_id: String,
+    pub kind: PreviewKind,
+    pub mime_type: String,
+    pub thumbnail_url: Option<String>,
+    pub text: Option<String>,
+    pub rows: Option<Vec<Vec<String>>>,
+    pub truncated: bool,
+}
+
+/// Returns a cached preview if one exists, otherwise downloads the part and builds one.
+/// Extracted text is stored alongside the preview and indexed for search.
+pub async fn get_attachment_preview(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32, part_id: &str) -> Result<AttachmentPreview, String> {
+    if let Ok(Some(json)) = database::get_attachment_preview_cache(app_handle, folder, uid, part_id) {
+        if let Ok(cached) = serde_json::from_str::<AttachmentPreview>(&json) {
+            let thumbnail_missing = cached.kind == PreviewKind::Image && cached.thumbnail_url.as_deref().map_or(true, |url| {
+                !std::path::Path::new(url.trim_start_matches("asset://localhost/")).exists()
+            });
+            if !thumbnail_missing {
+                return Ok(cached);
+            }
+        }
+    }
+
+    let attachment = lookup_attachment(app_handle, folder, uid, part_id);
+    let name = attachment.as_ref().map(|a| a.name.clone()).unwrap_or_default();
+    let declared_mime = attachment.as_ref().map(|a| a.type_mime.to_lowercase()).unwrap_or_default();
+
+    let bytes = message_body::fetch_attachment_part(account, folder, uid, part_id).await?;
+
+    let mime_type = resolve_mime(&declared_mime, &name);
+    let kind = classify(&mime_type, &name);
+
+    let thumbnail_path = app_handle.path().app_cache_dir().ok().map(|dir| {
+        let safe_folder = folder.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+        let safe_part = part_id.replace('.', "_");
+        dir.join("orbitmail_previews").join(format!("{}_{}_{}.png", safe_folder, uid, safe_part))
+    });
+
+    let part_id_owned = part_id.to_string();
+    let preview = tokio::task::spawn_blocking(move || {
+        build_preview(&part_id_owned, kind, mime_type, &bytes, thumbnail_path.as_deref())
+    }).await.map_err(|e| format!("Preview task failed: {}", e))?;
+
+    if let Ok(json) = serde_json::to_string(&preview) {
+        let indexed_text = preview.text.as_deref().filter(|t| !t.trim().is_empty());
+        if let Err(e) = database::save_attachment_preview(app_handle, folder, uid, part_id, &json, indexed_text) {
+            log::warn!("Failed to cache attachment preview for UID {} part {}: {}", uid, part_id, e);
+        }
+    }
+
+    Ok(preview)
+}
+
+/// Reads an attachment's metadata from the cached body, if the body has been fetched.
+pub fn lookup_attachment(app_handle: &AppHandle, folder: &str, uid: u32, part_id: &str) -> Option<MessageAttachment> {
+    let (_, attachments_json, _) = database::get_message_body_cache(app_handle, folder, uid).ok()??;
+    let attachments: Vec<MessageAttachment> = serde_json::from_str(&attachments_json?).ok()?;
+    attachments.into_iter().find(|a| a.part_id == part_id)
+}
+
+/// Reads an attachment's metadata, fetching and caching the body first if needed, so
+/// verdicts are based on the real file name rather than a placeholder.
+pub async fn attachment_metadata(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32, part_id: &str) -> Result<MessageAttachment, String> {
+    if let Some(attachment) = lookup_attachment(app_handle, folder, uid, part_id) {
+        return Ok(attachment);
+    }
+    let detail = message_body::fetch_and_cache_body_internal(app_handle, account, folder, uid).await?;
+    detail.attachments.into_iter()
+        .find(|a| a.part_id == part_id)
+        .ok_or_else(|| format!("Attachment {} not found in message {}", part_id, uid))
+}
+
+fn resolve_mime(declared: &str, name: &str) -> String {
+    // Many clients send attachments as application/octet-stream, so fall back to the extension
+    if declared.is_empty() || declared == "application/octet-stream" {
+        if let Some(guess) = mime_guess::from_path(name).first() {
+            return guess.essence_str().to_string();
+        }
+    }
+    declared.to_string()
+}
+
+pub fn classify(mime_type: &str, name: &str) -> PreviewKind {
+    let ext = name.rsplit('.').next().unwrap_or("").to_lowercase();
+    match mime_type {
+        "image/png" | "image/jpeg" | "image/jpg" | "image/gif" | "image/webp" | "image/bmp" => PreviewKind::Image,
+        "application/pdf" => PreviewKind::Pdf,
+        "text/csv" | "text/tab-separated-values" => PreviewKind::Csv,
+        _ if ext == "csv" || ext == "tsv" => PreviewKind::Csv,
+        m if m.starts_with("text/") => PreviewKind::Text,
+        "application/json" | "application/xml" => PreviewKind::Text,
+        _ if matches!(ext.as_str(), "txt" | "md" | "log" | "json" | "xml" | "ini" | "yaml" | "yml") => PreviewKind::Text,
+        _ => PreviewKind::Unsupported,
+    }
+}
+
+fn build_preview(part_id: &str, kind: PreviewKind, mime_type: String, bytes: &[u8], thumbnail_path: Option<&std::path::Path>) -> AttachmentPreview {
+    let mut preview = AttachmentPreview {
+        part_id: part_id.to_string(),
+        kind,
+        mime_type,
+        thumbnail_url: None,
+        text: None,
+        rows: None,
+        truncated: false,
+    };
+
+    if bytes.len() > MAX_PREVIEW_BYTES {
+        preview.kind = PreviewKind::Unsupported;
+        return preview;
+    }
+
+    match kind {
+        PreviewKind::Image => {
+            let Some(path) = thumbnail_path else { return preview };
+            match render_thumbnail(bytes, path) {
+                Ok(()) => preview.thumbnail_url = Some(format!("asset://localhost/{}", path.to_string_lossy().replace('\\', "/"))),
+                Err(e) => {
+                    log::warn!("Thumbnail generation failed for part {}: {}", part_id, e);
+                    preview.kind = PreviewKind::Unsupported;
+                }
+            }
+        }
+        PreviewKind::Pdf => {
+            // pdf-extract panics on some malformed documents, so contain it here
+            let owned = bytes.to_vec();
+            match std::panic::catch_unwind(move || pdf_extract::extract_text_from_mem(&owned)) {
+                Ok(Ok(text)) => {
+                    let (text, truncated) = truncate_text(&normalize_whitespace(&text));
+                    preview.text = Some(text);
+                    preview.truncated = truncated;
+                }
+                Ok(Err(e)) => log::warn!("PDF text extraction failed for part {}: {}", part_id, e),
+                Err(_) => log::warn!("PDF text extraction panicked for part {}", part_id),
+            }
+        }
+        PreviewKind::Text => {
+            let (text, truncated) = truncate_text(&String::from_utf8_lossy(bytes));
+            preview.text = Some(text);
+            preview.truncated = truncated;
+        }
+        PreviewKind::Csv => {
+            let decoded = String::from_utf8_lossy(bytes);
+            let delimiter = if preview.mime_type == "text/tab-separated-values" { '\t' } else { detect_delimiter(&decoded) };
+            let mut rows = parse_delimited(&decoded, delimiter);
+            preview.truncated = rows.len() > MAX_CSV_ROWS;
+            rows.truncate(MAX_CSV_ROWS);
+            preview.rows = Some(rows);
+            preview.text = Some(truncate_text(&decoded).0);
+        }
+        PreviewKind::Unsupported => {}
+    }
+
+    preview
+}
+
+fn render_thumbnail(bytes: &[u8], path: &std::path::Path) -> Result<(), String> {
+    let img = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    let thumb = img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    thumb.save_with_format(path, image::ImageFormat::Png).map_err(|e| e.to_string())
+}
+
+fn truncate_text(text: &str) -> (String, bool) {
+    match text.char_indices().nth(MAX_TEXT_CHARS) {
+        Some((idx, _)) => (text[..idx].to_string(), true),
+        None => (text.to_string(), false),
+    }
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn detect_delimiter(text: &str) -> char {
+    let first_line = text.lines().next().unwrap_or("");
+    [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|d| first_line.matches(*d).count())
+        .unwrap_or(',')
+}
+
+/// Minimal RFC 4180 parser: quoted fields, doubled quotes and embedded newlines.
+pub fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                if rows.len() > MAX_CSV_ROWS {
+                    return rows;
+                }
+            }
+            _ if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delimited_quotes() {
+        let rows = parse_delimited("name,amount\r\n\"Doe, Jane\",\"12\"\"\"\nBob,3", ',');
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1], vec!["Doe, Jane".to_string(), "12\"".to_string()]);
+        assert_eq!(rows[2], vec!["Bob".to_string(), "3".to_string()]);
+    }
+
+    #[test]
+    fn test_classify_falls_back_to_extension() {
+        assert_eq!(classify(&resolve_mime("application/octet-stream", "report.pdf"), "report.pdf"), PreviewKind::Pdf);
+        assert_eq!(classify("application/octet-stream", "data.csv"), PreviewKind::Csv);
+        assert_eq!(classify("application/zip", "archive.zip"), PreviewKind::Unsupported);
+    }
+}
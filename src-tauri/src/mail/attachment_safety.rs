@@ -0,0 +1,319 @@
+use once_cell::sync::Lazy;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "com", "scr", "pif", "msi", "msp", "dll", "cpl", "sys", "app", "dmg", "pkg", "deb", "rpm",
+    "apk", "jar", "lnk", "hta", "reg", "inf", "iso", "img", "vhd", "vhdx", "appx", "msix",
+];
+
+const SCRIPT_EXTENSIONS: &[&str] = &[
+    "bat", "cmd", "ps1", "psm1", "vbs", "vbe", "js", "jse", "wsf", "wsh", "sh", "bash", "zsh",
+    "py", "pl", "rb", "command", "scpt", "applescript",
+];
+
+/// Opened in a browser, where they can run scripts and pose as sign-in pages.
+const WEB_CONTENT_EXTENSIONS: &[&str] = &["html", "htm", "xhtml", "shtml", "mht", "mhtml", "svg", "svgz"];
+
+const MACRO_OFFICE_EXTENSIONS: &[&str] = &["docm", "dotm", "xlsm", "xltm", "xlam", "pptm", "potm", "ppam", "ppsm", "sldm"];
+
+/// Legacy binary Office formats can carry VBA macros without a distinguishing extension.
+const LEGACY_OFFICE_EXTENSIONS: &[&str] = &["doc", "dot", "xls", "xlt", "ppt", "pot", "pps"];
+
+/// Windows refuses these as file names regardless of extension.
+const RESERVED_WINDOWS_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Safe,
+    Caution,
+    Dangerous,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentRisk {
+    pub level: RiskLevel,
+    pub reasons: Vec<String>,
+    /// File name safe to offer as the default in the save dialog.
+    pub sanitized_name: String,
+    /// Type detected from the file's magic bytes, if recognised.
+    pub detected_type: Option<String>,
+    pub requires_acknowledgement: bool,
+}
+
+/// How long assessed bytes are kept for the save that usually follows the prompt.
+const STAGED_TTL: Duration = Duration::from_secs(10 * 60);
+/// Attachments staged at once; the oldest is dropped beyond this.
+const MAX_STAGED: usize = 4;
+
+/// Account email, folder, UID and part ID of a staged attachment.
+type StageKey = (String, String, u32, String);
+
+struct StagedAttachment {
+    staged_at: Instant,
+    bytes: Vec<u8>,
+}
+
+/// Recently assessed attachments, so saving one does not download it again.
+static STAGED: Lazy<Mutex<HashMap<StageKey, StagedAttachment>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn stage_key(account: &str, folder: &str, uid: u32, part_id: &str) -> StageKey {
+    (account.to_string(), folder.to_string(), uid, part_id.to_string())
+}
+
+/// Keeps the bytes of an assessed attachment for `take_staged`.
+pub fn stage(account: &str, folder: &str, uid: u32, part_id: &str, bytes: Vec<u8>) {
+    let Ok(mut staged) = STAGED.lock() else { return };
+    staged.retain(|_, s| s.staged_at.elapsed() < STAGED_TTL);
+    while staged.len() >= MAX_STAGED {
+        let Some(oldest) = staged.iter().min_by_key(|(_, s)| s.staged_at).map(|(k, _)| k.clone()) else { break };
+        staged.remove(&oldest);
+    }
+    staged.insert(stage_key(account, folder, uid, part_id), StagedAttachment { staged_at: Instant::now(), bytes });
+}
+
+/// Takes the staged bytes of exactly this attachment if they are still fresh.
+pub fn take_staged(account: &str, folder: &str, uid: u32, part_id: &str) -> Option<Vec<u8>> {
+    let mut staged = STAGED.lock().ok()?;
+    staged.remove(&stage_key(account, folder, uid, part_id))
+        .filter(|s| s.staged_at.elapsed() < STAGED_TTL)
+        .map(|s| s.bytes)
+}
+
+/// Produces a verdict for an attachment from its declared name, declared MIME type and content.
+pub fn assess_attachment(name: &str, declared_mime: &str, bytes: &[u8]) -> AttachmentRisk {
+    let mut level = RiskLevel::Safe;
+    let mut reasons = Vec::new();
+    let mut raise = |to: RiskLevel, reason: String, reasons: &mut Vec<String>| {
+        level = level.max(to);
+        reasons.push(reason);
+    };
+
+    let sanitized_name = sanitize_file_name(name);
+    if sanitized_name != name {
+        raise(RiskLevel::Caution, "File name contained path or reserved characters and was sanitized".to_string(), &mut reasons);
+    }
+
+    let lower = sanitized_name.to_lowercase();
+    let extensions: Vec<&str> = lower.split('.').skip(1).collect();
+    let ext = extensions.last().copied().unwrap_or("");
+
+    if EXECUTABLE_EXTENSIONS.contains(&ext) {
+        raise(RiskLevel::Dangerous, format!("Executable file type (.{})", ext), &mut reasons);
+    } else if SCRIPT_EXTENSIONS.contains(&ext) {
+        raise(RiskLevel::Dangerous, format!("Script file type (.{})", ext), &mut reasons);
+    } else if MACRO_OFFICE_EXTENSIONS.contains(&ext) {
+        raise(RiskLevel::Dangerous, format!("Macro-enabled Office document (.{})", ext), &mut reasons);
+    } else if WEB_CONTENT_EXTENSIONS.contains(&ext) || matches!(declared_mime.to_ascii_lowercase().as_str(), "text/html" | "image/svg+xml") {
+        raise(RiskLevel::Caution, "Web content that opens in a browser and can run scripts or imitate a sign-in page".to_string(), &mut reasons);
+    }
+
+    // "invoice.pdf.exe" style names hide the real extension when extensions are hidden
+    if extensions.len() >= 2 {
+        let inner = extensions[extensions.len() - 2];
+        let inner_is_document = mime_guess::from_ext(inner).first().is_some() && !is_active_content(inner);
+        if inner_is_document && is_active_content(ext) {
+            raise(RiskLevel::Dangerous, format!("Double extension disguises .{} as .{}", ext, inner), &mut reasons);
+        }
+    }
+
+    if name.contains('\u{202e}') {
+        raise(RiskLevel::Dangerous, "File name uses right-to-left override to disguise its extension".to_string(), &mut reasons);
+    }
+
+    let detected = sniff_magic(bytes);
+    if let Some(kind) = detected {
+        match kind {
+            "application/x-msdownload" | "application/x-executable" | "application/x-mach-binary" => {
+                raise(RiskLevel::Dangerous, "Content is a native executable".to_string(), &mut reasons);
+            }
+            "text/x-shellscript" => {
+                raise(RiskLevel::Dangerous, "Content is a script".to_string(), &mut reasons);
+            }
+            "application/x-ole-storage" if !LEGACY_OFFICE_EXTENSIONS.contains(&ext) && ext != "msg" => {
+                raise(RiskLevel::Caution, "Content is a legacy Office container that may contain macros".to_string(), &mut reasons);
+            }
+            "application/x-ole-storage" => {
+                raise(RiskLevel::Caution, "Legacy Office documents may contain macros".to_string(), &mut reasons);
+            }
+            _ => {}
+        }
+
+        if !magic_matches_declared(kind, declared_mime, ext) {
+            raise(
+                RiskLevel::Caution,
+                format!("Content looks like {} but was declared as {}", kind, if declared_mime.is_empty() { "unknown" } else { declared_mime }),
+                &mut reasons,
+            );
+        }
+    }
+
+    AttachmentRisk {
+        requires_acknowledgement: level != RiskLevel::Safe,
+        level,
+        reasons,
+        sanitized_name,
+        detected_type: detected.map(|s| s.to_string()),
+    }
+}
+
+fn is_active_content(ext: &str) -> bool {
+    EXECUTABLE_EXTENSIONS.contains(&ext)
+        || SCRIPT_EXTENSIONS.contains(&ext)
+        || MACRO_OFFICE_EXTENSIONS.contains(&ext)
+        || WEB_CONTENT_EXTENSIONS.contains(&ext)
+}
+
+/// Strips directory components, control characters and Windows-reserved names from an attachment name.
+pub fn sanitize_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+
+    let mut cleaned: String = base.chars()
+        .filter(|c| !c.is_control() && *c != '\u{202e}')
+        .map(|c| if matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+
+    cleaned = cleaned.trim().trim_end_matches('.').trim_start_matches('.').to_string();
+
+    let stem = cleaned.split('.').next().unwrap_or("").to_lowercase();
+    if RESERVED_WINDOWS_NAMES.contains(&stem.as_str()) {
+        cleaned = format!("_{}", cleaned);
+    }
+
+    if cleaned.is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// Rejects destination paths that are relative or climb out of their directory.
+pub fn validate_save_path(save_path: &str) -> Result<(), String> {
+    let path = Path::new(save_path);
+    if !path.is_absolute() {
+        return Err("Save path must be absolute".to_string());
+    }
+    if path.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err("Save path must not contain '..' components".to_string());
+    }
+    if path.file_name().is_none() {
+        return Err("Save path has no file name".to_string());
+    }
+    Ok(())
+}
+
+fn sniff_magic(bytes: &[u8]) -> Option<&'static str> {
+    let starts = |magic: &[u8]| bytes.starts_with(magic);
+
+    if starts(b"MZ") {
+        Some("application/x-msdownload")
+    } else if starts(b"\x7fELF") {
+        Some("application/x-executable")
+    } else if starts(&[0xCF, 0xFA, 0xED, 0xFE]) || starts(&[0xCE, 0xFA, 0xED, 0xFE]) || starts(&[0xCA, 0xFE, 0xBA, 0xBE]) {
+        Some("application/x-mach-binary")
+    } else if starts(b"#!") {
+        Some("text/x-shellscript")
+    } else if starts(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+        Some("application/x-ole-storage")
+    } else if starts(b"%PDF-") {
+        Some("application/pdf")
+    } else if starts(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if starts(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        Some("image/gif")
+    } else if starts(b"RIFF") && bytes.get(8..12) == Some(&b"WEBP"[..]) {
+        Some("image/webp")
+    } else if starts(b"PK\x03\x04") {
+        Some("application/zip")
+    } else {
+        None
+    }
+}
+
+fn magic_matches_declared(detected: &str, declared_mime: &str, ext: &str) -> bool {
+    let declared = declared_mime.to_lowercase();
+    // Generic declarations carry no claim to contradict
+    if declared.is_empty() || declared == "application/octet-stream" {
+        return !matches!(detected, "application/pdf" | "image/png" | "image/jpeg" | "image/gif" | "image/webp")
+            || mime_guess::from_ext(ext).iter().any(|m| m.essence_str() == detected);
+    }
+
+    match detected {
+        // OOXML, ODF, JAR and APK are all zip containers
+        "application/zip" => declared.contains("zip")
+            || declared.contains("openxmlformats")
+            || declared.contains("opendocument")
+            || declared.contains("java-archive")
+            || declared.contains("android")
+            || declared.contains("ms-")
+            || declared.contains("epub"),
+        "application/x-ole-storage" => declared.contains("ms") || declared.contains("vnd.") || declared.contains("outlook"),
+        "image/jpeg" => declared == "image/jpeg" || declared == "image/jpg" || declared == "image/pjpeg",
+        _ => declared == detected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_extension_executable() {
+        let risk = assess_attachment("invoice.pdf.exe", "application/pdf", b"MZ\x90\x00");
+        assert_eq!(risk.level, RiskLevel::Dangerous);
+        assert!(risk.requires_acknowledgement);
+        assert!(risk.reasons.iter().any(|r| r.contains("Double extension")));
+    }
+
+    #[test]
+    fn test_magic_mismatch_is_caution() {
+        let risk = assess_attachment("photo.png", "image/png", b"%PDF-1.7");
+        assert_eq!(risk.level, RiskLevel::Caution);
+        assert_eq!(risk.detected_type.as_deref(), Some("application/pdf"));
+    }
+
+    #[test]
+    fn test_web_content_needs_acknowledgement() {
+        for name in ["login.html", "Invoice.HTM", "logo.svg"] {
+            let risk = assess_attachment(name, "application/octet-stream", b"<html>");
+            assert_eq!(risk.level, RiskLevel::Caution, "{}", name);
+            assert!(risk.requires_acknowledgement);
+        }
+        assert_eq!(assess_attachment("statement.pdf.html", "text/html", b"<html>").level, RiskLevel::Dangerous);
+    }
+
+    #[test]
+    fn test_plain_pdf_is_safe() {
+        let risk = assess_attachment("report.pdf", "application/pdf", b"%PDF-1.4\n");
+        assert_eq!(risk.level, RiskLevel::Safe);
+        assert!(!risk.requires_acknowledgement);
+    }
+
+    #[test]
+    fn test_staged_bytes_only_match_their_part() {
+        stage("a@example.com", "inbox", 7, "2", b"first".to_vec());
+        stage("b@example.com", "inbox", 7, "2", b"second".to_vec());
+        assert_eq!(take_staged("a@example.com", "inbox", 7, "3"), None);
+        assert_eq!(take_staged("a@example.com", "inbox", 7, "2").as_deref(), Some(&b"first"[..]));
+        assert_eq!(take_staged("a@example.com", "inbox", 7, "2"), None);
+        assert_eq!(take_staged("b@example.com", "inbox", 7, "2").as_deref(), Some(&b"second"[..]));
+    }
+
+    #[test]
+    fn test_sanitize_path_traversal() {
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_file_name("..\\..\\Windows\\evil.dll"), "evil.dll");
+        assert_eq!(sanitize_file_name("CON.txt"), "_CON.txt");
+        assert_eq!(sanitize_file_name(".."), "attachment");
+    }
+}
@@ -0,0 +1,230 @@
+use serde::{Serialize, Deserialize};
+
+/// Header fields fetched alongside the body so we can show sender authentication.
+pub const AUTH_HEADER_FIELDS: &str = "AUTHENTICATION-RESULTS ARC-AUTHENTICATION-RESULTS ARC-SEAL ARC-MESSAGE-SIGNATURE DKIM-SIGNATURE FROM";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthStatus {
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+    None,
+    Policy,
+    TempError,
+    PermError,
+}
+
+impl AuthStatus {
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "pass" => AuthStatus::Pass,
+            "fail" | "hardfail" => AuthStatus::Fail,
+            "softfail" => AuthStatus::SoftFail,
+            "neutral" => AuthStatus::Neutral,
+            "policy" => AuthStatus::Policy,
+            "temperror" => AuthStatus::TempError,
+            "permerror" => AuthStatus::PermError,
+            _ => AuthStatus::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MechanismVerdict {
+    pub status: AuthStatus,
+    pub domain: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl MechanismVerdict {
+    pub fn none() -> Self {
+        Self { status: AuthStatus::None, domain: None, detail: None }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthVerdict {
+    pub authserv_id: Option<String>,
+    pub spf: MechanismVerdict,
+    pub dkim: MechanismVerdict,
+    pub dmarc: MechanismVerdict,
+    pub arc: MechanismVerdict,
+    /// Present only when the message was verified locally via `dkim::verify_dkim`.
+    pub local_dkim: Option<MechanismVerdict>,
+}
+
+/// Builds a verdict from the header block of a message.
+///
+/// Only the topmost Authentication-Results header is trusted, since that one was
+/// added by our own provider's MTA; anything below it may have been forged by the sender.
+pub fn parse_auth_headers(raw_headers: &[u8]) -> AuthVerdict {
+    let mut auth_results: Option<String> = None;
+    let mut arc_seals: Vec<String> = Vec::new();
+
+    if let Ok((headers, _)) = mailparse::parse_headers(raw_headers) {
+        for header in headers.iter() {
+            let key = header.get_key().to_lowercase();
+            match key.as_str() {
+                "authentication-results" if auth_results.is_none() => auth_results = Some(header.get_value()),
+                "arc-seal" => arc_seals.push(header.get_value()),
+                _ => {}
+            }
+        }
+    }
+
+    let mut verdict = match auth_results {
+        Some(value) => parse_authentication_results(&value),
+        None => AuthVerdict {
+            authserv_id: None,
+            spf: MechanismVerdict::none(),
+            dkim: MechanismVerdict::none(),
+            dmarc: MechanismVerdict::none(),
+            arc: MechanismVerdict::none(),
+            local_dkim: None,
+        },
+    };
+
+    // Fall back to the chain validation of the newest ARC-Seal when the
+    // Authentication-Results header did not report an arc= result.
+    if verdict.arc.status == AuthStatus::None {
+        if let Some(seal) = newest_arc_seal(&arc_seals) {
+            verdict.arc = seal;
+        }
+    }
+
+    verdict
+}
+
+pub fn parse_authentication_results(value: &str) -> AuthVerdict {
+    let unfolded = strip_comments(&value.split_whitespace().collect::<Vec<_>>().join(" "));
+    let mut sections = unfolded.split(';').map(|s| s.trim());
+
+    let authserv_id = sections.next()
+        .and_then(|s| s.split_whitespace().next())
+        .map(|s| s.to_string())
+        .filter(|s| !s.is_empty());
+
+    let mut verdict = AuthVerdict {
+        authserv_id,
+        spf: MechanismVerdict::none(),
+        dkim: MechanismVerdict::none(),
+        dmarc: MechanismVerdict::none(),
+        arc: MechanismVerdict::none(),
+        local_dkim: None,
+    };
+
+    for section in sections {
+        let mut tokens = section.split_whitespace();
+        let Some((method, result)) = tokens.next().and_then(|t| t.split_once('=')) else {
+            continue;
+        };
+
+        let props: Vec<(String, String)> = tokens
+            .filter_map(|t| t.split_once('='))
+            .map(|(k, v)| (k.to_lowercase(), v.trim_matches('"').to_string()))
+            .collect();
+
+        let prop = |names: &[&str]| -> Option<String> {
+            names.iter().find_map(|n| props.iter().find(|(k, _)| k == n).map(|(_, v)| v.clone()))
+        };
+
+        let status = AuthStatus::parse(result);
+        match method.to_lowercase().as_str() {
+            "spf" => {
+                let domain = prop(&["smtp.mailfrom", "smtp.helo"]).map(|v| domain_of(&v));
+                verdict.spf = MechanismVerdict { status, domain, detail: prop(&["smtp.mailfrom"]) };
+            }
+            "dkim" => {
+                // Multiple signatures are common; keep the first passing one, otherwise the first seen.
+                if verdict.dkim.status != AuthStatus::Pass {
+                    let domain = prop(&["header.d", "header.i"]).map(|v| domain_of(&v));
+                    verdict.dkim = MechanismVerdict { status, domain, detail: prop(&["header.s"]).map(|s| format!("selector={}", s)) };
+                }
+            }
+            "dmarc" => {
+                verdict.dmarc = MechanismVerdict { status, domain: prop(&["header.from"]), detail: None };
+            }
+            "arc" => {
+                verdict.arc = MechanismVerdict { status, domain: None, detail: None };
+            }
+            _ => {}
+        }
+    }
+
+    verdict
+}
+
+fn newest_arc_seal(seals: &[String]) -> Option<MechanismVerdict> {
+    seals.iter()
+        .filter_map(|seal| {
+            let tags = parse_tag_list(seal);
+            let instance: u32 = tags.iter().find(|(k, _)| k == "i")?.1.parse().ok()?;
+            let cv = tags.iter().find(|(k, _)| k == "cv").map(|(_, v)| v.clone()).unwrap_or_default();
+            let domain = tags.iter().find(|(k, _)| k == "d").map(|(_, v)| v.clone());
+            Some((instance, cv, domain))
+        })
+        .max_by_key(|(instance, _, _)| *instance)
+        .map(|(instance, cv, domain)| MechanismVerdict {
+            // cv=none is expected on the first instance and means the chain starts there
+            status: if cv.eq_ignore_ascii_case("none") && instance == 1 { AuthStatus::Pass } else { AuthStatus::parse(&cv) },
+            domain,
+            detail: Some(format!("i={}", instance)),
+        })
+}
+
+/// Parses a DKIM-style `tag=value; tag=value` list.
+pub fn parse_tag_list(value: &str) -> Vec<(String, String)> {
+    value.split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.split_whitespace().collect::<String>()))
+        .collect()
+}
+
+fn domain_of(value: &str) -> String {
+    value.rsplit('@').next().unwrap_or(value).to_lowercase()
+}
+
+fn strip_comments(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut depth = 0u32;
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gmail_authentication_results() {
+        let raw = b"Authentication-Results: mx.google.com;\r\n       dkim=pass header.i=@github.com header.s=pf2023 header.b=abc;\r\n       spf=pass (google.com: domain of noreply@github.com designates 1.2.3.4 as permitted sender) smtp.mailfrom=noreply@github.com;\r\n       dmarc=pass (p=REJECT sp=REJECT dis=NONE) header.from=github.com\r\nAuthentication-Results: forged.example; dkim=fail\r\n\r\n";
+
+        let verdict = parse_auth_headers(raw);
+        assert_eq!(verdict.authserv_id.as_deref(), Some("mx.google.com"));
+        assert_eq!(verdict.dkim.status, AuthStatus::Pass);
+        assert_eq!(verdict.dkim.domain.as_deref(), Some("github.com"));
+        assert_eq!(verdict.spf.status, AuthStatus::Pass);
+        assert_eq!(verdict.spf.domain.as_deref(), Some("github.com"));
+        assert_eq!(verdict.dmarc.status, AuthStatus::Pass);
+        assert_eq!(verdict.arc.status, AuthStatus::None);
+    }
+
+    #[test]
+    fn test_arc_seal_fallback() {
+        let raw = b"ARC-Seal: i=1; a=rsa-sha256; cv=none; d=google.com; s=arc-20160816; b=xyz\r\nARC-Seal: i=2; a=rsa-sha256; cv=fail; d=relay.example; s=arc; b=xyz\r\n\r\n";
+        let verdict = parse_auth_headers(raw);
+        assert_eq!(verdict.arc.status, AuthStatus::Fail);
+        assert_eq!(verdict.arc.domain.as_deref(), Some("relay.example"));
+    }
+}
@@ -249,7 +249,9 @@ impl BodyPrefetchManager {
                         };
                         let extracted_data = extracted_data_json.and_then(|json| serde_json::from_str(&json).ok());
                         
-                        let detail = MessageDetail { body: cached_body, attachments, extracted_data };
+                        let auth_results = database::get_message_auth_results(&app_handle, &job.key.folder, job.key.uid).unwrap_or(None);
+                        
+                        let detail = MessageDetail { body: cached_body, attachments, extracted_data, auth_results };
                         
                         for tx in job.responders {
                             let _ = tx.send(Ok(detail.clone()));
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use tauri::AppHandle;
 use tauri::Manager;
 use crate::mail::message_list::MessageHeader;
+use crate::mail::search_query::{FieldHighlight, SearchHit, SearchSort, MATCH_START, MATCH_END, parse_marked};
 
 #[derive(Debug, Clone, Default)]
 pub struct FolderSyncState {
@@ -188,6 +189,127 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
         conn.execute("ALTER TABLE messages ADD COLUMN message_id TEXT", ()).map_err(|e| e.to_string())?;
     }
 
+    let mut stmt = conn.prepare("PRAGMA table_info(messages)").unwrap();
+    let mut has_auth_results = false;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(1)?;
+        Ok(name)
+    }).unwrap();
+
+    for name in rows {
+        if let Ok(col_name) = name {
+            if col_name == "auth_results" {
+                has_auth_results = true;
+                break;
+            }
+        }
+    }
+
+    if !has_auth_results {
+        conn.execute("ALTER TABLE messages ADD COLUMN auth_results TEXT", ()).map_err(|e| e.to_string())?;
+    }
+
+    let mut stmt = conn.prepare("PRAGMA table_info(messages)").unwrap();
+    let mut has_attachment_text = false;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(1)?;
+        Ok(name)
+    }).unwrap();
+
+    for name in rows {
+        if let Ok(col_name) = name {
+            if col_name == "attachment_text" {
+                has_attachment_text = true;
+                break;
+            }
+        }
+    }
+
+    if !has_attachment_text {
+        conn.execute("ALTER TABLE messages ADD COLUMN attachment_text TEXT", ()).map_err(|e| e.to_string())?;
+    }
+
+    let mut stmt = conn.prepare("PRAGMA table_info(messages)").unwrap();
+    let mut has_size = false;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(1)?;
+        Ok(name)
+    }).unwrap();
+
+    for name in rows {
+        if let Ok(col_name) = name {
+            if col_name == "size" {
+                has_size = true;
+                break;
+            }
+        }
+    }
+
+    if !has_size {
+        conn.execute("ALTER TABLE messages ADD COLUMN size INTEGER", ()).map_err(|e| e.to_string())?;
+    }
+
+    let mut stmt = conn.prepare("PRAGMA table_info(messages)").unwrap();
+    let mut has_gm_msgid = false;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(1)?;
+        Ok(name)
+    }).unwrap();
+
+    for name in rows {
+        if let Ok(col_name) = name {
+            if col_name == "gm_msgid" {
+                has_gm_msgid = true;
+                break;
+            }
+        }
+    }
+
+    if !has_gm_msgid {
+        conn.execute("ALTER TABLE messages ADD COLUMN gm_msgid INTEGER", ()).map_err(|e| e.to_string())?;
+    }
+
+    let mut stmt = conn.prepare("PRAGMA table_info(messages)").unwrap();
+    let mut has_unsubscribe = false;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(1)?;
+        Ok(name)
+    }).unwrap();
+
+    for name in rows {
+        if let Ok(col_name) = name {
+            if col_name == "unsubscribe" {
+                has_unsubscribe = true;
+                break;
+            }
+        }
+    }
+
+    if !has_unsubscribe {
+        conn.execute("ALTER TABLE messages ADD COLUMN unsubscribe TEXT", ()).map_err(|e| e.to_string())?;
+    }
+
+    let mut stmt = conn.prepare("PRAGMA table_info(messages)").unwrap();
+    let mut has_sender_address = false;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(1)?;
+        Ok(name)
+    }).unwrap();
+
+    for name in rows {
+        if let Ok(col_name) = name {
+            if col_name == "sender_address" {
+                has_sender_address = true;
+                break;
+            }
+        }
+    }
+
+    if !has_sender_address {
+        conn.execute("ALTER TABLE messages ADD COLUMN sender_address TEXT", ()).map_err(|e| e.to_string())?;
+        backfill_sender_addresses(&conn)?;
+    }
+
     // Performance Indexes
     conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_folder_uid_desc ON messages(folder, uid DESC)", ()).map_err(|e| e.to_string())?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_folder_date ON messages(folder, date DESC)", ()).map_err(|e| e.to_string())?;
@@ -195,6 +317,8 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
     conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_seen ON messages(seen)", ()).map_err(|e| e.to_string())?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_flagged ON messages(flagged)", ()).map_err(|e| e.to_string())?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_message_id ON messages(message_id)", ()).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_gm_msgid ON messages(gm_msgid)", ()).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_sender_address ON messages(sender_address)", ()).map_err(|e| e.to_string())?;
 
     // FTS5 Setup & Resumable Contentless FTS Migration
     conn.execute(
@@ -209,8 +333,24 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
     let fts_version: Option<String> = stmt.query_row([], |row| row.get(0)).ok();
     let fts_version_num: u32 = fts_version.and_then(|v| v.parse().ok()).unwrap_or(1);
 
-    if fts_version_num < 2 {
-        log::info!("Performing resumable FTS migration to fts_version 2...");
+    // FTS reads column values back through this view (for snippet()/highlight()),
+    // so it must produce exactly what the triggers index.
+    conn.execute(
+        "CREATE VIEW IF NOT EXISTS messages_fts_source AS
+         SELECT rowid AS msg_rowid,
+                subject,
+                sender,
+                recipient,
+                coalesce(body, snippet, '') AS body,
+                coalesce(attachments_json, '') || ' ' || coalesce(attachment_text, '') AS attachment_names
+         FROM messages",
+        (),
+    ).map_err(|e| e.to_string())?;
+
+    // v3: attachment_names also carries text extracted from attachment previews
+    // v4: external content is the messages_fts_source view so snippet()/highlight() work
+    if fts_version_num < 4 {
+        log::info!("Performing resumable FTS migration to fts_version 4...");
         let _ = conn.execute("DROP TABLE IF EXISTS messages_fts_v2", ());
         conn.execute(
             "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts_v2 USING fts5(
@@ -219,21 +359,21 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
                 recipient,
                 body,
                 attachment_names,
-                content='messages',
-                content_rowid='rowid'
+                content='messages_fts_source',
+                content_rowid='msg_rowid'
             )",
             (),
         ).map_err(|e| e.to_string())?;
 
         let _ = conn.execute(
             "INSERT INTO messages_fts_v2(rowid, subject, sender, recipient, body, attachment_names)
-             SELECT rowid, subject, sender, recipient, coalesce(body, snippet, ''), coalesce(attachments_json, '') FROM messages",
+             SELECT msg_rowid, subject, sender, recipient, body, attachment_names FROM messages_fts_source",
             (),
         );
 
         let _ = conn.execute("DROP TABLE IF EXISTS messages_fts", ());
         conn.execute("ALTER TABLE messages_fts_v2 RENAME TO messages_fts", ()).map_err(|e| e.to_string())?;
-        conn.execute("INSERT OR REPLACE INTO app_metadata (key, value) VALUES ('fts_version', '2')", ()).map_err(|e| e.to_string())?;
+        conn.execute("INSERT OR REPLACE INTO app_metadata (key, value) VALUES ('fts_version', '4')", ()).map_err(|e| e.to_string())?;
     } else {
         conn.execute(
             "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
@@ -242,8 +382,8 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
                 recipient,
                 body,
                 attachment_names,
-                content='messages',
-                content_rowid='rowid'
+                content='messages_fts_source',
+                content_rowid='msg_rowid'
             )",
             (),
         ).map_err(|e| e.to_string())?;
@@ -255,7 +395,7 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
     conn.execute(
         "CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
             INSERT INTO messages_fts(rowid, subject, sender, recipient, body, attachment_names)
-            VALUES (new.rowid, new.subject, new.sender, new.recipient, coalesce(new.body, new.snippet, ''), coalesce(new.attachments_json, ''));
+            VALUES (new.rowid, new.subject, new.sender, new.recipient, coalesce(new.body, new.snippet, ''), coalesce(new.attachments_json, '') || ' ' || coalesce(new.attachment_text, ''));
         END",
         (),
     ).map_err(|e| e.to_string())?;
@@ -263,9 +403,9 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
     conn.execute(
         "CREATE TRIGGER IF NOT EXISTS messages_au AFTER UPDATE ON messages BEGIN
             INSERT INTO messages_fts(messages_fts, rowid, subject, sender, recipient, body, attachment_names)
-            VALUES ('delete', old.rowid, old.subject, old.sender, old.recipient, coalesce(old.body, old.snippet, ''), coalesce(old.attachments_json, ''));
+            VALUES ('delete', old.rowid, old.subject, old.sender, old.recipient, coalesce(old.body, old.snippet, ''), coalesce(old.attachments_json, '') || ' ' || coalesce(old.attachment_text, ''));
             INSERT INTO messages_fts(rowid, subject, sender, recipient, body, attachment_names)
-            VALUES (new.rowid, new.subject, new.sender, new.recipient, coalesce(new.body, new.snippet, ''), coalesce(new.attachments_json, ''));
+            VALUES (new.rowid, new.subject, new.sender, new.recipient, coalesce(new.body, new.snippet, ''), coalesce(new.attachments_json, '') || ' ' || coalesce(new.attachment_text, ''));
         END",
         (),
     ).map_err(|e| e.to_string())?;
@@ -301,6 +441,30 @@ pub fn init_db(app_handle: &AppHandle) -> Result<(), String> {
         (),
     ).map_err(|e| e.to_string())?;
 
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attachment_previews (
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            part_id TEXT NOT NULL,
+            preview_json TEXT NOT NULL,
+            extracted_text TEXT,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (folder, uid, part_id)
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+
+    crate::mail::saved_searches::init_saved_searches_table(&conn)?;
+    crate::mail::semantic_index::init_semantic_tables(&conn)?;
+    crate::mail::search_history::init_search_history_table(&conn)?;
+    crate::mail::rules::init_rules_tables(&conn)?;
+    crate::mail::snooze::init_snooze_table(&conn)?;
+    crate::mail::undo::init_undo_table(&conn)?;
+    crate::mail::pending_ops::init_pending_ops_table(&conn)?;
+    crate::mail::gmail_labels::init_labels_table(&conn)?;
+    crate::mail::tags::init_tags_table(&conn)?;
+    crate::mail::unsubscribe::init_unsubscribe_table(&conn)?;
+
     // Reset sync_in_progress on startup to avoid permanent soft-locks from previous crashes
     conn.execute("UPDATE folder_sync_state SET sync_in_progress = 0", ()).map_err(|e| e.to_string())?;
 
@@ -334,6 +498,9 @@ pub fn clear_messages(app_handle: &AppHandle, folder: &str) -> Result<(), String
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
     conn.execute("DELETE FROM messages WHERE folder = ?1", rusqlite::params![folder]).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM attachment_previews WHERE folder = ?1", rusqlite::params![folder]).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM message_embeddings WHERE folder = ?1", rusqlite::params![folder]).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM message_labels WHERE folder = ?1", rusqlite::params![folder]).map_err(|e| e.to_string())?;
 
     Ok(())
 }
@@ -348,6 +515,35 @@ pub fn get_highest_uid(app_handle: &AppHandle, folder: &str) -> Result<u32, Stri
     Ok(max_uid.unwrap_or(0))
 }
 
+/// The lowercased address of a `From` header value such as `Jane <jane@example.com>`.
+pub fn parse_sender_address(from: &str) -> Option<String> {
+    let addrs = mailparse::addrparse(from).ok()?;
+    addrs.iter().find_map(|a| match a {
+        mailparse::MailAddr::Single(info) => Some(info.addr.trim().to_lowercase()),
+        mailparse::MailAddr::Group(_) => None,
+    }).filter(|addr| addr.contains('@'))
+}
+
+/// Fills `sender_address` for rows cached before the column existed.
+fn backfill_sender_addresses(conn: &Connection) -> Result<(), String> {
+    let rows: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT rowid, sender FROM messages WHERE sender IS NOT NULL").map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(|e| e.to_string())?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+    conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+    {
+        let mut update = conn.prepare("UPDATE messages SET sender_address = ?1 WHERE rowid = ?2").map_err(|e| e.to_string())?;
+        for (rowid, sender) in rows {
+            if let Some(address) = parse_sender_address(&sender) {
+                update.execute(rusqlite::params![address, rowid]).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 pub fn insert_or_update_messages(app_handle: &AppHandle, messages: &[MessageHeader]) -> Result<(), String> {
     if messages.is_empty() {
         return Ok(());
@@ -359,17 +555,19 @@ pub fn insert_or_update_messages(app_handle: &AppHandle, messages: &[MessageHead
     let tx = conn.transaction().map_err(|e| e.to_string())?;
     {
         let mut stmt = tx.prepare(
-            "INSERT INTO messages (folder, uid, uid_validity, subject, sender, recipient, date, seen, flagged, snippet, message_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            "INSERT INTO messages (folder, uid, uid_validity, subject, sender, recipient, date, seen, flagged, snippet, message_id, unsubscribe, sender_address)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
              ON CONFLICT(folder, uid) DO UPDATE SET
                 subject = excluded.subject,
                 sender = excluded.sender,
+                sender_address = excluded.sender_address,
                 recipient = excluded.recipient,
                 date = excluded.date,
                 seen = excluded.seen,
                 flagged = excluded.flagged,
                 snippet = excluded.snippet,
-                message_id = excluded.message_id"
+                message_id = excluded.message_id,
+                unsubscribe = COALESCE(excluded.unsubscribe, messages.unsubscribe)"
         ).map_err(|e| e.to_string())?;
 
         for msg in messages {
@@ -385,6 +583,8 @@ pub fn insert_or_update_messages(app_handle: &AppHandle, messages: &[MessageHead
                 if msg.flagged { 1 } else { 0 },
                 msg.snippet.as_deref().unwrap_or(""),
                 &msg.message_id,
+                msg.unsubscribe.as_ref().and_then(|u| serde_json::to_string(u).ok()),
+                parse_sender_address(&msg.from),
             ]).map_err(|e| e.to_string())?;
         }
     }
@@ -394,10 +594,24 @@ pub fn insert_or_update_messages(app_handle: &AppHandle, messages: &[MessageHead
 }
 
 pub fn load_cached_messages(app_handle: &AppHandle, limit: usize) -> Result<Vec<MessageHeader>, String> {
-    load_messages_page(app_handle, "inbox", None, limit as u32)
+    load_messages_page(app_handle, "inbox", None, None, limit as u32, None)
 }
 
-pub fn load_messages_page(app_handle: &AppHandle, folder: &str, before_uid: Option<u32>, limit: u32) -> Result<Vec<MessageHeader>, String> {
+/// Loads a page of a folder, newest first. `tag` restricts real folders and Starred to
+/// messages carrying that tag. `before_folder` is the source folder of the `before_uid`
+/// message, which lists spanning folders need to tell apart equal UIDs.
+pub fn load_messages_page(app_handle: &AppHandle, folder: &str, before_uid: Option<u32>, before_folder: Option<&str>, limit: u32, tag: Option<&str>) -> Result<Vec<MessageHeader>, String> {
+    let before = before_uid.map(|uid| crate::mail::saved_searches::PageAnchor { uid, folder: before_folder.map(str::to_string) });
+    if let Some(id) = folder.strip_prefix(crate::mail::saved_searches::FOLDER_PREFIX) {
+        return crate::mail::saved_searches::load_saved_search_page(app_handle, id, before, limit);
+    }
+    if let Some(label) = folder.strip_prefix(crate::mail::gmail_labels::FOLDER_PREFIX) {
+        return crate::mail::gmail_labels::load_label_page(app_handle, label, before, limit);
+    }
+    if let Some(tag) = tag.filter(|t| !t.trim().is_empty()) {
+        return crate::mail::tags::load_tagged_page(app_handle, &folder.to_lowercase(), tag, before, limit);
+    }
+
     let db_path = get_db_path(app_handle)?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
@@ -416,6 +630,7 @@ pub fn load_messages_page(app_handle: &AppHandle, folder: &str, before_uid: Opti
             thread_id: row.get(10).unwrap_or(None),
             to: row.get(11).unwrap_or(None),
             message_id: row.get(12).unwrap_or(None),
+            unsubscribe: crate::mail::unsubscribe::from_column(row.get(13).unwrap_or(None)),
         })
     };
 
@@ -423,9 +638,9 @@ pub fn load_messages_page(app_handle: &AppHandle, folder: &str, before_uid: Opti
 
     if folder.to_lowercase() == "starred" {
         // Starred uses date-based sorting and pagination
-        let mut query = "SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id
+        let mut query = format!("SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id, unsubscribe
              FROM messages 
-             WHERE flagged = 1".to_string();
+             WHERE flagged = 1 AND {} AND {}", crate::mail::snooze::not_snoozed_sql("messages"), crate::mail::gmail_labels::unique_copy_sql("messages"));
              
         let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
@@ -463,26 +678,28 @@ pub fn load_messages_page(app_handle: &AppHandle, folder: &str, before_uid: Opti
         }
     } else {
         if let Some(uid) = before_uid {
-            let mut stmt = conn.prepare(
-                "SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id
+            let mut stmt = conn.prepare(&format!(
+                "SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id, unsubscribe
                  FROM messages 
-                 WHERE folder = ?1 AND uid < ?2
+                 WHERE folder = ?1 AND uid < ?2 AND {}
                  ORDER BY uid DESC 
-                 LIMIT ?3"
-            ).map_err(|e| e.to_string())?;
+                 LIMIT ?3",
+                crate::mail::snooze::not_snoozed_sql("messages")
+            )).map_err(|e| e.to_string())?;
 
             let msg_iter = stmt.query_map(rusqlite::params![folder, uid, limit], parse_row).map_err(|e| e.to_string())?;
             for msg in msg_iter {
                 messages.push(msg.map_err(|e| e.to_string())?);
             }
         } else {
-            let mut stmt = conn.prepare(
-                "SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id
+            let mut stmt = conn.prepare(&format!(
+                "SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id, unsubscribe
                  FROM messages 
-                 WHERE folder = ?1
+                 WHERE folder = ?1 AND {}
                  ORDER BY uid DESC 
-                 LIMIT ?2"
-            ).map_err(|e| e.to_string())?;
+                 LIMIT ?2",
+                crate::mail::snooze::not_snoozed_sql("messages")
+            )).map_err(|e| e.to_string())?;
 
             let msg_iter = stmt.query_map(rusqlite::params![folder, limit], parse_row).map_err(|e| e.to_string())?;
             for msg in msg_iter {
@@ -606,6 +823,65 @@ pub fn update_message_body(app_handle: &AppHandle, folder: &str, uid: u32, body:
     Ok(())
 }
 
+pub fn get_message_auth_results(app_handle: &AppHandle, folder: &str, uid: u32) -> Result<Option<crate::mail::auth_results::AuthVerdict>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare("SELECT auth_results FROM messages WHERE folder = ?1 AND uid = ?2").map_err(|e| e.to_string())?;
+    let json: Option<String> = stmt.query_row(rusqlite::params![folder, uid], |row| row.get(0)).unwrap_or(None);
+
+    Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+}
+
+pub fn update_message_auth_results(app_handle: &AppHandle, folder: &str, uid: u32, verdict: &crate::mail::auth_results::AuthVerdict) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let json = serde_json::to_string(verdict).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE messages SET auth_results = ?1 WHERE folder = ?2 AND uid = ?3",
+        rusqlite::params![json, folder, uid],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn get_attachment_preview_cache(app_handle: &AppHandle, folder: &str, uid: u32, part_id: &str) -> Result<Option<String>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare("SELECT preview_json FROM attachment_previews WHERE folder = ?1 AND uid = ?2 AND part_id = ?3").map_err(|e| e.to_string())?;
+    let json = stmt.query_row(rusqlite::params![folder, uid, part_id], |row| row.get(0)).ok();
+
+    Ok(json)
+}
+
+/// Stores a preview and refreshes the message's `attachment_text` so the FTS triggers re-index it.
+pub fn save_attachment_preview(app_handle: &AppHandle, folder: &str, uid: u32, part_id: &str, preview_json: &str, extracted_text: Option<&str>) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let now = chrono::Utc::now().timestamp();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT OR REPLACE INTO attachment_previews (folder, uid, part_id, preview_json, extracted_text, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![folder, uid, part_id, preview_json, extracted_text, now],
+    ).map_err(|e| e.to_string())?;
+
+    if extracted_text.is_some() {
+        tx.execute(
+            "UPDATE messages SET attachment_text = (
+                SELECT group_concat(extracted_text, ' ') FROM attachment_previews
+                WHERE folder = ?1 AND uid = ?2 AND extracted_text IS NOT NULL
+             ) WHERE folder = ?1 AND uid = ?2",
+            rusqlite::params![folder, uid],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
 pub fn get_unfetched_recent_uids(app_handle: &AppHandle, folder: &str, limit: u32) -> Result<Vec<u32>, String> {
     let db_path = get_db_path(app_handle)?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
@@ -662,6 +938,30 @@ pub fn set_message_flagged(app_handle: &AppHandle, folder: &str, uid: u32, flagg
     Ok(())
 }
 
+/// Sets the `seen` or `flagged` column for many messages in one transaction.
+fn set_messages_flag_column(app_handle: &AppHandle, folder: &str, uids: &[u32], column: &str, value: bool) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx.prepare(&format!("UPDATE messages SET {} = ?1 WHERE folder = ?2 AND uid = ?3", column))
+            .map_err(|e| e.to_string())?;
+        for uid in uids {
+            stmt.execute(rusqlite::params![if value { 1 } else { 0 }, folder, uid]).map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn set_messages_seen(app_handle: &AppHandle, folder: &str, uids: &[u32], seen: bool) -> Result<(), String> {
+    set_messages_flag_column(app_handle, folder, uids, "seen", seen)
+}
+
+pub fn set_messages_flagged(app_handle: &AppHandle, folder: &str, uids: &[u32], flagged: bool) -> Result<(), String> {
+    set_messages_flag_column(app_handle, folder, uids, "flagged", flagged)
+}
+
 pub fn delete_message_local(app_handle: &AppHandle, folder: &str, uid: u32) -> Result<(), String> {
     let db_path = get_db_path(app_handle)?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
@@ -670,7 +970,60 @@ pub fn delete_message_local(app_handle: &AppHandle, folder: &str, uid: u32) -> R
         "DELETE FROM messages WHERE folder = ?1 AND uid = ?2",
         rusqlite::params![folder, uid],
     ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM attachment_previews WHERE folder = ?1 AND uid = ?2",
+        rusqlite::params![folder, uid],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM message_embeddings WHERE folder = ?1 AND uid = ?2",
+        rusqlite::params![folder, uid],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM message_labels WHERE folder = ?1 AND uid = ?2",
+        rusqlite::params![folder, uid],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Applies several moves out of `folder` in one transaction. Each UID is rewritten to its
+/// `(folder, uid, uid_validity)` destination, or dropped along with its cached data when the
+/// destination is not a synced folder.
+pub fn move_messages_local(app_handle: &AppHandle, folder: &str, moves: &[(u32, Option<(&str, u32, u32)>)]) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    for (uid, dest) in moves {
+        match dest {
+            Some((dest_folder, dest_uid, dest_validity)) => {
+                tx.execute(
+                    "UPDATE OR REPLACE messages SET folder = ?1, uid = ?2, uid_validity = ?3 WHERE folder = ?4 AND uid = ?5",
+                    rusqlite::params![dest_folder, dest_uid, dest_validity, folder, uid],
+                ).map_err(|e| e.to_string())?;
+                for table in ["attachment_previews", "message_embeddings", "message_labels"] {
+                    tx.execute(
+                        &format!("UPDATE OR REPLACE {} SET folder = ?1, uid = ?2 WHERE folder = ?3 AND uid = ?4", table),
+                        rusqlite::params![dest_folder, dest_uid, folder, uid],
+                    ).map_err(|e| e.to_string())?;
+                }
+            }
+            None => {
+                for table in ["messages", "attachment_previews", "message_embeddings", "message_labels"] {
+                    tx.execute(
+                        &format!("DELETE FROM {} WHERE folder = ?1 AND uid = ?2", table),
+                        rusqlite::params![folder, uid],
+                    ).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        tx.execute(
+            "DELETE FROM snoozes WHERE server_side = 0 AND folder = ?1 AND uid = ?2",
+            rusqlite::params![folder, uid],
+        ).map_err(|e| e.to_string())?;
+    }
 
+    tx.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -788,100 +1141,173 @@ pub fn update_global_notification_time(app_handle: &AppHandle, timestamp: i64) -
 pub fn get_global_unread_count(app_handle: &AppHandle) -> Result<u32, String> {
     let db_path = get_db_path(app_handle)?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    let mut stmt = conn.prepare("SELECT COUNT(*) FROM messages WHERE seen = 0").unwrap();
+    let mut stmt = conn.prepare(&format!("SELECT COUNT(*) FROM messages WHERE seen = 0 AND {}", crate::mail::snooze::not_snoozed_sql("messages"))).unwrap();
     let count: u32 = stmt.query_row([], |row| row.get(0)).unwrap_or(0);
     Ok(count)
 }
 
-pub fn search_messages_local(app_handle: &AppHandle, folder: &str, query: &str, limit: u32) -> Result<Vec<MessageHeader>, String> {
+pub fn search_messages_local(app_handle: &AppHandle, folder: &str, query: &str, limit: u32, sort: SearchSort) -> Result<Vec<SearchHit>, String> {
     let db_path = get_db_path(app_handle)?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
-    // Clean query for FTS MATCH
-    let cleaned: Vec<String> = query.to_lowercase().split_whitespace()
-        .filter(|s| !s.is_empty())
-        .map(|s| {
-            let s_clean = s.replace(|c: char| !c.is_ascii_alphanumeric(), "");
-            format!("\"{}\"*", s_clean)
-        })
-        .collect();
-    let match_query = cleaned.join(" ");
-
-    if match_query.is_empty() {
+    let parsed = crate::mail::search_query::parse_query(query);
+    let Some(filter) = parsed.to_sql() else {
         return Ok(Vec::new());
+    };
+
+    let mut sql = String::from(
+        "SELECT m.uid, m.uid_validity, m.subject, m.sender, m.date, m.seen, m.flagged, m.snippet, m.folder, m.has_attachments, m.thread_id, m.recipient, m.message_id, m.unsubscribe, m.rowid"
+    );
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+
+    if let Some(rank_match) = &filter.rank_match {
+        sql.push_str(", r.score FROM messages m");
+        sql.push_str(" LEFT JOIN (SELECT rowid AS fts_rowid, bm25(messages_fts) AS score FROM messages_fts WHERE messages_fts MATCH ?) r ON r.fts_rowid = m.rowid");
+        params.push(rusqlite::types::Value::Text(rank_match.clone()));
+    } else {
+        sql.push_str(", NULL FROM messages m");
+    }
+
+    sql.push_str(" WHERE (");
+    sql.push_str(&filter.clause);
+    sql.push(')');
+    params.extend(filter.params);
+    sql.push_str(" AND ");
+    sql.push_str(&crate::mail::snooze::not_snoozed_sql("m"));
+
+    if folder != "all" {
+        sql.push_str(" AND m.folder = ?");
+        params.push(rusqlite::types::Value::Text(folder.to_string()));
+    } else if !filter.clause.contains("m.folder") {
+        // Show each Gmail message once, unless the query picks a folder's copy with `in:`
+        sql.push_str(" AND ");
+        sql.push_str(&crate::mail::gmail_labels::unique_copy_sql("m"));
     }
 
     // score = bm25(messages_fts) + recent_bonus + flag_bonus
     // In SQLite FTS5, bm25() is smaller (more negative) for better matches.
     // So we subtract bonuses to make the score even more negative.
-    let sql = if folder == "all" {
-        "SELECT m.uid, m.uid_validity, m.subject, m.sender, m.date, m.seen, m.flagged, m.snippet, m.folder, m.has_attachments, m.thread_id, m.recipient, m.message_id
-         FROM messages m
-         JOIN messages_fts f ON m.rowid = f.rowid
-         WHERE messages_fts MATCH ?1
-         ORDER BY (bm25(messages_fts) - (m.flagged * 5.0) - (CASE WHEN m.seen = 0 THEN 2.0 ELSE 0.0 END) - (m.date / 100000.0)) ASC
-         LIMIT ?2"
+    // Queries with only flag/date/size filters have nothing to rank, so they sort by date.
+    if sort == SearchSort::Relevance && filter.rank_match.is_some() {
+        sql.push_str(" ORDER BY (coalesce(r.score, 0.0) - (m.flagged * 5.0) - (CASE WHEN m.seen = 0 THEN 2.0 ELSE 0.0 END) - (m.date / 100000.0)) ASC");
     } else {
-        "SELECT m.uid, m.uid_validity, m.subject, m.sender, m.date, m.seen, m.flagged, m.snippet, m.folder, m.has_attachments, m.thread_id, m.recipient, m.message_id
-         FROM messages m
-         JOIN messages_fts f ON m.rowid = f.rowid
-         WHERE m.folder = ?1 AND messages_fts MATCH ?2
-         ORDER BY (bm25(messages_fts) - (m.flagged * 5.0) - (CASE WHEN m.seen = 0 THEN 2.0 ELSE 0.0 END) - (m.date / 100000.0)) ASC
-         LIMIT ?3"
-    };
+        sql.push_str(" ORDER BY m.date DESC");
+    }
+    sql.push_str(" LIMIT ?");
+    params.push(rusqlite::types::Value::Integer(limit as i64));
 
-    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
-    
-    let mut messages = Vec::new();
-    if folder == "all" {
-        let rows = stmt.query_map(rusqlite::params![match_query, limit], |row| {
-            Ok(MessageHeader {
-                uid: row.get(0)?,
-                uid_validity: row.get(1)?,
-                subject: row.get(2)?,
-                from: row.get(3)?,
-                date: row.get(4)?,
-                seen: row.get(5)?,
-                flagged: row.get(6)?,
-                snippet: row.get(7)?,
-                folder: row.get(8)?,
-                has_attachments: row.get(9)?,
-                thread_id: row.get(10)?,
-                to: row.get(11)?,
-                message_id: row.get(12)?,
-            })
-        }).map_err(|e| e.to_string())?;
-        for row in rows {
-            if let Ok(msg) = row {
-                messages.push(msg);
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+
+    let mut hits = Vec::new();
+    let mut rowids = Vec::new();
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        let header = MessageHeader {
+            uid: row.get(0)?,
+            uid_validity: row.get(1)?,
+            subject: row.get(2)?,
+            from: row.get(3)?,
+            date: row.get(4)?,
+            seen: row.get(5)?,
+            flagged: row.get(6)?,
+            snippet: row.get(7)?,
+            folder: row.get(8)?,
+            has_attachments: row.get(9)?,
+            thread_id: row.get(10)?,
+            to: row.get(11)?,
+            message_id: row.get(12)?,
+            unsubscribe: crate::mail::unsubscribe::from_column(row.get(13)?),
+        };
+        Ok((row.get::<_, i64>(14)?, header, row.get::<_, Option<f64>>(15)?))
+    }).map_err(|e| e.to_string())?;
+    for row in rows {
+        if let Ok((rowid, header, score)) = row {
+            rowids.push(rowid);
+            hits.push(SearchHit { header, score, highlights: Vec::new() });
+        }
+    }
+
+    if let Some(rank_match) = &filter.rank_match {
+        let mut highlights = load_search_highlights(&conn, rank_match, &rowids)?;
+        for (hit, rowid) in hits.iter_mut().zip(rowids.iter()) {
+            if let Some(h) = highlights.remove(rowid) {
+                hit.highlights = h;
             }
         }
-    } else {
-        let rows = stmt.query_map(rusqlite::params![folder, match_query, limit], |row| {
-            Ok(MessageHeader {
-                uid: row.get(0)?,
-                uid_validity: row.get(1)?,
-                subject: row.get(2)?,
-                from: row.get(3)?,
-                date: row.get(4)?,
-                seen: row.get(5)?,
-                flagged: row.get(6)?,
-                snippet: row.get(7)?,
-                folder: row.get(8)?,
-                has_attachments: row.get(9)?,
-                thread_id: row.get(10)?,
-                to: row.get(11)?,
-                message_id: row.get(12)?,
-            })
-        }).map_err(|e| e.to_string())?;
-        for row in rows {
-            if let Ok(msg) = row {
-                messages.push(msg);
+    }
+
+    Ok(hits)
+}
+
+/// Computes highlights only for the page of hits being returned; running
+/// highlight()/snippet() inside the ranking subquery would cost one call per match.
+fn load_search_highlights(conn: &Connection, rank_match: &str, rowids: &[i64]) -> Result<std::collections::HashMap<i64, Vec<FieldHighlight>>, String> {
+    let mut highlights = std::collections::HashMap::new();
+    if rowids.is_empty() {
+        return Ok(highlights);
+    }
+
+    let placeholders: Vec<String> = rowids.iter().map(|_| "?".to_string()).collect();
+    let sql = format!(
+        "SELECT rowid,
+                highlight(messages_fts, 0, ?1, ?2),
+                highlight(messages_fts, 1, ?1, ?2),
+                highlight(messages_fts, 2, ?1, ?2),
+                snippet(messages_fts, 3, ?1, ?2, '…', 24),
+                snippet(messages_fts, 4, ?1, ?2, '…', 12)
+         FROM messages_fts
+         WHERE messages_fts MATCH ?3 AND rowid IN ({})",
+        placeholders.join(",")
+    );
+
+    let mut params: Vec<rusqlite::types::Value> = vec![
+        rusqlite::types::Value::Text(MATCH_START.to_string()),
+        rusqlite::types::Value::Text(MATCH_END.to_string()),
+        rusqlite::types::Value::Text(rank_match.to_string()),
+    ];
+    params.extend(rowids.iter().map(|&r| rusqlite::types::Value::Integer(r)));
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        let rowid: i64 = row.get(0)?;
+        let mut fields = Vec::new();
+        for (idx, field) in [(1, "subject"), (2, "from"), (3, "to"), (4, "body"), (5, "attachments")] {
+            let marked: Option<String> = row.get(idx)?;
+            if let Some(marked) = marked {
+                let (text, ranges) = parse_marked(&marked);
+                if !ranges.is_empty() {
+                    fields.push(FieldHighlight { field: field.to_string(), text, ranges });
+                }
             }
         }
+        Ok((rowid, fields))
+    }).map_err(|e| e.to_string())?;
+
+    for row in rows {
+        if let Ok((rowid, fields)) = row {
+            highlights.insert(rowid, fields);
+        }
     }
+    Ok(highlights)
+}
 
-    Ok(messages)
+pub fn update_message_sizes(app_handle: &AppHandle, folder: &str, sizes: &[(u32, u32)]) -> Result<(), String> {
+    if sizes.is_empty() {
+        return Ok(());
+    }
+
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx.prepare("UPDATE messages SET size = ?1 WHERE folder = ?2 AND uid = ?3").map_err(|e| e.to_string())?;
+        for (uid, size) in sizes {
+            stmt.execute(rusqlite::params![size, folder, uid]).map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(())
 }
 
 pub fn get_existing_uids(app_handle: &AppHandle, folder: &str, uids: &[u32]) -> Result<std::collections::HashSet<u32>, String> {
@@ -919,7 +1345,7 @@ pub fn get_messages_by_uids(app_handle: &AppHandle, folder: &str, uids: &[u32])
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
     let placeholders: Vec<String> = uids.iter().map(|_| "?".to_string()).collect();
-    let sql = format!("SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id FROM messages WHERE folder = ? AND uid IN ({}) ORDER BY date DESC, uid DESC", placeholders.join(","));
+    let sql = format!("SELECT uid, uid_validity, subject, sender, date, seen, flagged, snippet, folder, has_attachments, thread_id, recipient, message_id, unsubscribe FROM messages WHERE folder = ? AND uid IN ({}) ORDER BY date DESC, uid DESC", placeholders.join(","));
     
     let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
     let mut params: Vec<rusqlite::types::Value> = vec![rusqlite::types::Value::Text(folder.to_string())];
@@ -942,6 +1368,7 @@ pub fn get_messages_by_uids(app_handle: &AppHandle, folder: &str, uids: &[u32])
             thread_id: row.get(10)?,
             to: row.get(11)?,
             message_id: row.get(12)?,
+            unsubscribe: crate::mail::unsubscribe::from_column(row.get(13)?),
         })
     }).map_err(|e| e.to_string())?;
     
@@ -0,0 +1,374 @@
+use crate::mail::auth_results::{parse_tag_list, AuthStatus, MechanismVerdict};
+use base64::Engine;
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::TokioAsyncResolver;
+use once_cell::sync::Lazy;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Looks up DNS TXT records for DKIM selectors. Kept behind a trait so tests can stub keys.
+pub trait DkimKeyResolver: Send + Sync {
+    fn resolve_txt(&self, name: &str) -> Pin<Box<dyn Future<Output = Result<Vec<String>, String>> + Send>>;
+}
+
+/// Resolves TXT records with the nameservers the system is configured to use.
+pub struct SystemKeyResolver;
+
+static SYSTEM_RESOLVER: Lazy<Result<TokioAsyncResolver, String>> = Lazy::new(|| {
+    TokioAsyncResolver::tokio_from_system_conf().map_err(|e| format!("DNS resolver unavailable: {}", e))
+});
+
+impl DkimKeyResolver for SystemKeyResolver {
+    fn resolve_txt(&self, name: &str) -> Pin<Box<dyn Future<Output = Result<Vec<String>, String>> + Send>> {
+        let name_clone = name.to_string();
+        let resolver = SYSTEM_RESOLVER.clone();
+        Box::pin(async move {
+            let resolver = resolver?;
+            match resolver.txt_lookup(name_clone).await {
+                // A TXT record may be split into several character-strings
+                Ok(lookup) => Ok(lookup.iter()
+                    .map(|txt| txt.txt_data().iter().map(|part| String::from_utf8_lossy(part)).collect::<String>())
+                    .collect()),
+                Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Ok(Vec::new()),
+                Err(e) => Err(format!("DNS lookup failed: {}", e)),
+            }
+        })
+    }
+}
+
+struct DkimSignature {
+    algorithm: String,
+    signature: Vec<u8>,
+    body_hash: String,
+    header_canon: Canonicalization,
+    body_canon: Canonicalization,
+    domain: String,
+    selector: String,
+    signed_headers: Vec<String>,
+    body_length: Option<usize>,
+    raw_field: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Canonicalization {
+    Simple,
+    Relaxed,
+}
+
+/// Verifies every DKIM-Signature of a raw RFC 822 message. A message passes if any
+/// signature verifies; otherwise the first signature's verdict is reported.
+pub async fn verify_dkim(raw: &[u8], resolver: &dyn DkimKeyResolver) -> MechanismVerdict {
+    verify_dkim_covering(raw, resolver, &[]).await
+}
+
+/// Like `verify_dkim`, but only signatures whose h= tag lists every header in `required`
+/// can pass. Others are reported with `AuthStatus::Policy`.
+pub async fn verify_dkim_covering(raw: &[u8], resolver: &dyn DkimKeyResolver, required: &[&str]) -> MechanismVerdict {
+    let normalized = normalize_line_endings(raw);
+    let (header_block, body) = split_message(&normalized);
+    let fields = split_header_fields(header_block);
+
+    let mut first_failure = None;
+    for sig_field in fields.iter().filter(|f| field_name(f).eq_ignore_ascii_case("dkim-signature")) {
+        let verdict = verify_signature(sig_field, &fields, body, resolver, required).await;
+        if verdict.status == AuthStatus::Pass {
+            return verdict;
+        }
+        first_failure.get_or_insert(verdict);
+    }
+
+    first_failure.unwrap_or_else(|| MechanismVerdict { status: AuthStatus::None, domain: None, detail: Some("Message is not signed".to_string()) })
+}
+
+async fn verify_signature(sig_field: &str, fields: &[String], body: &[u8], resolver: &dyn DkimKeyResolver, required: &[&str]) -> MechanismVerdict {
+    let sig = match parse_signature(sig_field) {
+        Ok(s) => s,
+        Err(e) => return MechanismVerdict { status: AuthStatus::PermError, domain: None, detail: Some(e) },
+    };
+
+    let verdict = |status: AuthStatus, detail: &str| MechanismVerdict {
+        status,
+        domain: Some(sig.domain.clone()),
+        detail: Some(detail.to_string()),
+    };
+
+    if sig.algorithm != "rsa-sha256" {
+        return verdict(AuthStatus::PermError, &format!("Unsupported algorithm {}", sig.algorithm));
+    }
+    if let Some(missing) = required.iter().find(|h| !sig.signed_headers.iter().any(|s| s.eq_ignore_ascii_case(h))) {
+        return verdict(AuthStatus::Policy, &format!("Signature does not cover {}", missing));
+    }
+
+    // 1. Body hash
+    let mut canon_body = canonicalize_body(body, sig.body_canon);
+    if let Some(len) = sig.body_length {
+        canon_body.truncate(len);
+    }
+    let computed_bh = base64::engine::general_purpose::STANDARD.encode(Sha256::digest(&canon_body));
+    if computed_bh != sig.body_hash {
+        return verdict(AuthStatus::Fail, "Body hash mismatch");
+    }
+
+    // 2. Public key
+    let key_name = format!("{}._domainkey.{}", sig.selector, sig.domain);
+    let records = match resolver.resolve_txt(&key_name).await {
+        Ok(r) => r,
+        Err(e) => return verdict(AuthStatus::TempError, &e),
+    };
+    let Some(record) = records.iter().find(|r| r.contains("p=")) else {
+        return verdict(AuthStatus::PermError, "No DKIM key published for selector");
+    };
+    let key_b64 = parse_tag_list(record).into_iter().find(|(k, _)| k == "p").map(|(_, v)| v).unwrap_or_default();
+    if key_b64.is_empty() {
+        return verdict(AuthStatus::PermError, "DKIM key has been revoked");
+    }
+    let Ok(key_der) = base64::engine::general_purpose::STANDARD.decode(key_b64) else {
+        return verdict(AuthStatus::PermError, "Malformed DKIM key");
+    };
+    let Ok(public_key) = RsaPublicKey::from_public_key_der(&key_der).or_else(|_| RsaPublicKey::from_pkcs1_der(&key_der)) else {
+        return verdict(AuthStatus::PermError, "Unreadable DKIM key");
+    };
+
+    // 3. Header hash
+    let hashed = Sha256::digest(build_header_hash_input(fields, &sig));
+    match public_key.verify(Pkcs1v15Sign::new::<Sha256>(), &hashed, &sig.signature) {
+        Ok(_) => verdict(AuthStatus::Pass, &format!("Verified with selector {}", sig.selector)),
+        Err(_) => verdict(AuthStatus::Fail, "Signature does not match headers"),
+    }
+}
+
+fn parse_signature(field: &str) -> Result<DkimSignature, String> {
+    let value = field.split_once(':').map(|(_, v)| v).unwrap_or_default();
+    let tags = parse_tag_list(value);
+    let tag = |name: &str| tags.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+
+    let (header_canon, body_canon) = match tag("c").as_deref().map(|c| c.to_lowercase()) {
+        None => (Canonicalization::Simple, Canonicalization::Simple),
+        Some(c) => {
+            let mut parts = c.split('/');
+            let h = parts.next().unwrap_or("simple");
+            let b = parts.next().unwrap_or("simple");
+            let parse = |v: &str| if v == "relaxed" { Canonicalization::Relaxed } else { Canonicalization::Simple };
+            (parse(h), parse(b))
+        }
+    };
+
+    Ok(DkimSignature {
+        algorithm: tag("a").ok_or("Missing a= tag")?.to_lowercase(),
+        signature: base64::engine::general_purpose::STANDARD
+            .decode(tag("b").ok_or("Missing b= tag")?)
+            .map_err(|_| "Malformed b= tag".to_string())?,
+        body_hash: tag("bh").ok_or("Missing bh= tag")?,
+        header_canon,
+        body_canon,
+        domain: tag("d").ok_or("Missing d= tag")?.to_lowercase(),
+        selector: tag("s").ok_or("Missing s= tag")?,
+        signed_headers: tag("h").ok_or("Missing h= tag")?.split(':').map(|h| h.trim().to_lowercase()).collect(),
+        body_length: tag("l").and_then(|l| l.parse().ok()),
+        raw_field: field.to_string(),
+    })
+}
+
+fn build_header_hash_input(fields: &[String], sig: &DkimSignature) -> Vec<u8> {
+    let mut input = Vec::new();
+    let mut used = vec![false; fields.len()];
+
+    // Repeated header names are consumed bottom-up, as required by RFC 6376 5.4.2
+    for name in &sig.signed_headers {
+        if let Some(idx) = (0..fields.len()).rev().find(|&i| !used[i] && field_name(&fields[i]).eq_ignore_ascii_case(name)) {
+            used[idx] = true;
+            input.extend_from_slice(canonicalize_header(&fields[idx], sig.header_canon).as_bytes());
+            input.extend_from_slice(b"\r\n");
+        }
+    }
+
+    let stripped = strip_signature_value(&sig.raw_field);
+    input.extend_from_slice(canonicalize_header(&stripped, sig.header_canon).as_bytes());
+    input
+}
+
+/// Empties the b= tag of the signature header while keeping every other byte intact.
+fn strip_signature_value(field: &str) -> String {
+    let Some((name, value)) = field.split_once(':') else { return field.to_string() };
+    let stripped: Vec<String> = value.split(';')
+        .map(|tag| match tag.split_once('=') {
+            Some((k, _)) if k.trim().eq_ignore_ascii_case("b") => format!("{}=", k),
+            _ => tag.to_string(),
+        })
+        .collect();
+    format!("{}:{}", name, stripped.join(";"))
+}
+
+fn canonicalize_header(field: &str, canon: Canonicalization) -> String {
+    match canon {
+        Canonicalization::Simple => field.to_string(),
+        Canonicalization::Relaxed => {
+            let (name, value) = field.split_once(':').unwrap_or((field, ""));
+            let unfolded = value.replace("\r\n", "");
+            let collapsed = unfolded.split([' ', '\t']).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ");
+            format!("{}:{}", name.trim().to_lowercase(), collapsed.trim())
+        }
+    }
+}
+
+fn canonicalize_body(body: &[u8], canon: Canonicalization) -> Vec<u8> {
+    let text = String::from_utf8_lossy(body);
+    let mut lines: Vec<String> = text.split("\r\n").map(|l| l.to_string()).collect();
+
+    if canon == Canonicalization::Relaxed {
+        for line in lines.iter_mut() {
+            let collapsed = line.split([' ', '\t']).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ");
+            let leading_ws = line.starts_with(' ') || line.starts_with('\t');
+            *line = if leading_ws && !collapsed.is_empty() { format!(" {}", collapsed) } else { collapsed };
+        }
+    }
+
+    while lines.last().map(|l| l.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+
+    if lines.is_empty() {
+        return if canon == Canonicalization::Simple { b"\r\n".to_vec() } else { Vec::new() };
+    }
+
+    let mut out = lines.join("\r\n").into_bytes();
+    out.extend_from_slice(b"\r\n");
+    out
+}
+
+fn normalize_line_endings(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len() + raw.len() / 40);
+    let mut prev = 0u8;
+    for &b in raw {
+        if b == b'\n' && prev != b'\r' {
+            out.push(b'\r');
+        }
+        out.push(b);
+        prev = b;
+    }
+    out
+}
+
+fn split_message(raw: &[u8]) -> (&[u8], &[u8]) {
+    match raw.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(idx) => (&raw[..idx + 2], &raw[idx + 4..]),
+        None => (raw, &[]),
+    }
+}
+
+/// Splits the header block into raw fields, keeping folded continuation lines attached.
+fn split_header_fields(header_block: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(header_block);
+    let mut fields: Vec<String> = Vec::new();
+    for line in text.split("\r\n") {
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = fields.last_mut() {
+                last.push_str("\r\n");
+                last.push_str(line);
+            }
+        } else {
+            fields.push(line.to_string());
+        }
+    }
+    fields
+}
+
+fn field_name(field: &str) -> &str {
+    field.split(':').next().unwrap_or("").trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StubResolver(Vec<String>);
+    impl DkimKeyResolver for StubResolver {
+        fn resolve_txt(&self, _name: &str) -> Pin<Box<dyn Future<Output = Result<Vec<String>, String>> + Send>> {
+            let records = self.0.clone();
+            Box::pin(async move { Ok(records) })
+        }
+    }
+
+    fn signed_message(body: &str, body_hash: &str) -> Vec<u8> {
+        format!(
+            "DKIM-Signature: v=1; a=rsa-sha256; c=relaxed/relaxed; d=example.com; s=sel;\r\n\th=from:subject; bh={}; b=AAAA\r\nFrom: a@example.com\r\nSubject: Hi\r\n\r\n{}",
+            body_hash, body
+        ).into_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_body_hash_mismatch_fails() {
+        let raw = signed_message("Hello\r\n", "bm90LXRoZS1yZWFsLWhhc2g=");
+        let verdict = verify_dkim(&raw, &StubResolver(vec![])).await;
+        assert_eq!(verdict.status, AuthStatus::Fail);
+        assert_eq!(verdict.domain.as_deref(), Some("example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_is_permerror() {
+        let bh = base64::engine::general_purpose::STANDARD.encode(Sha256::digest(b"Hello\r\n"));
+        let raw = signed_message("Hello  \r\n\r\n", &bh);
+        let verdict = verify_dkim(&raw, &StubResolver(vec!["v=spf1 -all".to_string()])).await;
+        assert_eq!(verdict.status, AuthStatus::PermError);
+    }
+
+    /// Signs `from:subject` and the body with a throwaway 1024-bit key, the way a sender would.
+    fn sign(headers: &str, body: &str) -> (Vec<u8>, String) {
+        use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey};
+
+        const TEST_KEY: &str = "MIICdwIBADANBgkqhkiG9w0BAQEFAASCAmEwggJdAgEAAoGBANa3P9oLIYBqGpncDyt9Zy5D2Z2kASC/SMP+nztHuxcOHLs8GNLjXNs08zABWy3Iw64Zyeg+C5En99vYY5+Vr7G6wjQ1FqT7j0zL8r2tacR2vaEwzwaNiPO1+1Ih10tjUcieLh3sqecNehhH4/NXSWx4LvgcEpg6FRb9pBE2qidXAgMBAAECgYEAjU+1Wk+REO7D6xcFdN3nlm9ZPYK+q0q5HSTqrx0aaKYFCsUWoVS5vBsxIhsCYzOKdX7Lf2m2OyZO3gWgK1tccSuZsvqMiDnyl/QzwuxBJrc4XKymY6kE85iMBD4y9fu9g4U6NCQW7WxcXI5ID9W+2661EtU8z+SKWnY9WvMi4aECQQDrMSvJ7wUmWKff+V4L+zFu8L3GBGtRTMFI0HptlJonPWkufUoc1hijMXaBjbPX8kmwNf1gTu0ohGxgYKo7IiRtAkEA6bZQlwf8GYYF6UTyU5u2VMxRtlE9fDzJbzwxUESRGNO68R3o1TLJDc04a3/454w2YclYfzoI1nvmITGKu9K4UwJBAIatKX4AuNo0eizvBsOlm7EDeigh77ImWafweaq2JW/C4rAUXpQUpRcOA+Y71ngZ7chRoj279GH3Mngd4lzqOCECQBlUgDGOVP1zMCrTJSx4oO9Z4bVP4skXUYGz1Whstgkp0YYWhsiso2vShT3pddGqua4pg2TupkyQyyVn2DB5Ky8CQEbzsnyuzFDz4RjmYcuTvOYYXBMBr+bniwxqyaJ2Y9JwqXQVqrzgSwBEokI9N0JL8gY/S34hLXrwwzNsFcoP1Cc=";
+        let der = base64::engine::general_purpose::STANDARD.decode(TEST_KEY).unwrap();
+        let private_key = rsa::RsaPrivateKey::from_pkcs8_der(&der).unwrap();
+        let public_b64 = base64::engine::general_purpose::STANDARD.encode(private_key.to_public_key().to_public_key_der().unwrap().as_bytes());
+
+        let bh = base64::engine::general_purpose::STANDARD.encode(Sha256::digest(canonicalize_body(body.as_bytes(), Canonicalization::Relaxed)));
+        let unsigned = format!("DKIM-Signature: v=1; a=rsa-sha256; c=relaxed/relaxed; d=example.com; s=sel;\r\n\th=from:subject; bh={}; b=", bh);
+        let mut fields = split_header_fields(headers.as_bytes());
+        fields.insert(0, unsigned.clone());
+        let sig = parse_signature(&format!("{}AAAA", unsigned)).unwrap();
+        let hashed = Sha256::digest(build_header_hash_input(&fields, &sig));
+        let b = base64::engine::general_purpose::STANDARD.encode(private_key.sign(Pkcs1v15Sign::new::<Sha256>(), &hashed).unwrap());
+
+        (format!("{}{}\r\n{}\r\n{}", unsigned, b, headers, body).into_bytes(), format!("v=DKIM1; k=rsa; p={}", public_b64))
+    }
+
+    #[tokio::test]
+    async fn test_valid_signature_passes() {
+        let (raw, record) = sign("From: a@example.com\r\nSubject: Hi\r\n", "Hello  there\r\n\r\n");
+        let verdict = verify_dkim(&raw, &StubResolver(vec![record.clone()])).await;
+        assert_eq!(verdict.status, AuthStatus::Pass);
+        assert_eq!(verdict.domain.as_deref(), Some("example.com"));
+
+        let tampered = String::from_utf8(raw.clone()).unwrap().replace("Subject: Hi", "Subject: Hey");
+        let verdict = verify_dkim(tampered.as_bytes(), &StubResolver(vec![record.clone()])).await;
+        assert_eq!(verdict.status, AuthStatus::Fail);
+
+        // A broken signature added in transit must not hide the valid one below it
+        let mut resigned = b"DKIM-Signature: v=1; a=rsa-sha256; d=list.example; s=x; h=from; bh=AAAA; b=AAAA\r\n".to_vec();
+        resigned.extend_from_slice(&raw);
+        let verdict = verify_dkim(&resigned, &StubResolver(vec![record])).await;
+        assert_eq!(verdict.status, AuthStatus::Pass);
+    }
+
+    #[tokio::test]
+    async fn test_required_headers_must_be_signed() {
+        let (raw, record) = sign("From: a@example.com\r\nSubject: Hi\r\n", "Hello\r\n");
+        let verdict = verify_dkim_covering(&raw, &StubResolver(vec![record.clone()]), &["From", "subject"]).await;
+        assert_eq!(verdict.status, AuthStatus::Pass);
+        let verdict = verify_dkim_covering(&raw, &StubResolver(vec![record]), &["from", "list-unsubscribe"]).await;
+        assert_eq!(verdict.status, AuthStatus::Policy);
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_message() {
+        let verdict = verify_dkim(b"From: a@example.com\r\n\r\nbody", &StubResolver(vec![])).await;
+        assert_eq!(verdict.status, AuthStatus::None);
+    }
+}
@@ -9,8 +9,9 @@ pub mod invoice;
 pub mod account;
 pub mod provider_registry;
 pub mod commerce;
+pub mod phishing;
 
-pub const CURRENT_EXTRACTOR_VERSION: u32 = 9;
+pub const CURRENT_EXTRACTOR_VERSION: u32 = 10;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ExtractionSource {
@@ -33,6 +34,7 @@ pub enum EntityType {
     TransactionReference,
     SubscriptionReference,
     SchemaOrgObject,
+    SecurityWarning,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -62,7 +64,16 @@ pub struct ExtractedData {
     pub entities: Vec<ExtractedEntity>,
 }
 
-pub fn run_extraction_pipeline(html: &str, text: &str) -> ExtractedData {
+/// Message-level facts that extractors cannot derive from the body alone.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractionContext {
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    pub sender_is_known: bool,
+    pub known_domains: Vec<String>,
+}
+
+pub fn run_extraction_pipeline(html: &str, text: &str, context: &ExtractionContext) -> ExtractedData {
     let mut entities = Vec::new();
 
     let now = std::time::SystemTime::now()
@@ -78,6 +89,7 @@ pub fn run_extraction_pipeline(html: &str, text: &str) -> ExtractedData {
     entities.extend(commerce::extract(html, text));
     entities.extend(invoice::extract(html, text));
     entities.extend(account::extract(html, text));
+    entities.extend(phishing::extract(html, text, context));
 
     ExtractedData {
         version: CURRENT_EXTRACTOR_VERSION,
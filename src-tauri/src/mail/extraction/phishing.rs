@@ -0,0 +1,374 @@
+use super::provider_registry::PROVIDERS;
+use super::{EntityType, ExtractedEntity, ExtractionContext, ExtractionSource, Provenance};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+
+const REPORT_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Serialize)]
+struct RiskSignal {
+    kind: &'static str,
+    detail: String,
+    weight: f32,
+}
+
+pub fn extract(html: &str, text: &str, context: &ExtractionContext) -> Vec<ExtractedEntity> {
+    let Some(sender) = context.sender.as_deref() else {
+        return Vec::new();
+    };
+    let (display_name, address) = split_sender(sender);
+    let Some(sender_domain) = address.rsplit_once('@').map(|(_, d)| d.to_ascii_lowercase()) else {
+        return Vec::new();
+    };
+
+    let mut signals = Vec::new();
+    signals.extend(display_name_mismatch(&display_name, &address, &sender_domain));
+    signals.extend(lookalike_domain(&sender_domain, &context.known_domains));
+    signals.extend(link_mismatches(html));
+    signals.extend(urgent_payment_language(&format!("{}\n{}", context.subject.as_deref().unwrap_or(""), text)));
+
+    // Being a stranger is not suspicious on its own, only as a multiplier for other signals
+    if !context.sender_is_known && !signals.is_empty() {
+        signals.push(RiskSignal {
+            kind: "first_time_sender",
+            detail: format!("You have never corresponded with {}", address),
+            weight: 0.15,
+        });
+    }
+
+    let score = signals.iter().map(|s| s.weight).sum::<f32>().min(1.0);
+    if score < REPORT_THRESHOLD {
+        return Vec::new();
+    }
+
+    let level = if score >= 0.8 { "high" } else { "medium" };
+    let summary = signals.iter().map(|s| s.detail.clone()).collect::<Vec<_>>().join("; ");
+
+    vec![ExtractedEntity {
+        id: "security:phishing:0".to_string(),
+        entity_type: EntityType::SecurityWarning,
+        provider: None,
+        value: level.to_string(),
+        confidence: score,
+        provenance: Provenance {
+            source: ExtractionSource::Html,
+            extractor: "phishing.rs".to_string(),
+        },
+        evidence: Some(summary),
+        metadata: json!({
+            "score": score,
+            "level": level,
+            "senderAddress": address,
+            "senderDomain": sender_domain,
+            "signals": signals,
+        }),
+    }]
+}
+
+fn split_sender(sender: &str) -> (String, String) {
+    match (sender.rfind('<'), sender.rfind('>')) {
+        (Some(start), Some(end)) if end > start => (
+            sender[..start].trim().trim_matches('"').to_string(),
+            sender[start + 1..end].trim().to_ascii_lowercase(),
+        ),
+        _ => (String::new(), sender.trim().to_ascii_lowercase()),
+    }
+}
+
+fn display_name_mismatch(display_name: &str, address: &str, sender_domain: &str) -> Vec<RiskSignal> {
+    let mut signals = Vec::new();
+    if display_name.is_empty() {
+        return signals;
+    }
+
+    // "support@paypal.com" <attacker@evil.test>
+    if let Ok(re) = Regex::new(r"(?i)[A-Z0-9._%+-]+@[A-Z0-9.-]+\.[A-Z]{2,}") {
+        if let Some(m) = re.find(display_name) {
+            if !m.as_str().eq_ignore_ascii_case(address) {
+                signals.push(RiskSignal {
+                    kind: "display_name_address",
+                    detail: format!("Display name shows {} but mail came from {}", m.as_str(), address),
+                    weight: 0.4,
+                });
+            }
+        }
+    }
+
+    // "PayPal Billing" <billing@paypa1-secure.test>
+    let lower_name = display_name.to_ascii_lowercase();
+    for provider in PROVIDERS {
+        if lower_name.contains(&provider.name.to_ascii_lowercase())
+            && !provider.domains.iter().any(|d| domain_matches(sender_domain, d) || same_name(sender_domain, d))
+        {
+            signals.push(RiskSignal {
+                kind: "display_name_brand",
+                detail: format!("Claims to be {} but was sent from {}", provider.name, sender_domain),
+                weight: 0.35,
+            });
+            break;
+        }
+    }
+
+    signals
+}
+
+fn lookalike_domain(sender_domain: &str, known_domains: &[String]) -> Vec<RiskSignal> {
+    let sender_base = registrable_domain(sender_domain);
+    let mut signals = Vec::new();
+
+    if sender_domain.split('.').any(|label| label.starts_with("xn--")) {
+        signals.push(RiskSignal {
+            kind: "punycode_domain",
+            detail: format!("Sender domain {} uses internationalized characters", sender_domain),
+            weight: 0.25,
+        });
+    }
+
+    let candidates: Vec<String> = PROVIDERS.iter()
+        .flat_map(|p| p.domains.iter().map(|d| registrable_domain(d)))
+        .chain(known_domains.iter().map(|d| registrable_domain(d)))
+        .collect();
+
+    // An exact match with any known domain means nothing to flag, wherever it appears in
+    // the list; otherwise a lookalike of an earlier candidate would win.
+    if candidates.contains(&sender_base) {
+        return signals;
+    }
+    // Brands run regional sites under the same name (amazon.de, acme.co.uk), so only
+    // altered names count as imitations
+    if candidates.iter().any(|c| same_name(&sender_base, c)) {
+        return signals;
+    }
+
+    let sender_label = sender_base.split('.').next().unwrap_or("");
+    for candidate_base in candidates {
+        let candidate_label = candidate_base.split('.').next().unwrap_or("");
+        if candidate_label.len() < 4 {
+            continue;
+        }
+
+        if normalize_homoglyphs(sender_label) == normalize_homoglyphs(candidate_label) {
+            signals.push(RiskSignal {
+                kind: "homoglyph_domain",
+                detail: format!("{} imitates {}", sender_base, candidate_base),
+                weight: 0.45,
+            });
+            break;
+        }
+
+        let distance = edit_distance(sender_label, candidate_label);
+        if distance > 0 && distance <= if candidate_label.len() >= 8 { 2 } else { 1 } {
+            signals.push(RiskSignal {
+                kind: "lookalike_domain",
+                detail: format!("{} is one typo away from {}", sender_base, candidate_base),
+                weight: 0.35,
+            });
+            break;
+        }
+
+        // paypal-secure-login.test, amazon.account-verify.test
+        if sender_domain.contains(candidate_label) && !domain_matches(sender_domain, &candidate_base) {
+            signals.push(RiskSignal {
+                kind: "embedded_brand_domain",
+                detail: format!("{} embeds the name of {}", sender_domain, candidate_base),
+                weight: 0.3,
+            });
+            break;
+        }
+    }
+
+    signals
+}
+
+fn link_mismatches(html: &str) -> Vec<RiskSignal> {
+    let Ok(anchor_re) = Regex::new(r#"(?si)<a\b[^>]*href\s*=\s*["']([^"']+)["'][^>]*>(.*?)</a>"#) else {
+        return Vec::new();
+    };
+    let Ok(tag_re) = Regex::new(r"(?s)<[^>]+>") else {
+        return Vec::new();
+    };
+    let Ok(domain_re) = Regex::new(r"(?i)^(?:https?://)?(?:www\.)?([a-z0-9-]+(?:\.[a-z0-9-]+)+)") else {
+        return Vec::new();
+    };
+
+    let mut mismatched = Vec::new();
+    for cap in anchor_re.captures_iter(html) {
+        let href = cap.get(1).map(|m| m.as_str()).unwrap_or("");
+        let link_text = tag_re.replace_all(cap.get(2).map(|m| m.as_str()).unwrap_or(""), "");
+        let link_text = link_text.trim();
+
+        let Some(href_domain) = url::Url::parse(href).ok().and_then(|u| u.host_str().map(|h| h.to_ascii_lowercase())) else {
+            continue;
+        };
+        let Some(text_domain) = domain_re.captures(link_text).and_then(|c| c.get(1)).map(|m| m.as_str().to_ascii_lowercase()) else {
+            continue;
+        };
+
+        if registrable_domain(&text_domain) != registrable_domain(&href_domain) {
+            mismatched.push(format!("{} -> {}", text_domain, href_domain));
+        }
+    }
+
+    if mismatched.is_empty() {
+        return Vec::new();
+    }
+
+    vec![RiskSignal {
+        kind: "link_text_mismatch",
+        detail: format!("Links point somewhere other than they claim ({})", mismatched.iter().take(3).cloned().collect::<Vec<_>>().join(", ")),
+        weight: 0.3,
+    }]
+}
+
+fn urgent_payment_language(text: &str) -> Vec<RiskSignal> {
+    let urgency = Regex::new(r"(?i)\b(urgent|immediately|within 24 hours|final notice|account (?:has been )?(?:suspended|locked|disabled)|verify your (?:account|identity)|unusual (?:activity|sign-in))\b")
+        .ok()
+        .and_then(|re| re.find(text).map(|m| m.as_str().to_string()));
+    let payment = Regex::new(r"(?i)\b(wire transfer|gift cards?|bank details|payment (?:overdue|failed|details)|update (?:your )?(?:billing|payment)|bitcoin|crypto wallet)\b")
+        .ok()
+        .and_then(|re| re.find(text).map(|m| m.as_str().to_string()));
+
+    match (urgency, payment) {
+        (Some(u), Some(p)) => vec![RiskSignal {
+            kind: "urgent_payment",
+            detail: format!("Pressures you to act (\"{}\") on payment (\"{}\")", u, p),
+            weight: 0.3,
+        }],
+        (Some(u), None) => vec![RiskSignal {
+            kind: "urgent_language",
+            detail: format!("Uses pressure language (\"{}\")", u),
+            weight: 0.15,
+        }],
+        (None, Some(p)) => vec![RiskSignal {
+            kind: "payment_request",
+            detail: format!("Asks about payment (\"{}\")", p),
+            weight: 0.1,
+        }],
+        (None, None) => Vec::new(),
+    }
+}
+
+fn domain_matches(domain: &str, known: &str) -> bool {
+    domain == known || domain.ends_with(&format!(".{}", known))
+}
+
+/// Whether two domains register the same name under different suffixes (amazon.com, amazon.co.uk).
+fn same_name(a: &str, b: &str) -> bool {
+    let (a, b) = (registrable_domain(a), registrable_domain(b));
+    a.split('.').next() == b.split('.').next()
+}
+
+/// Approximates the registrable domain without a public suffix list (example.co.uk, mail.example.com).
+fn registrable_domain(domain: &str) -> String {
+    let labels: Vec<&str> = domain.trim_end_matches('.').split('.').collect();
+    if labels.len() <= 2 {
+        return labels.join(".");
+    }
+    let tld = labels[labels.len() - 1];
+    let sld = labels[labels.len() - 2];
+    let take = if tld.len() == 2 && sld.len() <= 3 { 3 } else { 2 };
+    labels[labels.len().saturating_sub(take)..].join(".")
+}
+
+fn normalize_homoglyphs(label: &str) -> String {
+    let mapped: String = label.chars().map(|c| match c {
+        '0' | 'о' | 'ο' => 'o',
+        '1' | 'ı' | 'і' | '|' => 'l',
+        '3' | 'е' => 'e',
+        '5' | 'ѕ' => 's',
+        '@' | 'а' | 'α' => 'a',
+        'р' => 'p',
+        'с' => 'c',
+        'х' => 'x',
+        'у' => 'y',
+        _ => c.to_ascii_lowercase(),
+    }).collect();
+    // "i" and "l" are visually interchangeable in most sans-serif fonts
+    mapped.replace("rn", "m").replace("vv", "w").replace('i', "l")
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(sender: &str, known: bool) -> ExtractionContext {
+        ExtractionContext {
+            sender: Some(sender.to_string()),
+            subject: None,
+            sender_is_known: known,
+            known_domains: vec!["acme-corp.com".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_brand_impersonation_is_flagged() {
+        let html = r#"<a href="https://paypa1-login.test/verify">https://www.paypal.com/account</a>"#;
+        let text = "Your account has been suspended. Update your billing details immediately.";
+        let entities = extract(html, text, &context("\"PayPal Support\" <service@paypa1.com>", false));
+
+        assert_eq!(entities.len(), 1);
+        let signals = entities[0].metadata["signals"].as_array().unwrap();
+        let kinds: Vec<&str> = signals.iter().filter_map(|s| s["kind"].as_str()).collect();
+        assert!(kinds.contains(&"display_name_brand"));
+        assert!(kinds.contains(&"homoglyph_domain"));
+        assert!(kinds.contains(&"link_text_mismatch"));
+        assert_eq!(entities[0].value, "high");
+    }
+
+    #[test]
+    fn test_legitimate_sender_is_clean() {
+        let html = r#"<a href="https://github.com/notifications">github.com/notifications</a>"#;
+        let entities = extract(html, "New comment on your pull request", &context("GitHub <noreply@github.com>", true));
+        assert!(entities.is_empty());
+    }
+
+    #[test]
+    fn test_lookalike_of_known_contact() {
+        let signals = lookalike_domain("acme-c0rp.com", &["acme-corp.com".to_string()]);
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].kind, "homoglyph_domain");
+    }
+
+    #[test]
+    fn test_exact_match_wins_over_earlier_lookalike() {
+        let known = ["acme-c0rp.com".to_string(), "acme-corp.com".to_string()];
+        assert!(lookalike_domain("acme-corp.com", &known).is_empty());
+    }
+
+    #[test]
+    fn test_same_name_on_another_tld_is_not_a_lookalike() {
+        assert!(lookalike_domain("amazon.de", &[]).is_empty());
+        assert!(lookalike_domain("amazon.co.uk", &[]).is_empty());
+        assert!(lookalike_domain("acme-corp.de", &["acme-corp.com".to_string()]).is_empty());
+
+        let entities = extract("", "Your order has shipped", &context("Amazon <shipment-tracking@amazon.de>", false));
+        assert!(entities.is_empty());
+        // Altered names are still caught on any TLD
+        assert_eq!(lookalike_domain("arnazon.de", &[])[0].kind, "homoglyph_domain");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("amazon", "amazon"), 0);
+        assert_eq!(edit_distance("amazon", "arnazon"), 2);
+        assert_eq!(edit_distance("github", "githb"), 1);
+    }
+}
@@ -28,7 +28,7 @@ pub struct ProviderDetection {
     pub matched_domain: Option<String>,
 }
 
-const PROVIDERS: &[Provider] = &[
+pub const PROVIDERS: &[Provider] = &[
     Provider {
         name: "Steam",
         category: ProviderCategory::Gaming,
@@ -1,14 +1,27 @@
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::Mutex;
 use crate::auth::account::MailProvider;
 
+/// Junk mailboxes that custom servers advertised with the `\Junk` attribute, by IMAP host.
+/// Many of them call it "Spam", so the "Junk" default only applies until a sync finds it.
+static JUNK_MAILBOXES: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MailFolder {
     Inbox,
     Sent,
     Starred,
+    /// Gmail's All Mail, populated on demand by "everywhere" searches rather than synced.
+    #[serde(rename = "all_mail")]
+    AllMail,
+    /// The provider's spam mailbox, synced at a low cadence.
+    Junk,
 }
 
 impl fmt::Display for MailFolder {
@@ -17,6 +30,8 @@ impl fmt::Display for MailFolder {
             MailFolder::Inbox => write!(f, "inbox"),
             MailFolder::Sent => write!(f, "sent"),
             MailFolder::Starred => write!(f, "starred"),
+            MailFolder::AllMail => write!(f, "all_mail"),
+            MailFolder::Junk => write!(f, "junk"),
         }
     }
 }
@@ -29,23 +44,53 @@ impl FromStr for MailFolder {
             "inbox" => Ok(MailFolder::Inbox),
             "sent" => Ok(MailFolder::Sent),
             "starred" => Ok(MailFolder::Starred),
+            "all_mail" => Ok(MailFolder::AllMail),
+            "junk" => Ok(MailFolder::Junk),
             _ => Err(format!("Unknown MailFolder: {}", s)),
         }
     }
 }
 
+/// The provider's Trash mailbox, where deleted messages are moved.
+pub fn trash_mailbox(provider: &MailProvider) -> &'static str {
+    match provider {
+        MailProvider::Google => "[Gmail]/Trash",
+        MailProvider::Outlook => "Deleted Items",
+        MailProvider::Custom { .. } => "Trash",
+    }
+}
+
 impl MailFolder {
     /// Returns the corresponding IMAP mailbox name for the folder.
     /// Returns None for local virtual folders (e.g. Starred).
-    pub fn to_imap_mailbox(&self, provider: &MailProvider) -> Option<&'static str> {
+    pub fn to_imap_mailbox(&self, provider: &MailProvider) -> Option<Cow<'static, str>> {
         match self {
-            MailFolder::Inbox => Some("INBOX"),
+            MailFolder::Inbox => Some(Cow::Borrowed("INBOX")),
             MailFolder::Sent => match provider {
-                MailProvider::Google => Some("[Gmail]/Sent Mail"),
-                MailProvider::Outlook => Some("Sent Items"),
-                MailProvider::Custom { .. } => Some("Sent"),
+                MailProvider::Google => Some(Cow::Borrowed("[Gmail]/Sent Mail")),
+                MailProvider::Outlook => Some(Cow::Borrowed("Sent Items")),
+                MailProvider::Custom { .. } => Some(Cow::Borrowed("Sent")),
             },
             MailFolder::Starred => None,
+            MailFolder::AllMail => match provider {
+                MailProvider::Google => Some(Cow::Borrowed("[Gmail]/All Mail")),
+                _ => None,
+            },
+            MailFolder::Junk => match provider {
+                MailProvider::Google => Some(Cow::Borrowed("[Gmail]/Spam")),
+                MailProvider::Outlook => Some(Cow::Borrowed("Junk Email")),
+                MailProvider::Custom { imap, .. } => Some(
+                    JUNK_MAILBOXES.lock().unwrap().get(&imap.host)
+                        .map_or(Cow::Borrowed("Junk"), |name| Cow::Owned(name.clone())),
+                ),
+            },
         }
     }
 }
+
+/// Records the Junk mailbox a custom server advertised, so `MailFolder::Junk` maps to it.
+pub fn set_junk_mailbox(provider: &MailProvider, mailbox: &str) {
+    if let MailProvider::Custom { imap, .. } = provider {
+        JUNK_MAILBOXES.lock().unwrap().insert(imap.host.clone(), mailbox.to_string());
+    }
+}
@@ -0,0 +1,436 @@
+//! Gmail labels (`X-GM-LABELS`) and message identity (`X-GM-MSGID`).
+//!
+//! A Gmail message appears once per label-backed mailbox, so labels are kept in their own
+//! table and copies of the same message are told apart by `gm_msgid`. The imap crate cannot
+//! parse FETCH responses carrying Gmail's extension attributes, so label reads and writes go
+//! through `ImapSession::raw_command` on the pooled sessions.
+
+use crate::auth::account::{Account, MailProvider};
+use crate::mail::database::get_db_path;
+use crate::mail::folder::MailFolder;
+use crate::mail::imap_session::{execute_with_imap_session, ImapSession, SessionKind};
+use crate::mail::mailbox_actions::{self, BulkResult, FolderOutcome, MessageRef, GONE_ERROR, UID_CHUNK};
+use crate::mail::message_list::MessageHeader;
+use crate::mail::saved_searches::{push_page_anchor, query_headers, PageAnchor, SPANNING_ORDER};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+use tauri::AppHandle;
+
+/// Label views are exposed to the UI as virtual folders named `label:<name>`.
+pub const FOLDER_PREFIX: &str = "label:";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GmailFetch {
+    pub uid: u32,
+    pub msgid: Option<u64>,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelSummary {
+    pub label: String,
+    pub total: u32,
+    pub unread: u32,
+}
+
+pub fn init_labels_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS message_labels (
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            label TEXT NOT NULL,
+            PRIMARY KEY (folder, uid, label)
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_message_labels_label ON message_labels(label COLLATE NOCASE)", ()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Condition keeping one row per Gmail message in lists that span folders: the copy in
+/// Inbox, then Sent, then any other folder, ties broken by rowid. Rows without a
+/// `gm_msgid` are always kept.
+pub fn unique_copy_sql(alias: &str) -> String {
+    let rank = |table: &str| format!("(CASE {}.folder WHEN 'inbox' THEN 0 WHEN 'sent' THEN 1 ELSE 2 END)", table);
+    format!(
+        "({a}.gm_msgid IS NULL OR NOT EXISTS (SELECT 1 FROM messages dup WHERE dup.gm_msgid = {a}.gm_msgid AND ({d} < {r} OR ({d} = {r} AND dup.rowid < {a}.rowid))))",
+        a = alias,
+        d = rank("dup"),
+        r = rank(alias),
+    )
+}
+
+/// System labels such as `\Inbox` are sent as atoms, user labels as quoted strings.
+fn label_arg(label: &str) -> String {
+    let label = label.trim();
+    match label.strip_prefix('\\') {
+        Some(name) if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()) => label.to_string(),
+        _ => mailbox_actions::imap_quote(label),
+    }
+}
+
+/// Parses the untagged responses to `UID FETCH <set> (UID X-GM-MSGID X-GM-LABELS)`.
+/// Quoted labels are unescaped.
+pub(crate) fn parse_gmail_fetches(response: &str) -> Vec<GmailFetch> {
+    let mut result = Vec::new();
+
+    for line in response.lines() {
+        if !line.starts_with("* ") || !line.contains("FETCH") {
+            continue;
+        }
+
+        let mut labels = Vec::new();
+        let mut rest = line.to_string();
+        if let Some(start) = line.find("X-GM-LABELS (") {
+            let chars: Vec<char> = line[start + "X-GM-LABELS (".len()..].chars().collect();
+            let mut i = 0;
+            let mut current = String::new();
+            let mut in_quotes = false;
+            while i < chars.len() {
+                let c = chars[i];
+                if in_quotes {
+                    match c {
+                        '\\' if i + 1 < chars.len() => {
+                            current.push(chars[i + 1]);
+                            i += 1;
+                        }
+                        '"' => {
+                            in_quotes = false;
+                            labels.push(std::mem::take(&mut current));
+                        }
+                        _ => current.push(c),
+                    }
+                } else if c == '"' {
+                    in_quotes = true;
+                } else if c == ')' || c.is_whitespace() {
+                    if !current.is_empty() {
+                        labels.push(std::mem::take(&mut current));
+                    }
+                    if c == ')' {
+                        break;
+                    }
+                } else {
+                    current.push(c);
+                }
+                i += 1;
+            }
+            let consumed: usize = chars[..i.min(chars.len())].iter().map(|c| c.len_utf8()).sum();
+            rest = format!("{}{}", &line[..start], &line[(start + "X-GM-LABELS (".len() + consumed).min(line.len())..]);
+        }
+
+        let value_after = |name: &str| rest
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .skip_while(|token| !token.eq_ignore_ascii_case(name))
+            .nth(1)
+            .map(|token| token.to_string());
+
+        if let Some(uid) = value_after("UID").and_then(|token| token.parse::<u32>().ok()) {
+            let msgid = value_after("X-GM-MSGID").and_then(|token| token.parse::<u64>().ok());
+            result.push(GmailFetch { uid, msgid, labels });
+        }
+    }
+
+    result
+}
+
+/// Labels and Gmail message IDs for `uids` in the selected mailbox.
+pub(crate) fn fetch_labels(imap: &mut ImapSession, uids: &[u32]) -> Result<Vec<GmailFetch>, String> {
+    let mut fetches = Vec::new();
+    for chunk in uids.chunks(UID_CHUNK) {
+        let lines = imap.raw_command(&format!("UID FETCH {} (UID X-GM-MSGID X-GM-LABELS)", mailbox_actions::uid_set(chunk)))?;
+        fetches.extend(parse_gmail_fetches(&lines.join("\r\n")));
+    }
+    Ok(fetches)
+}
+
+/// Adds or removes `labels` on `uids` in the selected mailbox, one UID STORE per chunk.
+pub(crate) fn store_labels(imap: &mut ImapSession, uids: &[u32], labels: &[String], add: bool) -> Result<FolderOutcome, String> {
+    let (present, missing) = mailbox_actions::partition_existing(&mut imap.session, uids)?;
+    let list = labels.iter().map(|l| label_arg(l)).collect::<Vec<_>>().join(" ");
+    let sign = if add { '+' } else { '-' };
+    let mut outcome = FolderOutcome::all_failed(&missing, GONE_ERROR);
+    for chunk in present.chunks(UID_CHUNK) {
+        match imap.raw_command(&format!("UID STORE {} {}X-GM-LABELS ({})", mailbox_actions::uid_set(chunk), sign, list)) {
+            Ok(_) => outcome.done.extend_from_slice(chunk),
+            Err(e) => outcome.failed.extend(chunk.iter().map(|uid| (*uid, format!("IMAP Store Error: {}", e)))),
+        }
+    }
+    Ok(outcome)
+}
+
+/// Replaces the cached labels and Gmail message IDs of fetched messages.
+pub(crate) fn save_fetched(app_handle: &AppHandle, folder: &str, fetches: &[GmailFetch]) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut set_msgid = tx.prepare("UPDATE messages SET gm_msgid = ?1 WHERE folder = ?2 AND uid = ?3").map_err(|e| e.to_string())?;
+        let mut clear = tx.prepare("DELETE FROM message_labels WHERE folder = ?1 AND uid = ?2").map_err(|e| e.to_string())?;
+        let mut insert = tx.prepare("INSERT OR IGNORE INTO message_labels (folder, uid, label) VALUES (?1, ?2, ?3)").map_err(|e| e.to_string())?;
+
+        for fetch in fetches {
+            if let Some(msgid) = fetch.msgid {
+                set_msgid.execute(rusqlite::params![msgid as i64, folder, fetch.uid]).map_err(|e| e.to_string())?;
+            }
+            clear.execute(rusqlite::params![folder, fetch.uid]).map_err(|e| e.to_string())?;
+            for label in &fetch.labels {
+                insert.execute(rusqlite::params![folder, fetch.uid, label]).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Applies label edits to every cached copy of the messages, since Gmail labels the message
+/// rather than the copy in one folder.
+pub(crate) fn save_changes(app_handle: &AppHandle, folder: &str, uids: &[u32], add: &[String], remove: &[String]) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    apply_changes(&mut conn, folder, uids, add, remove)
+}
+
+fn apply_changes(conn: &mut Connection, folder: &str, uids: &[u32], add: &[String], remove: &[String]) -> Result<(), String> {
+    // The edited row itself, plus every row sharing its X-GM-MSGID when that is known
+    const COPIES: &str = "SELECT ?1, ?2 UNION SELECT m.folder, m.uid FROM messages m
+         WHERE m.gm_msgid = (SELECT gm_msgid FROM messages WHERE folder = ?1 AND uid = ?2)";
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut insert = tx.prepare(&format!(
+            "INSERT OR IGNORE INTO message_labels (folder, uid, label) SELECT copy.*, ?3 FROM ({}) copy", COPIES
+        )).map_err(|e| e.to_string())?;
+        let mut delete = tx.prepare(&format!(
+            "DELETE FROM message_labels WHERE label = ?3 AND (folder, uid) IN ({})", COPIES
+        )).map_err(|e| e.to_string())?;
+        for uid in uids {
+            for label in add {
+                insert.execute(rusqlite::params![folder, uid, label.trim()]).map_err(|e| e.to_string())?;
+            }
+            for label in remove {
+                delete.execute(rusqlite::params![folder, uid, label.trim()]).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Fetches labels and Gmail message IDs for newly synced messages of `folder`.
+pub async fn sync_labels(app_handle: &AppHandle, account: &Account, folder: &str, uids: &[u32]) -> Result<(), String> {
+    if !matches!(account.provider, MailProvider::Google) || uids.is_empty() {
+        return Ok(());
+    }
+    let mailbox = mailbox_actions::source_mailbox(account, folder)?;
+    let uids = uids.to_vec();
+    let fetches = execute_with_imap_session(account, SessionKind::Sync, move |imap| {
+        imap.session.examine(&mailbox).map_err(|e| format!("IMAP Examine Error: {}", e))?;
+        fetch_labels(imap, &uids)
+    }).await?;
+    save_fetched(app_handle, folder, &fetches)
+}
+
+async fn modify_in_folder(app_handle: &AppHandle, account: &Account, folder: &str, uids: &[u32], add: &[String], remove: &[String]) -> Result<FolderOutcome, String> {
+    let mailbox = mailbox_actions::source_mailbox(account, folder)?;
+    let (uids_owned, add_owned, remove_owned) = (uids.to_vec(), add.to_vec(), remove.to_vec());
+    let outcome = execute_with_imap_session(account, SessionKind::Sync, move |imap| {
+        imap.session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        let mut outcome = FolderOutcome { done: uids_owned.clone(), ..Default::default() };
+        for (labels, add) in [(&add_owned, true), (&remove_owned, false)] {
+            if labels.is_empty() || outcome.done.is_empty() {
+                continue;
+            }
+            let step = store_labels(imap, &outcome.done, labels, add)?;
+            outcome.failed.extend(step.failed);
+            outcome.done = step.done;
+        }
+        Ok(outcome)
+    }).await?;
+
+    save_changes(app_handle, folder, &outcome.done, add, remove)?;
+
+    // Without its Inbox label a message leaves the Inbox, as with archiving
+    if MailFolder::from_str(folder).ok() == Some(MailFolder::Inbox) && remove.iter().any(|l| l.trim().eq_ignore_ascii_case("\\Inbox")) {
+        let moves: Vec<(u32, Option<(&str, u32, u32)>)> = outcome.done.iter().map(|uid| (*uid, None)).collect();
+        crate::mail::database::move_messages_local(app_handle, folder, &moves)?;
+    }
+    Ok(outcome)
+}
+
+/// Adds and removes Gmail labels on messages from any number of folders.
+pub async fn modify_labels(app_handle: &AppHandle, account: &Account, refs: &[MessageRef], add: &[String], remove: &[String]) -> Result<BulkResult, String> {
+    if !matches!(account.provider, MailProvider::Google) {
+        return Err("Labels are only supported for Gmail accounts".to_string());
+    }
+    let add: Vec<String> = add.iter().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+    let remove: Vec<String> = remove.iter().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+
+    let mut result = BulkResult::default();
+    for (folder, uids) in mailbox_actions::group_by_folder(refs) {
+        match modify_in_folder(app_handle, account, &folder, &uids, &add, &remove).await {
+            Ok(outcome) => result.record(&folder, outcome),
+            Err(e) => result.record(&folder, FolderOutcome::all_failed(&uids, &e)),
+        }
+    }
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    Ok(result)
+}
+
+pub fn get_message_labels(app_handle: &AppHandle, folder: &str, uid: u32) -> Result<Vec<String>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare("SELECT label FROM message_labels WHERE folder = ?1 AND uid = ?2 ORDER BY label").map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params![folder, uid], |row| row.get(0)).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Every cached label with message counts, counting each Gmail message once.
+/// System labels such as `\Important` are included.
+pub fn list_labels(app_handle: &AppHandle) -> Result<Vec<LabelSummary>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let sql = format!(
+        "SELECT l.label, COUNT(*), SUM(CASE WHEN m.seen = 0 THEN 1 ELSE 0 END)
+         FROM message_labels l JOIN messages m ON m.folder = l.folder AND m.uid = l.uid
+         WHERE {}
+         GROUP BY l.label ORDER BY l.label",
+        unique_copy_sql("m")
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |row| Ok(LabelSummary { label: row.get(0)?, total: row.get(1)?, unread: row.get(2)? }))
+        .map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Loads a page of a label's virtual folder. Like saved searches, it spans folders, so pages
+/// are anchored on `(date, folder, uid)`. Folder names reach here lowercased, and Gmail labels
+/// are case-insensitive, so the label is matched likewise.
+pub fn load_label_page(app_handle: &AppHandle, label: &str, before: Option<PageAnchor>, limit: u32) -> Result<Vec<MessageHeader>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    label_page(&conn, label, before.as_ref(), limit)
+}
+
+fn label_page(conn: &Connection, label: &str, before: Option<&PageAnchor>, limit: u32) -> Result<Vec<MessageHeader>, String> {
+    let scope = format!(
+        "EXISTS (SELECT 1 FROM message_labels l WHERE l.folder = m.folder AND l.uid = m.uid AND l.label = ? COLLATE NOCASE) AND {}",
+        unique_copy_sql("m")
+    );
+    let scope_params = vec![Value::Text(label.to_string())];
+    let mut sql = format!(
+        "SELECT m.uid, m.uid_validity, m.subject, m.sender, m.date, m.seen, m.flagged, m.snippet, m.folder, m.has_attachments, m.thread_id, m.recipient, m.message_id, m.unsubscribe
+         FROM messages m
+         WHERE {}",
+        scope
+    );
+    let mut params = scope_params.clone();
+
+    if let Some(anchor) = before {
+        push_page_anchor(conn, &mut sql, &mut params, &scope, &scope_params, anchor)?;
+    }
+
+    sql.push_str(SPANNING_ORDER);
+    sql.push_str(" LIMIT ?");
+    params.push(Value::Integer(limit as i64));
+
+    query_headers(conn, &sql, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gmail_fetches() {
+        let response = "* 1 FETCH (X-GM-LABELS (\\Inbox \"\\\\Important\" \"Team Notes\" Work) UID 345 X-GM-MSGID 1278455344230334865)\r\n\
+                        * 2 FETCH (UID 346 X-GM-LABELS ())\r\n\
+                        G3 OK Success\r\n";
+        assert_eq!(parse_gmail_fetches(response), vec![
+            GmailFetch {
+                uid: 345,
+                msgid: Some(1278455344230334865),
+                labels: vec!["\\Inbox".to_string(), "\\Important".to_string(), "Team Notes".to_string(), "Work".to_string()],
+            },
+            GmailFetch { uid: 346, msgid: None, labels: Vec::new() },
+        ]);
+    }
+
+    #[test]
+    fn test_label_pages_span_folders_without_skipping() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (
+                folder TEXT NOT NULL, uid INTEGER NOT NULL, uid_validity INTEGER, subject TEXT, sender TEXT,
+                recipient TEXT, date INTEGER NOT NULL, snippet TEXT, seen INTEGER DEFAULT 0, flagged INTEGER DEFAULT 0,
+                has_attachments INTEGER DEFAULT 0, thread_id TEXT, message_id TEXT, unsubscribe TEXT, gm_msgid INTEGER,
+                PRIMARY KEY (folder, uid)
+            );"
+        ).unwrap();
+        init_labels_table(&conn).unwrap();
+        for (folder, uid, date) in [("inbox", 7, 2_000), ("sent", 7, 2_000), ("inbox", 3, 2_000), ("sent", 1, 1_000)] {
+            conn.execute(
+                "INSERT INTO messages (folder, uid, uid_validity, subject, sender, date) VALUES (?1, ?2, 1, 'Hi', 'a@example.com', ?3)",
+                rusqlite::params![folder, uid, date],
+            ).unwrap();
+            conn.execute("INSERT INTO message_labels (folder, uid, label) VALUES (?1, ?2, 'Work')", rusqlite::params![folder, uid]).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut before: Option<PageAnchor> = None;
+        loop {
+            let page = label_page(&conn, "work", before.as_ref(), 1).unwrap();
+            let Some(last) = page.last() else { break };
+            seen.push((last.folder.clone(), last.uid));
+            before = Some(PageAnchor { uid: last.uid, folder: Some(last.folder.clone()) });
+        }
+        assert_eq!(seen, vec![
+            ("sent".to_string(), 7),
+            ("inbox".to_string(), 7),
+            ("inbox".to_string(), 3),
+            ("sent".to_string(), 1),
+        ]);
+    }
+
+    #[test]
+    fn test_label_changes_apply_to_every_copy() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (
+                folder TEXT NOT NULL, uid INTEGER NOT NULL, uid_validity INTEGER, subject TEXT, sender TEXT,
+                recipient TEXT, date INTEGER NOT NULL, snippet TEXT, seen INTEGER DEFAULT 0, flagged INTEGER DEFAULT 0,
+                has_attachments INTEGER DEFAULT 0, thread_id TEXT, message_id TEXT, unsubscribe TEXT, gm_msgid INTEGER,
+                PRIMARY KEY (folder, uid)
+            );
+            INSERT INTO messages (folder, uid, uid_validity, subject, sender, date, gm_msgid) VALUES
+                ('inbox', 5, 1, 'Hi', 'a@example.com', 1000, 42),
+                ('sent', 9, 1, 'Hi', 'a@example.com', 1000, 42),
+                ('inbox', 6, 1, 'Other', 'b@example.com', 900, NULL);"
+        ).unwrap();
+        init_labels_table(&conn).unwrap();
+
+        // Labelling the Sent copy lists the message under the label through its Inbox copy
+        apply_changes(&mut conn, "sent", &[9], &["Work".to_string()], &[]).unwrap();
+        let page = label_page(&conn, "Work", None, 10).unwrap();
+        assert_eq!(page.iter().map(|h| (h.folder.as_str(), h.uid)).collect::<Vec<_>>(), vec![("inbox", 5)]);
+
+        // Removing it from either copy removes it from both
+        apply_changes(&mut conn, "inbox", &[5], &[], &["Work".to_string()]).unwrap();
+        assert!(label_page(&conn, "Work", None, 10).unwrap().is_empty());
+
+        // Without a Gmail message ID only the edited row changes
+        apply_changes(&mut conn, "inbox", &[6], &["Work".to_string()], &[]).unwrap();
+        let labelled: i64 = conn.query_row("SELECT COUNT(*) FROM message_labels WHERE label = 'Work'", [], |row| row.get(0)).unwrap();
+        assert_eq!(labelled, 1);
+    }
+
+    #[test]
+    fn test_label_arg() {
+        assert_eq!(label_arg("\\Inbox"), "\\Inbox");
+        assert_eq!(label_arg(" Team \"A\" "), "\"Team \\\"A\\\"\"");
+        assert_eq!(label_arg("\\Weird Label"), "\"\\\\Weird Label\"");
+    }
+}
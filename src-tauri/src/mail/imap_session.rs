@@ -5,7 +5,9 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex as AsyncMutex;
 use std::time::Instant;
-use native_tls::TlsConnector;
+use native_tls::{TlsConnector, TlsStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SessionKind {
@@ -16,10 +18,111 @@ pub enum SessionKind {
     Search,
 }
 
+/// The session's TLS stream behind a shared handle. Reads hand out at most one line, so the
+/// imap crate never buffers past the response it is parsing and `ImapSession::raw_command`
+/// can use the same connection for responses the crate cannot parse (e.g. `X-GM-LABELS`).
+#[derive(Clone)]
+pub struct SharedStream(Arc<StdMutex<BufReader<TlsStream<TcpStream>>>>);
+
+impl SharedStream {
+    fn lock(&self) -> std::io::Result<std::sync::MutexGuard<'_, BufReader<TlsStream<TcpStream>>>> {
+        self.0.lock().map_err(|_| std::io::Error::other("IMAP stream lock poisoned"))
+    }
+}
+
+impl Read for SharedStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut reader = self.lock()?;
+        let available = reader.fill_buf()?;
+        let line_len = available.iter().position(|b| *b == b'\n').map_or(available.len(), |i| i + 1);
+        let n = line_len.min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        reader.consume(n);
+        Ok(n)
+    }
+}
+
+impl Write for SharedStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.lock()?.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.lock()?.get_mut().flush()
+    }
+}
+
+impl imap::extensions::idle::SetReadTimeout for SharedStream {
+    fn set_read_timeout(&mut self, timeout: Option<std::time::Duration>) -> imap::error::Result<()> {
+        self.lock()?.get_ref().get_ref().set_read_timeout(timeout).map_err(imap::error::Error::Io)
+    }
+}
+
+pub type Session = imap::Session<SharedStream>;
+
 pub struct ImapSession {
-    pub session: imap::Session<native_tls::TlsStream<std::net::TcpStream>>,
+    pub session: Session,
     pub last_used: Instant,
     pub created_at: Instant,
+    stream: SharedStream,
+    raw_tag: u32,
+}
+
+impl ImapSession {
+    /// Sends a command the imap crate cannot parse the response to and returns its untagged
+    /// lines, with any `{N}` literals spliced back in as quoted strings.
+    pub fn raw_command(&mut self, command: &str) -> Result<Vec<String>, String> {
+        self.raw_tag += 1;
+        let tag = format!("R{}", self.raw_tag);
+        self.write_raw(&format!("{} {}\r\n", tag, command))?;
+
+        let verb = command.split_whitespace().take(2).collect::<Vec<_>>().join(" ");
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_response_line()?;
+            if let Some(status) = line.strip_prefix(&tag).map(str::trim_start) {
+                if status.get(..2).is_some_and(|s| s.eq_ignore_ascii_case("OK")) {
+                    return Ok(lines);
+                }
+                return Err(format!("{} failed: {}", verb, status));
+            }
+            if line.starts_with("* BYE") {
+                return Err(format!("IMAP server closed the connection: {}", line));
+            }
+            lines.push(line);
+        }
+    }
+
+    fn write_raw(&mut self, data: &str) -> Result<(), String> {
+        self.stream.write_all(data.as_bytes()).and_then(|_| self.stream.flush()).map_err(|e| format!("IMAP Write Error: {}", e))
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut reader = self.stream.lock().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).map_err(|e| format!("IMAP Read Error: {}", e))?;
+        if read == 0 {
+            return Err("IMAP server closed the connection".to_string());
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn read_response_line(&mut self) -> Result<String, String> {
+        let mut line = self.read_line()?;
+        loop {
+            let literal = line.strip_suffix('}')
+                .and_then(|head| head.rfind('{').map(|open| (open, &head[open + 1..])))
+                .and_then(|(open, len)| len.parse::<usize>().ok().map(|len| (open, len)));
+            let Some((open, len)) = literal else { return Ok(line) };
+
+            let mut buf = vec![0u8; len];
+            self.stream.lock().map_err(|e| e.to_string())?.read_exact(&mut buf).map_err(|e| format!("IMAP Read Error: {}", e))?;
+            let text = String::from_utf8_lossy(&buf);
+            let quoted = format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+            let next = self.read_line()?;
+            line = format!("{}{}{}", &line[..open], quoted, next);
+        }
+    }
 }
 
 pub struct ManagedSession {
@@ -30,7 +133,7 @@ pub static SESSION_MANAGER: Lazy<StdMutex<HashMap<(String, SessionKind), Arc<Man
     Lazy::new(|| StdMutex::new(HashMap::new()));
 
 pub fn create_session(account: &Account, kind: SessionKind) -> Result<ImapSession, String> {
-    let mut session = connect_and_authenticate(account, kind)?;
+    let (mut session, stream) = connect_and_authenticate(account, kind)?;
 
     // GUARANTEE: Always select INBOX on fresh creation
     session.select("INBOX").map_err(|e| format!("IMAP Select Error: {}", e))?;
@@ -41,6 +144,8 @@ pub fn create_session(account: &Account, kind: SessionKind) -> Result<ImapSessio
         session,
         last_used: Instant::now(),
         created_at: Instant::now(),
+        stream,
+        raw_tag: 0,
     })
 }
 
@@ -48,7 +153,7 @@ pub fn create_session(account: &Account, kind: SessionKind) -> Result<ImapSessio
 pub fn connect_and_authenticate(
     account: &Account,
     kind: SessionKind,
-) -> Result<imap::Session<native_tls::TlsStream<std::net::TcpStream>>, String> {
+) -> Result<(Session, SharedStream), String> {
     log::info!("Creating new IMAP session {:?}", kind);
     let imap_config = account.provider.imap_config();
     let domain = imap_config.host;
@@ -58,8 +163,14 @@ pub fn connect_and_authenticate(
         .build()
         .map_err(|e| format!("TLS Builder Error: {}", e))?;
 
-    let client = imap::connect((domain.as_str(), port), domain.as_str(), &tls)
+    let tcp = TcpStream::connect((domain.as_str(), port))
         .map_err(|e| format!("IMAP Connection Error: {}", e))?;
+    let tls_stream = tls.connect(domain.as_str(), tcp)
+        .map_err(|e| format!("IMAP Connection Error: {}", e))?;
+    let stream = SharedStream(Arc::new(StdMutex::new(BufReader::new(tls_stream))));
+
+    let mut client = imap::Client::new(stream.clone());
+    client.read_greeting().map_err(|e| format!("IMAP Connection Error: {}", e))?;
 
     let auth_raw = format!(
         "user={}\x01auth=Bearer {}\x01\x01",
@@ -82,12 +193,22 @@ pub fn connect_and_authenticate(
         .authenticate("XOAUTH2", &auth)
         .map_err(|(e, _)| format!("IMAP Authentication Failed: {}", e))?;
 
-    Ok(session)
+    Ok((session, stream))
 }
 
 pub async fn execute_with_session<F, R>(account: &Account, kind: SessionKind, mut f: F) -> Result<R, String>
 where
-    F: FnMut(&mut imap::Session<native_tls::TlsStream<std::net::TcpStream>>) -> Result<R, String> + Send + 'static,
+    F: FnMut(&mut Session) -> Result<R, String> + Send + 'static,
+    R: Send + 'static,
+{
+    execute_with_imap_session(account, kind, move |s| f(&mut s.session)).await
+}
+
+/// Like `execute_with_session`, but hands the closure the whole `ImapSession` so it can mix
+/// regular calls with `raw_command`.
+pub async fn execute_with_imap_session<F, R>(account: &Account, kind: SessionKind, mut f: F) -> Result<R, String>
+where
+    F: FnMut(&mut ImapSession) -> Result<R, String> + Send + 'static,
     R: Send + 'static,
 {
     // 1. Get or create the ManagedSession for this Account+Kind
@@ -139,7 +260,7 @@ where
         let imap_session_wrapper = owned_guard.as_mut().unwrap();
 
         // 4. Execute the closure
-        let result = f(&mut imap_session_wrapper.session);
+        let result = f(imap_session_wrapper);
 
         if result.is_err() {
             log::warn!("[SessionStats] Session operation failed for {:?}. Attempting auto-recovery...", kind);
@@ -153,7 +274,7 @@ where
                 Err(e) => return Err(e),
             };
 
-            let retry_result = f(&mut new_session.session);
+            let retry_result = f(&mut new_session);
             if retry_result.is_ok() {
                 new_session.last_used = Instant::now();
                 *owned_guard = Some(new_session);
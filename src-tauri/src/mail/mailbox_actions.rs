@@ -0,0 +1,605 @@
+use crate::auth::account::{Account, MailProvider};
+use crate::mail::database;
+use crate::mail::folder::MailFolder;
+use crate::mail::imap_session::{execute_with_imap_session, execute_with_session, Session, SessionKind};
+use crate::mail::undo::{JournalMessage, UndoStep};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use tauri::AppHandle;
+
+/// RFC 6154 special-use mailboxes, whose names vary by provider and locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialUse {
+    All,
+    Archive,
+    Trash,
+    Junk,
+}
+
+impl SpecialUse {
+    fn attribute(&self) -> &'static str {
+        match self {
+            SpecialUse::All => "\\All",
+            SpecialUse::Archive => "\\Archive",
+            SpecialUse::Trash => "\\Trash",
+            SpecialUse::Junk => "\\Junk",
+        }
+    }
+
+    /// The usual name when the server does not advertise special-use attributes.
+    fn fallback(&self, provider: &MailProvider) -> &'static str {
+        match (self, provider) {
+            (SpecialUse::All, _) => "[Gmail]/All Mail",
+            (SpecialUse::Archive, _) => "Archive",
+            (SpecialUse::Trash, _) => crate::mail::folder::trash_mailbox(provider),
+            (SpecialUse::Junk, MailProvider::Google) => "[Gmail]/Spam",
+            (SpecialUse::Junk, MailProvider::Outlook) => "Junk Email",
+            (SpecialUse::Junk, MailProvider::Custom { .. }) => "Junk",
+        }
+    }
+}
+
+/// Finds a special-use mailbox by its attribute, falling back to the provider's usual name.
+pub fn find_special_use(session: &mut Session, special: SpecialUse, provider: &MailProvider) -> String {
+    if let Ok(names) = session.list(Some(""), Some("*")) {
+        for name in names.iter() {
+            let matches = name.attributes().iter().any(|attr| {
+                matches!(attr, imap::types::NameAttribute::Custom(custom) if custom.eq_ignore_ascii_case(special.attribute()))
+            });
+            if matches {
+                if special == SpecialUse::Junk {
+                    crate::mail::folder::set_junk_mailbox(provider, name.name());
+                }
+                return name.name().to_string();
+            }
+        }
+    }
+    special.fallback(provider).to_string()
+}
+
+/// Where a message ended up after a move.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveResult {
+    pub mailbox: String,
+    /// The local folder the message now lives in, when the destination is one the app syncs.
+    pub folder: Option<String>,
+    /// The message's UID in the destination, from COPYUID or a Message-ID lookup.
+    pub uid: Option<u32>,
+    /// Journal entry that `undo_last_action` would reverse, once the command has recorded it.
+    pub undo_id: Option<String>,
+    #[serde(skip)]
+    pub(crate) undo: Option<UndoStep>,
+}
+
+/// Destination of a move: a synced folder, a special-use mailbox or any mailbox by name.
+#[derive(Debug, Clone)]
+pub enum MoveTarget {
+    Folder(MailFolder),
+    Special(SpecialUse),
+    Mailbox(String),
+}
+
+impl MoveTarget {
+    /// Accepts a folder id such as `inbox`, otherwise treats `dest` as an IMAP mailbox name.
+    pub fn parse(dest: &str, provider: &MailProvider) -> MoveTarget {
+        match MailFolder::from_str(dest) {
+            Ok(folder) if folder.to_imap_mailbox(provider).is_some() => MoveTarget::Folder(folder),
+            _ => MoveTarget::Mailbox(dest.to_string()),
+        }
+    }
+}
+
+/// Reported for UIDs that a STORE or MOVE found missing from the mailbox.
+pub(crate) const GONE_ERROR: &str = "Message no longer exists on the server";
+
+/// Keeps each UID set well under the command-line limits servers enforce.
+pub(crate) const UID_CHUNK: usize = 500;
+
+pub(crate) fn imap_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Compresses UIDs into an IMAP sequence set such as `4,7:9`.
+pub(crate) fn uid_set(uids: &[u32]) -> String {
+    let mut sorted = uids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut parts = Vec::new();
+    let mut iter = sorted.into_iter().peekable();
+    while let Some(first) = iter.next() {
+        let mut last = first;
+        while iter.peek() == Some(&(last + 1)) {
+            last = iter.next().unwrap_or(last);
+        }
+        parts.push(if first == last { first.to_string() } else { format!("{}:{}", first, last) });
+    }
+    parts.join(",")
+}
+
+/// Expands an IMAP sequence set such as `4,7:9` into UIDs.
+fn expand_uid_set(set: &str) -> Vec<u32> {
+    set.split(',')
+        .flat_map(|part| match part.split_once(':') {
+            Some((a, b)) => match (a.parse::<u32>(), b.parse::<u32>()) {
+                (Ok(a), Ok(b)) => (a.min(b)..=a.max(b)).collect(),
+                _ => Vec::new(),
+            },
+            None => part.parse().ok().into_iter().collect(),
+        })
+        .collect()
+}
+
+/// Parses UIDPLUS `[COPYUID <validity> <source-set> <dest-set>]` response codes into the
+/// destination UIDVALIDITY and `(source, dest)` UID pairs. A server may split a large move
+/// into several responses, so every code in `response` is collected.
+pub(crate) fn parse_copyuid(response: &str) -> Option<(u32, Vec<(u32, u32)>)> {
+    let mut validity = None;
+    let mut pairs = Vec::new();
+    for (index, _) in response.match_indices("[COPYUID ") {
+        let start = index + "[COPYUID ".len();
+        let Some(len) = response[start..].find(']') else { continue };
+        let mut parts = response[start..start + len].split_whitespace();
+        let (Some(v), Some(sources), Some(dests)) = (parts.next().and_then(|v| v.parse().ok()), parts.next(), parts.next()) else { continue };
+        let sources = expand_uid_set(sources);
+        let dests = expand_uid_set(dests);
+        if sources.len() != dests.len() {
+            continue;
+        }
+        validity = Some(v);
+        pairs.extend(sources.into_iter().zip(dests));
+    }
+    validity.map(|v| (v, pairs))
+}
+
+/// A message addressed by its local folder and UID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRef {
+    pub folder: String,
+    pub uid: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkFailure {
+    pub folder: String,
+    pub uid: u32,
+    pub error: String,
+}
+
+/// Outcome of a bulk operation; a failure in one message or folder does not stop the rest.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkResult {
+    pub succeeded: Vec<MessageRef>,
+    pub failed: Vec<BulkFailure>,
+    pub undo_id: Option<String>,
+    #[serde(skip)]
+    pub(crate) undo: Vec<UndoStep>,
+}
+
+impl BulkResult {
+    pub(crate) fn record(&mut self, folder: &str, outcome: FolderOutcome) {
+        self.succeeded.extend(outcome.done.into_iter().map(|uid| MessageRef { folder: folder.to_string(), uid }));
+        self.failed.extend(outcome.failed.into_iter().map(|(uid, error)| BulkFailure { folder: folder.to_string(), uid, error }));
+    }
+}
+
+/// Per-folder result of a batched command, with destination `(uid_validity, uid)` for moves.
+#[derive(Debug, Default)]
+pub(crate) struct FolderOutcome {
+    pub done: Vec<u32>,
+    pub failed: Vec<(u32, String)>,
+    pub dests: HashMap<u32, (u32, u32)>,
+}
+
+impl FolderOutcome {
+    pub(crate) fn all_failed(uids: &[u32], error: &str) -> FolderOutcome {
+        FolderOutcome { failed: uids.iter().map(|uid| (*uid, error.to_string())).collect(), ..Default::default() }
+    }
+}
+
+/// Groups refs by folder, keeping the order folders first appear in.
+pub(crate) fn group_by_folder(refs: &[MessageRef]) -> Vec<(String, Vec<u32>)> {
+    let mut groups: Vec<(String, Vec<u32>)> = Vec::new();
+    for r in refs {
+        let folder = r.folder.to_lowercase();
+        match groups.iter_mut().find(|(f, _)| *f == folder) {
+            Some((_, uids)) => uids.push(r.uid),
+            None => groups.push((folder, vec![r.uid])),
+        }
+    }
+    for (_, uids) in groups.iter_mut() {
+        uids.sort_unstable();
+        uids.dedup();
+    }
+    groups
+}
+
+/// Splits `uids` into those still in the selected mailbox and those that are gone, since
+/// UID STORE and UID MOVE silently skip missing UIDs.
+pub(crate) fn partition_existing(session: &mut Session, uids: &[u32]) -> Result<(Vec<u32>, Vec<u32>), String> {
+    let mut present = HashSet::new();
+    for chunk in uids.chunks(UID_CHUNK) {
+        let found = session.uid_search(format!("UID {}", uid_set(chunk))).map_err(|e| format!("IMAP Search Error: {}", e))?;
+        present.extend(found);
+    }
+    Ok(uids.iter().partition(|uid| present.contains(*uid)))
+}
+
+/// Runs one UID STORE per chunk of `uids` in the selected mailbox.
+pub(crate) fn store_selected(session: &mut Session, uids: &[u32], command: &str) -> Result<FolderOutcome, String> {
+    let (present, missing) = partition_existing(session, uids)?;
+    let mut outcome = FolderOutcome::all_failed(&missing, GONE_ERROR);
+    for chunk in present.chunks(UID_CHUNK) {
+        match session.uid_store(uid_set(chunk), command) {
+            Ok(_) => outcome.done.extend_from_slice(chunk),
+            Err(e) => outcome.failed.extend(chunk.iter().map(|uid| (*uid, format!("IMAP Store Error: {}", e)))),
+        }
+    }
+    Ok(outcome)
+}
+
+/// Moves messages out of the selected mailbox with one command per chunk. Uses UID MOVE when
+/// the server has it, otherwise UID COPY + `\Deleted`, expunging only the moved UIDs with
+/// UIDPLUS. Without it the originals stay flagged `\Deleted`, since a plain EXPUNGE would also
+/// remove every other deleted message. Destination UIDs come from COPYUID, or from matching
+/// Message-IDs among the messages that arrived in `dest`.
+pub(crate) fn move_selected(session: &mut Session, uids: &[u32], dest: &str, message_ids: &HashMap<u32, String>) -> Result<FolderOutcome, String> {
+    let caps = session.capabilities().map_err(|e| format!("IMAP Capability Error: {}", e))?;
+    let has_move = caps.has_str("MOVE");
+    let has_uidplus = caps.has_str("UIDPLUS");
+    drop(caps);
+
+    let (present, missing) = partition_existing(session, uids)?;
+    let mut outcome = FolderOutcome::all_failed(&missing, GONE_ERROR);
+    if present.is_empty() {
+        return Ok(outcome);
+    }
+    let before = session.status(dest, "(UIDNEXT UIDVALIDITY)").ok();
+
+    for chunk in present.chunks(UID_CHUNK) {
+        let set = uid_set(chunk);
+        let moved = if has_move {
+            // RFC 6851 sends COPYUID in an untagged OK, which survives in the response bytes
+            session.run_command_and_read_response(format!("UID MOVE {} {}", set, imap_quote(dest)))
+                .map(|response| {
+                    if let Some((validity, pairs)) = parse_copyuid(&String::from_utf8_lossy(&response)) {
+                        outcome.dests.extend(pairs.into_iter().map(|(src, dst)| (src, (validity, dst))));
+                    }
+                })
+                .map_err(|e| format!("IMAP Move Error: {}", e))
+        } else {
+            session.uid_copy(&set, dest).map_err(|e| format!("IMAP Copy Error: {}", e))
+                .and_then(|_| session.uid_store(&set, "+FLAGS.SILENT (\\Deleted)").map_err(|e| format!("IMAP Store Error: {}", e)))
+                .and_then(|_| if has_uidplus {
+                    session.uid_expunge(&set).map(|_| ()).map_err(|e| format!("IMAP Expunge Error: {}", e))
+                } else {
+                    Ok(())
+                })
+        };
+        match moved {
+            Ok(()) => outcome.done.extend_from_slice(chunk),
+            Err(e) => outcome.failed.extend(chunk.iter().map(|uid| (*uid, e.clone()))),
+        }
+    }
+
+    // The tagged COPYUID of a plain COPY is not exposed by the imap crate, so look the copies up
+    let unresolved: Vec<u32> = outcome.done.iter().copied()
+        .filter(|uid| !outcome.dests.contains_key(uid) && message_ids.contains_key(uid))
+        .collect();
+    let Some(uid_next) = before.and_then(|b| b.uid_next) else { return Ok(outcome) };
+    if unresolved.is_empty() {
+        return Ok(outcome);
+    }
+    let mailbox = session.examine(dest).map_err(|e| format!("IMAP Examine Error: {}", e))?;
+    let validity = mailbox.uid_validity.unwrap_or(0);
+    let fetches = session.uid_fetch(format!("{}:*", uid_next), "(UID BODY.PEEK[HEADER.FIELDS (MESSAGE-ID)])")
+        .map_err(|e| format!("IMAP Fetch Error: {}", e))?;
+    let arrived: HashMap<String, u32> = fetches.iter()
+        .filter_map(|fetch| {
+            let dst = fetch.uid.filter(|u| *u >= uid_next)?;
+            let message_id = crate::mail::sync::parse_header_to_message(fetch, 0, "")?.message_id?;
+            Some((message_id, dst))
+        })
+        .collect();
+    for uid in unresolved {
+        if let Some(dst) = message_ids.get(&uid).and_then(|id| arrived.get(id)) {
+            outcome.dests.insert(uid, (validity, *dst));
+        }
+    }
+    Ok(outcome)
+}
+
+/// The synced folder that a server mailbox corresponds to, if any.
+pub(crate) fn local_folder_for(mailbox: &str, provider: &MailProvider) -> Option<MailFolder> {
+    [MailFolder::Inbox, MailFolder::Sent, MailFolder::AllMail, MailFolder::Junk].into_iter()
+        .find(|f| f.to_imap_mailbox(provider).is_some_and(|m| m.eq_ignore_ascii_case(mailbox)))
+}
+
+pub(crate) fn source_mailbox(account: &Account, folder: &str) -> Result<String, String> {
+    MailFolder::from_str(folder).ok()
+        .and_then(|f| f.to_imap_mailbox(&account.provider))
+        .map(|mailbox| mailbox.into_owned())
+        .ok_or_else(|| format!("Messages in {} are not on the server", folder))
+}
+
+/// Moves messages of one folder on the server and rewrites their local rows to the
+/// destination folder and UID, or drops the rows when the destination is not synced.
+async fn move_in_folder(app_handle: &AppHandle, account: &Account, folder: &str, uids: &[u32], target: &MoveTarget) -> Result<(String, FolderOutcome, Option<UndoStep>), String> {
+    let source = source_mailbox(account, folder)?;
+    let message_ids: HashMap<u32, String> = database::get_messages_by_uids(app_handle, folder, uids)?
+        .into_iter()
+        .filter_map(|h| Some((h.uid, h.message_id?)))
+        .collect();
+    let provider = account.provider.clone();
+    let target = target.clone();
+    let uids_owned = uids.to_vec();
+    let ids = message_ids.clone();
+    let source_owned = source.clone();
+
+    let (mailbox, outcome) = execute_with_imap_session(account, SessionKind::Sync, move |imap| {
+        let session = &mut imap.session;
+        let dest = match &target {
+            MoveTarget::Folder(f) => f.to_imap_mailbox(&provider).map(String::from).ok_or("Destination folder is not on the server")?,
+            MoveTarget::Special(special) => find_special_use(session, *special, &provider),
+            MoveTarget::Mailbox(name) => name.clone(),
+        };
+        if dest.eq_ignore_ascii_case(&source_owned) {
+            return Err("Message is already in that folder".to_string());
+        }
+        if matches!(target, MoveTarget::Special(SpecialUse::Archive)) {
+            // Fails harmlessly when the mailbox already exists
+            let _ = session.create(&dest);
+        }
+        session.select(&source_owned).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        let mut outcome = move_selected(session, &uids_owned, &dest, &ids)?;
+        if matches!(provider, MailProvider::Google) && matches!(target, MoveTarget::Special(SpecialUse::Trash)) {
+            trash_by_label(imap, &mut outcome);
+        }
+        Ok((dest, outcome))
+    }).await?;
+
+    let dest_folder = local_folder_for(&mailbox, &account.provider).map(|f| f.to_string());
+    let moves: Vec<(u32, Option<(&str, u32, u32)>)> = outcome.done.iter()
+        .map(|uid| {
+            let dest = match (&dest_folder, outcome.dests.get(uid)) {
+                (Some(f), Some((validity, new_uid))) => Some((f.as_str(), *new_uid, *validity)),
+                _ => None,
+            };
+            (*uid, dest)
+        })
+        .collect();
+    database::move_messages_local(app_handle, folder, &moves)?;
+
+    let undo = (!outcome.done.is_empty()).then(|| UndoStep::Move {
+        folder: folder.to_string(),
+        source_mailbox: source,
+        mailbox: mailbox.clone(),
+        messages: outcome.done.iter()
+            .map(|uid| JournalMessage {
+                uid: *uid,
+                message_id: message_ids.get(uid).cloned(),
+                dest_uid: outcome.dests.get(uid).map(|(_, u)| *u),
+                dest_validity: outcome.dests.get(uid).map(|(v, _)| *v),
+                previous: None,
+            })
+            .collect(),
+    });
+    Ok((mailbox, outcome, undo))
+}
+
+/// Gmail fallback for messages whose move to Trash failed: adding the `\Trash` label moves
+/// them there just the same. Expects the source mailbox to still be selected.
+fn trash_by_label(imap: &mut crate::mail::imap_session::ImapSession, outcome: &mut FolderOutcome) {
+    let (retry, gone): (Vec<(u32, String)>, Vec<(u32, String)>) = std::mem::take(&mut outcome.failed)
+        .into_iter()
+        .partition(|(_, e)| e != GONE_ERROR);
+    outcome.failed = gone;
+    if retry.is_empty() {
+        return;
+    }
+    let uids: Vec<u32> = retry.iter().map(|(uid, _)| *uid).collect();
+    log::warn!("MOVE to Trash failed for {} message(s), falling back to X-GM-LABELS", uids.len());
+    match imap.raw_command(&format!("UID STORE {} +X-GM-LABELS (\\Trash)", uid_set(&uids))) {
+        Ok(_) => outcome.done.extend(uids),
+        Err(e) => {
+            log::warn!("X-GM-LABELS fallback failed: {}", e);
+            outcome.failed.extend(retry);
+        }
+    }
+}
+
+/// Moves a message on the server and rewrites its local row to the destination folder and
+/// UID, or drops the row when the destination is not a synced folder.
+pub async fn move_message(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32, target: MoveTarget) -> Result<MoveResult, String> {
+    let (mailbox, outcome, undo) = move_in_folder(app_handle, account, folder, &[uid], &target).await?;
+    if let Some((_, error)) = outcome.failed.into_iter().next() {
+        return Err(error);
+    }
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    Ok(MoveResult {
+        folder: local_folder_for(&mailbox, &account.provider).map(|f| f.to_string()),
+        uid: outcome.dests.get(&uid).map(|(_, u)| *u),
+        mailbox,
+        undo_id: None,
+        undo,
+    })
+}
+
+/// Moves messages from any number of folders, one UID set per folder.
+pub async fn move_messages(app_handle: &AppHandle, account: &Account, refs: &[MessageRef], target: MoveTarget) -> BulkResult {
+    let mut result = BulkResult::default();
+    for (folder, uids) in group_by_folder(refs) {
+        match move_in_folder(app_handle, account, &folder, &uids, &target).await {
+            Ok((_, outcome, undo)) => {
+                result.record(&folder, outcome);
+                result.undo.extend(undo);
+            }
+            Err(e) => result.record(&folder, FolderOutcome::all_failed(&uids, &e)),
+        }
+    }
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    result
+}
+
+/// Removes Gmail's Inbox label from Inbox messages, leaving them in All Mail.
+async fn gmail_archive(app_handle: &AppHandle, account: &Account, folder: &str, uids: &[u32]) -> Result<(FolderOutcome, Option<UndoStep>), String> {
+    if MailFolder::from_str(folder).ok() != Some(MailFolder::Inbox) {
+        return Err("Only Inbox messages can be archived on Gmail".to_string());
+    }
+    let message_ids: HashMap<u32, String> = database::get_messages_by_uids(app_handle, folder, uids)?
+        .into_iter()
+        .filter_map(|h| Some((h.uid, h.message_id?)))
+        .collect();
+    let uids_owned = uids.to_vec();
+    let outcome = execute_with_imap_session(account, SessionKind::Sync, move |imap| {
+        imap.session.select("INBOX").map_err(|e| format!("IMAP Select Error: {}", e))?;
+        crate::mail::gmail_labels::store_labels(imap, &uids_owned, &["\\Inbox".to_string()], false)
+    }).await?;
+
+    let moves: Vec<(u32, Option<(&str, u32, u32)>)> = outcome.done.iter().map(|uid| (*uid, None)).collect();
+    database::move_messages_local(app_handle, folder, &moves)?;
+
+    let undo = (!outcome.done.is_empty()).then(|| UndoStep::GmailArchive {
+        folder: folder.to_string(),
+        messages: outcome.done.iter()
+            .map(|uid| JournalMessage { uid: *uid, message_id: message_ids.get(uid).cloned(), dest_uid: None, dest_validity: None, previous: None })
+            .collect(),
+    });
+    Ok((outcome, undo))
+}
+
+/// Archives a message. On Gmail this removes the Inbox label, leaving it in All Mail;
+/// elsewhere the message moves to the `\Archive` mailbox, which is created if missing.
+pub async fn archive_message(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32) -> Result<MoveResult, String> {
+    if !matches!(account.provider, MailProvider::Google) {
+        return move_message(app_handle, account, folder, uid, MoveTarget::Special(SpecialUse::Archive)).await;
+    }
+
+    let (outcome, undo) = gmail_archive(app_handle, account, folder, &[uid]).await?;
+    if let Some((_, error)) = outcome.failed.into_iter().next() {
+        return Err(error);
+    }
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    Ok(MoveResult {
+        mailbox: MailFolder::AllMail.to_imap_mailbox(&account.provider).unwrap_or_default().to_string(),
+        folder: None,
+        uid: None,
+        undo_id: None,
+        undo,
+    })
+}
+
+/// Archives messages from any number of folders, one UID set per folder.
+pub async fn archive_messages(app_handle: &AppHandle, account: &Account, refs: &[MessageRef]) -> BulkResult {
+    if !matches!(account.provider, MailProvider::Google) {
+        return move_messages(app_handle, account, refs, MoveTarget::Special(SpecialUse::Archive)).await;
+    }
+
+    let mut result = BulkResult::default();
+    for (folder, uids) in group_by_folder(refs) {
+        match gmail_archive(app_handle, account, &folder, &uids).await {
+            Ok((outcome, undo)) => {
+                result.record(&folder, outcome);
+                result.undo.extend(undo);
+            }
+            Err(e) => result.record(&folder, FolderOutcome::all_failed(&uids, &e)),
+        }
+    }
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    result
+}
+
+/// Moves a message to the `\Trash` mailbox.
+pub async fn trash_message(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32) -> Result<MoveResult, String> {
+    move_message(app_handle, account, folder, uid, MoveTarget::Special(SpecialUse::Trash)).await
+}
+
+/// Moves messages to the `\Trash` mailbox, one UID set per folder.
+pub async fn trash_messages(app_handle: &AppHandle, account: &Account, refs: &[MessageRef]) -> BulkResult {
+    move_messages(app_handle, account, refs, MoveTarget::Special(SpecialUse::Trash)).await
+}
+
+/// A system flag the bulk commands can set or clear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageFlag {
+    Seen,
+    Flagged,
+}
+
+impl MessageFlag {
+    pub(crate) fn store_command(&self, value: bool) -> String {
+        format!(
+            "{}FLAGS.SILENT ({})",
+            if value { "+" } else { "-" },
+            match self { MessageFlag::Seen => "\\Seen", MessageFlag::Flagged => "\\Flagged" },
+        )
+    }
+
+    /// Sets the flag in the cache for all of `uids` in one transaction.
+    pub(crate) fn save_local(&self, app_handle: &AppHandle, folder: &str, uids: &[u32], value: bool) -> Result<(), String> {
+        match self {
+            MessageFlag::Seen => database::set_messages_seen(app_handle, folder, uids, value),
+            MessageFlag::Flagged => database::set_messages_flagged(app_handle, folder, uids, value),
+        }
+    }
+}
+
+/// Sets or clears a flag on messages from any number of folders with one UID STORE per
+/// UID set, then updates the cache for the messages that succeeded in one transaction.
+pub async fn set_flag(app_handle: &AppHandle, account: &Account, refs: &[MessageRef], flag: MessageFlag, value: bool) -> BulkResult {
+    let command = flag.store_command(value);
+
+    let mut result = BulkResult::default();
+    for (folder, uids) in group_by_folder(refs) {
+        let undo = crate::mail::undo::flag_step(app_handle, &folder, &uids, flag, true);
+        let mut outcome = match source_mailbox(account, &folder) {
+            Ok(mailbox) => {
+                let uids_owned = uids.clone();
+                let command = command.clone();
+                execute_with_session(account, SessionKind::Sync, move |session| {
+                    session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+                    store_selected(session, &uids_owned, &command)
+                }).await.unwrap_or_else(|e| FolderOutcome::all_failed(&uids, &e))
+            }
+            Err(e) => FolderOutcome::all_failed(&uids, &e),
+        };
+
+        if let Err(e) = flag.save_local(app_handle, &folder, &outcome.done, value) {
+            let done = std::mem::take(&mut outcome.done);
+            outcome.failed.extend(done.into_iter().map(|uid| (uid, format!("Updated on the server but not locally: {}", e))));
+        }
+        result.undo.extend(undo.and_then(|step| step.retain_uids(&outcome.done)));
+        result.record(&folder, outcome);
+    }
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_copyuid() {
+        let response = "* OK [COPYUID 38505 304,319:320 3956:3958] Done\r\n* 12 EXPUNGE\r\n";
+        assert_eq!(parse_copyuid(response), Some((38505, vec![(304, 3956), (319, 3957), (320, 3958)])));
+        assert_eq!(parse_copyuid("* OK [COPYUID 1 2:3 4] Done"), None);
+        assert_eq!(parse_copyuid("* 3 EXPUNGE"), None);
+        let split = "* OK [COPYUID 7 1:2 10:11] Moved\r\n* OK [COPYUID 7 5 12] Moved\r\n";
+        assert_eq!(parse_copyuid(split), Some((7, vec![(1, 10), (2, 11), (5, 12)])));
+    }
+
+    #[test]
+    fn test_uid_set() {
+        assert_eq!(uid_set(&[9, 4, 7, 8, 4, 1, 2]), "1:2,4,7:9");
+        assert_eq!(uid_set(&[42]), "42");
+        assert_eq!(uid_set(&[]), "");
+        assert_eq!(expand_uid_set(&uid_set(&[3, 5, 6, 7])), vec![3, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,446 @@
+//! Blocking ManageSieve (RFC 5804) client for storing server-side filter scripts.
+//!
+//! Connections always upgrade with STARTTLS before authenticating, and authenticate with
+//! the same XOAUTH2 bearer token used for IMAP.
+
+use crate::auth::account::Account;
+use base64::Engine;
+use native_tls::{TlsConnector, TlsStream};
+use serde::{Serialize, Deserialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+pub const MANAGESIEVE_PORT: u16 = 4190;
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SieveScript {
+    pub name: String,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SieveCapabilities {
+    pub implementation: Option<String>,
+    pub sasl: Vec<String>,
+    /// Sieve extensions the server supports, e.g. `fileinto`, `imap4flags`.
+    pub extensions: Vec<String>,
+    pub starttls: bool,
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Atom(String),
+    Str(String),
+}
+
+impl Token {
+    fn text(&self) -> &str {
+        match self {
+            Token::Atom(s) | Token::Str(s) => s,
+        }
+    }
+}
+
+/// The `OK`/`NO`/`BYE` line that ends every response.
+#[derive(Debug)]
+struct Status {
+    ok: bool,
+    code: Option<String>,
+    text: Option<String>,
+}
+
+impl Status {
+    fn into_result(self, context: &str) -> Result<Option<String>, String> {
+        if self.ok {
+            return Ok(self.text);
+        }
+        let mut message = format!("{} failed", context);
+        if let Some(code) = self.code {
+            message.push_str(&format!(" ({})", code));
+        }
+        if let Some(text) = self.text {
+            message.push_str(&format!(": {}", text.trim()));
+        }
+        Err(message)
+    }
+}
+
+struct Response {
+    lines: Vec<Vec<Token>>,
+    status: Status,
+}
+
+pub struct ManageSieveClient {
+    reader: BufReader<Stream>,
+    capabilities: SieveCapabilities,
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Splits a response line into atoms and quoted strings. A trailing `{N}` literal marker is
+/// returned separately so the caller can read the literal bytes.
+fn tokenize(line: &str) -> Result<(Vec<Token>, Option<usize>), String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '(' | ')' => { chars.next(); }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => s.extend(chars.next()),
+                        Some('"') => break,
+                        Some(ch) => s.push(ch),
+                        None => return Err("Unterminated quoted string in ManageSieve response".to_string()),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            '{' => {
+                let rest: String = chars.collect();
+                let len = rest.trim_start_matches('{').trim_end_matches('}').trim_end_matches('+');
+                let len = len.parse::<usize>().map_err(|_| format!("Bad literal marker in ManageSieve response: {}", rest))?;
+                return Ok((tokens, Some(len)));
+            }
+            _ => {
+                let mut s = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch == ' ' || ch == '(' || ch == ')' {
+                        break;
+                    }
+                    s.push(ch);
+                    chars.next();
+                }
+                tokens.push(Token::Atom(s));
+            }
+        }
+    }
+    Ok((tokens, None))
+}
+
+impl ManageSieveClient {
+    /// Connects to the account's mail host, upgrades to TLS and authenticates.
+    pub fn connect(account: &Account) -> Result<Self, String> {
+        let host = account.provider.imap_config().host;
+        let tcp = TcpStream::connect((host.as_str(), MANAGESIEVE_PORT)).map_err(|e| format!("ManageSieve Connection Error: {}", e))?;
+        tcp.set_read_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+        tcp.set_write_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+
+        let mut client = Self::greet(Stream::Plain(tcp))?.starttls(&host)?;
+        client.authenticate(&account.email, &account.access_token)?;
+        Ok(client)
+    }
+
+    fn greet(stream: Stream) -> Result<Self, String> {
+        let mut client = ManageSieveClient { reader: BufReader::new(stream), capabilities: SieveCapabilities::default() };
+        client.read_capabilities()?;
+        Ok(client)
+    }
+
+    pub fn capabilities(&self) -> &SieveCapabilities {
+        &self.capabilities
+    }
+
+    fn read_capabilities(&mut self) -> Result<(), String> {
+        let response = self.read_response()?;
+        response.status.into_result("ManageSieve greeting")?;
+
+        let mut caps = SieveCapabilities::default();
+        for line in &response.lines {
+            let Some(name) = line.first() else { continue };
+            let value = line.get(1).map(|t| t.text().to_string());
+            match name.text().to_uppercase().as_str() {
+                "IMPLEMENTATION" => caps.implementation = value,
+                "SASL" => caps.sasl = value.unwrap_or_default().split_whitespace().map(|s| s.to_uppercase()).collect(),
+                "SIEVE" => caps.extensions = value.unwrap_or_default().split_whitespace().map(|s| s.to_lowercase()).collect(),
+                "STARTTLS" => caps.starttls = true,
+                _ => {}
+            }
+        }
+        self.capabilities = caps;
+        Ok(())
+    }
+
+    fn starttls(mut self, host: &str) -> Result<Self, String> {
+        if !self.capabilities.starttls {
+            return Err("Server does not offer STARTTLS for ManageSieve".to_string());
+        }
+        self.command("STARTTLS")?;
+
+        let Stream::Plain(tcp) = self.reader.into_inner() else {
+            return Err("ManageSieve connection is already encrypted".to_string());
+        };
+        let connector = TlsConnector::builder().build().map_err(|e| format!("TLS Builder Error: {}", e))?;
+        let tls = connector.connect(host, tcp).map_err(|e| format!("ManageSieve TLS Error: {}", e))?;
+
+        // RFC 5804 2.2: the server re-announces its capabilities after the handshake
+        Self::greet(Stream::Tls(Box::new(tls)))
+    }
+
+    fn authenticate(&mut self, email: &str, access_token: &str) -> Result<(), String> {
+        if !self.capabilities.sasl.iter().any(|m| m == "XOAUTH2") {
+            return Err("Server does not support XOAUTH2 for ManageSieve".to_string());
+        }
+        let initial = base64::engine::general_purpose::STANDARD.encode(format!("user={}\x01auth=Bearer {}\x01\x01", email, access_token));
+        self.write_line(&format!("AUTHENTICATE \"XOAUTH2\" {}", quote(&initial)))?;
+
+        // A rejected token comes back as a SASL challenge carrying the error, which must be
+        // answered with an empty response before the server sends its final NO
+        let first = self.reader.fill_buf().map_err(|e| format!("ManageSieve Read Error: {}", e))?.first().copied();
+        if matches!(first, Some(b'"') | Some(b'{')) {
+            let challenge = self.read_response_line()?;
+            log::warn!("ManageSieve XOAUTH2 challenge: {:?}", challenge);
+            self.write_line("\"\"")?;
+        }
+        self.read_response()?.status.into_result("ManageSieve authentication")?;
+        Ok(())
+    }
+
+    pub fn list_scripts(&mut self) -> Result<Vec<SieveScript>, String> {
+        let lines = self.command("LISTSCRIPTS")?;
+        Ok(lines.iter()
+            .filter_map(|line| {
+                let name = line.first()?.text().to_string();
+                let active = line.get(1).is_some_and(|t| t.text().eq_ignore_ascii_case("ACTIVE"));
+                Some(SieveScript { name, active })
+            })
+            .collect())
+    }
+
+    pub fn get_script(&mut self, name: &str) -> Result<String, String> {
+        let lines = self.command(&format!("GETSCRIPT {}", quote(name)))?;
+        lines.first()
+            .and_then(|line| line.first())
+            .map(|t| t.text().to_string())
+            .ok_or_else(|| format!("Server returned no content for script \"{}\"", name))
+    }
+
+    /// Validates a script without storing it. Returns any warnings the server reported.
+    pub fn check_script(&mut self, script: &str) -> Result<Option<String>, String> {
+        self.write_with_literal("CHECKSCRIPT", script)?;
+        self.read_response()?.status.into_result("Sieve script check")
+    }
+
+    pub fn put_script(&mut self, name: &str, script: &str) -> Result<Option<String>, String> {
+        self.write_with_literal(&format!("PUTSCRIPT {}", quote(name)), script)?;
+        self.read_response()?.status.into_result("Sieve script upload")
+    }
+
+    /// Activates `name`; an empty name deactivates all scripts.
+    pub fn set_active(&mut self, name: &str) -> Result<(), String> {
+        self.command(&format!("SETACTIVE {}", quote(name)))?;
+        Ok(())
+    }
+
+    pub fn delete_script(&mut self, name: &str) -> Result<(), String> {
+        self.command(&format!("DELETESCRIPT {}", quote(name)))?;
+        Ok(())
+    }
+
+    pub fn logout(mut self) {
+        let _ = self.write_line("LOGOUT");
+        let _ = self.read_response();
+    }
+
+    /// Sends a single-line command and returns its data lines once the server answers `OK`.
+    fn command(&mut self, line: &str) -> Result<Vec<Vec<Token>>, String> {
+        self.write_line(line)?;
+        let Response { lines, status } = self.read_response()?;
+        status.into_result(line.split_whitespace().next().unwrap_or(line))?;
+        Ok(lines)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        let stream = self.reader.get_mut();
+        stream.write_all(line.as_bytes()).and_then(|_| stream.write_all(b"\r\n")).and_then(|_| stream.flush())
+            .map_err(|e| format!("ManageSieve Write Error: {}", e))
+    }
+
+    /// Sends `prefix` followed by a non-synchronizing literal, as PUTSCRIPT and CHECKSCRIPT take.
+    fn write_with_literal(&mut self, prefix: &str, content: &str) -> Result<(), String> {
+        let stream = self.reader.get_mut();
+        let head = format!("{} {{{}+}}\r\n", prefix, content.len());
+        stream.write_all(head.as_bytes())
+            .and_then(|_| stream.write_all(content.as_bytes()))
+            .and_then(|_| stream.write_all(b"\r\n"))
+            .and_then(|_| stream.flush())
+            .map_err(|e| format!("ManageSieve Write Error: {}", e))
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line).map_err(|e| format!("ManageSieve Read Error: {}", e))?;
+        if read == 0 {
+            return Err("ManageSieve server closed the connection".to_string());
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Reads one logical response line, pulling in any literals it announces.
+    fn read_response_line(&mut self) -> Result<Vec<Token>, String> {
+        let mut tokens = Vec::new();
+        let mut raw = self.read_line()?;
+        loop {
+            let (mut line_tokens, literal) = tokenize(&raw)?;
+            tokens.append(&mut line_tokens);
+            let Some(len) = literal else { break };
+            let mut buf = vec![0u8; len];
+            self.reader.read_exact(&mut buf).map_err(|e| format!("ManageSieve Read Error: {}", e))?;
+            tokens.push(Token::Str(String::from_utf8_lossy(&buf).to_string()));
+            raw = self.read_line()?;
+        }
+        Ok(tokens)
+    }
+
+    /// Reads response lines up to and including the final `OK`, `NO` or `BYE`.
+    fn read_response(&mut self) -> Result<Response, String> {
+        let mut lines = Vec::new();
+        loop {
+            let tokens = self.read_response_line()?;
+
+            let status = match tokens.first() {
+                Some(Token::Atom(a)) if a.eq_ignore_ascii_case("OK") => Some(true),
+                Some(Token::Atom(a)) if a.eq_ignore_ascii_case("NO") || a.eq_ignore_ascii_case("BYE") => Some(false),
+                _ => None,
+            };
+            let Some(ok) = status else {
+                if !tokens.is_empty() {
+                    lines.push(tokens);
+                }
+                continue;
+            };
+
+            // Response codes are the atoms between the status and the human-readable string
+            let code = tokens[1..].iter()
+                .take_while(|t| matches!(t, Token::Atom(_)))
+                .map(|t| t.text())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let text = tokens[1..].iter().rev().find_map(|t| match t {
+                Token::Str(s) => Some(s.clone()),
+                Token::Atom(_) => None,
+            });
+            return Ok(Response {
+                lines,
+                status: Status { ok, code: if code.is_empty() { None } else { Some(code) }, text },
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    const GREETING: &str = "\"IMPLEMENTATION\" \"Stand-in\"\r\n\"SASL\" \"PLAIN XOAUTH2\"\r\n\"SIEVE\" \"fileinto imap4flags copy\"\r\nOK \"ready\"\r\n";
+
+    /// Serves a scripted conversation: each request line is answered with the matching reply.
+    fn stand_in(exchanges: Vec<(&'static str, &'static str)>) -> (std::net::SocketAddr, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(GREETING.as_bytes()).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut received = Vec::new();
+            for (expect, reply) in exchanges {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(len) = line.trim_end().strip_suffix("+}").and_then(|l| l.rsplit('{').next()) {
+                    let mut body = vec![0u8; len.parse::<usize>().unwrap() + 2];
+                    reader.read_exact(&mut body).unwrap();
+                    line.push_str(&String::from_utf8_lossy(&body));
+                }
+                assert!(line.starts_with(expect), "expected {:?}, got {:?}", expect, line);
+                received.push(line);
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+            received
+        });
+        (addr, handle)
+    }
+
+    fn connect_plain(addr: std::net::SocketAddr) -> ManageSieveClient {
+        ManageSieveClient::greet(Stream::Plain(TcpStream::connect(addr).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_tokenize_codes_and_literals() {
+        let (tokens, literal) = tokenize("NO (QUOTA/MAXSIZE) \"Script too large\"").unwrap();
+        assert_eq!(tokens, vec![Token::Atom("NO".into()), Token::Atom("QUOTA/MAXSIZE".into()), Token::Str("Script too large".into())]);
+        assert_eq!(literal, None);
+        assert_eq!(tokenize("{12}").unwrap().1, Some(12));
+    }
+
+    #[test]
+    fn test_session_against_stand_in() {
+        let (addr, server) = stand_in(vec![
+            ("AUTHENTICATE \"XOAUTH2\"", "OK \"Logged in\"\r\n"),
+            ("LISTSCRIPTS", "\"vacation\"\r\n\"orionmail\" ACTIVE\r\nOK\r\n"),
+            ("GETSCRIPT \"orionmail\"", "{7}\r\nkeep;\r\n\r\nOK\r\n"),
+            ("PUTSCRIPT \"orionmail\" {5+}", "NO (QUOTA) \"Over quota\"\r\n"),
+            ("SETACTIVE \"orionmail\"", "OK\r\n"),
+        ]);
+
+        let mut client = connect_plain(addr);
+        assert_eq!(client.capabilities().extensions, vec!["fileinto", "imap4flags", "copy"]);
+        client.authenticate("me@example.com", "token").unwrap();
+
+        let scripts = client.list_scripts().unwrap();
+        assert_eq!(scripts, vec![
+            SieveScript { name: "vacation".into(), active: false },
+            SieveScript { name: "orionmail".into(), active: true },
+        ]);
+        assert_eq!(client.get_script("orionmail").unwrap(), "keep;\r\n");
+
+        let err = client.put_script("orionmail", "stop;").unwrap_err();
+        assert_eq!(err, "Sieve script upload failed (QUOTA): Over quota");
+        client.set_active("orionmail").unwrap();
+
+        let received = server.join().unwrap();
+        let auth = received[0].split('"').nth(3).unwrap();
+        let decoded = base64::engine::general_purpose::STANDARD.decode(auth).unwrap();
+        assert_eq!(decoded, b"user=me@example.com\x01auth=Bearer token\x01\x01");
+        assert!(received[3].ends_with("stop;\r\n"));
+    }
+}
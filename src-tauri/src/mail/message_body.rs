@@ -4,6 +4,7 @@ use crate::mail::imap_session;
 use imap_proto::types::BodyStructure;
 use crate::mail::extraction;
 use crate::mail::extraction::ExtractedData;
+use crate::mail::auth_results::{self, AuthVerdict};
 
 use tauri::{AppHandle, Manager, Emitter};
 use mailparse::{parse_mail, ParsedMail};
@@ -46,6 +47,7 @@ pub struct MessageDetail {
     pub body: String,
     pub attachments: Vec<MessageAttachment>,
     pub extracted_data: Option<serde_json::Value>,
+    pub auth_results: Option<AuthVerdict>,
 }
 
 struct CidCandidate {
@@ -335,6 +337,25 @@ fn generate_preview(html: &str) -> String {
     }
 }
 
+fn load_extraction_context(app_handle: &AppHandle, folder: &str, uid: u32) -> extraction::ExtractionContext {
+    let header = database::get_messages_by_uids(app_handle, folder, &[uid])
+        .ok()
+        .and_then(|mut msgs| msgs.pop());
+
+    let Some(header) = header else {
+        return extraction::ExtractionContext::default();
+    };
+
+    let address = database::parse_sender_address(&header.from).unwrap_or_default();
+
+    extraction::ExtractionContext {
+        sender_is_known: crate::contacts::contact_store::is_known_sender(app_handle, &address, folder, uid).unwrap_or(false),
+        known_domains: crate::contacts::contact_store::get_known_contact_domains(app_handle, 200).unwrap_or_default(),
+        sender: Some(header.from),
+        subject: Some(header.subject),
+    }
+}
+
 pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32) -> Result<MessageDetail, String> {
     let app_handle_cache = app_handle.clone();
     let folder_cache = folder.to_string();
@@ -370,7 +391,8 @@ pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Acc
                 needs_reextract = true;
             }
 
-            return Ok((Some(MessageDetail { body: cached_body, attachments, extracted_data }), stored_validity, needs_reextract, extracted_data_json.is_none()));
+            let auth_results = database::get_message_auth_results(&app_handle_cache, &folder_cache, uid).unwrap_or(None);
+            return Ok((Some(MessageDetail { body: cached_body, attachments, extracted_data, auth_results }), stored_validity, needs_reextract, extracted_data_json.is_none()));
         }
 
         Ok::<_, String>((None, stored_validity, false, true))
@@ -388,7 +410,8 @@ pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Acc
             
             tokio::spawn(async move {
                 let text = String::from_utf8_lossy(body_clone.as_bytes()).to_string();
-                let extracted = extraction::run_extraction_pipeline(&body_clone, &text);
+                let context = load_extraction_context(&app_clone, &folder_clone, uid);
+                let extracted = extraction::run_extraction_pipeline(&body_clone, &text, &context);
                 if let Ok(ext_json) = serde_json::to_string(&extracted) {
                     let attachments_json = serde_json::to_string(&attachments_clone).ok();
                     let _ = database::update_message_body(&app_clone, &folder_clone, uid, &body_clone, "", attachments_json, Some(ext_json.clone()));
@@ -429,6 +452,7 @@ pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Acc
         let mut target_part = String::new();
         let mut full_payload: Vec<u8> = Vec::new();
         let mut attachments = Vec::new();
+        let mut auth_headers: Vec<u8> = Vec::new();
         
         let fetch_bs = session.uid_fetch(uid.to_string(), "(BODYSTRUCTURE)")
             .map_err(|e| format!("IMAP fetch_bs error: {}", e))?;
@@ -445,7 +469,7 @@ pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Acc
         let fetch_query = if target_part.is_empty() {
             "(BODY.PEEK[TEXT] BODY.PEEK[HEADER])".to_string()
         } else {
-            format!("(BODY.PEEK[{}.MIME] BODY.PEEK[{}])", target_part, target_part)
+            format!("(BODY.PEEK[{}.MIME] BODY.PEEK[{}] BODY.PEEK[HEADER.FIELDS ({})])", target_part, target_part, auth_results::AUTH_HEADER_FIELDS)
         };
 
         log::debug!("Fetching with IMAP Query: UID {}, {}", uid, fetch_query);
@@ -453,6 +477,8 @@ pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Acc
             .map_err(|e| format!("IMAP fetch payload error: {}", e))?;
 
         if let Some(msg) = fetch_results.iter().next() {
+            if let Some(h) = msg.header() { auth_headers.extend_from_slice(h); }
+
             if target_part.is_empty() {
                 if let Some(h) = msg.header() { full_payload.extend_from_slice(h); }
                 if let Some(t) = msg.text() { full_payload.extend_from_slice(t); }
@@ -472,10 +498,10 @@ pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Acc
                 }
             }
         }
-        Ok::<_, String>((target_part, full_payload, attachments))
+        Ok::<_, String>((target_part, full_payload, attachments, auth_headers))
     }).await;
     
-    let (_fetched_target_part, fetched_full_payload, fetched_attachments) = match imap_result {
+    let (_fetched_target_part, fetched_full_payload, fetched_attachments, fetched_auth_headers) = match imap_result {
         Ok(data) => data,
         Err(e) => return Err(format!("IMAP Execution Error: {:?}", e)),
     };
@@ -520,15 +546,21 @@ pub async fn fetch_and_cache_body_internal(app_handle: &AppHandle, account: &Acc
         extract_ics_text(&parsed, &mut text);
     }
 
-    let extracted = extraction::run_extraction_pipeline(&parsed_body, &text);
+    let context = load_extraction_context(app_handle, folder, uid);
+    let extracted = extraction::run_extraction_pipeline(&parsed_body, &text, &context);
     let extracted_json = serde_json::to_string(&extracted).ok();
     
     let _ = database::update_message_body(app_handle, folder, uid, &parsed_body, &preview, attachments_json, extracted_json.clone());
+    crate::mail::semantic_index::spawn_indexer(app_handle.clone());
+
+    let auth_verdict = auth_results::parse_auth_headers(&fetched_auth_headers);
+    let _ = database::update_message_auth_results(app_handle, folder, uid, &auth_verdict);
     
     Ok(MessageDetail {
         body: parsed_body,
         attachments: fetched_attachments,
         extracted_data: extracted_json.and_then(|s| serde_json::from_str(&s).ok()),
+        auth_results: Some(auth_verdict),
     })
 }
 
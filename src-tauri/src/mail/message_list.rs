@@ -19,6 +19,9 @@ pub struct MessageHeader {
     pub snippet: Option<String>,
     pub to: Option<String>,
     pub message_id: Option<String>,
+    /// How the sender lets the user unsubscribe, from `List-Unsubscribe`.
+    #[serde(default)]
+    pub unsubscribe: Option<crate::mail::unsubscribe::Unsubscribe>,
 }
 
 pub async fn get_inbox_messages(app_handle: &AppHandle, account: Account) -> Result<Vec<MessageHeader>, String> {
@@ -122,6 +125,7 @@ pub async fn get_inbox_messages(app_handle: &AppHandle, account: Account) -> Res
                         snippet,
                         to: to_opt,
                         message_id,
+                        unsubscribe: None,
                     });
                 }
             }
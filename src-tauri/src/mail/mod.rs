@@ -15,3 +15,23 @@ pub mod sync_manager;
 pub mod extraction;
 pub mod shutdown;
 pub mod search;
+pub mod raw_message;
+pub mod auth_results;
+pub mod dkim;
+pub mod attachment_preview;
+pub mod attachment_safety;
+pub mod search_query;
+pub mod saved_searches;
+pub mod semantic_index;
+pub mod search_history;
+pub mod rules;
+pub mod managesieve;
+pub mod sieve;
+pub mod snooze;
+pub mod mailbox_actions;
+pub mod undo;
+pub mod pending_ops;
+pub mod gmail_labels;
+pub mod tags;
+pub mod spam;
+pub mod unsubscribe;
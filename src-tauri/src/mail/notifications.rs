@@ -93,3 +93,22 @@ pub fn show_new_emails(app: &AppHandle, new_emails: &[(String, String, u32)]) {
         }
     }
 }
+
+pub fn show_saved_search_matches(app: &AppHandle, search_name: &str, matches: &[&crate::mail::message_list::MessageHeader]) {
+    let Some(first) = matches.first() else { return };
+
+    let body = if matches.len() == 1 {
+        format!("{} - {}", clean_sender(&first.from), first.subject)
+    } else {
+        format!("{} - {}\n+{} more", clean_sender(&first.from), first.subject, matches.len() - 1)
+    };
+
+    app.notification()
+        .builder()
+        .title(format!("New in \"{}\"", search_name))
+        .body(body)
+        .icon("icons/128x128.png")
+        .action_type_id("saved_search")
+        .show()
+        .ok();
+}
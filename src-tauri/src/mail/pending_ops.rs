@@ -0,0 +1,429 @@
+use crate::auth::account::Account;
+use crate::mail::database::{self, get_db_path};
+use crate::mail::mailbox_actions::{self, MessageFlag, GONE_ERROR};
+use crate::mail::imap_session::{execute_with_session, SessionKind};
+use crate::mail::message_list::MessageHeader;
+use crate::mail::undo::{ActionKind, JournalMessage, UndoStep};
+use once_cell::sync::Lazy;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Mutex, Notify};
+
+const MIN_RETRY_SECS: u64 = 30;
+const MAX_RETRY_SECS: u64 = 5 * 60;
+/// Failures that do not look like lost connectivity are retried this often before the
+/// operation is given up as a conflict.
+const MAX_ATTEMPTS: u32 = 5;
+
+static REPLAYER_RUNNING: AtomicBool = AtomicBool::new(false);
+static REPLAYER_WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+/// Replays run one at a time so operations reach the server in the order they were made.
+static REPLAY_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OpKind {
+    Seen,
+    Flagged,
+    Delete,
+}
+
+impl OpKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OpKind::Seen => "seen",
+            OpKind::Flagged => "flagged",
+            OpKind::Delete => "delete",
+        }
+    }
+}
+
+impl FromStr for OpKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "seen" => Ok(OpKind::Seen),
+            "flagged" => Ok(OpKind::Flagged),
+            "delete" => Ok(OpKind::Delete),
+            _ => Err(format!("Unknown pending operation: {}", s)),
+        }
+    }
+}
+
+impl From<MessageFlag> for OpKind {
+    fn from(flag: MessageFlag) -> Self {
+        match flag {
+            MessageFlag::Seen => OpKind::Seen,
+            MessageFlag::Flagged => OpKind::Flagged,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingOperation {
+    pub id: i64,
+    pub folder: String,
+    pub uid: u32,
+    pub kind: OpKind,
+    /// New flag value; unused for deletes.
+    pub value: bool,
+    pub created_at: i64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// The cached row before the change, used to roll it back if the server refuses it.
+    #[serde(skip)]
+    snapshot: Option<MessageHeader>,
+    /// Undo journal entry of a delete, updated with the real move once it is replayed.
+    #[serde(skip)]
+    undo_id: Option<String>,
+}
+
+/// Sent as `mail:pending_conflict` when a queued operation could not be applied.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingConflict {
+    pub operation: PendingOperation,
+    pub error: String,
+    /// Whether the local change was rolled back or the row dropped to match the server.
+    pub reverted: bool,
+}
+
+pub fn init_pending_ops_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_operations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            value INTEGER NOT NULL DEFAULT 0,
+            snapshot TEXT,
+            created_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            undo_id TEXT
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare("PRAGMA table_info(pending_operations)").map_err(|e| e.to_string())?;
+    let has_undo_id = stmt.query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .any(|name| name == "undo_id");
+    if !has_undo_id {
+        conn.execute("ALTER TABLE pending_operations ADD COLUMN undo_id TEXT", ()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn open(app_handle: &AppHandle) -> Result<Connection, String> {
+    let db_path = get_db_path(app_handle)?;
+    Connection::open(db_path).map_err(|e| e.to_string())
+}
+
+const SELECT_COLUMNS: &str = "SELECT id, folder, uid, kind, value, snapshot, created_at, attempts, last_error, undo_id FROM pending_operations";
+
+fn row_to_op(row: &rusqlite::Row) -> rusqlite::Result<PendingOperation> {
+    let kind: String = row.get(3)?;
+    let snapshot: Option<String> = row.get(5)?;
+    Ok(PendingOperation {
+        id: row.get(0)?,
+        folder: row.get(1)?,
+        uid: row.get(2)?,
+        kind: kind.parse().map_err(|e: String| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, e.into()))?,
+        value: row.get::<_, i32>(4)? != 0,
+        snapshot: snapshot.and_then(|s| serde_json::from_str(&s).ok()),
+        created_at: row.get(6)?,
+        attempts: row.get(7)?,
+        last_error: row.get(8)?,
+        undo_id: row.get(9)?,
+    })
+}
+
+pub fn list_pending(app_handle: &AppHandle) -> Result<Vec<PendingOperation>, String> {
+    let conn = open(app_handle)?;
+    let mut stmt = conn.prepare(&format!("{} ORDER BY id ASC", SELECT_COLUMNS)).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], row_to_op).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Queues an operation for the server and returns its id. A flag change replaces any
+/// still-pending change of the same flag on the same message, since only the final value matters.
+pub fn enqueue(app_handle: &AppHandle, folder: &str, uid: u32, kind: OpKind, value: bool, snapshot: Option<&MessageHeader>) -> Result<i64, String> {
+    let mut snapshot = snapshot.map(serde_json::to_string).transpose().map_err(|e| e.to_string())?;
+    let mut conn = open(app_handle)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    if kind != OpKind::Delete {
+        // The superseded operation's snapshot still holds the value from before either change
+        let previous: Option<Option<String>> = tx.query_row(
+            "SELECT snapshot FROM pending_operations WHERE folder = ?1 AND uid = ?2 AND kind = ?3 ORDER BY id ASC LIMIT 1",
+            rusqlite::params![folder, uid, kind.as_str()],
+            |row| row.get(0),
+        ).optional().map_err(|e| e.to_string())?;
+        if let Some(previous) = previous {
+            snapshot = previous.or(snapshot);
+        }
+        tx.execute(
+            "DELETE FROM pending_operations WHERE folder = ?1 AND uid = ?2 AND kind = ?3",
+            rusqlite::params![folder, uid, kind.as_str()],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.execute(
+        "INSERT INTO pending_operations (folder, uid, kind, value, snapshot, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![folder, uid, kind.as_str(), value as i32, snapshot, chrono::Utc::now().timestamp()],
+    ).map_err(|e| e.to_string())?;
+    let id = tx.last_insert_rowid();
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Applies a flag change to the cache and queues it for the server, unless the folder is
+/// virtual and exists only locally.
+pub fn apply_flag(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32, flag: MessageFlag, value: bool) -> Result<(), String> {
+    let snapshot = database::get_messages_by_uids(app_handle, folder, &[uid])?.into_iter().next();
+    flag.save_local(app_handle, folder, &[uid], value)?;
+    if mailbox_actions::source_mailbox(account, folder).is_ok() {
+        enqueue(app_handle, folder, uid, flag.into(), value, snapshot.as_ref())?;
+        wake();
+    }
+    Ok(())
+}
+
+/// Drops a message from the cache and queues its move to Trash, unless the folder is virtual.
+/// The delete is journaled right away, so it can be undone while it is still queued.
+pub fn apply_delete(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32) -> Result<(), String> {
+    let snapshot = database::get_messages_by_uids(app_handle, folder, &[uid])?.into_iter().next();
+    database::move_messages_local(app_handle, folder, &[(uid, None)])?;
+    if mailbox_actions::source_mailbox(account, folder).is_ok() {
+        let op_id = enqueue(app_handle, folder, uid, OpKind::Delete, false, snapshot.as_ref())?;
+        let step = UndoStep::QueuedDelete {
+            folder: folder.to_string(),
+            messages: vec![JournalMessage { uid, message_id: snapshot.and_then(|s| s.message_id), dest_uid: None, dest_validity: None, previous: None }],
+        };
+        if let Some(undo_id) = crate::mail::undo::journal(app_handle, ActionKind::Delete, vec![step]) {
+            let conn = open(app_handle)?;
+            conn.execute("UPDATE pending_operations SET undo_id = ?1 WHERE id = ?2", rusqlite::params![undo_id, op_id])
+                .map_err(|e| e.to_string())?;
+        }
+        wake();
+    }
+    Ok(())
+}
+
+/// Cancels a queued delete and puts the cached row back. Returns false when the delete is no
+/// longer queued, because it has already reached the server.
+pub async fn cancel_delete(app_handle: &AppHandle, folder: &str, uid: u32) -> Result<bool, String> {
+    // Waits out a replay in progress, which may be this very delete
+    let _guard = REPLAY_LOCK.lock().await;
+    let conn = open(app_handle)?;
+    let op = conn.query_row(
+        &format!("{} WHERE folder = ?1 AND uid = ?2 AND kind = ?3 ORDER BY id DESC LIMIT 1", SELECT_COLUMNS),
+        rusqlite::params![folder, uid, OpKind::Delete.as_str()],
+        row_to_op,
+    ).optional().map_err(|e| e.to_string())?;
+    let Some(op) = op else { return Ok(false) };
+
+    remove_op(app_handle, op.id)?;
+    if let Some(snapshot) = &op.snapshot {
+        database::insert_or_update_messages(app_handle, std::slice::from_ref(snapshot))?;
+    }
+    Ok(true)
+}
+
+fn next_op(app_handle: &AppHandle) -> Result<Option<PendingOperation>, String> {
+    let conn = open(app_handle)?;
+    conn.query_row(&format!("{} ORDER BY id ASC LIMIT 1", SELECT_COLUMNS), [], row_to_op)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+fn remove_op(app_handle: &AppHandle, id: i64) -> Result<(), String> {
+    let conn = open(app_handle)?;
+    conn.execute("DELETE FROM pending_operations WHERE id = ?1", rusqlite::params![id]).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn record_attempt(app_handle: &AppHandle, id: i64, error: &str) -> Result<u32, String> {
+    let conn = open(app_handle)?;
+    conn.execute(
+        "UPDATE pending_operations SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+        rusqlite::params![error, id],
+    ).map_err(|e| e.to_string())?;
+    conn.query_row("SELECT attempts FROM pending_operations WHERE id = ?1", rusqlite::params![id], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Whether an error means the server could not be reached, as opposed to refusing the operation.
+fn is_connectivity_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    ["connection", "authentication failed", "tls", "timed out", "broken pipe", "os error", "no active account", "spawn blocking"]
+        .iter()
+        .any(|needle| error.contains(needle))
+}
+
+/// Applies one operation on the server.
+async fn replay(app_handle: &AppHandle, account: &Account, op: &PendingOperation) -> Result<(), String> {
+    match op.kind {
+        OpKind::Seen | OpKind::Flagged => {
+            let mailbox = mailbox_actions::source_mailbox(account, &op.folder)?;
+            let flag = if op.kind == OpKind::Seen { MessageFlag::Seen } else { MessageFlag::Flagged };
+            let command = flag.store_command(op.value);
+            let uid = op.uid;
+            let outcome = execute_with_session(account, SessionKind::Sync, move |session| {
+                session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+                mailbox_actions::store_selected(session, &[uid], &command)
+            }).await?;
+            match outcome.failed.into_iter().next() {
+                Some((_, error)) => Err(error),
+                None => Ok(()),
+            }
+        }
+        OpKind::Delete => {
+            let mut moved = mailbox_actions::trash_message(app_handle, account, &op.folder, op.uid).await?;
+            // The row was dropped when the delete was queued, so the Message-ID comes from the snapshot
+            if let Some(UndoStep::Move { messages, .. }) = moved.undo.as_mut() {
+                for m in messages.iter_mut().filter(|m| m.message_id.is_none()) {
+                    m.message_id = op.snapshot.as_ref().and_then(|s| s.message_id.clone());
+                }
+            }
+            if let (Some(undo_id), Some(step)) = (&op.undo_id, moved.undo) {
+                if let Err(e) = crate::mail::undo::replace_steps(app_handle, undo_id, vec![step]) {
+                    log::warn!("[PENDING] Failed to update undo entry {}: {}", undo_id, e);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Makes the cache agree with the server after an operation is given up, and reports it.
+fn resolve_conflict(app_handle: &AppHandle, op: PendingOperation, error: String) {
+    let gone = error == GONE_ERROR;
+    let reverted = match (op.kind, gone) {
+        // The message is gone either way, which is what the delete wanted
+        (OpKind::Delete, true) => {
+            log::info!("[PENDING] Message {} in {} was already gone; delete is complete", op.uid, op.folder);
+            return;
+        }
+        (OpKind::Delete, false) => op.snapshot.as_ref()
+            .is_some_and(|s| database::insert_or_update_messages(app_handle, std::slice::from_ref(s)).is_ok()),
+        (_, true) => database::move_messages_local(app_handle, &op.folder, &[(op.uid, None)]).is_ok(),
+        (kind, false) => op.snapshot.as_ref().is_some_and(|s| {
+            let saved = if kind == OpKind::Seen {
+                database::set_message_seen(app_handle, &op.folder, op.uid, s.seen)
+            } else {
+                database::set_message_flagged(app_handle, &op.folder, op.uid, s.flagged)
+            };
+            saved.is_ok()
+        }),
+    };
+
+    log::warn!("[PENDING] Giving up on {:?} for UID {} in {}: {}", op.kind, op.uid, op.folder, error);
+    let folder = op.folder.clone();
+    let _ = app_handle.emit("mail:pending_conflict", PendingConflict { operation: op, error, reverted });
+    let _ = app_handle.emit("mail:updated", &folder);
+}
+
+/// Replays queued operations in order, stopping at the first one that fails for lack of
+/// connectivity, or failing for a reason worth retrying, so later operations never overtake it.
+pub async fn flush(app_handle: &AppHandle, account: &Account) -> Result<(), String> {
+    let _guard = REPLAY_LOCK.lock().await;
+    let mut replayed = 0;
+
+    while let Some(op) = next_op(app_handle)? {
+        match replay(app_handle, account, &op).await {
+            Ok(()) => {
+                remove_op(app_handle, op.id)?;
+                replayed += 1;
+            }
+            Err(e) if e == GONE_ERROR => {
+                remove_op(app_handle, op.id)?;
+                resolve_conflict(app_handle, op, e);
+            }
+            Err(e) => {
+                let attempts = record_attempt(app_handle, op.id, &e)?;
+                if is_connectivity_error(&e) || attempts < MAX_ATTEMPTS {
+                    return Err(e);
+                }
+                remove_op(app_handle, op.id)?;
+                resolve_conflict(app_handle, op, e);
+            }
+        }
+    }
+
+    if replayed > 0 {
+        log::info!("[PENDING] Replayed {} queued operations", replayed);
+        crate::tray_state::refresh_unread_count_from_db(app_handle);
+    }
+    Ok(())
+}
+
+/// Asks the replayer to try the queue now, e.g. after a new operation or a successful sync.
+pub fn wake() {
+    REPLAYER_WAKE.notify_one();
+}
+
+/// Background task that replays the queue whenever it is woken, backing off while offline.
+pub fn spawn_replayer(app: AppHandle) {
+    if REPLAYER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        log::info!("[PENDING] Replayer started");
+        let mut retry_secs = MIN_RETRY_SECS;
+
+        loop {
+            let queued = next_op(&app).ok().flatten().is_some();
+            let mut failed = false;
+            if queued {
+                match crate::auth::bootstrap::ensure_active_account(&app).await {
+                    Ok(account) => {
+                        if let Err(e) = flush(&app, &account).await {
+                            log::info!("[PENDING] Replay paused, will retry in {}s: {}", retry_secs, e);
+                            failed = true;
+                        }
+                    }
+                    Err(e) => {
+                        log::debug!("[PENDING] No usable account: {}", e);
+                        failed = true;
+                    }
+                }
+            }
+
+            let sleep_secs = if failed { retry_secs } else { MAX_RETRY_SECS };
+            retry_secs = if failed { (retry_secs * 2).min(MAX_RETRY_SECS) } else { MIN_RETRY_SECS };
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(sleep_secs)) => {}
+                _ = REPLAYER_WAKE.notified() => {}
+                _ = crate::mail::shutdown::PENDING_OPS_TOKEN.cancelled() => {
+                    log::info!("[PENDING] Shutdown requested, exiting replayer.");
+                    break;
+                }
+            }
+        }
+
+        REPLAYER_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_connectivity_error() {
+        assert!(is_connectivity_error("IMAP Connection Error: failed to lookup address information"));
+        assert!(is_connectivity_error("IMAP Store Error: Connection Lost"));
+        assert!(is_connectivity_error("IMAP Select Error: Broken pipe (os error 32)"));
+        assert!(!is_connectivity_error(GONE_ERROR));
+        assert!(!is_connectivity_error("IMAP Store Error: No Response: [CANNOT] Mailbox is read-only"));
+    }
+}
@@ -61,6 +61,9 @@ pub fn start_polling(app_handle: AppHandle, account: Account) {
             } else {
                 log::info!("[POLL] Sync already running. Skipping tick.");
             }
+
+            // Junk is throttled by its own minimum interval in the sync manager
+            crate::mail::sync_manager::enqueue_sync(app_handle.clone(), account.clone(), crate::mail::folder::MailFolder::Junk).await;
         }
     });
 
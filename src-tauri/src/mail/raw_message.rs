@@ -0,0 +1,163 @@
+use crate::auth::account::Account;
+use crate::mail::imap_session::{execute_with_session, SessionKind};
+use mailparse::parse_headers;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderField {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceivedHop {
+    pub from: Option<String>,
+    pub by: Option<String>,
+    pub with: Option<String>,
+    pub timestamp: Option<i64>,
+    pub delay_secs: Option<i64>,
+    pub raw: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawMessage {
+    pub source: String,
+    pub size: usize,
+    pub headers: Vec<HeaderField>,
+    pub received_chain: Vec<ReceivedHop>,
+}
+
+/// Fetches the complete RFC 822 source of a message without setting \Seen.
+pub async fn fetch_raw_message(account: &Account, folder: &str, uid: u32) -> Result<Vec<u8>, String> {
+    let folder_clone = folder.to_string();
+    let provider_clone = account.provider.clone();
+
+    execute_with_session(account, SessionKind::Primary, move |session| {
+        let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
+            Ok(mf) => match mf.to_imap_mailbox(&provider_clone) {
+                Some(mb) => mb.to_string(),
+                None => return Err("Cannot fetch from virtual folder".to_string()),
+            },
+            Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
+        };
+
+        session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+
+        let fetch_results = session.uid_fetch(uid.to_string(), "(UID BODY.PEEK[])")
+            .map_err(|e| format!("IMAP fetch raw message error: {}", e))?;
+
+        if let Some(msg) = fetch_results.iter().next() {
+            if let Some(body) = msg.body() {
+                return Ok(body.to_vec());
+            }
+        }
+        Err("Could not retrieve raw message.".to_string())
+    }).await
+}
+
+/// Builds the header view for a raw message. Header values are RFC 2047 decoded
+/// and the Received chain is returned oldest hop first.
+pub fn parse_raw_message(raw: &[u8]) -> RawMessage {
+    let mut headers = Vec::new();
+    let mut received_raw = Vec::new();
+
+    if let Ok((parsed, _)) = parse_headers(raw) {
+        for header in parsed.iter() {
+            let name = header.get_key();
+            let value = header.get_value();
+            if name.eq_ignore_ascii_case("received") {
+                received_raw.push(value.clone());
+            }
+            headers.push(HeaderField { name, value });
+        }
+    }
+
+    RawMessage {
+        source: String::from_utf8_lossy(raw).to_string(),
+        size: raw.len(),
+        headers,
+        received_chain: build_received_chain(&received_raw),
+    }
+}
+
+/// Received headers are prepended by each relay, so the topmost one is the newest.
+/// We reverse them and compute the delay each hop added relative to the previous one.
+pub fn build_received_chain(received_newest_first: &[String]) -> Vec<ReceivedHop> {
+    let mut hops: Vec<ReceivedHop> = received_newest_first.iter().rev().map(|v| parse_received(v)).collect();
+
+    let mut previous: Option<i64> = None;
+    for hop in hops.iter_mut() {
+        if let (Some(prev), Some(ts)) = (previous, hop.timestamp) {
+            hop.delay_secs = Some(ts - prev);
+        }
+        if hop.timestamp.is_some() {
+            previous = hop.timestamp;
+        }
+    }
+
+    hops
+}
+
+fn parse_received(value: &str) -> ReceivedHop {
+    let unfolded = value.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let (clauses, date_part) = match unfolded.rfind(';') {
+        Some(idx) => (&unfolded[..idx], Some(unfolded[idx + 1..].trim())),
+        None => (unfolded.as_str(), None),
+    };
+
+    let timestamp = date_part.and_then(|d| {
+        // Strip trailing comments such as "(UTC)" which chrono rejects
+        let cleaned = match d.find('(') {
+            Some(idx) => d[..idx].trim(),
+            None => d,
+        };
+        chrono::DateTime::parse_from_rfc2822(cleaned).ok().map(|dt| dt.timestamp())
+    });
+
+    ReceivedHop {
+        from: received_clause(clauses, "from"),
+        by: received_clause(clauses, "by"),
+        with: received_clause(clauses, "with"),
+        timestamp,
+        delay_secs: None,
+        raw: unfolded.clone(),
+    }
+}
+
+fn received_clause(clauses: &str, keyword: &str) -> Option<String> {
+    let tokens: Vec<&str> = clauses.split(' ').collect();
+    tokens.iter()
+        .position(|t| t.eq_ignore_ascii_case(keyword))
+        .and_then(|idx| tokens.get(idx + 1))
+        .map(|t| t.trim_matches(|c| c == '(' || c == ')' || c == ';').to_string())
+        .filter(|t| !t.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_received_chain_order_and_delay() {
+        let raw = b"Received: by mx.example.net with SMTP id abc; Mon, 6 Jan 2025 10:00:30 +0000\r\n\
+Received: from mail.sender.org (mail.sender.org [10.0.0.1]) by relay.example.net with ESMTPS; Mon, 6 Jan 2025 10:00:00 +0000\r\n\
+Subject: =?UTF-8?B?SGVsbG8gV29ybGQ=?=\r\n\
+\r\n\
+body\r\n";
+
+        let parsed = parse_raw_message(raw);
+        assert_eq!(parsed.received_chain.len(), 2);
+        assert_eq!(parsed.received_chain[0].from.as_deref(), Some("mail.sender.org"));
+        assert_eq!(parsed.received_chain[0].delay_secs, None);
+        assert_eq!(parsed.received_chain[1].by.as_deref(), Some("mx.example.net"));
+        assert_eq!(parsed.received_chain[1].delay_secs, Some(30));
+
+        let subject = parsed.headers.iter().find(|h| h.name == "Subject").unwrap();
+        assert_eq!(subject.value, "Hello World");
+    }
+}
@@ -0,0 +1,861 @@
+use crate::auth::account::Account;
+use crate::mail::database::{self, get_db_path};
+use crate::mail::folder::MailFolder;
+use crate::mail::imap_session::{execute_with_session, SessionKind};
+use crate::mail::mailbox_actions::{self, MessageRef, MoveTarget};
+use crate::mail::message_list::MessageHeader;
+use regex::{Regex, RegexBuilder};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use tauri::{AppHandle, Emitter};
+
+/// Evaluation log rows kept for debugging; older entries are pruned.
+const MAX_LOG_ENTRIES: i64 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TextOp {
+    Contains,
+    NotContains,
+    Equals,
+    StartsWith,
+    EndsWith,
+    /// Case-insensitive regular expression.
+    Matches,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "field", rename_all = "camelCase")]
+pub enum RuleCondition {
+    From { op: TextOp, value: String },
+    To { op: TextOp, value: String },
+    Subject { op: TextOp, value: String },
+    Body { op: TextOp, value: String },
+    Header { name: String, op: TextOp, value: String },
+    SizeGreaterThan { bytes: u64 },
+    SizeLessThan { bytes: u64 },
+    HasAttachment { value: bool },
+    /// An extracted entity type such as `TrackingNumber` or `CalendarEvent`.
+    EntityType { entity: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchMode {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RuleAction {
+    /// Moves to a folder id such as `inbox`, or any server mailbox by its IMAP name.
+    Move { mailbox: String },
+    /// A Gmail label, or an IMAP keyword on other servers.
+    Label { label: String },
+    MarkRead,
+    Star,
+    Delete,
+    Forward { to: String },
+    Notify,
+    SuppressNotification,
+    /// Skips the remaining rules for this message.
+    StopProcessing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+    /// Rules run in ascending position order.
+    pub position: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleTrigger {
+    Sync,
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleLogEntry {
+    pub id: i64,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub folder: String,
+    pub uid: u32,
+    pub subject: String,
+    pub matched: bool,
+    pub actions: Vec<String>,
+    pub error: Option<String>,
+    pub trigger: RuleTrigger,
+    pub evaluated_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleRunSummary {
+    pub evaluated: u32,
+    pub matched: u32,
+    pub errors: u32,
+}
+
+/// What the rules decided for one message, for the caller's notification logic.
+#[derive(Debug, Clone, Default)]
+pub struct RuleOutcome {
+    /// `Some(true)` forces a notification, `Some(false)` suppresses it.
+    pub notify: Option<bool>,
+    /// The message was moved or deleted and is no longer in its folder.
+    pub removed: bool,
+    pub matched: bool,
+    /// Actions that failed; the log row carries the message.
+    pub errors: u32,
+}
+
+/// Everything conditions can look at. Body, headers and entities are only loaded when an
+/// enabled rule needs them, since they cost an extra fetch per message.
+#[derive(Debug, Clone, Default)]
+pub struct RuleMessage {
+    pub folder: String,
+    pub uid: u32,
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub body: Option<String>,
+    pub headers: Option<String>,
+    pub size: Option<u64>,
+    pub has_attachments: bool,
+    pub entity_types: Vec<String>,
+}
+
+pub fn init_rules_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rules (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            enabled INTEGER DEFAULT 1,
+            match_mode TEXT NOT NULL DEFAULT 'all',
+            conditions_json TEXT NOT NULL,
+            actions_json TEXT NOT NULL,
+            position INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rule_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            rule_id TEXT NOT NULL,
+            rule_name TEXT NOT NULL,
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            subject TEXT,
+            matched INTEGER NOT NULL,
+            actions TEXT,
+            error TEXT,
+            trigger TEXT NOT NULL,
+            evaluated_at INTEGER NOT NULL
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+
+    // Rules an account's server runs through the app's Sieve script
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS server_rules (
+            account_id TEXT NOT NULL,
+            rule_id TEXT NOT NULL,
+            PRIMARY KEY (account_id, rule_id)
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+impl Rule {
+    fn needs_body(&self) -> bool {
+        self.conditions.iter().any(|c| matches!(c, RuleCondition::Body { .. } | RuleCondition::HasAttachment { .. } | RuleCondition::EntityType { .. }))
+            || self.actions.iter().any(|a| matches!(a, RuleAction::Forward { .. }))
+    }
+
+    fn needs_headers(&self) -> bool {
+        self.conditions.iter().any(|c| matches!(c, RuleCondition::Header { .. }))
+    }
+
+    /// Rejects rules that could never run: no conditions or actions, bad regexes, empty targets.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Rule name cannot be empty".to_string());
+        }
+        if self.conditions.is_empty() {
+            return Err("A rule needs at least one condition".to_string());
+        }
+        if self.actions.is_empty() {
+            return Err("A rule needs at least one action".to_string());
+        }
+        for condition in &self.conditions {
+            if let RuleCondition::Header { name, .. } = condition {
+                if name.trim().is_empty() {
+                    return Err("Header condition needs a header name".to_string());
+                }
+            }
+            if let Some((TextOp::Matches, value)) = text_condition(condition) {
+                build_pattern(value).map_err(|e| format!("Invalid pattern \"{}\": {}", value, e))?;
+            }
+        }
+        for action in &self.actions {
+            match action {
+                RuleAction::Move { mailbox } if mailbox.trim().is_empty() => return Err("Move action needs a mailbox".to_string()),
+                RuleAction::Label { label } if label.trim().is_empty() => return Err("Label action needs a label".to_string()),
+                RuleAction::Forward { to } if to.trim().parse::<lettre::Address>().is_err() => return Err(format!("Invalid forward address: {}", to)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A rule with its `Matches` patterns compiled once, when the rules for a run are loaded.
+pub struct CompiledRule {
+    pub rule: Rule,
+    /// One entry per condition; `Some` for `Matches` conditions whose pattern compiles.
+    patterns: Vec<Option<Regex>>,
+}
+
+impl CompiledRule {
+    pub fn new(rule: Rule) -> Self {
+        let patterns = rule.conditions.iter()
+            .map(|c| match text_condition(c) {
+                Some((TextOp::Matches, value)) => build_pattern(value).ok(),
+                _ => None,
+            })
+            .collect();
+        CompiledRule { rule, patterns }
+    }
+}
+
+fn text_condition(condition: &RuleCondition) -> Option<(TextOp, &str)> {
+    match condition {
+        RuleCondition::From { op, value } | RuleCondition::To { op, value }
+        | RuleCondition::Subject { op, value } | RuleCondition::Body { op, value }
+        | RuleCondition::Header { op, value, .. } => Some((*op, value)),
+        _ => None,
+    }
+}
+
+fn build_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+fn text_matches(op: TextOp, haystack: &str, needle: &str, pattern: Option<&Regex>) -> bool {
+    let h = haystack.to_lowercase();
+    let n = needle.to_lowercase();
+    match op {
+        TextOp::Contains => h.contains(&n),
+        TextOp::NotContains => !h.contains(&n),
+        TextOp::Equals => h.trim() == n.trim(),
+        TextOp::StartsWith => h.starts_with(&n),
+        TextOp::EndsWith => h.ends_with(&n),
+        TextOp::Matches => pattern.is_some_and(|re| re.is_match(haystack)),
+    }
+}
+
+/// Values of a header in a raw header block, with folded continuation lines unfolded.
+fn header_values(raw: &str, name: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current: Option<String> = None;
+    for line in raw.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(v) = current.as_mut() {
+                v.push(' ');
+                v.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some(v) = current.take() {
+            values.push(v);
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if key.trim().eq_ignore_ascii_case(name) {
+                current = Some(value.trim().to_string());
+            }
+        }
+    }
+    values.extend(current);
+    values
+}
+
+fn condition_matches(condition: &RuleCondition, pattern: Option<&Regex>, msg: &RuleMessage) -> bool {
+    match condition {
+        RuleCondition::From { op, value } => text_matches(*op, &msg.from, value, pattern),
+        RuleCondition::To { op, value } => text_matches(*op, &msg.to, value, pattern),
+        RuleCondition::Subject { op, value } => text_matches(*op, &msg.subject, value, pattern),
+        RuleCondition::Body { op, value } => text_matches(*op, msg.body.as_deref().unwrap_or(""), value, pattern),
+        RuleCondition::Header { name, op, value } => {
+            let values = header_values(msg.headers.as_deref().unwrap_or(""), name);
+            if *op == TextOp::NotContains {
+                values.iter().all(|v| text_matches(*op, v, value, pattern))
+            } else {
+                values.iter().any(|v| text_matches(*op, v, value, pattern))
+            }
+        }
+        RuleCondition::SizeGreaterThan { bytes } => msg.size.is_some_and(|s| s > *bytes),
+        RuleCondition::SizeLessThan { bytes } => msg.size.is_some_and(|s| s < *bytes),
+        RuleCondition::HasAttachment { value } => msg.has_attachments == *value,
+        RuleCondition::EntityType { entity } => msg.entity_types.iter().any(|t| t.eq_ignore_ascii_case(entity)),
+    }
+}
+
+pub fn evaluate(compiled: &CompiledRule, msg: &RuleMessage) -> bool {
+    let mut results = compiled.rule.conditions.iter()
+        .zip(&compiled.patterns)
+        .map(|(c, pattern)| condition_matches(c, pattern.as_ref(), msg));
+    match compiled.rule.match_mode {
+        MatchMode::All => results.all(|m| m),
+        MatchMode::Any => results.any(|m| m),
+    }
+}
+
+// --- Persistence ---
+
+fn row_to_rule(row: &rusqlite::Row) -> rusqlite::Result<Rule> {
+    let match_mode: String = row.get(3)?;
+    let conditions_json: String = row.get(4)?;
+    let actions_json: String = row.get(5)?;
+    Ok(Rule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        enabled: row.get::<_, i32>(2)? != 0,
+        match_mode: if match_mode == "any" { MatchMode::Any } else { MatchMode::All },
+        conditions: serde_json::from_str(&conditions_json).unwrap_or_default(),
+        actions: serde_json::from_str(&actions_json).unwrap_or_default(),
+        position: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+pub fn list_rules(app_handle: &AppHandle) -> Result<Vec<Rule>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, name, enabled, match_mode, conditions_json, actions_json, position, created_at FROM rules ORDER BY position ASC, created_at ASC"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], row_to_rule).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+pub fn save_rule(app_handle: &AppHandle, mut rule: Rule) -> Result<Rule, String> {
+    rule.name = rule.name.trim().to_string();
+    rule.validate()?;
+
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let exists = !rule.id.is_empty()
+        && conn.query_row("SELECT 1 FROM rules WHERE id = ?1", rusqlite::params![rule.id], |_| Ok(())).optional().map_err(|e| e.to_string())?.is_some();
+    if !exists {
+        if rule.id.is_empty() {
+            rule.id = uuid::Uuid::new_v4().to_string();
+        }
+        rule.created_at = chrono::Utc::now().timestamp();
+        rule.position = conn.query_row("SELECT coalesce(MAX(position), -1) + 1 FROM rules", [], |row| row.get(0)).map_err(|e| e.to_string())?;
+    }
+
+    let conditions_json = serde_json::to_string(&rule.conditions).map_err(|e| e.to_string())?;
+    let actions_json = serde_json::to_string(&rule.actions).map_err(|e| e.to_string())?;
+    let match_mode = match rule.match_mode { MatchMode::All => "all", MatchMode::Any => "any" };
+
+    conn.execute(
+        "INSERT INTO rules (id, name, enabled, match_mode, conditions_json, actions_json, position, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(id) DO UPDATE SET name = excluded.name, enabled = excluded.enabled, match_mode = excluded.match_mode,
+            conditions_json = excluded.conditions_json, actions_json = excluded.actions_json, position = excluded.position",
+        rusqlite::params![rule.id, rule.name, rule.enabled as i32, match_mode, conditions_json, actions_json, rule.position, rule.created_at],
+    ).map_err(|e| e.to_string())?;
+    // The server still runs the version that was pushed, so the app takes the rule back
+    conn.execute("DELETE FROM server_rules WHERE rule_id = ?1", rusqlite::params![rule.id]).map_err(|e| e.to_string())?;
+
+    Ok(rule)
+}
+
+pub fn delete_rule(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM rules WHERE id = ?1", rusqlite::params![id]).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM server_rules WHERE rule_id = ?1", rusqlite::params![id]).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Replaces the set of rules that `account_id`'s server runs at delivery.
+pub fn set_server_rules(app_handle: &AppHandle, account_id: &str, rule_ids: &[String]) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM server_rules WHERE account_id = ?1", rusqlite::params![account_id]).map_err(|e| e.to_string())?;
+    for rule_id in rule_ids {
+        tx.execute("INSERT OR IGNORE INTO server_rules (account_id, rule_id) VALUES (?1, ?2)", rusqlite::params![account_id, rule_id])
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn server_rule_ids(app_handle: &AppHandle, account_id: &str) -> Result<HashSet<String>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare("SELECT rule_id FROM server_rules WHERE account_id = ?1").map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params![account_id], |row| row.get(0)).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Rewrites positions to follow `ids`; rules not listed keep their relative order after them.
+pub fn reorder_rules(app_handle: &AppHandle, ids: &[String]) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let existing: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT id FROM rules ORDER BY position ASC, created_at ASC").map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], |row| row.get(0)).map_err(|e| e.to_string())?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+    let order = ids.iter().filter(|id| existing.contains(id))
+        .chain(existing.iter().filter(|id| !ids.contains(id)));
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for (position, id) in order.enumerate() {
+        tx.execute("UPDATE rules SET position = ?1 WHERE id = ?2", rusqlite::params![position as i64, id]).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn get_rule_log(app_handle: &AppHandle, limit: u32) -> Result<Vec<RuleLogEntry>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, rule_id, rule_name, folder, uid, subject, matched, actions, error, trigger, evaluated_at
+         FROM rule_log ORDER BY id DESC LIMIT ?1"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params![limit], |row| {
+        let actions: Option<String> = row.get(7)?;
+        let trigger: String = row.get(9)?;
+        Ok(RuleLogEntry {
+            id: row.get(0)?,
+            rule_id: row.get(1)?,
+            rule_name: row.get(2)?,
+            folder: row.get(3)?,
+            uid: row.get(4)?,
+            subject: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+            matched: row.get::<_, i32>(6)? != 0,
+            actions: actions.and_then(|a| serde_json::from_str(&a).ok()).unwrap_or_default(),
+            error: row.get(8)?,
+            trigger: if trigger == "manual" { RuleTrigger::Manual } else { RuleTrigger::Sync },
+            evaluated_at: row.get(10)?,
+        })
+    }).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+pub fn clear_rule_log(app_handle: &AppHandle) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM rule_log", ()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Records a rule that matched a message. Non-matches are not logged; with many rules and a
+/// large sync they would flush the useful rows out of the log.
+fn write_log(conn: &Connection, rule: &Rule, msg: &RuleMessage, actions: &[String], error: Option<&str>, trigger: RuleTrigger) {
+    let trigger = match trigger { RuleTrigger::Sync => "sync", RuleTrigger::Manual => "manual" };
+    let res = conn.execute(
+        "INSERT INTO rule_log (rule_id, rule_name, folder, uid, subject, matched, actions, error, trigger, evaluated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        rusqlite::params![
+            rule.id, rule.name, msg.folder, msg.uid, msg.subject, 1,
+            serde_json::to_string(actions).unwrap_or_default(), error, trigger, chrono::Utc::now().timestamp()
+        ],
+    );
+    if let Err(e) = res {
+        log::warn!("Failed to write rule log: {}", e);
+    }
+}
+
+fn prune_log(conn: &Connection) {
+    let _ = conn.execute(
+        "DELETE FROM rule_log WHERE id <= (SELECT id FROM rule_log ORDER BY id DESC LIMIT 1 OFFSET ?1)",
+        rusqlite::params![MAX_LOG_ENTRIES],
+    );
+}
+
+// --- Message loading ---
+
+/// Cached size, body, attachment list and extracted entity types for a message.
+fn load_cached_details(conn: &Connection, folder: &str, uid: u32) -> (Option<u64>, Option<String>, bool, Vec<String>) {
+    let row = conn.query_row(
+        "SELECT size, processed_html, attachments_json, extracted_data, has_attachments FROM messages WHERE folder = ?1 AND uid = ?2",
+        rusqlite::params![folder, uid],
+        |row| Ok((
+            row.get::<_, Option<i64>>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, i32>(4).unwrap_or(0) != 0,
+        )),
+    ).optional().ok().flatten();
+
+    let Some((size, html, attachments_json, extracted, has_attachments)) = row else {
+        return (None, None, false, Vec::new());
+    };
+
+    let attachment_count = attachments_json
+        .and_then(|j| serde_json::from_str::<Vec<serde_json::Value>>(&j).ok())
+        .map(|a| a.len())
+        .unwrap_or(0);
+    let entity_types = extracted
+        .and_then(|j| serde_json::from_str::<serde_json::Value>(&j).ok())
+        .and_then(|v| v["entities"].as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|e| e["entityType"].as_str().map(|s| s.to_string()))
+        .collect();
+
+    (size.map(|s| s as u64), html, has_attachments || attachment_count > 0, entity_types)
+}
+
+/// Full header blocks for `uids`, for `Header` conditions.
+async fn fetch_raw_headers(account: &Account, folder: &str, uids: &[u32]) -> Result<HashMap<u32, String>, String> {
+    let Some(mailbox) = MailFolder::from_str(folder).ok().and_then(|f| f.to_imap_mailbox(&account.provider)) else {
+        return Ok(HashMap::new());
+    };
+    let uid_set = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+
+    execute_with_session(account, SessionKind::Sync, move |session| {
+        session.examine(&mailbox).map_err(|e| format!("IMAP Examine Error: {}", e))?;
+        let fetches = session.uid_fetch(&uid_set, "(UID BODY.PEEK[HEADER])").map_err(|e| format!("IMAP Fetch Error: {}", e))?;
+        Ok(fetches.iter()
+            .filter_map(|f| Some((f.uid?, String::from_utf8_lossy(f.header()?).to_string())))
+            .collect())
+    }).await
+}
+
+async fn build_rule_messages(app_handle: &AppHandle, account: &Account, folder: &str, headers: &[MessageHeader], needs_body: bool, needs_headers: bool) -> Vec<RuleMessage> {
+    let raw_headers = if needs_headers {
+        let uids: Vec<u32> = headers.iter().map(|h| h.uid).collect();
+        fetch_raw_headers(account, folder, &uids).await.unwrap_or_else(|e| {
+            log::warn!("Rules could not fetch headers: {}", e);
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    };
+
+    let mut messages = Vec::with_capacity(headers.len());
+    let conn = get_db_path(app_handle).and_then(|p| Connection::open(p).map_err(|e| e.to_string())).ok();
+    for header in headers {
+        if needs_body {
+            let cached = database::get_message_body_cache(app_handle, folder, header.uid).ok().flatten().is_some();
+            if !cached {
+                if let Err(e) = crate::mail::message_body::fetch_and_cache_body_internal(app_handle, account, folder, header.uid).await {
+                    log::warn!("Rules could not fetch body for {} in {}: {}", header.uid, folder, e);
+                }
+            }
+        }
+
+        let (size, html, has_attachments, entity_types) = match &conn {
+            Some(conn) => load_cached_details(conn, folder, header.uid),
+            None => (None, None, false, Vec::new()),
+        };
+
+        messages.push(RuleMessage {
+            folder: folder.to_string(),
+            uid: header.uid,
+            from: header.from.clone(),
+            to: header.to.clone().unwrap_or_default(),
+            subject: header.subject.clone(),
+            body: html.map(|h| crate::mail::semantic_index::strip_html(&h)),
+            headers: raw_headers.get(&header.uid).cloned(),
+            size,
+            has_attachments: has_attachments || header.has_attachments,
+            entity_types,
+        });
+    }
+    messages
+}
+
+// --- Actions ---
+
+/// IMAP keywords are atoms, so labels are reduced to atom characters on non-Gmail servers.
+pub(crate) fn keyword_for_label(label: &str) -> String {
+    label.trim().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.$".contains(c) { c } else { '_' })
+        .collect()
+}
+
+async fn run_store(account: &Account, folder: &str, uid: u32, query: String) -> Result<(), String> {
+    let mailbox = MailFolder::from_str(folder).ok().and_then(|f| f.to_imap_mailbox(&account.provider))
+        .ok_or_else(|| format!("Cannot modify messages in {}", folder))?;
+    execute_with_session(account, SessionKind::Sync, move |session| {
+        session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        session.uid_store(uid.to_string(), &query).map_err(|e| format!("IMAP Store Error: {}", e))?;
+        Ok(())
+    }).await
+}
+
+async fn execute_action(app_handle: &AppHandle, account: &Account, msg: &RuleMessage, action: &RuleAction, outcome: &mut RuleOutcome) -> Result<(), String> {
+    match action {
+        RuleAction::MarkRead => {
+            run_store(account, &msg.folder, msg.uid, "+FLAGS.SILENT (\\Seen)".to_string()).await?;
+            database::set_message_seen(app_handle, &msg.folder, msg.uid, true)?;
+        }
+        RuleAction::Star => {
+            run_store(account, &msg.folder, msg.uid, "+FLAGS.SILENT (\\Flagged)".to_string()).await?;
+            database::set_message_flagged(app_handle, &msg.folder, msg.uid, true)?;
+        }
+        RuleAction::Label { label } => {
+            let refs = [MessageRef { folder: msg.folder.clone(), uid: msg.uid }];
+            let update = crate::mail::tags::tag_messages(app_handle, account, &refs, &[label.clone()], &[]).await?;
+            if let Some(failure) = update.result.failed.into_iter().next() {
+                return Err(failure.error);
+            }
+        }
+        RuleAction::Move { mailbox } => {
+            let target = MoveTarget::parse(mailbox.trim(), &account.provider);
+            mailbox_actions::move_message(app_handle, account, &msg.folder, msg.uid, target).await?;
+            outcome.removed = true;
+        }
+        RuleAction::Delete => {
+            mailbox_actions::trash_message(app_handle, account, &msg.folder, msg.uid).await?;
+            outcome.removed = true;
+        }
+        RuleAction::Forward { to } => {
+            // Never forward our own sent mail, which would loop between two forwarding rules
+            if msg.folder == MailFolder::Sent.to_string() || to.trim().eq_ignore_ascii_case(&account.email) {
+                return Err("Skipped forwarding from Sent or to this account".to_string());
+            }
+            let html = database::get_message_body_cache(app_handle, &msg.folder, msg.uid)?
+                .map(|(html, _, _)| html)
+                .unwrap_or_default();
+            let intro = format!("---------- Forwarded message ----------\nFrom: {}\nSubject: {}\n\n", msg.from, msg.subject);
+            let plain = format!("{}{}", intro, msg.body.clone().unwrap_or_default());
+            let html_body = format!("<p>{}</p>{}", intro.replace('\n', "<br>"), html);
+
+            let mut sender = account.clone();
+            crate::mail::smtp_client::send_email(
+                app_handle, &mut sender, vec![to.trim().to_string()], Vec::new(), Vec::new(), None,
+                &format!("Fwd: {}", msg.subject), &plain, &html_body, Vec::new(),
+            ).await.map_err(|e| e.to_string())?;
+        }
+        RuleAction::Notify => outcome.notify = Some(true),
+        RuleAction::SuppressNotification => outcome.notify = Some(false),
+        RuleAction::StopProcessing => {}
+    }
+    Ok(())
+}
+
+fn action_label(action: &RuleAction) -> String {
+    match action {
+        RuleAction::Move { mailbox } => format!("move:{}", mailbox),
+        RuleAction::Label { label } => format!("label:{}", label),
+        RuleAction::MarkRead => "markRead".to_string(),
+        RuleAction::Star => "star".to_string(),
+        RuleAction::Delete => "delete".to_string(),
+        RuleAction::Forward { to } => format!("forward:{}", to),
+        RuleAction::Notify => "notify".to_string(),
+        RuleAction::SuppressNotification => "suppressNotification".to_string(),
+        RuleAction::StopProcessing => "stop".to_string(),
+    }
+}
+
+/// Evaluates enabled rules (or just `only_rule`) against `headers` in `folder` and runs the
+/// actions of every match, logging each match. Returns outcomes keyed by UID. On sync, rules
+/// the account's server already ran on delivery to the Inbox are skipped.
+pub async fn apply_rules(app_handle: &AppHandle, account: &Account, folder: &str, headers: &[MessageHeader], trigger: RuleTrigger, only_rule: Option<&str>) -> HashMap<u32, RuleOutcome> {
+    let mut outcomes = HashMap::new();
+
+    let server_side = if trigger == RuleTrigger::Sync && folder == MailFolder::Inbox.to_string() {
+        server_rule_ids(app_handle, &account.id).unwrap_or_else(|e| {
+            log::warn!("Failed to load server-side rules: {}", e);
+            HashSet::new()
+        })
+    } else {
+        HashSet::new()
+    };
+
+    let rules: Vec<CompiledRule> = match list_rules(app_handle) {
+        Ok(rules) => rules.into_iter().filter(|r| match only_rule {
+            Some(id) => r.id == id,
+            None => r.enabled && !server_side.contains(&r.id),
+        }).map(CompiledRule::new).collect(),
+        Err(e) => {
+            log::error!("Failed to load rules: {}", e);
+            return outcomes;
+        }
+    };
+    if rules.is_empty() || headers.is_empty() {
+        return outcomes;
+    }
+
+    let needs_body = rules.iter().any(|c| c.rule.needs_body());
+    let needs_headers = rules.iter().any(|c| c.rule.needs_headers());
+    let messages = build_rule_messages(app_handle, account, folder, headers, needs_body, needs_headers).await;
+
+    let Ok(conn) = get_db_path(app_handle).and_then(|p| Connection::open(p).map_err(|e| e.to_string())) else {
+        return outcomes;
+    };
+
+    for msg in &messages {
+        let mut outcome = RuleOutcome::default();
+
+        for compiled in &rules {
+            if outcome.removed {
+                break;
+            }
+            if !evaluate(compiled, msg) {
+                continue;
+            }
+            let rule = &compiled.rule;
+            outcome.matched = true;
+
+            let mut done = Vec::new();
+            let mut error = None;
+            for action in &rule.actions {
+                match execute_action(app_handle, account, msg, action, &mut outcome).await {
+                    Ok(()) => done.push(action_label(action)),
+                    Err(e) => {
+                        log::warn!("Rule \"{}\" action {} failed for {}: {}", rule.name, action_label(action), msg.uid, e);
+                        error = Some(e);
+                        outcome.errors += 1;
+                        break;
+                    }
+                }
+                if outcome.removed {
+                    break;
+                }
+            }
+            write_log(&conn, rule, msg, &done, error.as_deref(), trigger);
+
+            if rule.actions.contains(&RuleAction::StopProcessing) {
+                break;
+            }
+        }
+
+        outcomes.insert(msg.uid, outcome);
+    }
+
+    prune_log(&conn);
+
+    if outcomes.values().any(|o| o.matched) {
+        let _ = app_handle.emit("mail:updated", folder);
+        crate::tray_state::refresh_unread_count_from_db(app_handle);
+    }
+
+    outcomes
+}
+
+/// Runs rules retroactively over the newest `limit` cached messages of a folder.
+pub async fn run_rules_on_folder(app_handle: &AppHandle, account: &Account, folder: &str, rule_id: Option<&str>, limit: u32) -> Result<RuleRunSummary, String> {
+    let headers = database::load_messages_page(app_handle, folder, None, None, limit, None)?;
+    let outcomes = apply_rules(app_handle, account, folder, &headers, RuleTrigger::Manual, rule_id).await;
+
+    Ok(RuleRunSummary {
+        evaluated: outcomes.len() as u32,
+        matched: outcomes.values().filter(|o| o.matched).count() as u32,
+        errors: outcomes.values().map(|o| o.errors).sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> RuleMessage {
+        RuleMessage {
+            folder: "inbox".to_string(),
+            uid: 7,
+            from: "Shop <orders@shop.example>".to_string(),
+            to: "me@example.com".to_string(),
+            subject: "Your order has shipped".to_string(),
+            body: Some("Tracking number 1Z999".to_string()),
+            headers: Some("List-Id: Deals\r\n <deals.shop.example>\r\nX-Priority: 3\r\n".to_string()),
+            size: Some(42_000),
+            has_attachments: false,
+            entity_types: vec!["TrackingNumber".to_string()],
+        }
+    }
+
+    fn compiled(match_mode: MatchMode, conditions: Vec<RuleCondition>) -> CompiledRule {
+        CompiledRule::new(rule(match_mode, conditions))
+    }
+
+    fn rule(match_mode: MatchMode, conditions: Vec<RuleCondition>) -> Rule {
+        Rule {
+            id: "r1".to_string(),
+            name: "Test".to_string(),
+            enabled: true,
+            match_mode,
+            conditions,
+            actions: vec![RuleAction::MarkRead],
+            position: 0,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_all_and_any_modes() {
+        let conditions = vec![
+            RuleCondition::From { op: TextOp::Contains, value: "@shop.example".to_string() },
+            RuleCondition::Subject { op: TextOp::StartsWith, value: "invoice".to_string() },
+        ];
+        assert!(!evaluate(&compiled(MatchMode::All, conditions.clone()), &message()));
+        assert!(evaluate(&compiled(MatchMode::Any, conditions), &message()));
+    }
+
+    #[test]
+    fn test_header_size_and_entity_conditions() {
+        let msg = message();
+        assert!(evaluate(&compiled(MatchMode::All, vec![
+            RuleCondition::Header { name: "list-id".to_string(), op: TextOp::Contains, value: "deals.shop.example".to_string() },
+            RuleCondition::SizeGreaterThan { bytes: 10_000 },
+            RuleCondition::EntityType { entity: "trackingnumber".to_string() },
+            RuleCondition::HasAttachment { value: false },
+        ]), &msg));
+        assert!(!evaluate(&compiled(MatchMode::All, vec![RuleCondition::SizeLessThan { bytes: 1_000 }]), &msg));
+    }
+
+    #[test]
+    fn test_matches_uses_compiled_pattern() {
+        let pattern = |value: &str| vec![RuleCondition::Subject { op: TextOp::Matches, value: value.to_string() }];
+        assert!(evaluate(&compiled(MatchMode::All, pattern(r"^your ORDER\b")), &message()));
+        assert!(!evaluate(&compiled(MatchMode::All, pattern(r"^invoice")), &message()));
+        // An invalid pattern saved before validation existed never matches
+        assert!(!evaluate(&compiled(MatchMode::All, pattern("(unclosed")), &message()));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_rules() {
+        let mut r = rule(MatchMode::All, vec![RuleCondition::Body { op: TextOp::Matches, value: "(unclosed".to_string() }]);
+        assert!(r.validate().is_err());
+        r.conditions = vec![RuleCondition::Body { op: TextOp::Matches, value: r"order\s+#\d+".to_string() }];
+        assert!(r.validate().is_ok());
+        r.actions = vec![RuleAction::Forward { to: "not an address".to_string() }];
+        assert!(r.validate().is_err());
+    }
+
+    #[test]
+    fn test_keyword_for_label() {
+        assert_eq!(keyword_for_label(" Team Notes/2024 "), "Team_Notes_2024");
+    }
+}
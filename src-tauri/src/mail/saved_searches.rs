@@ -0,0 +1,389 @@
+use crate::mail::database::get_db_path;
+use crate::mail::message_list::MessageHeader;
+use crate::mail::search_query::{self, SqlFilter};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use tauri::{AppHandle, Emitter};
+
+/// Saved searches are exposed to the UI as virtual folders named `saved:<id>`.
+pub const FOLDER_PREFIX: &str = "saved:";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearch {
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    pub notify: bool,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearchMatchPayload {
+    pub id: String,
+    pub name: String,
+    pub folder: String,
+    pub new_matches: Vec<MessageHeader>,
+}
+
+pub fn init_saved_searches_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS saved_searches (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            query TEXT NOT NULL,
+            notify INTEGER DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            last_match_rowid INTEGER DEFAULT 0
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn compile(query: &str) -> Result<SqlFilter, String> {
+    search_query::parse_query(query).to_sql().ok_or_else(|| "Saved search query is empty".to_string())
+}
+
+fn max_match_rowid(conn: &Connection, filter: &SqlFilter) -> Result<i64, String> {
+    let sql = format!("SELECT coalesce(MAX(m.rowid), 0) FROM messages m WHERE ({})", filter.clause);
+    conn.query_row(&sql, rusqlite::params_from_iter(filter.params.iter()), |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+pub fn list_saved_searches(app_handle: &AppHandle) -> Result<Vec<SavedSearch>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare("SELECT id, name, query, notify, created_at FROM saved_searches ORDER BY created_at ASC").map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |row| {
+        Ok(SavedSearch {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            query: row.get(2)?,
+            notify: row.get::<_, i32>(3)? != 0,
+            created_at: row.get(4)?,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+pub fn get_saved_search(app_handle: &AppHandle, id: &str) -> Result<Option<SavedSearch>, String> {
+    Ok(list_saved_searches(app_handle)?.into_iter().find(|s| s.id == id))
+}
+
+pub fn create_saved_search(app_handle: &AppHandle, name: &str, query: &str, notify: bool) -> Result<SavedSearch, String> {
+    let filter = compile(query)?;
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let search = SavedSearch {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.trim().to_string(),
+        query: query.trim().to_string(),
+        notify,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+
+    // Existing matches are not "new", so start the notification watermark at the current maximum
+    let watermark = max_match_rowid(&conn, &filter)?;
+    conn.execute(
+        "INSERT INTO saved_searches (id, name, query, notify, created_at, last_match_rowid) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![search.id, search.name, search.query, search.notify as i32, search.created_at, watermark],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(search)
+}
+
+pub fn update_saved_search(app_handle: &AppHandle, id: &str, name: &str, query: &str, notify: bool) -> Result<SavedSearch, String> {
+    let filter = compile(query)?;
+    let existing = get_saved_search(app_handle, id)?.ok_or_else(|| format!("Saved search not found: {}", id))?;
+
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let watermark = max_match_rowid(&conn, &filter)?;
+    conn.execute(
+        "UPDATE saved_searches SET name = ?1, query = ?2, notify = ?3, last_match_rowid = ?4 WHERE id = ?5",
+        rusqlite::params![name.trim(), query.trim(), notify as i32, watermark, id],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(SavedSearch {
+        name: name.trim().to_string(),
+        query: query.trim().to_string(),
+        notify,
+        ..existing
+    })
+}
+
+pub fn delete_saved_search(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.execute("DELETE FROM saved_searches WHERE id = ?1", rusqlite::params![id]).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Loads a page of a saved search's virtual folder. Like Starred, it spans folders and is
+/// ordered by date, so pages continue after `before` by `(date, folder, uid)`.
+pub fn load_saved_search_page(app_handle: &AppHandle, id: &str, before: Option<PageAnchor>, limit: u32) -> Result<Vec<MessageHeader>, String> {
+    let search = get_saved_search(app_handle, id)?.ok_or_else(|| format!("Saved search not found: {}", id))?;
+    let filter = compile(&search.query)?;
+
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    saved_search_page(&conn, &filter, before.as_ref(), limit)
+}
+
+fn saved_search_page(conn: &Connection, filter: &SqlFilter, before: Option<&PageAnchor>, limit: u32) -> Result<Vec<MessageHeader>, String> {
+    let mut sql = format!(
+        "SELECT m.uid, m.uid_validity, m.subject, m.sender, m.date, m.seen, m.flagged, m.snippet, m.folder, m.has_attachments, m.thread_id, m.recipient, m.message_id, m.unsubscribe
+         FROM messages m
+         WHERE ({}) AND {}",
+        filter.clause, crate::mail::snooze::not_snoozed_sql("m")
+    );
+    let mut params = filter.params.clone();
+
+    if let Some(anchor) = before {
+        push_page_anchor(conn, &mut sql, &mut params, &filter.clause, &filter.params, anchor)?;
+    }
+
+    sql.push_str(SPANNING_ORDER);
+    sql.push_str(" LIMIT ?");
+    params.push(Value::Integer(limit as i64));
+
+    query_headers(conn, &sql, params)
+}
+
+/// Unread counts keyed by virtual folder name, for `get_unread_counts`.
+pub fn unread_counts(conn: &Connection) -> Result<Vec<(String, u32)>, String> {
+    let mut stmt = conn.prepare("SELECT id, query FROM saved_searches").map_err(|e| e.to_string())?;
+    let searches: Vec<(String, String)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut counts = Vec::new();
+    for (id, query) in searches {
+        let Ok(filter) = compile(&query) else { continue };
+        let sql = format!(
+            "SELECT COUNT(*) FROM messages m WHERE ({}) AND m.seen = 0 AND {}",
+            filter.clause, crate::mail::snooze::not_snoozed_sql("m")
+        );
+        match conn.query_row(&sql, rusqlite::params_from_iter(filter.params.iter()), |row| row.get(0)) {
+            Ok(count) => counts.push((format!("{}{}", FOLDER_PREFIX, id), count)),
+            Err(e) => log::warn!("Failed to count unread for saved search {}: {}", id, e),
+        }
+    }
+    Ok(counts)
+}
+
+/// Called after a sync inserts new messages. Emits `mail:saved_search_matches` for every
+/// saved search with new hits and raises a notification for those that opted in.
+pub fn check_new_matches(app_handle: &AppHandle) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare("SELECT id, name, query, notify, last_match_rowid FROM saved_searches").map_err(|e| e.to_string())?;
+    let searches: Vec<(String, String, String, bool, i64)> = stmt.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get::<_, i32>(3)? != 0, row.get(4)?))
+    }).map_err(|e| e.to_string())?.filter_map(|r| r.ok()).collect();
+
+    for (id, name, query, notify, last_rowid) in searches {
+        let Ok(filter) = compile(&query) else { continue };
+
+        let sql = format!(
+            "SELECT m.uid, m.uid_validity, m.subject, m.sender, m.date, m.seen, m.flagged, m.snippet, m.folder, m.has_attachments, m.thread_id, m.recipient, m.message_id, m.unsubscribe
+             FROM messages m
+             WHERE ({}) AND m.rowid > ?
+             ORDER BY m.date DESC LIMIT 50",
+            filter.clause
+        );
+        let mut params = filter.params.clone();
+        params.push(Value::Integer(last_rowid));
+
+        let new_matches = query_headers(&conn, &sql, params)?;
+        if new_matches.is_empty() {
+            continue;
+        }
+
+        let watermark = max_match_rowid(&conn, &filter)?;
+        conn.execute("UPDATE saved_searches SET last_match_rowid = ?1 WHERE id = ?2", rusqlite::params![watermark, id])
+            .map_err(|e| e.to_string())?;
+
+        if notify {
+            let unread: Vec<&MessageHeader> = new_matches.iter().filter(|m| !m.seen).collect();
+            if !unread.is_empty() {
+                crate::mail::notifications::show_saved_search_matches(app_handle, &name, &unread);
+            }
+        }
+
+        let _ = app_handle.emit("mail:saved_search_matches", SavedSearchMatchPayload {
+            folder: format!("{}{}", FOLDER_PREFIX, id),
+            id,
+            name,
+            new_matches,
+        });
+    }
+
+    Ok(())
+}
+
+/// The last message of the previous page in a list that spans folders. Clients that do not
+/// send the folder get the newest message with that UID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageAnchor {
+    pub uid: u32,
+    pub folder: Option<String>,
+}
+
+/// Order of lists that span folders; folder and UID break ties between messages sharing a
+/// date so keyset pages neither skip nor repeat them.
+pub(crate) const SPANNING_ORDER: &str = " ORDER BY m.date DESC, m.folder DESC, m.uid DESC";
+
+/// Restricts a query over `messages m` to rows that sort after `anchor` in `SPANNING_ORDER`.
+/// The anchor is looked up among rows matching `scope`; if it is gone, nothing is added.
+pub(crate) fn push_page_anchor(conn: &Connection, sql: &mut String, params: &mut Vec<Value>, scope: &str, scope_params: &[Value], anchor: &PageAnchor) -> Result<(), String> {
+    let anchor_sql = format!(
+        "SELECT m.date, m.folder FROM messages m WHERE ({}) AND m.uid = ? AND (? IS NULL OR m.folder = ?) ORDER BY m.date DESC LIMIT 1",
+        scope
+    );
+    let folder = anchor.folder.clone().map(Value::Text).unwrap_or(Value::Null);
+    let mut anchor_params = scope_params.to_vec();
+    anchor_params.extend([Value::Integer(anchor.uid as i64), folder.clone(), folder]);
+
+    let position: Option<(i64, String)> = conn.query_row(&anchor_sql, rusqlite::params_from_iter(anchor_params), |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some((date, folder)) = position {
+        sql.push_str(" AND (m.date, m.folder, m.uid) < (?, ?, ?)");
+        params.extend([Value::Integer(date), Value::Text(folder), Value::Integer(anchor.uid as i64)]);
+    }
+    Ok(())
+}
+
+pub(crate) fn query_headers(conn: &Connection, sql: &str, params: Vec<Value>) -> Result<Vec<MessageHeader>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok(MessageHeader {
+            uid: row.get(0)?,
+            uid_validity: row.get(1)?,
+            subject: row.get(2)?,
+            from: row.get(3)?,
+            date: row.get(4)?,
+            seen: row.get::<_, i32>(5)? != 0,
+            flagged: row.get::<_, i32>(6)? != 0,
+            snippet: row.get(7).unwrap_or(None),
+            folder: row.get(8).unwrap_or_else(|_| "INBOX".to_string()),
+            has_attachments: row.get::<_, i32>(9).unwrap_or(0) != 0,
+            thread_id: row.get(10).unwrap_or(None),
+            to: row.get(11).unwrap_or(None),
+            message_id: row.get(12).unwrap_or(None),
+            unsubscribe: crate::mail::unsubscribe::from_column(row.get(13).unwrap_or(None)),
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut messages = Vec::new();
+    for row in rows {
+        messages.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (
+                folder TEXT NOT NULL, uid INTEGER NOT NULL, uid_validity INTEGER, subject TEXT, sender TEXT,
+                recipient TEXT, date INTEGER NOT NULL, snippet TEXT, seen INTEGER DEFAULT 0, flagged INTEGER DEFAULT 0,
+                has_attachments INTEGER DEFAULT 0, thread_id TEXT, message_id TEXT, unsubscribe TEXT,
+                PRIMARY KEY (folder, uid)
+            );"
+        ).unwrap();
+        init_saved_searches_table(&conn).unwrap();
+        crate::mail::snooze::init_snooze_table(&conn).unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, folder: &str, uid: u32, date: i64, seen: bool) {
+        conn.execute(
+            "INSERT INTO messages (folder, uid, uid_validity, subject, sender, date, seen) VALUES (?1, ?2, 1, 'Hi', 'a@example.com', ?3, ?4)",
+            rusqlite::params![folder, uid, date, seen as i32],
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_pages_do_not_skip_messages_sharing_a_date() {
+        let conn = test_db();
+        // Same UID in two folders and several messages on the same second
+        for (folder, uid) in [("inbox", 7), ("sent", 7), ("inbox", 8), ("archive", 3), ("inbox", 2)] {
+            insert(&conn, folder, uid, 1_000, false);
+        }
+        insert(&conn, "inbox", 1, 900, false);
+        let filter = compile("is:unread").unwrap();
+
+        let mut seen = Vec::new();
+        let mut anchor = None;
+        loop {
+            let page = saved_search_page(&conn, &filter, anchor.as_ref(), 2).unwrap();
+            if page.is_empty() {
+                break;
+            }
+            let last = page.last().unwrap();
+            anchor = Some(PageAnchor { uid: last.uid, folder: Some(last.folder.clone()) });
+            seen.extend(page.into_iter().map(|m| (m.folder, m.uid)));
+        }
+
+        assert_eq!(seen, vec![
+            ("sent".to_string(), 7),
+            ("inbox".to_string(), 8),
+            ("inbox".to_string(), 7),
+            ("inbox".to_string(), 2),
+            ("archive".to_string(), 3),
+            ("inbox".to_string(), 1),
+        ]);
+    }
+
+    #[test]
+    fn test_unread_counts_per_saved_search() {
+        let conn = test_db();
+        insert(&conn, "inbox", 1, 1_000, false);
+        insert(&conn, "inbox", 2, 2_000, true);
+        insert(&conn, "sent", 3, 3_000, false);
+        conn.execute(
+            "INSERT INTO saved_searches (id, name, query, notify, created_at) VALUES ('a', 'Inbox', 'in:inbox', 0, 0), ('b', 'Bad', '', 0, 0)",
+            [],
+        ).unwrap();
+
+        assert_eq!(unread_counts(&conn).unwrap(), vec![("saved:a".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_locally_snoozed_messages_are_hidden() {
+        let conn = test_db();
+        insert(&conn, "inbox", 1, 1_000, false);
+        insert(&conn, "inbox", 2, 2_000, false);
+        conn.execute(
+            "INSERT INTO snoozes (id, folder, uid, until, server_side, created_at) VALUES ('s', 'inbox', 2, 9999, 0, 0)",
+            [],
+        ).unwrap();
+        conn.execute("INSERT INTO saved_searches (id, name, query, notify, created_at) VALUES ('a', 'Inbox', 'in:inbox', 0, 0)", []).unwrap();
+
+        let filter = compile("in:inbox").unwrap();
+        let uids: Vec<u32> = saved_search_page(&conn, &filter, None, 10).unwrap().iter().map(|m| m.uid).collect();
+        assert_eq!(uids, vec![1]);
+        assert_eq!(unread_counts(&conn).unwrap(), vec![("saved:a".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_empty_query_is_rejected() {
+        assert!(compile("   ").is_err());
+    }
+}
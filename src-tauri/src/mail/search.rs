@@ -1,7 +1,9 @@
 use crate::auth::account::{Account, MailProvider};
 use crate::mail::database;
+use crate::mail::search_query::{self, SearchHit, SearchSort};
 use crate::mail::message_list::MessageHeader;
-use crate::mail::imap_session::{execute_with_session, SessionKind};
+use crate::mail::imap_session::{execute_with_imap_session, execute_with_session, Session, SessionKind};
+use crate::mail::gmail_labels::GmailFetch;
 use crate::mail::message_body::MessageDetail;
 use crate::mail::body_prefetch_manager::{PREFETCH_MANAGER, PrefetchPriority};
 use tauri::{AppHandle, Emitter};
@@ -64,6 +66,27 @@ pub struct SearchIncrementalPayload {
     pub query: String,
     pub folder: String,
     pub new_messages: Vec<MessageHeader>,
+    /// Set by global searches for results that belong to a non-active account.
+    #[serde(default)]
+    pub account_id: Option<String>,
+}
+
+/// Where remote search looks. `Everywhere` searches Gmail's All Mail so archived mail is
+/// reachable; other providers fall back to the selected folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchScope {
+    #[default]
+    Folder,
+    Everywhere,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchLabelsPayload {
+    pub search_id: String,
+    pub folder: String,
+    /// Gmail labels per UID of `folder`, system labels included (e.g. `\Important`).
+    pub labels: Vec<(u32, Vec<String>)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -87,16 +110,48 @@ pub struct ProviderCapabilities {
 }
 
 pub trait LocalSearchEngine: Send + Sync {
-    fn search(&self, app_handle: &AppHandle, folder: &str, query: &str, limit: u32) -> Result<Vec<MessageHeader>, String>;
+    fn search(&self, app_handle: &AppHandle, folder: &str, query: &str, limit: u32, sort: SearchSort) -> Result<Vec<SearchHit>, String>;
 }
 
 pub struct FTS5SearchEngine;
 impl LocalSearchEngine for FTS5SearchEngine {
-    fn search(&self, app_handle: &AppHandle, folder: &str, query: &str, limit: u32) -> Result<Vec<MessageHeader>, String> {
-        database::search_messages_local(app_handle, folder, query, limit)
+    fn search(&self, app_handle: &AppHandle, folder: &str, query: &str, limit: u32, sort: SearchSort) -> Result<Vec<SearchHit>, String> {
+        database::search_messages_local(app_handle, folder, query, limit, sort)
+    }
+}
+
+/// BM25 from `messages_fts` blended with cosine similarity over on-device embeddings.
+/// Date-sorted searches skip the semantic pass since ranking does not apply.
+pub struct HybridSearchEngine;
+impl LocalSearchEngine for HybridSearchEngine {
+    fn search(&self, app_handle: &AppHandle, folder: &str, query: &str, limit: u32, sort: SearchSort) -> Result<Vec<SearchHit>, String> {
+        let hits = database::search_messages_local(app_handle, folder, query, limit, sort)?;
+        if sort != SearchSort::Relevance {
+            return Ok(hits);
+        }
+        crate::mail::semantic_index::rerank(app_handle, folder, query, hits, limit)
     }
 }
 
+pub fn get_local_search_engine(app_handle: &AppHandle) -> Box<dyn LocalSearchEngine> {
+    if crate::mail::semantic_index::is_available() && crate::mail::semantic_index::is_enabled(app_handle) {
+        Box::new(HybridSearchEngine)
+    } else {
+        Box::new(FTS5SearchEngine)
+    }
+}
+
+/// Runs the local engine on the blocking pool: the semantic rerank embeds the query on the
+/// CPU, which must not stall the async runtime.
+async fn search_local(app_handle: &AppHandle, folder: &str, query: &str, limit: u32, sort: SearchSort) -> Result<Vec<SearchHit>, String> {
+    let app = app_handle.clone();
+    let folder = folder.to_string();
+    let query = query.to_string();
+    tokio::task::spawn_blocking(move || get_local_search_engine(&app).search(&app, &folder, &query, limit, sort))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 pub trait SearchBackend: Send + Sync {
     fn search(&self, account: &Account, folder: &str, query: &str) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>>;
     fn load_more(&self, account: &Account, folder: &str, query: &str, cursor_uid: u32) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>>;
@@ -104,53 +159,56 @@ pub trait SearchBackend: Send + Sync {
 }
 
 pub struct GmailSearchBackend;
+
+/// Runs a query through Gmail's native `X-GM-RAW` search so results match the Gmail web UI,
+/// including labels, `category:` and `has:drive`. If Gmail rejects the translated raw query
+/// and the query has no Gmail-only operators, it falls back to the standard IMAP criteria.
+fn gmail_uid_search(account: &Account, folder: &str, query: &str, uid_range: Option<String>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>> {
+    let account_clone = account.clone();
+    let folder_clone = folder.to_string();
+    let parsed = search_query::parse_query(query);
+    let raw_query = parsed.to_gmail_search();
+    let fallback_query = if parsed.has_gmail_operators() { None } else { parsed.to_imap_search(&folder_clone) };
+    Box::pin(async move {
+        let Some(raw_query) = raw_query else { return Ok(Vec::new()) };
+        let provider_clone = account_clone.provider.clone();
+        execute_with_session(&account_clone, SessionKind::Search, move |session| {
+            let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
+                Ok(mf) => match mf.to_imap_mailbox(&provider_clone) {
+                    Some(mb) => mb.to_string(),
+                    None => return Err("Cannot fetch from virtual folder".to_string()),
+                },
+                Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
+            };
+            session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+
+            let uids = match session.uid_search(raw_query.command(uid_range.as_deref())) {
+                Ok(uids) => uids,
+                Err(imap::error::Error::Bad(e)) if fallback_query.is_some() => {
+                    log::warn!("Gmail rejected X-GM-RAW query ({}), retrying with standard SEARCH", e);
+                    let fallback = fallback_query.as_ref().map(|q| q.command(uid_range.as_deref())).unwrap_or_default();
+                    session.uid_search(fallback).map_err(|e| format!("IMAP Search Error: {}", e))?
+                }
+                Err(e) => return Err(format!("IMAP Search Error: {}", e)),
+            };
+            let mut uid_vec: Vec<u32> = uids.into_iter().collect();
+            uid_vec.sort_unstable_by(|a, b| b.cmp(a)); // Newest first
+            Ok(uid_vec)
+        }).await
+    })
+}
+
 impl SearchBackend for GmailSearchBackend {
     fn search(&self, account: &Account, folder: &str, query: &str) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>> {
-        let account_clone = account.clone();
-        let folder_clone = folder.to_string();
-        let query_clone = query.to_string();
-        Box::pin(async move {
-            let provider_clone = account_clone.provider.clone();
-            execute_with_session(&account_clone, SessionKind::Search, move |session| {
-                let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
-                    Ok(mf) => match mf.to_imap_mailbox(&provider_clone) {
-                        Some(mb) => mb.to_string(),
-                        None => return Err("Cannot fetch from virtual folder".to_string()),
-                    },
-                    Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
-                };
-                session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
-                let search_query = format!("X-GM-RAW \"{}\"", query_clone.replace('"', "\\\""));
-                let uids = session.uid_search(&search_query).map_err(|e| format!("IMAP Search Error: {}", e))?;
-                let mut uid_vec: Vec<u32> = uids.into_iter().collect();
-                uid_vec.sort_unstable_by(|a, b| b.cmp(a)); // Newest first
-                Ok(uid_vec)
-            }).await
-        })
+        gmail_uid_search(account, folder, query, None)
     }
 
+    /// Pages backwards by restricting the same raw query to UIDs below the last one returned.
     fn load_more(&self, account: &Account, folder: &str, query: &str, cursor_uid: u32) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>> {
-        let account_clone = account.clone();
-        let folder_clone = folder.to_string();
-        let query_clone = query.to_string();
-        Box::pin(async move {
-            let provider_clone = account_clone.provider.clone();
-            execute_with_session(&account_clone, SessionKind::Search, move |session| {
-                let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
-                    Ok(mf) => match mf.to_imap_mailbox(&provider_clone) {
-                        Some(mb) => mb.to_string(),
-                        None => return Err("Cannot fetch from virtual folder".to_string()),
-                    },
-                    Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
-                };
-                session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
-                let search_query = format!("UID 1:{} X-GM-RAW \"{}\"", cursor_uid.saturating_sub(1), query_clone.replace('"', "\\\""));
-                let uids = session.uid_search(&search_query).map_err(|e| format!("IMAP Search Error: {}", e))?;
-                let mut uid_vec: Vec<u32> = uids.into_iter().collect();
-                uid_vec.sort_unstable_by(|a, b| b.cmp(a));
-                Ok(uid_vec)
-            }).await
-        })
+        if cursor_uid <= 1 {
+            return Box::pin(async { Ok(Vec::new()) });
+        }
+        gmail_uid_search(account, folder, query, Some(format!("1:{}", cursor_uid - 1)))
     }
 
     fn supports_feature(&self) -> ProviderCapabilities {
@@ -169,8 +227,9 @@ impl SearchBackend for OutlookSearchBackend {
     fn search(&self, account: &Account, folder: &str, query: &str) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>> {
         let account_clone = account.clone();
         let folder_clone = folder.to_string();
-        let query_clone = query.to_string();
+        let server_query = search_query::parse_query(query).to_imap_search(folder);
         Box::pin(async move {
+            let Some(server_query) = server_query else { return Ok(Vec::new()) };
             let provider_clone = account_clone.provider.clone();
             execute_with_session(&account_clone, SessionKind::Search, move |session| {
                 let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
@@ -181,7 +240,7 @@ impl SearchBackend for OutlookSearchBackend {
                     Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
                 };
                 session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
-                let search_query = format!("TEXT \"{}\"", query_clone.replace('"', "\\\""));
+                let search_query = server_query.command(None);
                 let uids = session.uid_search(&search_query).map_err(|e| format!("IMAP Search Error: {}", e))?;
                 let mut uid_vec: Vec<u32> = uids.into_iter().collect();
                 uid_vec.sort_unstable_by(|a, b| b.cmp(a));
@@ -193,8 +252,9 @@ impl SearchBackend for OutlookSearchBackend {
     fn load_more(&self, account: &Account, folder: &str, query: &str, cursor_uid: u32) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>> {
         let account_clone = account.clone();
         let folder_clone = folder.to_string();
-        let query_clone = query.to_string();
+        let server_query = search_query::parse_query(query).to_imap_search(folder);
         Box::pin(async move {
+            let Some(server_query) = server_query else { return Ok(Vec::new()) };
             let provider_clone = account_clone.provider.clone();
             execute_with_session(&account_clone, SessionKind::Search, move |session| {
                 let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
@@ -205,7 +265,7 @@ impl SearchBackend for OutlookSearchBackend {
                     Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
                 };
                 session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
-                let search_query = format!("UID 1:{} TEXT \"{}\"", cursor_uid.saturating_sub(1), query_clone.replace('"', "\\\""));
+                let search_query = server_query.command(Some(&format!("1:{}", cursor_uid.saturating_sub(1))));
                 let uids = session.uid_search(&search_query).map_err(|e| format!("IMAP Search Error: {}", e))?;
                 let mut uid_vec: Vec<u32> = uids.into_iter().collect();
                 uid_vec.sort_unstable_by(|a, b| b.cmp(a));
@@ -230,8 +290,9 @@ impl SearchBackend for GenericImapSearchBackend {
     fn search(&self, account: &Account, folder: &str, query: &str) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>> {
         let account_clone = account.clone();
         let folder_clone = folder.to_string();
-        let query_clone = query.to_string();
+        let server_query = search_query::parse_query(query).to_imap_search(folder);
         Box::pin(async move {
+            let Some(server_query) = server_query else { return Ok(Vec::new()) };
             let provider_clone = account_clone.provider.clone();
             execute_with_session(&account_clone, SessionKind::Search, move |session| {
                 let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
@@ -242,7 +303,7 @@ impl SearchBackend for GenericImapSearchBackend {
                     Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
                 };
                 session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
-                let search_query = format!("TEXT \"{}\"", query_clone.replace('"', "\\\""));
+                let search_query = server_query.command(None);
                 let uids = session.uid_search(&search_query).map_err(|e| format!("IMAP Search Error: {}", e))?;
                 let mut uid_vec: Vec<u32> = uids.into_iter().collect();
                 uid_vec.sort_unstable_by(|a, b| b.cmp(a));
@@ -254,8 +315,9 @@ impl SearchBackend for GenericImapSearchBackend {
     fn load_more(&self, account: &Account, folder: &str, query: &str, cursor_uid: u32) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u32>, String>> + Send>> {
         let account_clone = account.clone();
         let folder_clone = folder.to_string();
-        let query_clone = query.to_string();
+        let server_query = search_query::parse_query(query).to_imap_search(folder);
         Box::pin(async move {
+            let Some(server_query) = server_query else { return Ok(Vec::new()) };
             let provider_clone = account_clone.provider.clone();
             execute_with_session(&account_clone, SessionKind::Search, move |session| {
                 let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
@@ -266,7 +328,7 @@ impl SearchBackend for GenericImapSearchBackend {
                     Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
                 };
                 session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
-                let search_query = format!("UID 1:{} TEXT \"{}\"", cursor_uid.saturating_sub(1), query_clone.replace('"', "\\\""));
+                let search_query = server_query.command(Some(&format!("1:{}", cursor_uid.saturating_sub(1))));
                 let uids = session.uid_search(&search_query).map_err(|e| format!("IMAP Search Error: {}", e))?;
                 let mut uid_vec: Vec<u32> = uids.into_iter().collect();
                 uid_vec.sort_unstable_by(|a, b| b.cmp(a));
@@ -326,12 +388,89 @@ pub struct SearchContext {
 
 pub static ACTIVE_SEARCHES: Lazy<DashMap<String, Arc<SearchContext>>> = Lazy::new(|| DashMap::new());
 
-pub async fn fetch_and_index_search_message(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32) -> Result<MessageDetail, String> {
+/// Finds Gmail's All Mail by its `\All` special-use attribute, since the mailbox name is
+/// localised (e.g. `[Google Mail]/All Mail`), falling back to the English default.
+fn all_mail_mailbox(session: &mut Session) -> String {
+    crate::mail::mailbox_actions::find_special_use(session, crate::mail::mailbox_actions::SpecialUse::All, &crate::auth::account::MailProvider::Google)
+}
+
+/// Runs a Gmail search against All Mail and returns the newest `limit` hits with their labels.
+async fn gmail_all_mail_search(account: &Account, query: &str, limit: usize) -> Result<Vec<GmailFetch>, String> {
+    let Some(server_query) = search_query::parse_query(query).to_gmail_search() else { return Ok(Vec::new()) };
+
+    execute_with_imap_session(account, SessionKind::Search, move |imap| {
+        let mailbox = all_mail_mailbox(&mut imap.session);
+        imap.session.examine(&mailbox).map_err(|e| format!("IMAP Examine Error: {}", e))?;
+
+        let uids = imap.session.uid_search(server_query.command(None)).map_err(|e| format!("IMAP Search Error: {}", e))?;
+        let mut uid_vec: Vec<u32> = uids.into_iter().collect();
+        uid_vec.sort_unstable_by(|a, b| b.cmp(a)); // Newest first
+        uid_vec.truncate(limit);
+        if uid_vec.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut fetched: std::collections::HashMap<u32, GmailFetch> = crate::mail::gmail_labels::fetch_labels(imap, &uid_vec)
+            .map_err(|e| format!("IMAP fetch labels error: {}", e))?
+            .into_iter().map(|f| (f.uid, f)).collect();
+
+        Ok(uid_vec.into_iter().map(|uid| fetched.remove(&uid).unwrap_or(GmailFetch { uid, msgid: None, labels: Vec::new() })).collect())
+    }).await
+}
+
+/// Builds the cached header of a search hit from a header-only fetch.
+fn search_hit_header(msg: &imap::types::Fetch, folder: &str) -> Option<MessageHeader> {
+    let uid = msg.uid?;
+    let parsed = msg.header().and_then(|h| mailparse::parse_mail(h).ok());
+    let header_value = |name: &str| parsed.as_ref()
+        .and_then(|p| p.headers.iter().find(|hdr| hdr.get_key().eq_ignore_ascii_case(name)).map(|hdr| hdr.get_value()));
+
+    let subject = header_value("subject").unwrap_or_default();
+    let sender = header_value("from").unwrap_or_default();
+    let date = header_value("date")
+        .and_then(|d| chrono::DateTime::parse_from_rfc2822(&d).ok()).map(|dt| dt.timestamp())
+        .unwrap_or_else(|| chrono::Utc::now().timestamp());
+
+    let mut seen = false;
+    let mut flagged = false;
+    for flag in msg.flags() {
+        match flag {
+            imap::types::Flag::Seen => seen = true,
+            imap::types::Flag::Flagged => flagged = true,
+            _ => {}
+        }
+    }
+
+    Some(MessageHeader {
+        uid,
+        uid_validity: 1,
+        subject: if subject.is_empty() { "(No Subject)".to_string() } else { subject },
+        from: if sender.is_empty() { "Unknown".to_string() } else { sender },
+        date,
+        seen,
+        flagged,
+        snippet: Some("Search match found in mailbox history.".to_string()),
+        folder: folder.to_string(),
+        has_attachments: false,
+        thread_id: None,
+        to: None,
+        message_id: None,
+        unsubscribe: None,
+    })
+}
+
+/// Fetches the headers of `uids` in `folder` with one UID FETCH per chunk and caches them.
+async fn fetch_and_index_search_messages(app_handle: &AppHandle, account: &Account, folder: &str, uids: &[u32]) -> Result<Vec<MessageHeader>, String> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
     let folder_clone = folder.to_string();
     let provider_clone = account.provider.clone();
-    
-    let fetch_res = execute_with_session(account, SessionKind::Search, move |session| {
+    let uids = uids.to_vec();
+
+    let headers = execute_with_session(account, SessionKind::Search, move |session| {
         let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
+            Ok(crate::mail::folder::MailFolder::AllMail) => all_mail_mailbox(session),
             Ok(mf) => match mf.to_imap_mailbox(&provider_clone) {
                 Some(mb) => mb.to_string(),
                 None => return Err("Cannot fetch from virtual folder".to_string()),
@@ -339,83 +478,40 @@ pub async fn fetch_and_index_search_message(app_handle: &AppHandle, account: &Ac
             Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
         };
         session.select(&imap_mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
-        
-        let fetch_query = "(UID FLAGS BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE TO CC REPLY-TO)])";
-        let results = session.uid_fetch(uid.to_string(), fetch_query).map_err(|e| format!("IMAP fetch header error: {}", e))?;
-        
-        if let Some(msg) = results.iter().next() {
-            let subject = if let Some(h) = msg.header() {
-                if let Ok(parsed) = mailparse::parse_mail(h) {
-                    parsed.headers.iter().find(|hdr| hdr.get_key().to_lowercase() == "subject").map(|hdr| hdr.get_value()).unwrap_or_default()
-                } else {
-                    String::new()
-                }
-            } else {
-                String::new()
-            };
-
-            let sender = if let Some(h) = msg.header() {
-                if let Ok(parsed) = mailparse::parse_mail(h) {
-                    parsed.headers.iter().find(|hdr| hdr.get_key().to_lowercase() == "from").map(|hdr| hdr.get_value()).unwrap_or_default()
-                } else {
-                    String::new()
-                }
-            } else {
-                String::new()
-            };
 
-            let date = if let Some(h) = msg.header() {
-                if let Ok(parsed) = mailparse::parse_mail(h) {
-                    parsed.headers.iter().find(|hdr| hdr.get_key().to_lowercase() == "date").map(|hdr| hdr.get_value())
-                        .and_then(|d| chrono::DateTime::parse_from_rfc2822(&d).ok()).map(|dt| dt.timestamp()).unwrap_or_else(|| chrono::Utc::now().timestamp())
-                } else {
-                    chrono::Utc::now().timestamp()
-                }
-            } else {
-                chrono::Utc::now().timestamp()
-            };
-
-            let mut seen = false;
-            let mut flagged = false;
-            for flag in msg.flags() {
-                match flag {
-                    imap::types::Flag::Seen => seen = true,
-                    imap::types::Flag::Flagged => flagged = true,
-                    _ => {}
-                }
-            }
-
-            let header = MessageHeader {
-                uid,
-                uid_validity: 1,
-                subject: if subject.is_empty() { "(No Subject)".to_string() } else { subject },
-                from: if sender.is_empty() { "Unknown".to_string() } else { sender },
-                date,
-                seen,
-                flagged,
-                snippet: Some("Search match found in mailbox history.".to_string()),
-                folder: folder_clone.clone(),
-                has_attachments: false,
-                thread_id: None,
-                to: None,
-                message_id: None,
-            };
-            
-            return Ok(header);
+        let fetch_query = "(UID FLAGS BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE TO CC REPLY-TO)])";
+        let mut headers = Vec::new();
+        for chunk in uids.chunks(100) {
+            let uid_set = chunk.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+            let results = session.uid_fetch(uid_set, fetch_query).map_err(|e| format!("IMAP fetch header error: {}", e))?;
+            headers.extend(results.iter().filter_map(|msg| search_hit_header(msg, &folder_clone)));
         }
-        Err("Could not retrieve message header.".to_string())
+        Ok(headers)
     }).await?;
 
-    let _ = database::insert_or_update_messages(app_handle, &[fetch_res]);
+    let _ = database::insert_or_update_messages(app_handle, &headers);
+    Ok(headers)
+}
+
+pub async fn fetch_and_index_search_message(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32) -> Result<MessageDetail, String> {
+    let headers = fetch_and_index_search_messages(app_handle, account, folder, &[uid]).await?;
+    if headers.is_empty() {
+        return Err("Could not retrieve message header.".to_string());
+    }
 
     Ok(MessageDetail {
         body: "Search match found in mailbox history.".to_string(),
         attachments: Vec::new(),
         extracted_data: None,
+        auth_results: None,
     })
 }
 
-pub async fn start_search(app_handle: AppHandle, account: Account, folder: String, query: String) -> Result<(String, Vec<MessageHeader>, RemoteSearchState), String> {
+pub async fn start_search(app_handle: AppHandle, account: Account, folder: String, query: String, sort: SearchSort, scope: SearchScope) -> Result<(String, Vec<SearchHit>, RemoteSearchState), String> {
+    if scope == SearchScope::Everywhere && matches!(account.provider, MailProvider::Google) {
+        return start_everywhere_search(app_handle, account, query, sort).await;
+    }
+
     let start_time = Instant::now();
     let search_id = uuid::Uuid::new_v4().to_string();
 
@@ -432,8 +528,7 @@ pub async fn start_search(app_handle: AppHandle, account: Account, folder: Strin
     }
 
     // 2. Perform Local FTS5 Search instantly
-    let local_engine = FTS5SearchEngine;
-    let local_results = local_engine.search(&app_handle, &folder, &query, 100)?;
+    let local_results = search_local(&app_handle, &folder, &query, 100, sort).await?;
     let local_ms = start_time.elapsed().as_millis();
 
     // 3. Create SearchContext
@@ -575,6 +670,7 @@ pub async fn start_search(app_handle: AppHandle, account: Account, folder: Strin
                     query: query_bg.clone(),
                     folder: folder_bg.clone(),
                     new_messages: existing_msgs,
+                    account_id: None,
                 });
             }
         }
@@ -648,6 +744,7 @@ pub async fn start_search(app_handle: AppHandle, account: Account, folder: Strin
                             query: query_bg.clone(),
                             folder: folder_bg.clone(),
                             new_messages: new_msgs,
+                            account_id: None,
                         });
                     }
                     batch_buffer.clear();
@@ -663,6 +760,7 @@ pub async fn start_search(app_handle: AppHandle, account: Account, folder: Strin
                     query: query_bg.clone(),
                     folder: folder_bg.clone(),
                     new_messages: new_msgs,
+                    account_id: None,
                 });
             }
         }
@@ -689,6 +787,429 @@ pub async fn start_search(app_handle: AppHandle, account: Account, folder: Strin
     Ok((search_id, local_results, RemoteSearchState::Running))
 }
 
+/// Folder name used by global searches for the local pass and in incremental payloads.
+pub const GLOBAL_SEARCH_FOLDER: &str = "all";
+
+const GLOBAL_REMOTE_FOLDERS: [crate::mail::folder::MailFolder; 2] = [
+    crate::mail::folder::MailFolder::Inbox,
+    crate::mail::folder::MailFolder::Sent,
+];
+
+/// Normalised dedupe key: the Message-ID when present, otherwise the account/folder/uid triple.
+fn dedupe_key(account_email: &str, header: &MessageHeader) -> String {
+    match header.message_id.as_deref().map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_lowercase()) {
+        Some(id) if !id.is_empty() => id,
+        _ => format!("{}/{}/{}", account_email, header.folder, header.uid),
+    }
+}
+
+/// Keeps the headers whose dedupe key has not been returned yet, recording them in `seen`.
+fn take_unseen(seen: &mut HashSet<String>, account_email: &str, headers: Vec<MessageHeader>) -> Vec<MessageHeader> {
+    headers.into_iter().filter(|h| seen.insert(dedupe_key(account_email, h))).collect()
+}
+
+/// Fetches headers for search hits without touching the local cache. Used for accounts
+/// other than the active one, whose mail is not stored in the database.
+async fn fetch_remote_headers(account: &Account, folder: &str, uids: &[u32]) -> Result<Vec<MessageHeader>, String> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let folder_clone = folder.to_string();
+    let provider_clone = account.provider.clone();
+    let uid_set = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+
+    execute_with_session(account, SessionKind::Search, move |session| {
+        let imap_mailbox = match crate::mail::folder::MailFolder::from_str(&folder_clone) {
+            Ok(mf) => match mf.to_imap_mailbox(&provider_clone) {
+                Some(mb) => mb.to_string(),
+                None => return Err("Cannot fetch from virtual folder".to_string()),
+            },
+            Err(_) => return Err(format!("Unknown folder: {}", folder_clone)),
+        };
+        let mailbox = session.examine(&imap_mailbox).map_err(|e| format!("IMAP Examine Error: {}", e))?;
+        let validity = mailbox.uid_validity.unwrap_or(0);
+
+        let results = session.uid_fetch(&uid_set, "(UID FLAGS BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE TO MESSAGE-ID)])")
+            .map_err(|e| format!("IMAP fetch header error: {}", e))?;
+
+        let mut headers: Vec<MessageHeader> = results.iter()
+            .filter_map(|msg| crate::mail::sync::parse_header_to_message(msg, validity, &folder_clone))
+            .collect();
+        headers.sort_by(|a, b| b.date.cmp(&a.date));
+        Ok(headers)
+    }).await
+}
+
+/// Returns every account that can currently be searched, refreshing expiring tokens for
+/// non-active accounts (the active one has already been refreshed by the caller).
+async fn searchable_accounts(app_handle: &AppHandle, active: &Account) -> Vec<Account> {
+    let now = chrono::Utc::now().timestamp();
+    let mut accounts = vec![active.clone()];
+
+    for mut acc in crate::auth::session::load_accounts(app_handle) {
+        if acc.id == active.id || acc.needs_reauth {
+            continue;
+        }
+        if acc.expires_at <= now + 300 && !acc.refresh_token.is_empty() {
+            if let Err(e) = crate::auth::oauth::refresh_google_token(&mut acc).await {
+                log::warn!("Skipping {} in global search, token refresh failed: {}", acc.email, e);
+                continue;
+            }
+            let _ = crate::auth::session::save_account(app_handle, acc.clone(), false);
+        }
+        accounts.push(acc);
+    }
+    accounts
+}
+
+/// Searches every locally synced folder in one FTS pass, then fans the query out to the
+/// Inbox and Sent of every signed-in account in parallel. Remote hits are de-duplicated by
+/// Message-ID against everything already returned and streamed as `mail:search_incremental`
+/// events with folder `all`; hits from non-active accounts carry their `account_id`.
+pub async fn start_global_search(app_handle: AppHandle, account: Account, query: String, sort: SearchSort) -> Result<(String, Vec<SearchHit>, RemoteSearchState), String> {
+    let start_time = Instant::now();
+    let search_id = uuid::Uuid::new_v4().to_string();
+
+    // 1. Cancel previous global searches
+    let mut to_remove = Vec::new();
+    for entry in ACTIVE_SEARCHES.iter() {
+        if entry.value().folder == GLOBAL_SEARCH_FOLDER {
+            entry.value().cancellation_token.cancel();
+            to_remove.push(entry.key().clone());
+        }
+    }
+    for key in to_remove {
+        ACTIVE_SEARCHES.remove(&key);
+    }
+
+    // 2. One local pass over every folder
+    let mut seen_keys = HashSet::new();
+    let local_results: Vec<SearchHit> = search_local(&app_handle, GLOBAL_SEARCH_FOLDER, &query, 200, sort)
+        .await?
+        .into_iter()
+        .filter(|hit| seen_keys.insert(dedupe_key(&account.email, &hit.header)))
+        .collect();
+    let local_ms = start_time.elapsed().as_millis();
+
+    // 3. Create SearchContext. Global searches have no cursor, so load_more completes immediately.
+    let token = CancellationToken::new();
+    let context = Arc::new(SearchContext {
+        search_id: search_id.clone(),
+        account: account.clone(),
+        folder: GLOBAL_SEARCH_FOLDER.to_string(),
+        query: query.clone(),
+        cancellation_token: token.clone(),
+        state: Mutex::new(SearchContextState {
+            pending: VecDeque::new(),
+            streamed: HashSet::new(),
+            cursor: None,
+            last_activity: Instant::now(),
+            metrics: SearchMetrics {
+                local_ms,
+                cache_miss: true,
+                ..SearchMetrics::default()
+            },
+            remote_state: RemoteSearchState::Running,
+        }),
+    });
+
+    ACTIVE_SEARCHES.insert(search_id.clone(), context.clone());
+
+    // 4. Fan out remote searches in the background
+    let app_handle_bg = app_handle.clone();
+    let search_id_bg = search_id.clone();
+    let query_bg = query.clone();
+    let seen_keys = Arc::new(Mutex::new(seen_keys));
+
+    tokio::spawn(async move {
+        let _ = app_handle_bg.emit("mail:search_progress", SearchProgress {
+            search_id: search_id_bg.clone(),
+            state: SearchState::SearchingRemote,
+            matched: 0, downloaded: 0, indexed: 0, streamed: 0, total: 0,
+            progress_text: "Searching all accounts...".to_string(),
+        });
+
+        let remote_start = Instant::now();
+        let accounts = searchable_accounts(&app_handle_bg, &account).await;
+
+        let mut tasks = Vec::new();
+        for acc in &accounts {
+            for mail_folder in GLOBAL_REMOTE_FOLDERS.iter() {
+                if mail_folder.to_imap_mailbox(&acc.provider).is_none() {
+                    continue;
+                }
+                let acc = acc.clone();
+                let folder = mail_folder.to_string();
+                let is_active = acc.id == account.id;
+                let app = app_handle_bg.clone();
+                let search_id = search_id_bg.clone();
+                let query = query_bg.clone();
+                let token = token.clone();
+                let seen_keys = seen_keys.clone();
+
+                tasks.push(async move {
+                    let backend = get_search_backend(&acc.provider);
+                    let search_res = tokio::select! {
+                        res = tokio::time::timeout(Duration::from_secs(10), backend.search(&acc, &folder, &query)) => match res {
+                            Ok(r) => r,
+                            Err(_) => Err("IMAP search timeout".to_string()),
+                        },
+                        _ = token.cancelled() => return 0,
+                    };
+                    let mut uids = match search_res {
+                        Ok(u) => u,
+                        Err(e) => {
+                            log::warn!("Global search failed for {} {}: {}", acc.email, folder, e);
+                            return 0;
+                        }
+                    };
+                    uids.truncate(100);
+
+                    let headers = if is_active {
+                        // The active account's mail lives in the local cache: reuse what is there
+                        // and index the rest so follow-up searches find it locally.
+                        let existing = database::get_existing_uids(&app, &folder, &uids).unwrap_or_default();
+                        let missing: Vec<u32> = uids.iter().filter(|&&uid| !existing.contains(&uid)).copied().collect();
+                        if !missing.is_empty() {
+                            if let Ok(fetched) = fetch_remote_headers(&acc, &folder, &missing).await {
+                                let _ = database::insert_or_update_messages(&app, &fetched);
+                            }
+                        }
+                        database::get_messages_by_uids(&app, &folder, &uids).unwrap_or_default()
+                    } else {
+                        fetch_remote_headers(&acc, &folder, &uids).await.unwrap_or_default()
+                    };
+
+                    if token.is_cancelled() {
+                        return 0;
+                    }
+
+                    let new_messages = take_unseen(&mut *seen_keys.lock().await, &acc.email, headers);
+                    let count = new_messages.len();
+                    if count > 0 {
+                        let _ = app.emit("mail:search_incremental", SearchIncrementalPayload {
+                            search_id,
+                            query,
+                            folder: GLOBAL_SEARCH_FOLDER.to_string(),
+                            new_messages,
+                            account_id: if is_active { None } else { Some(acc.id.clone()) },
+                        });
+                    }
+                    count
+                });
+            }
+        }
+
+        let streamed: usize = futures::future::join_all(tasks).await.into_iter().sum();
+        let cancelled = token.is_cancelled();
+
+        {
+            let mut state = context.state.lock().await;
+            state.metrics.remote_ms = remote_start.elapsed().as_millis();
+            state.metrics.cancelled = cancelled;
+            state.remote_state = RemoteSearchState::Completed;
+            state.last_activity = Instant::now();
+        }
+
+        let _ = app_handle_bg.emit("mail:search_progress", SearchProgress {
+            search_id: search_id_bg.clone(),
+            state: if cancelled { SearchState::Cancelled } else { SearchState::Completed },
+            matched: streamed,
+            downloaded: streamed,
+            indexed: streamed,
+            streamed,
+            total: streamed,
+            progress_text: if cancelled { "Cancelled.".to_string() } else { "Complete.".to_string() },
+        });
+    });
+
+    Ok((search_id, local_results, RemoteSearchState::Running))
+}
+
+/// Returns headers for `uids` in `folder`, downloading and indexing the ones not cached yet.
+async fn reconcile_folder_hits(app_handle: &AppHandle, account: &Account, folder: &str, uids: &[u32], token: &CancellationToken) -> Vec<MessageHeader> {
+    let existing = database::get_existing_uids(app_handle, folder, uids).unwrap_or_default();
+    let missing: Vec<u32> = uids.iter().copied().filter(|uid| !existing.contains(uid)).collect();
+    if !missing.is_empty() && !token.is_cancelled() {
+        if let Err(e) = fetch_and_index_search_messages(app_handle, account, folder, &missing).await {
+            log::warn!("Failed to fetch {} search hits in {}: {}", missing.len(), folder, e);
+        }
+    }
+    database::get_messages_by_uids(app_handle, folder, uids).unwrap_or_default()
+}
+
+/// Gmail "everywhere" search: searches All Mail, then maps each hit back to where it lives.
+/// Hits labelled Inbox or Sent are resolved to those folders' own UIDs, so they merge with
+/// synced mail; archived hits are indexed under the `all_mail` folder and their labels are
+/// emitted as `mail:search_labels`.
+async fn start_everywhere_search(app_handle: AppHandle, account: Account, query: String, sort: SearchSort) -> Result<(String, Vec<SearchHit>, RemoteSearchState), String> {
+    use crate::mail::folder::MailFolder;
+
+    let start_time = Instant::now();
+    let search_id = uuid::Uuid::new_v4().to_string();
+
+    let mut to_remove = Vec::new();
+    for entry in ACTIVE_SEARCHES.iter() {
+        if entry.value().account.email == account.email && entry.value().folder == GLOBAL_SEARCH_FOLDER {
+            entry.value().cancellation_token.cancel();
+            to_remove.push(entry.key().clone());
+        }
+    }
+    for key in to_remove {
+        ACTIVE_SEARCHES.remove(&key);
+    }
+
+    let local_results = search_local(&app_handle, GLOBAL_SEARCH_FOLDER, &query, 100, sort).await?;
+    let local_ms = start_time.elapsed().as_millis();
+
+    let token = CancellationToken::new();
+    let context = Arc::new(SearchContext {
+        search_id: search_id.clone(),
+        account: account.clone(),
+        folder: GLOBAL_SEARCH_FOLDER.to_string(),
+        query: query.clone(),
+        cancellation_token: token.clone(),
+        state: Mutex::new(SearchContextState {
+            pending: VecDeque::new(),
+            streamed: HashSet::new(),
+            cursor: None,
+            last_activity: Instant::now(),
+            metrics: SearchMetrics {
+                local_ms,
+                cache_miss: true,
+                ..SearchMetrics::default()
+            },
+            remote_state: RemoteSearchState::Running,
+        }),
+    });
+
+    ACTIVE_SEARCHES.insert(search_id.clone(), context.clone());
+
+    let app_handle_bg = app_handle.clone();
+    let search_id_bg = search_id.clone();
+    let query_bg = query.clone();
+
+    tokio::spawn(async move {
+        let emit_progress = |state: SearchState, matched: usize, streamed: usize, text: &str| {
+            let _ = app_handle_bg.emit("mail:search_progress", SearchProgress {
+                search_id: search_id_bg.clone(),
+                state,
+                matched,
+                downloaded: streamed,
+                indexed: streamed,
+                streamed,
+                total: matched,
+                progress_text: text.to_string(),
+            });
+        };
+        emit_progress(SearchState::SearchingRemote, 0, 0, "Searching all mail...");
+
+        let remote_start = Instant::now();
+        let hits = tokio::select! {
+            res = tokio::time::timeout(Duration::from_secs(15), gmail_all_mail_search(&account, &query_bg, 200)) => match res {
+                Ok(Ok(hits)) => hits,
+                failed => {
+                    let e = match failed {
+                        Ok(Err(e)) => e,
+                        _ => "IMAP search timeout".to_string(),
+                    };
+                    log::warn!("All Mail search failed: {}", e);
+                    context.state.lock().await.remote_state = RemoteSearchState::Offline;
+                    emit_progress(SearchState::Completed, 0, 0, if e.contains("Offline") { "Offline (Local results only)" } else { "Complete (Local results only)" });
+                    return;
+                }
+            },
+            _ = token.cancelled() => {
+                let mut state = context.state.lock().await;
+                state.metrics.cancelled = true;
+                state.remote_state = RemoteSearchState::Completed;
+                emit_progress(SearchState::Cancelled, 0, 0, "Cancelled.");
+                return;
+            }
+        };
+
+        let total_matches = hits.len();
+        context.state.lock().await.metrics.remote_ms = remote_start.elapsed().as_millis();
+        emit_progress(SearchState::Reconciling, total_matches, 0, &format!("{} matches found...", total_matches));
+
+        let has_label = |hit: &GmailFetch, wanted: &str| hit.labels.iter().any(|l| l.eq_ignore_ascii_case(wanted));
+        let in_inbox = hits.iter().any(|hit| has_label(hit, "\\Inbox"));
+        let in_sent = hits.iter().any(|hit| has_label(hit, "\\Sent"));
+        let archived: Vec<GmailFetch> = hits.into_iter()
+            .filter(|hit| !has_label(hit, "\\Inbox") && !has_label(hit, "\\Sent"))
+            .collect();
+
+        let mut streamed = 0;
+        let backend = GmailSearchBackend;
+        let download_start = Instant::now();
+
+        for (folder, wanted) in [(MailFolder::Inbox, in_inbox), (MailFolder::Sent, in_sent)] {
+            if !wanted || token.is_cancelled() {
+                continue;
+            }
+            let folder = folder.to_string();
+            let Ok(Ok(mut uids)) = tokio::time::timeout(Duration::from_secs(10), backend.search(&account, &folder, &query_bg)).await else { continue };
+            uids.truncate(100);
+
+            let messages = reconcile_folder_hits(&app_handle_bg, &account, &folder, &uids, &token).await;
+            streamed += messages.len();
+            if !messages.is_empty() {
+                let _ = app_handle_bg.emit("mail:search_incremental", SearchIncrementalPayload {
+                    search_id: search_id_bg.clone(),
+                    query: query_bg.clone(),
+                    folder: GLOBAL_SEARCH_FOLDER.to_string(),
+                    new_messages: messages,
+                    account_id: None,
+                });
+            }
+            emit_progress(SearchState::Downloading, total_matches, streamed, &format!("Downloading {}/{}...", streamed, total_matches));
+        }
+
+        if !archived.is_empty() && !token.is_cancelled() {
+            let all_mail = MailFolder::AllMail.to_string();
+            let uids: Vec<u32> = archived.iter().map(|hit| hit.uid).take(100).collect();
+
+            let messages = reconcile_folder_hits(&app_handle_bg, &account, &all_mail, &uids, &token).await;
+            if let Err(e) = crate::mail::gmail_labels::save_fetched(&app_handle_bg, &all_mail, &archived[..uids.len()]) {
+                log::warn!("Failed to cache labels of All Mail hits: {}", e);
+            }
+            streamed += messages.len();
+            if !messages.is_empty() {
+                let _ = app_handle_bg.emit("mail:search_incremental", SearchIncrementalPayload {
+                    search_id: search_id_bg.clone(),
+                    query: query_bg.clone(),
+                    folder: GLOBAL_SEARCH_FOLDER.to_string(),
+                    new_messages: messages,
+                    account_id: None,
+                });
+            }
+            let _ = app_handle_bg.emit("mail:search_labels", SearchLabelsPayload {
+                search_id: search_id_bg.clone(),
+                folder: all_mail,
+                labels: archived.into_iter().map(|hit| (hit.uid, hit.labels)).collect(),
+            });
+        }
+
+        let cancelled = token.is_cancelled();
+        {
+            let mut state = context.state.lock().await;
+            state.metrics.download_ms = download_start.elapsed().as_millis();
+            state.metrics.indexed = streamed;
+            state.metrics.cancelled = cancelled;
+            state.remote_state = RemoteSearchState::Completed;
+            state.last_activity = Instant::now();
+        }
+
+        if cancelled {
+            emit_progress(SearchState::Cancelled, total_matches, streamed, "Cancelled.");
+        } else {
+            emit_progress(SearchState::Completed, total_matches, streamed, "Complete.");
+        }
+    });
+
+    Ok((search_id, local_results, RemoteSearchState::Running))
+}
+
 pub async fn load_more_results(app_handle: AppHandle, search_id: String) -> Result<RemoteSearchState, String> {
     let context = match ACTIVE_SEARCHES.get(&search_id) {
         Some(c) => c.clone(),
@@ -731,6 +1252,7 @@ pub async fn load_more_results(app_handle: AppHandle, search_id: String) -> Resu
                                 query: query.clone(),
                                 folder: folder.clone(),
                                 new_messages: existing_msgs,
+                                account_id: None,
                             });
                         }
                     }
@@ -777,6 +1299,7 @@ pub async fn load_more_results(app_handle: AppHandle, search_id: String) -> Resu
                             query: query.clone(),
                             folder: folder.clone(),
                             new_messages: new_msgs,
+                            account_id: None,
                         });
                     }
                     batch_buffer.clear();
@@ -792,6 +1315,7 @@ pub async fn load_more_results(app_handle: AppHandle, search_id: String) -> Resu
                     query: query.clone(),
                     folder: folder.clone(),
                     new_messages: new_msgs,
+                    account_id: None,
                 });
             }
         }
@@ -845,6 +1369,59 @@ mod tests {
         assert!(cap_out.supports_server_or);
     }
 
+    fn header(folder: &str, uid: u32, message_id: Option<&str>) -> MessageHeader {
+        MessageHeader {
+            folder: folder.to_string(),
+            uid,
+            uid_validity: 1,
+            subject: String::new(),
+            from: String::new(),
+            date: 0,
+            seen: false,
+            flagged: false,
+            has_attachments: false,
+            thread_id: None,
+            snippet: None,
+            to: None,
+            message_id: message_id.map(str::to_string),
+            unsubscribe: None,
+        }
+    }
+
+    #[test]
+    fn test_dedupe_key_normalises_message_id() {
+        let a = header("inbox", 1, Some("<Abc@Example.com>"));
+        let b = header("sent", 9, Some("  abc@example.com "));
+        assert_eq!(dedupe_key("me@example.com", &a), dedupe_key("other@example.com", &b));
+
+        // Without a Message-ID the account, folder and UID identify the message
+        let c = header("inbox", 1, None);
+        let d = header("inbox", 1, Some("<>"));
+        assert_eq!(dedupe_key("me@example.com", &c), "me@example.com/inbox/1");
+        assert_eq!(dedupe_key("me@example.com", &d), "me@example.com/inbox/1");
+        assert_ne!(dedupe_key("me@example.com", &c), dedupe_key("other@example.com", &c));
+    }
+
+    #[test]
+    fn test_take_unseen_merges_accounts() {
+        let mut seen = HashSet::new();
+        let local = take_unseen(&mut seen, "me@example.com", vec![
+            header("inbox", 1, Some("<shared@example.com>")),
+            header("inbox", 2, None),
+        ]);
+        assert_eq!(local.len(), 2);
+
+        // A copy of a local hit in another account is dropped, same-numbered UIDs are not
+        let remote = take_unseen(&mut seen, "other@example.com", vec![
+            header("inbox", 7, Some("<SHARED@example.com>")),
+            header("inbox", 2, None),
+            header("sent", 3, Some("<new@example.com>")),
+            header("inbox", 4, Some("<new@example.com>")),
+        ]);
+        let kept: Vec<(String, u32)> = remote.into_iter().map(|h| (h.folder, h.uid)).collect();
+        assert_eq!(kept, vec![("inbox".to_string(), 2), ("sent".to_string(), 3)]);
+    }
+
     #[test]
     fn test_search_metrics_default() {
         let metrics = SearchMetrics::default();
@@ -0,0 +1,304 @@
+use crate::mail::database::get_db_path;
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+use tauri::AppHandle;
+
+const MAX_HISTORY: i64 = 200;
+const MAX_SUGGESTIONS: usize = 12;
+/// Searches run while typing; a query superseded by a longer one this soon is a keystroke, not a search.
+const SUPERSEDE_WINDOW_SECS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionKind {
+    History,
+    Operator,
+    Value,
+    Contact,
+    Folder,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSuggestion {
+    pub kind: SuggestionKind,
+    pub label: String,
+    /// The full query text to put in the search box when the suggestion is accepted.
+    pub completion: String,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHistoryEntry {
+    pub query: String,
+    pub use_count: u32,
+    pub last_used: i64,
+}
+
+/// Operators offered while typing a bare word, with the completion they insert and a hint.
+/// Entries ending in `:` expect a value; the rest are complete terms.
+const OPERATORS: &[(&str, &str)] = &[
+    ("from:", "Sender name or address"),
+    ("to:", "Recipient name or address"),
+    ("subject:", "Words in the subject"),
+    ("filename:", "Attachment name"),
+    ("in:", "Folder"),
+    ("has:attachment", "Has attachments"),
+    ("is:unread", "Unread messages"),
+    ("is:read", "Read messages"),
+    ("is:starred", "Starred messages"),
+    ("before:", "Before a date (YYYY-MM-DD)"),
+    ("after:", "After a date (YYYY-MM-DD)"),
+    ("older_than:", "Older than e.g. 7d, 2w, 1y"),
+    ("newer_than:", "Newer than e.g. 7d, 2w, 1y"),
+    ("larger:", "Larger than e.g. 5M"),
+    ("smaller:", "Smaller than e.g. 100K"),
+    ("label:", "Gmail label"),
+    ("category:", "Gmail category"),
+];
+
+/// Fixed values for operators that take one of a few keywords.
+const OPERATOR_VALUES: &[(&str, &[&str])] = &[
+    ("is", &["unread", "read", "starred"]),
+    ("has", &["attachment"]),
+    ("in", &["inbox", "sent", "starred", "all_mail"]),
+    ("older_than", &["1d", "7d", "1m", "1y"]),
+    ("newer_than", &["1d", "7d", "1m", "1y"]),
+    ("larger", &["1M", "5M", "10M"]),
+    ("smaller", &["100K", "1M"]),
+    ("category", &["primary", "social", "promotions", "updates", "forums"]),
+];
+
+/// Operators whose value is a person, completed from the contacts table.
+const CONTACT_OPERATORS: &[&str] = &["from", "to", "cc", "bcc"];
+
+pub fn init_search_history_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS search_history (
+            query TEXT PRIMARY KEY COLLATE NOCASE,
+            use_count INTEGER NOT NULL DEFAULT 1,
+            last_used INTEGER NOT NULL
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn record_search(app_handle: &AppHandle, query: &str) -> Result<(), String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(());
+    }
+
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp();
+
+    // Drop the partial queries the search box fired on the way to this one
+    conn.execute(
+        "DELETE FROM search_history
+         WHERE use_count = 1 AND last_used >= ?1 AND length(query) < length(?2) AND substr(lower(?2), 1, length(query)) = lower(query)",
+        rusqlite::params![now - SUPERSEDE_WINDOW_SECS, query],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO search_history (query, use_count, last_used) VALUES (?1, 1, ?2)
+         ON CONFLICT(query) DO UPDATE SET use_count = use_count + 1, last_used = excluded.last_used",
+        rusqlite::params![query, now],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "DELETE FROM search_history WHERE query NOT IN (SELECT query FROM search_history ORDER BY last_used DESC LIMIT ?1)",
+        rusqlite::params![MAX_HISTORY],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn list_search_history(app_handle: &AppHandle, limit: u32) -> Result<Vec<SearchHistoryEntry>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    history_matching(&conn, "", limit)
+}
+
+pub fn clear_search_history(app_handle: &AppHandle) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM search_history", ()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn history_matching(conn: &Connection, prefix: &str, limit: u32) -> Result<Vec<SearchHistoryEntry>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT query, use_count, last_used FROM search_history
+         WHERE substr(lower(query), 1, length(?1)) = lower(?1)
+         ORDER BY last_used DESC LIMIT ?2"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params![prefix, limit], |row| {
+        Ok(SearchHistoryEntry { query: row.get(0)?, use_count: row.get(1)?, last_used: row.get(2)? })
+    }).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Splits the text being typed into everything before the current word, an optional `-`
+/// negation, and the word itself.
+fn split_current_term(input: &str) -> (&str, &str, &str) {
+    let start = input.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+    let (head, term) = input.split_at(start);
+    match term.strip_prefix('-') {
+        Some(rest) => (head, "-", rest),
+        None => (head, "", term),
+    }
+}
+
+fn quote_if_needed(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value.replace('"', ""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Suggestions that need no database: operator names and their keyword values.
+pub fn operator_suggestions(input: &str) -> Vec<SearchSuggestion> {
+    let (head, negation, term) = split_current_term(input);
+    let lower = term.to_lowercase();
+    let mut suggestions = Vec::new();
+
+    if let Some((key, value)) = lower.split_once(':') {
+        if let Some((_, values)) = OPERATOR_VALUES.iter().find(|(k, _)| *k == key) {
+            for v in values.iter().filter(|v| v.to_lowercase().starts_with(value) && v.to_lowercase() != value) {
+                suggestions.push(SearchSuggestion {
+                    kind: if key == "in" { SuggestionKind::Folder } else { SuggestionKind::Value },
+                    label: format!("{}:{}", key, v),
+                    completion: format!("{}{}{}:{} ", head, negation, key, v),
+                    detail: None,
+                });
+            }
+        }
+    } else if !lower.is_empty() {
+        for (op, hint) in OPERATORS.iter().filter(|(op, _)| op.starts_with(&lower) && *op != lower) {
+            let trailing = if op.ends_with(':') { "" } else { " " };
+            suggestions.push(SearchSuggestion {
+                kind: SuggestionKind::Operator,
+                label: op.to_string(),
+                completion: format!("{}{}{}{}", head, negation, op, trailing),
+                detail: Some(hint.to_string()),
+            });
+        }
+    }
+
+    suggestions
+}
+
+/// Completes the current word of a search query from previous searches, operators,
+/// operator values, folders and contacts, in that order.
+pub fn suggest_search(app_handle: &AppHandle, input: &str) -> Result<Vec<SearchSuggestion>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut suggestions: Vec<SearchSuggestion> = history_matching(&conn, input.trim_start(), 5)?
+        .into_iter()
+        .filter(|entry| entry.query != input.trim())
+        .map(|entry| SearchSuggestion {
+            kind: SuggestionKind::History,
+            label: entry.query.clone(),
+            completion: entry.query,
+            detail: None,
+        })
+        .collect();
+
+    suggestions.extend(operator_suggestions(input));
+
+    let (head, negation, term) = split_current_term(input);
+    let (contact_key, contact_query) = match term.split_once(':') {
+        Some((key, value)) if CONTACT_OPERATORS.contains(&key.to_lowercase().as_str()) => (Some(key.to_lowercase()), value),
+        Some(_) => (None, ""),
+        None => (None, term),
+    };
+
+    // Bare words complete to `from:` since that is what people search for most
+    if contact_query.chars().count() >= 2 {
+        let key = contact_key.unwrap_or_else(|| "from".to_string());
+        for (email, name) in matching_contacts(&conn, contact_query, 5)? {
+            suggestions.push(SearchSuggestion {
+                kind: SuggestionKind::Contact,
+                label: name.clone().unwrap_or_else(|| email.clone()),
+                completion: format!("{}{}{}:{} ", head, negation, key, quote_if_needed(&email)),
+                detail: Some(email),
+            });
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    suggestions.retain(|s| seen.insert(s.completion.to_lowercase()));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    Ok(suggestions)
+}
+
+fn matching_contacts(conn: &Connection, query: &str, limit: u32) -> Result<Vec<(String, Option<String>)>, String> {
+    let q = escape_like(&query.trim_matches('"').to_lowercase());
+    let mut stmt = conn.prepare(
+        "SELECT email, display_name FROM contacts
+         WHERE LOWER(email) LIKE ?1 ESCAPE '\\' OR LOWER(display_name) LIKE ?1 ESCAPE '\\'
+         ORDER BY
+           CASE WHEN LOWER(email) LIKE ?2 ESCAPE '\\' OR LOWER(display_name) LIKE ?2 ESCAPE '\\' THEN 0 ELSE 1 END,
+           usage_count DESC,
+           last_used DESC
+         LIMIT ?3"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params![format!("%{}%", q), format!("{}%", q), limit], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    }).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Escapes LIKE wildcards so `_` and `%` typed by the user match literally (with `ESCAPE '\'`).
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contact_matches_treat_wildcards_literally() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE contacts (email TEXT PRIMARY KEY, display_name TEXT, usage_count INTEGER NOT NULL DEFAULT 1, last_used INTEGER NOT NULL, source TEXT);
+             INSERT INTO contacts (email, display_name, last_used) VALUES
+               ('j_doe@example.com', NULL, 1), ('jxdoe@example.com', NULL, 2), ('100%@example.com', 'Full', 3);"
+        ).unwrap();
+
+        let emails = |q: &str| matching_contacts(&conn, q, 5).unwrap().into_iter().map(|(e, _)| e).collect::<Vec<_>>();
+        assert_eq!(emails("j_d"), vec!["j_doe@example.com".to_string()]);
+        assert_eq!(emails("0%@"), vec!["100%@example.com".to_string()]);
+        assert_eq!(emails("%").len(), 1);
+    }
+
+    #[test]
+    fn test_split_current_term() {
+        assert_eq!(split_current_term("invoice -is:un"), ("invoice ", "-", "is:un"));
+        assert_eq!(split_current_term("fr"), ("", "", "fr"));
+        assert_eq!(split_current_term("report "), ("report ", "", ""));
+    }
+
+    #[test]
+    fn test_operator_suggestions() {
+        let ops = operator_suggestions("budget ha");
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].completion, "budget has:attachment ");
+
+        let from: Vec<String> = operator_suggestions("fr").into_iter().map(|s| s.completion).collect();
+        assert_eq!(from, vec!["from:".to_string()]);
+
+        let values: Vec<String> = operator_suggestions("x -is:un").into_iter().map(|s| s.completion).collect();
+        assert_eq!(values, vec!["x -is:unread ".to_string()]);
+
+        let folders = operator_suggestions("in:s");
+        assert!(folders.iter().all(|s| s.kind == SuggestionKind::Folder));
+        assert_eq!(folders.len(), 2);
+    }
+}
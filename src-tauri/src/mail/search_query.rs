@@ -0,0 +1,821 @@
+//! Structured search query language shared by local and server search.
+//!
+//! A query is parsed once into a `QueryNode` tree and then compiled per target:
+//! `to_sql` for the local FTS5 index, `to_imap_search` for generic IMAP `SEARCH`,
+//! and `to_gmail_search` for Gmail's `X-GM-RAW`. Operators the app does not interpret
+//! (`label:`, `category:`, `has:drive`, `in:`...) are kept as `QueryNode::Operator`
+//! and passed through verbatim to Gmail, which evaluates them natively.
+
+use crate::mail::folder::MailFolder;
+use crate::mail::message_list::MessageHeader;
+use chrono::{Local, NaiveDate, TimeZone};
+use rusqlite::types::Value;
+use serde::{Serialize, Deserialize};
+
+/// Markers passed to FTS5 `highlight()`/`snippet()`; stripped again by `parse_marked`.
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSort {
+    /// BM25 with flag/unread/recency bonuses; falls back to date when nothing is rankable.
+    #[default]
+    Relevance,
+    Date,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldHighlight {
+    pub field: String,
+    pub text: String,
+    /// `[start, end)` offsets into `text`, in UTF-16 code units so the UI can slice JS strings directly.
+    pub ranges: Vec<[usize; 2]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub header: MessageHeader,
+    pub score: Option<f64>,
+    pub highlights: Vec<FieldHighlight>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Any,
+    From,
+    To,
+    Subject,
+    Filename,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagFilter {
+    Unread,
+    Read,
+    Starred,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryNode {
+    Text { field: TextField, value: String, phrase: bool },
+    HasAttachment,
+    Is(FlagFilter),
+    /// Unix timestamp, exclusive upper bound.
+    Before(i64),
+    /// Unix timestamp, inclusive lower bound.
+    After(i64),
+    Larger(u64),
+    Smaller(u64),
+    /// `in:<folder>` for folders the app knows (`inbox`, `sent`, `starred`, `all_mail`).
+    Folder(String),
+    /// `key:value` operators we do not interpret locally (e.g. `label:`), kept for providers that do.
+    Operator { key: String, value: String },
+    Not(Box<QueryNode>),
+    And(Vec<QueryNode>),
+    Or(Vec<QueryNode>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedQuery {
+    pub root: Option<QueryNode>,
+}
+
+/// WHERE clause over `messages m` plus the FTS expression used for BM25 ranking.
+#[derive(Debug, Clone)]
+pub struct SqlFilter {
+    pub clause: String,
+    pub params: Vec<Value>,
+    pub rank_match: Option<String>,
+}
+
+/// Server-side search criteria ready to hand to `UID SEARCH`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerQuery {
+    pub utf8: bool,
+    pub keys: String,
+}
+
+impl ServerQuery {
+    /// Builds the full search command, optionally restricted to a UID range for paging.
+    pub fn command(&self, uid_range: Option<&str>) -> String {
+        let mut parts = Vec::new();
+        if self.utf8 {
+            parts.push("CHARSET UTF-8".to_string());
+        }
+        if let Some(range) = uid_range {
+            parts.push(format!("UID {}", range));
+        }
+        parts.push(self.keys.clone());
+        parts.join(" ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Or,
+    Not,
+    Term { key: Option<String>, value: String, quoted: bool },
+}
+
+pub fn parse_query(input: &str) -> ParsedQuery {
+    parse_query_at(input, chrono::Utc::now().timestamp())
+}
+
+/// Parses a query relative to `now`, which anchors `older_than:`/`newer_than:`.
+pub fn parse_query_at(input: &str, now: i64) -> ParsedQuery {
+    let mut parser = Parser { tokens: tokenize(input), pos: 0, now };
+
+    let mut parts = Vec::new();
+    while parser.pos < parser.tokens.len() {
+        match parser.parse_or() {
+            Some(node) => parts.push(node),
+            // Stray closing parenthesis at the top level
+            None => parser.pos += 1,
+        }
+    }
+
+    ParsedQuery { root: collapse(parts, QueryNode::And) }
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let is_boundary = |c: char| c.is_whitespace() || c == '(' || c == ')';
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '-' if chars.get(i + 1).is_some_and(|n| !n.is_whitespace()) => {
+                tokens.push(Token::Not);
+                i += 1;
+            }
+            '"' => {
+                let (value, next) = read_quoted(&chars, i + 1);
+                tokens.push(Token::Term { key: None, value, quoted: true });
+                i = next;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !is_boundary(chars[i]) && chars[i] != ':' {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                if i < chars.len() && chars[i] == ':' && !word.is_empty() {
+                    i += 1;
+                    let key = Some(word.to_lowercase());
+                    if chars.get(i) == Some(&'"') {
+                        let (value, next) = read_quoted(&chars, i + 1);
+                        tokens.push(Token::Term { key, value, quoted: true });
+                        i = next;
+                    } else {
+                        let value_start = i;
+                        while i < chars.len() && !is_boundary(chars[i]) {
+                            i += 1;
+                        }
+                        let value: String = chars[value_start..i].iter().collect();
+                        tokens.push(Token::Term { key, value, quoted: false });
+                    }
+                } else if word == "OR" || word == "|" {
+                    tokens.push(Token::Or);
+                } else if word != "AND" {
+                    tokens.push(Token::Term { key: None, value: word, quoted: false });
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+fn read_quoted(chars: &[char], start: usize) -> (String, usize) {
+    let mut i = start;
+    let mut value = String::new();
+    while i < chars.len() && chars[i] != '"' {
+        value.push(chars[i]);
+        i += 1;
+    }
+    // Skip the closing quote; an unterminated phrase runs to the end of input
+    (value, (i + 1).min(chars.len()))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    now: i64,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Option<QueryNode> {
+        let mut branches = Vec::new();
+        if let Some(node) = self.parse_and() {
+            branches.push(node);
+        }
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            if let Some(node) = self.parse_and() {
+                branches.push(node);
+            }
+        }
+        collapse(branches, QueryNode::Or)
+    }
+
+    fn parse_and(&mut self) -> Option<QueryNode> {
+        let mut items = Vec::new();
+        while let Some(token) = self.peek() {
+            if matches!(token, Token::Or | Token::RParen) {
+                break;
+            }
+            if let Some(node) = self.parse_unary() {
+                items.push(node);
+            }
+        }
+        collapse(items, QueryNode::And)
+    }
+
+    fn parse_unary(&mut self) -> Option<QueryNode> {
+        let token = self.tokens.get(self.pos)?.clone();
+        self.pos += 1;
+
+        match token {
+            Token::Not => self.parse_unary().map(|node| QueryNode::Not(Box::new(node))),
+            Token::LParen => {
+                let inner = self.parse_or();
+                if self.peek() == Some(&Token::RParen) {
+                    self.pos += 1;
+                }
+                inner
+            }
+            Token::RParen | Token::Or => None,
+            Token::Term { key, value, quoted } => self.term(key, value, quoted),
+        }
+    }
+
+    fn term(&self, key: Option<String>, value: String, quoted: bool) -> Option<QueryNode> {
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            return None;
+        }
+
+        let text = |field: TextField| {
+            if value.chars().any(|c| c.is_alphanumeric()) {
+                Some(QueryNode::Text { field, value: value.clone(), phrase: quoted })
+            } else {
+                None
+            }
+        };
+
+        let Some(key) = key else { return text(TextField::Any) };
+        let lower = value.to_lowercase();
+        let operator = || Some(QueryNode::Operator { key: key.clone(), value: value.clone() });
+
+        match key.as_str() {
+            "from" => text(TextField::From),
+            "to" => text(TextField::To),
+            "subject" => text(TextField::Subject),
+            "filename" => text(TextField::Filename),
+            "has" if lower == "attachment" => Some(QueryNode::HasAttachment),
+            "in" => lower.parse::<MailFolder>().map(|f| QueryNode::Folder(f.to_string())).ok().or_else(operator),
+            "is" => match lower.as_str() {
+                "unread" => Some(QueryNode::Is(FlagFilter::Unread)),
+                "read" => Some(QueryNode::Is(FlagFilter::Read)),
+                "starred" | "flagged" => Some(QueryNode::Is(FlagFilter::Starred)),
+                _ => operator(),
+            },
+            "before" => parse_date(&value).map(QueryNode::Before).or_else(operator),
+            "after" => parse_date(&value).map(QueryNode::After).or_else(operator),
+            "older_than" => parse_relative(&lower).and_then(|secs| self.now.checked_sub(secs)).map(QueryNode::Before).or_else(operator),
+            "newer_than" => parse_relative(&lower).and_then(|secs| self.now.checked_sub(secs)).map(QueryNode::After).or_else(operator),
+            "larger" => parse_size(&lower).map(QueryNode::Larger).or_else(operator),
+            "smaller" => parse_size(&lower).map(QueryNode::Smaller).or_else(operator),
+            _ => operator(),
+        }
+    }
+}
+
+fn collapse(mut nodes: Vec<QueryNode>, wrap: fn(Vec<QueryNode>) -> QueryNode) -> Option<QueryNode> {
+    match nodes.len() {
+        0 => None,
+        1 => nodes.pop(),
+        _ => Some(wrap(nodes)),
+    }
+}
+
+/// Accepts `2024-01-31`, `2024/01/31` and `01/31/2024`, interpreted as local midnight.
+fn parse_date(value: &str) -> Option<i64> {
+    let date = ["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y"]
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(value, fmt).ok())?;
+    Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest().map(|dt| dt.timestamp())
+}
+
+/// Parses Gmail-style relative ages such as `7d`, `2w`, `3m`, `1y` into seconds. Amounts
+/// that overflow are rejected like any other malformed value.
+fn parse_relative(value: &str) -> Option<i64> {
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    let day = 86_400;
+    let seconds = match unit {
+        'd' => day,
+        'w' => 7 * day,
+        'm' => 30 * day,
+        'y' => 365 * day,
+        _ => return None,
+    };
+    amount.checked_mul(seconds)
+}
+
+/// Parses `500`, `100k`, `10m`/`10mb`, `1g` into bytes. Sizes beyond what SQLite can store
+/// are rejected.
+fn parse_size(value: &str) -> Option<u64> {
+    let trimmed = value.trim_end_matches('b');
+    let digits_end = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+    let amount: u64 = trimmed[..digits_end].parse().ok()?;
+    let multiplier = match &trimmed[digits_end..] {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    amount.checked_mul(multiplier).filter(|bytes| i64::try_from(*bytes).is_ok())
+}
+
+impl ParsedQuery {
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Compiles to a predicate over `messages m`. Text terms become FTS subqueries so they
+    /// can be freely combined with flag, date and size predicates under OR and NOT.
+    pub fn to_sql(&self) -> Option<SqlFilter> {
+        let root = self.root.as_ref()?;
+        let mut params = Vec::new();
+        let clause = node_sql(root, &mut params);
+
+        let mut rank_terms = Vec::new();
+        collect_rank_terms(root, false, &mut rank_terms);
+
+        Some(SqlFilter {
+            clause,
+            params,
+            rank_match: if rank_terms.is_empty() { None } else { Some(rank_terms.join(" OR ")) },
+        })
+    }
+
+    /// Standard IMAP SEARCH keys (RFC 3501) for a search of `folder`. IMAP has no key for the
+    /// mailbox, so `in:` terms are decided against `folder` first; `None` means the query is
+    /// empty or cannot match anything there.
+    pub fn to_imap_search(&self, folder: &str) -> Option<ServerQuery> {
+        let keys = match resolve_folders(self.root.as_ref()?, folder) {
+            Resolved::Never => return None,
+            Resolved::Always => "ALL".to_string(),
+            Resolved::Node(QueryNode::And(items)) => items.iter().map(imap_key).collect::<Vec<_>>().join(" "),
+            Resolved::Node(other) => imap_key(&other),
+        };
+        Some(ServerQuery { utf8: !keys.is_ascii(), keys })
+    }
+
+    /// Gmail's own search syntax, for use with `X-GM-RAW`.
+    pub fn to_gmail_raw(&self) -> Option<String> {
+        self.root.as_ref().map(|root| gmail_term(root, false))
+    }
+
+    /// The query text when it is nothing but free-text terms, e.g. `the contract from the lawyer`.
+    pub fn free_text(&self) -> Option<String> {
+        fn collect(node: &QueryNode, out: &mut Vec<String>) -> bool {
+            match node {
+                QueryNode::Text { field: TextField::Any, value, .. } => {
+                    out.push(value.clone());
+                    true
+                }
+                QueryNode::And(items) => items.iter().all(|n| collect(n, out)),
+                _ => false,
+            }
+        }
+        let mut terms = Vec::new();
+        (self.root.as_ref().is_some_and(|root| collect(root, &mut terms)) && !terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    /// All non-negated text values, for ranking structured queries by meaning.
+    pub fn text_terms(&self) -> Option<String> {
+        fn collect(node: &QueryNode, negated: bool, out: &mut Vec<String>) {
+            match node {
+                QueryNode::Text { value, .. } if !negated => out.push(value.clone()),
+                QueryNode::Not(inner) => collect(inner, !negated, out),
+                QueryNode::And(items) | QueryNode::Or(items) => items.iter().for_each(|n| collect(n, negated, out)),
+                _ => {}
+            }
+        }
+        let mut terms = Vec::new();
+        if let Some(root) = &self.root {
+            collect(root, false, &mut terms);
+        }
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    /// True when the query uses operators only Gmail understands, so no generic IMAP or
+    /// local translation can return equivalent results.
+    pub fn has_gmail_operators(&self) -> bool {
+        self.root.as_ref().is_some_and(has_gmail_operator)
+    }
+
+    pub fn to_gmail_search(&self) -> Option<ServerQuery> {
+        let raw = self.to_gmail_raw()?;
+        Some(ServerQuery { utf8: !raw.is_ascii(), keys: format!("X-GM-RAW {}", imap_quote(&raw)) })
+    }
+}
+
+fn fts_expr(node: &QueryNode) -> Option<String> {
+    let (field, value, phrase) = match node {
+        QueryNode::Text { field, value, phrase } => (*field, value.clone(), *phrase),
+        QueryNode::Operator { key, value } => (TextField::Any, format!("{}:{}", key, value), true),
+        _ => return None,
+    };
+
+    let escaped = format!("\"{}\"", value.replace('"', "\"\""));
+    let term = if phrase { escaped } else { format!("{}*", escaped) };
+
+    Some(match field {
+        TextField::Any => term,
+        TextField::From => format!("sender : {}", term),
+        TextField::To => format!("recipient : {}", term),
+        TextField::Subject => format!("subject : {}", term),
+        TextField::Filename => format!("attachment_names : {}", term),
+    })
+}
+
+fn node_sql(node: &QueryNode, params: &mut Vec<Value>) -> String {
+    match node {
+        QueryNode::Text { .. } | QueryNode::Operator { .. } => {
+            params.push(Value::Text(fts_expr(node).unwrap_or_default()));
+            "m.rowid IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?)".to_string()
+        }
+        QueryNode::HasAttachment => "m.has_attachments = 1".to_string(),
+        // Starred is virtual: it spans folders and is defined by the flag
+        QueryNode::Folder(folder) if folder == "starred" => "m.flagged = 1".to_string(),
+        QueryNode::Folder(folder) => {
+            params.push(Value::Text(folder.clone()));
+            "m.folder = ?".to_string()
+        }
+        QueryNode::Is(FlagFilter::Unread) => "m.seen = 0".to_string(),
+        QueryNode::Is(FlagFilter::Read) => "m.seen = 1".to_string(),
+        QueryNode::Is(FlagFilter::Starred) => "m.flagged = 1".to_string(),
+        QueryNode::Before(ts) => {
+            params.push(Value::Integer(*ts));
+            "m.date < ?".to_string()
+        }
+        QueryNode::After(ts) => {
+            params.push(Value::Integer(*ts));
+            "m.date >= ?".to_string()
+        }
+        QueryNode::Larger(bytes) => {
+            params.push(Value::Integer(*bytes as i64));
+            "m.size > ?".to_string()
+        }
+        QueryNode::Smaller(bytes) => {
+            params.push(Value::Integer(*bytes as i64));
+            "m.size < ?".to_string()
+        }
+        QueryNode::Not(inner) => format!("NOT ({})", node_sql(inner, params)),
+        QueryNode::And(items) => format!("({})", items.iter().map(|n| node_sql(n, params)).collect::<Vec<_>>().join(" AND ")),
+        QueryNode::Or(items) => format!("({})", items.iter().map(|n| node_sql(n, params)).collect::<Vec<_>>().join(" OR ")),
+    }
+}
+
+fn collect_rank_terms(node: &QueryNode, negated: bool, out: &mut Vec<String>) {
+    match node {
+        QueryNode::Text { .. } | QueryNode::Operator { .. } if !negated => {
+            if let Some(expr) = fts_expr(node) {
+                out.push(expr);
+            }
+        }
+        QueryNode::Not(inner) => collect_rank_terms(inner, !negated, out),
+        QueryNode::And(items) | QueryNode::Or(items) => {
+            for item in items {
+                collect_rank_terms(item, negated, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn imap_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn imap_date(ts: i64) -> String {
+    Local.timestamp_opt(ts, 0)
+        .single()
+        .map(|dt| dt.format("%d-%b-%Y").to_string())
+        .unwrap_or_else(|| "01-Jan-1970".to_string())
+}
+
+/// A query with its `in:` terms evaluated for one mailbox.
+enum Resolved {
+    Always,
+    Never,
+    Node(QueryNode),
+}
+
+fn resolve_folders(node: &QueryNode, folder: &str) -> Resolved {
+    match node {
+        // Starred is virtual: it spans folders and is defined by the flag
+        QueryNode::Folder(f) if f == "starred" => Resolved::Node(QueryNode::Is(FlagFilter::Starred)),
+        QueryNode::Folder(f) if f.eq_ignore_ascii_case(folder) => Resolved::Always,
+        QueryNode::Folder(_) => Resolved::Never,
+        QueryNode::Not(inner) => match resolve_folders(inner, folder) {
+            Resolved::Always => Resolved::Never,
+            Resolved::Never => Resolved::Always,
+            Resolved::Node(n) => Resolved::Node(QueryNode::Not(Box::new(n))),
+        },
+        QueryNode::And(items) => {
+            let mut kept = Vec::new();
+            for item in items {
+                match resolve_folders(item, folder) {
+                    Resolved::Never => return Resolved::Never,
+                    Resolved::Always => {}
+                    Resolved::Node(n) => kept.push(n),
+                }
+            }
+            match kept.len() {
+                0 => Resolved::Always,
+                1 => Resolved::Node(kept.remove(0)),
+                _ => Resolved::Node(QueryNode::And(kept)),
+            }
+        }
+        QueryNode::Or(items) => {
+            let mut kept = Vec::new();
+            for item in items {
+                match resolve_folders(item, folder) {
+                    Resolved::Always => return Resolved::Always,
+                    Resolved::Never => {}
+                    Resolved::Node(n) => kept.push(n),
+                }
+            }
+            match kept.len() {
+                0 => Resolved::Never,
+                1 => Resolved::Node(kept.remove(0)),
+                _ => Resolved::Node(QueryNode::Or(kept)),
+            }
+        }
+        other => Resolved::Node(other.clone()),
+    }
+}
+
+fn imap_key(node: &QueryNode) -> String {
+    match node {
+        QueryNode::Text { field, value, .. } => {
+            let key = match field {
+                TextField::Any => "TEXT",
+                TextField::From => "FROM",
+                TextField::To => "TO",
+                TextField::Subject => "SUBJECT",
+                // Attachment names live in the MIME part headers, which BODY covers on most servers
+                TextField::Filename => "BODY",
+            };
+            format!("{} {}", key, imap_quote(value))
+        }
+        QueryNode::Operator { value, .. } => format!("TEXT {}", imap_quote(value)),
+        QueryNode::HasAttachment => "HEADER Content-Type \"multipart/mixed\"".to_string(),
+        // Resolved against the searched mailbox by `resolve_folders` before reaching here
+        QueryNode::Folder(_) => "ALL".to_string(),
+        QueryNode::Is(FlagFilter::Unread) => "UNSEEN".to_string(),
+        QueryNode::Is(FlagFilter::Read) => "SEEN".to_string(),
+        QueryNode::Is(FlagFilter::Starred) => "FLAGGED".to_string(),
+        QueryNode::Before(ts) => format!("BEFORE {}", imap_date(*ts)),
+        QueryNode::After(ts) => format!("SINCE {}", imap_date(*ts)),
+        QueryNode::Larger(bytes) => format!("LARGER {}", bytes),
+        QueryNode::Smaller(bytes) => format!("SMALLER {}", bytes),
+        QueryNode::Not(inner) => format!("NOT {}", imap_key(inner)),
+        QueryNode::And(items) => format!("({})", items.iter().map(imap_key).collect::<Vec<_>>().join(" ")),
+        // OR is binary in IMAP, so "OR a OR b c" means a OR (b OR c)
+        QueryNode::Or(items) => {
+            let keys: Vec<String> = items.iter().map(imap_key).collect();
+            let (last, rest) = keys.split_last().expect("OR node always has at least two branches");
+            format!("{}{}", rest.iter().map(|k| format!("OR {} ", k)).collect::<String>(), last)
+        }
+    }
+}
+
+/// Gmail search operators that have no local or standard IMAP equivalent.
+const GMAIL_OPERATORS: &[&str] = &["label", "category", "in", "list", "deliveredto", "cc", "bcc", "rfc822msgid", "around", "has", "is"];
+
+fn has_gmail_operator(node: &QueryNode) -> bool {
+    match node {
+        QueryNode::Operator { key, .. } => GMAIL_OPERATORS.contains(&key.as_str()),
+        QueryNode::Not(inner) => has_gmail_operator(inner),
+        QueryNode::And(items) | QueryNode::Or(items) => items.iter().any(has_gmail_operator),
+        _ => false,
+    }
+}
+
+fn gmail_value(value: &str, phrase: bool) -> String {
+    let cleaned = value.replace('"', "");
+    if phrase || cleaned.contains(char::is_whitespace) {
+        format!("\"{}\"", cleaned)
+    } else {
+        cleaned
+    }
+}
+
+fn gmail_term(node: &QueryNode, nested: bool) -> String {
+    match node {
+        QueryNode::Text { field, value, phrase } => {
+            let prefix = match field {
+                TextField::Any => "",
+                TextField::From => "from:",
+                TextField::To => "to:",
+                TextField::Subject => "subject:",
+                TextField::Filename => "filename:",
+            };
+            format!("{}{}", prefix, gmail_value(value, *phrase))
+        }
+        QueryNode::Operator { key, value } => format!("{}:{}", key, gmail_value(value, false)),
+        QueryNode::HasAttachment => "has:attachment".to_string(),
+        QueryNode::Folder(folder) => match folder.as_str() {
+            "starred" => "is:starred".to_string(),
+            "all_mail" => "in:anywhere".to_string(),
+            other => format!("in:{}", other),
+        },
+        QueryNode::Is(FlagFilter::Unread) => "is:unread".to_string(),
+        QueryNode::Is(FlagFilter::Read) => "is:read".to_string(),
+        QueryNode::Is(FlagFilter::Starred) => "is:starred".to_string(),
+        // Gmail accepts epoch seconds for before:/after:, which keeps sub-day precision
+        QueryNode::Before(ts) => format!("before:{}", ts),
+        QueryNode::After(ts) => format!("after:{}", ts),
+        QueryNode::Larger(bytes) => format!("larger:{}", bytes),
+        QueryNode::Smaller(bytes) => format!("smaller:{}", bytes),
+        QueryNode::Not(inner) => format!("-{}", gmail_term(inner, true)),
+        QueryNode::And(items) => {
+            let joined = items.iter().map(|n| gmail_term(n, true)).collect::<Vec<_>>().join(" ");
+            if nested { format!("({})", joined) } else { joined }
+        }
+        QueryNode::Or(items) => {
+            let joined = items.iter().map(|n| gmail_term(n, true)).collect::<Vec<_>>().join(" OR ");
+            if nested { format!("({})", joined) } else { joined }
+        }
+    }
+}
+
+/// Strips `MATCH_START`/`MATCH_END` markers, returning the clean text and highlighted ranges.
+pub fn parse_marked(marked: &str) -> (String, Vec<[usize; 2]>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    let mut open: Option<usize> = None;
+
+    for c in marked.chars() {
+        if c == MATCH_START {
+            open = Some(offset);
+        } else if c == MATCH_END {
+            if let Some(start) = open.take() {
+                if offset > start {
+                    ranges.push([start, offset]);
+                }
+            }
+        } else {
+            text.push(c);
+            offset += c.len_utf16();
+        }
+    }
+
+    (text, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn test_parse_operators_and_negation() {
+        let parsed = parse_query_at("from:alice \"quarterly report\" -is:read has:attachment larger:10M", NOW);
+        let Some(QueryNode::And(items)) = parsed.root else { panic!("expected AND root") };
+        assert_eq!(items[0], QueryNode::Text { field: TextField::From, value: "alice".to_string(), phrase: false });
+        assert_eq!(items[1], QueryNode::Text { field: TextField::Any, value: "quarterly report".to_string(), phrase: true });
+        assert_eq!(items[2], QueryNode::Not(Box::new(QueryNode::Is(FlagFilter::Read))));
+        assert_eq!(items[3], QueryNode::HasAttachment);
+        assert_eq!(items[4], QueryNode::Larger(10 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_overflowing_values_are_invalid_operators() {
+        let parsed = parse_query_at("larger:99999999999999999999 smaller:9999999999999g older_than:9999999999999999y", NOW);
+        let Some(QueryNode::And(items)) = parsed.root else { panic!("expected AND root") };
+        assert!(items.iter().all(|item| matches!(item, QueryNode::Operator { .. })), "{:?}", items);
+        assert_eq!(parse_size("8191g"), Some(8191 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_or_binds_looser_than_and() {
+        let parsed = parse_query_at("invoice OR receipt older_than:7d", NOW);
+        let Some(QueryNode::Or(branches)) = parsed.root else { panic!("expected OR root") };
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1], QueryNode::And(vec![
+            QueryNode::Text { field: TextField::Any, value: "receipt".to_string(), phrase: false },
+            QueryNode::Before(NOW - 7 * 86_400),
+        ]));
+    }
+
+    #[test]
+    fn test_sql_compilation() {
+        let filter = parse_query_at("subject:budget is:unread", NOW).to_sql().unwrap();
+        assert_eq!(filter.clause, "(m.rowid IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?) AND m.seen = 0)");
+        assert_eq!(filter.params, vec![Value::Text("subject : \"budget\"*".to_string())]);
+        assert_eq!(filter.rank_match.as_deref(), Some("subject : \"budget\"*"));
+    }
+
+    #[test]
+    fn test_imap_and_gmail_compilation() {
+        let parsed = parse_query_at("from:bob (urgent OR asap) -is:starred", NOW);
+        assert_eq!(
+            parsed.to_imap_search("inbox").unwrap().command(Some("1:99")),
+            "UID 1:99 FROM \"bob\" OR TEXT \"urgent\" TEXT \"asap\" NOT FLAGGED"
+        );
+        assert_eq!(parsed.to_gmail_raw().unwrap(), "from:bob (urgent OR asap) -is:starred");
+    }
+
+    #[test]
+    fn test_imap_folder_terms_resolve_against_mailbox() {
+        let key = |query: &str, folder: &str| parse_query_at(query, NOW).to_imap_search(folder).map(|q| q.keys);
+
+        assert_eq!(key("in:inbox invoice", "inbox").as_deref(), Some("TEXT \"invoice\""));
+        assert_eq!(key("in:sent invoice", "inbox"), None);
+        assert_eq!(key("-in:inbox", "inbox"), None);
+        assert_eq!(key("-in:sent", "inbox").as_deref(), Some("ALL"));
+        assert_eq!(key("in:sent OR is:unread", "inbox").as_deref(), Some("UNSEEN"));
+        assert_eq!(key("in:inbox OR is:unread", "inbox").as_deref(), Some("ALL"));
+        assert_eq!(key("in:starred from:bob", "sent").as_deref(), Some("FLAGGED FROM \"bob\""));
+    }
+
+    #[test]
+    fn test_parse_marked_utf16_ranges() {
+        let (text, ranges) = parse_marked("caf\u{e9} \u{2}\u{1F4E7}mail\u{3} and \u{2}more\u{3}");
+        assert_eq!(text, "caf\u{e9} \u{1F4E7}mail and more");
+        // the envelope emoji is two UTF-16 code units
+        assert_eq!(ranges, vec![[5, 11], [16, 20]]);
+    }
+
+    #[test]
+    fn test_unknown_operator_passthrough() {
+        let parsed = parse_query_at("label:work category:updates", NOW);
+        assert_eq!(parsed.to_gmail_raw().unwrap(), "label:work category:updates");
+        assert!(parse_query_at("  ( ) ", NOW).is_empty());
+    }
+
+    #[test]
+    fn test_folder_operator() {
+        let parsed = parse_query_at("in:sent report", NOW);
+        let sql = parsed.to_sql().unwrap();
+        assert!(sql.clause.contains("m.folder = ?"));
+        assert_eq!(parsed.to_gmail_raw().unwrap(), "in:sent report");
+        // unknown folders stay Gmail-native operators
+        assert!(parse_query_at("in:trash", NOW).has_gmail_operators());
+    }
+
+    #[test]
+    fn test_free_text_and_terms() {
+        assert_eq!(parse_query_at("the contract from the lawyer", NOW).free_text().as_deref(), Some("the contract from the lawyer"));
+        let structured = parse_query_at("contract from:alice -draft is:unread", NOW);
+        assert_eq!(structured.free_text(), None);
+        assert_eq!(structured.text_terms().as_deref(), Some("contract alice"));
+    }
+
+    #[test]
+    fn test_gmail_native_operators() {
+        let parsed = parse_query_at("has:drive -category:promotions label:\"Team Notes\"", NOW);
+        assert!(parsed.has_gmail_operators());
+        assert_eq!(
+            parsed.to_gmail_search().unwrap().command(Some("1:41")),
+            "UID 1:41 X-GM-RAW \"has:drive -category:promotions label:\\\"Team Notes\\\"\""
+        );
+        assert!(!parse_query_at("from:bob has:attachment is:unread", NOW).has_gmail_operators());
+    }
+}
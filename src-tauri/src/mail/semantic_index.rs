@@ -0,0 +1,451 @@
+use crate::mail::database::get_db_path;
+use crate::mail::search_query::SearchHit;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+/// Identifies the vectors in `message_embeddings`; rows from another model are re-embedded.
+pub const EMBEDDING_MODEL: &str = "bge-small-en-v1.5";
+
+/// Weight of cosine similarity in the hybrid score; the remainder goes to BM25.
+const SEMANTIC_WEIGHT: f64 = 0.5;
+/// Subject plus the start of the body is enough signal and keeps CPU embedding cheap.
+const MAX_DOCUMENT_CHARS: usize = 2000;
+const INDEX_BATCH_SIZE: usize = 32;
+/// Semantic neighbours below this similarity are not added to keyword results.
+const MIN_NEIGHBOUR_SIMILARITY: f32 = 0.55;
+
+static INDEXER_RUNNING: AtomicBool = AtomicBool::new(false);
+/// `semantic_search_enabled` as last read or written; every local search consults it.
+static ENABLED: Lazy<Mutex<Option<bool>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticIndexProgress {
+    pub indexed: u32,
+    pub total: u32,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticIndexStatus {
+    pub enabled: bool,
+    pub available: bool,
+    pub running: bool,
+    pub indexed: u32,
+    pub total: u32,
+    pub model: String,
+}
+
+pub fn init_semantic_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS message_embeddings (
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            model TEXT NOT NULL,
+            vector BLOB NOT NULL,
+            indexed_at INTEGER NOT NULL,
+            PRIMARY KEY (folder, uid)
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Whether this build ships the embedding model runtime.
+pub fn is_available() -> bool {
+    cfg!(feature = "semantic-search")
+}
+
+pub fn is_enabled(app_handle: &AppHandle) -> bool {
+    let mut cached = ENABLED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(enabled) = *cached {
+        return enabled;
+    }
+    let Ok(db_path) = get_db_path(app_handle) else { return false };
+    let Ok(conn) = Connection::open(db_path) else { return false };
+    let enabled = conn.query_row("SELECT value FROM app_metadata WHERE key = 'semantic_search_enabled'", [], |row| row.get::<_, String>(0))
+        .map(|v| v == "1")
+        .unwrap_or(false);
+    *cached = Some(enabled);
+    enabled
+}
+
+pub fn set_enabled(app_handle: &AppHandle, enabled: bool) -> Result<(), String> {
+    if enabled && !is_available() {
+        return Err("Semantic search is not included in this build".to_string());
+    }
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_metadata (key, value) VALUES ('semantic_search_enabled', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![if enabled { "1" } else { "0" }],
+    ).map_err(|e| e.to_string())?;
+    *ENABLED.lock().unwrap_or_else(|e| e.into_inner()) = Some(enabled);
+
+    if enabled {
+        spawn_indexer(app_handle.clone());
+    }
+    Ok(())
+}
+
+pub fn get_status(app_handle: &AppHandle) -> Result<SemanticIndexStatus, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let (indexed, total) = index_counts(&conn)?;
+    Ok(SemanticIndexStatus {
+        enabled: is_enabled(app_handle),
+        available: is_available(),
+        running: INDEXER_RUNNING.load(Ordering::SeqCst),
+        indexed,
+        total,
+        model: EMBEDDING_MODEL.to_string(),
+    })
+}
+
+fn index_counts(conn: &Connection) -> Result<(u32, u32), String> {
+    let total: u32 = conn.query_row("SELECT COUNT(*) FROM messages WHERE body_fetched = 1 AND processed_html IS NOT NULL", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let indexed: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM message_embeddings e JOIN messages m ON m.folder = e.folder AND m.uid = e.uid
+         WHERE e.model = ?1 AND m.body_fetched = 1 AND m.processed_html IS NOT NULL",
+        rusqlite::params![EMBEDDING_MODEL],
+        |row| row.get(0),
+    ).map_err(|e| e.to_string())?;
+    Ok((indexed, total))
+}
+
+/// Starts the background indexer if semantic search is enabled and it is not already running.
+/// Cheap to call after every body download; the worker drains everything not yet embedded.
+pub fn spawn_indexer(app_handle: AppHandle) {
+    if !is_available() || !is_enabled(&app_handle) {
+        return;
+    }
+    if INDEXER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let result = run_indexer(&app_handle).await;
+        INDEXER_RUNNING.store(false, Ordering::SeqCst);
+
+        if let Err(e) = result {
+            log::error!("Semantic indexing stopped: {}", e);
+            let _ = app_handle.emit("mail:semantic_index_progress", SemanticIndexProgress {
+                indexed: 0,
+                total: 0,
+                done: true,
+                error: Some(e),
+            });
+        }
+    });
+}
+
+async fn run_indexer(app_handle: &AppHandle) -> Result<(), String> {
+    loop {
+        if !is_enabled(app_handle) {
+            return Ok(());
+        }
+
+        let app = app_handle.clone();
+        let batch_done = tokio::task::spawn_blocking(move || -> Result<Option<(u32, u32)>, String> {
+            let db_path = get_db_path(&app)?;
+            let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+            let batch = pending_documents(&conn, INDEX_BATCH_SIZE)?;
+            if batch.is_empty() {
+                return Ok(None);
+            }
+
+            let texts: Vec<String> = batch.iter().map(|(_, _, text)| text.clone()).collect();
+            let vectors = embedder::embed(&app, texts)?;
+
+            let now = chrono::Utc::now().timestamp();
+            for ((folder, uid, _), vector) in batch.iter().zip(vectors) {
+                conn.execute(
+                    "INSERT INTO message_embeddings (folder, uid, model, vector, indexed_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                     ON CONFLICT(folder, uid) DO UPDATE SET model = excluded.model, vector = excluded.vector, indexed_at = excluded.indexed_at",
+                    rusqlite::params![folder, uid, EMBEDDING_MODEL, encode_vector(&normalize(vector)), now],
+                ).map_err(|e| e.to_string())?;
+            }
+
+            index_counts(&conn).map(Some)
+        }).await.map_err(|e| e.to_string())??;
+
+        match batch_done {
+            Some((indexed, total)) => {
+                let _ = app_handle.emit("mail:semantic_index_progress", SemanticIndexProgress { indexed, total, done: false, error: None });
+            }
+            None => {
+                let db_path = get_db_path(app_handle)?;
+                let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+                let (indexed, total) = index_counts(&conn)?;
+                let _ = app_handle.emit("mail:semantic_index_progress", SemanticIndexProgress { indexed, total, done: true, error: None });
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Newest cached bodies without an up-to-date embedding, as `(folder, uid, document text)`.
+fn pending_documents(conn: &Connection, limit: usize) -> Result<Vec<(String, u32, String)>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT m.folder, m.uid, m.subject, m.processed_html
+         FROM messages m
+         LEFT JOIN message_embeddings e ON e.folder = m.folder AND e.uid = m.uid AND e.model = ?1
+         WHERE m.body_fetched = 1 AND m.processed_html IS NOT NULL AND e.uid IS NULL
+         ORDER BY m.date DESC
+         LIMIT ?2"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(rusqlite::params![EMBEDDING_MODEL, limit as i64], |row| {
+        let subject: Option<String> = row.get(2)?;
+        let body: Option<String> = row.get(3)?;
+        Ok((row.get(0)?, row.get(1)?, document_text(subject.as_deref().unwrap_or(""), body.as_deref().unwrap_or(""))))
+    }).map_err(|e| e.to_string())?;
+
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Plain text that gets embedded for a message: subject, then the tag-stripped body.
+pub fn document_text(subject: &str, body: &str) -> String {
+    let text = format!("{}\n\n{}", subject.trim(), strip_html(body));
+    text.chars().take(MAX_DOCUMENT_CHARS).collect()
+}
+
+/// Collapses cached HTML bodies to whitespace-normalised plain text.
+pub fn strip_html(html: &str) -> String {
+    static RE_BLOCKS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?si)<(style|script)[^>]*>.*?</(style|script)>").unwrap());
+    static RE_TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]+>").unwrap());
+    static RE_SPACE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
+
+    let stripped = RE_BLOCKS.replace_all(html, " ");
+    let stripped = RE_TAGS.replace_all(&stripped, " ");
+    let stripped = stripped.replace("&nbsp;", " ").replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">");
+    RE_SPACE.replace_all(&stripped, " ").trim().to_string()
+}
+
+pub fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for v in vector.iter_mut() {
+            *v /= norm;
+        }
+    }
+    vector
+}
+
+pub fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+}
+
+/// Cosine similarity of two normalised vectors.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Blends BM25 and cosine similarity into one score where lower is better, matching
+/// SQLite's `bm25()` convention. BM25 is min-max normalised across the candidates;
+/// candidates found only semantically get no keyword credit.
+pub fn hybrid_scores(bm25: &[Option<f64>], similarity: &[f32]) -> Vec<f64> {
+    let present: Vec<f64> = bm25.iter().flatten().copied().collect();
+    let best = present.iter().copied().fold(f64::INFINITY, f64::min);
+    let worst = present.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    bm25.iter().zip(similarity).map(|(score, sim)| {
+        let keyword = match score {
+            Some(s) if worst > best => (worst - s) / (worst - best),
+            Some(_) => 1.0,
+            None => 0.0,
+        };
+        let semantic = (*sim as f64).max(0.0);
+        -((1.0 - SEMANTIC_WEIGHT) * keyword + SEMANTIC_WEIGHT * semantic)
+    }).collect()
+}
+
+/// Re-ranks keyword hits by the hybrid score and, for plain free-text queries, adds the
+/// nearest semantic neighbours that keyword search missed. Returns the hits unchanged
+/// when the index is unavailable.
+pub fn rerank(app_handle: &AppHandle, folder: &str, query: &str, hits: Vec<SearchHit>, limit: u32) -> Result<Vec<SearchHit>, String> {
+    let parsed = crate::mail::search_query::parse_query(query);
+    let Some(query_text) = parsed.free_text().or_else(|| parsed.text_terms()) else { return Ok(hits) };
+
+    let query_vector = match embedder::embed_query(app_handle, &query_text) {
+        Ok(v) => normalize(v),
+        Err(e) => {
+            log::warn!("Semantic query embedding failed, using keyword ranking: {}", e);
+            return Ok(hits);
+        }
+    };
+
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let mut candidates = hits;
+    let mut similarity: Vec<f32> = candidates.iter()
+        .map(|hit| load_vector(&conn, &hit.header.folder, hit.header.uid).map(|v| cosine(&query_vector, &v)).unwrap_or(0.0))
+        .collect();
+
+    // Structured queries must keep honouring their filters, so only plain text queries
+    // pull in neighbours that did not match any keyword.
+    if parsed.free_text().is_some() {
+        let known: std::collections::HashSet<(String, u32)> = candidates.iter().map(|h| (h.header.folder.clone(), h.header.uid)).collect();
+        let mut neighbours = nearest_neighbours(&conn, folder, &query_vector, limit as usize)?;
+        neighbours.retain(|(f, uid, sim)| *sim >= MIN_NEIGHBOUR_SIMILARITY && !known.contains(&(f.clone(), *uid)));
+
+        let mut by_folder: HashMap<String, Vec<(u32, f32)>> = HashMap::new();
+        for (f, uid, sim) in neighbours {
+            by_folder.entry(f).or_default().push((uid, sim));
+        }
+        for (f, entries) in by_folder {
+            let uids: Vec<u32> = entries.iter().map(|(uid, _)| *uid).collect();
+            let sims: HashMap<u32, f32> = entries.into_iter().collect();
+            for header in crate::mail::database::get_messages_by_uids(app_handle, &f, &uids)? {
+                similarity.push(sims.get(&header.uid).copied().unwrap_or(0.0));
+                candidates.push(SearchHit { header, score: None, highlights: Vec::new() });
+            }
+        }
+    }
+
+    let bm25: Vec<Option<f64>> = candidates.iter().map(|h| h.score).collect();
+    let scores = hybrid_scores(&bm25, &similarity);
+
+    let mut ranked: Vec<(f64, SearchHit)> = scores.into_iter().zip(candidates).collect();
+    ranked.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    ranked.truncate(limit as usize);
+
+    Ok(ranked.into_iter().map(|(score, mut hit)| {
+        hit.score = Some(score);
+        hit
+    }).collect())
+}
+
+fn load_vector(conn: &Connection, folder: &str, uid: u32) -> Option<Vec<f32>> {
+    conn.query_row(
+        "SELECT vector FROM message_embeddings WHERE folder = ?1 AND uid = ?2 AND model = ?3",
+        rusqlite::params![folder, uid, EMBEDDING_MODEL],
+        |row| row.get::<_, Vec<u8>>(0),
+    ).optional().ok().flatten().map(|bytes| decode_vector(&bytes))
+}
+
+/// Brute-force scan; a mailbox's worth of 384-dim vectors is a few milliseconds of dot products.
+fn nearest_neighbours(conn: &Connection, folder: &str, query: &[f32], k: usize) -> Result<Vec<(String, u32, f32)>, String> {
+    let mut sql = format!(
+        "SELECT folder, uid, vector FROM message_embeddings WHERE model = ?1 AND {}",
+        crate::mail::snooze::not_snoozed_sql("message_embeddings")
+    );
+    let mut params: Vec<rusqlite::types::Value> = vec![rusqlite::types::Value::Text(EMBEDDING_MODEL.to_string())];
+    if folder != "all" {
+        sql.push_str(" AND folder = ?2");
+        params.push(rusqlite::types::Value::Text(folder.to_string()));
+    }
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?, row.get::<_, Vec<u8>>(2)?))
+    }).map_err(|e| e.to_string())?;
+
+    let mut scored: Vec<(String, u32, f32)> = rows
+        .filter_map(|r| r.ok())
+        .map(|(f, uid, bytes)| {
+            let sim = cosine(query, &decode_vector(&bytes));
+            (f, uid, sim)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(k);
+    Ok(scored)
+}
+
+#[cfg(feature = "semantic-search")]
+mod embedder {
+    use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+    use once_cell::sync::Lazy;
+    use std::sync::Mutex;
+    use tauri::{AppHandle, Manager};
+
+    /// Loaded on first use; the ONNX model is downloaded once into the app cache directory.
+    static MODEL: Lazy<Mutex<Option<TextEmbedding>>> = Lazy::new(|| Mutex::new(None));
+
+    /// BGE models expect retrieval queries to carry this instruction prefix.
+    const QUERY_PREFIX: &str = "Represent this sentence for searching relevant passages: ";
+
+    pub fn embed(app_handle: &AppHandle, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let mut guard = MODEL.lock().map_err(|e| e.to_string())?;
+        if guard.is_none() {
+            let cache_dir = app_handle.path().app_cache_dir().map_err(|e| e.to_string())?.join("orbitmail_models");
+            let model = TextEmbedding::try_new(
+                InitOptions::new(EmbeddingModel::BGESmallENV15)
+                    .with_cache_dir(cache_dir)
+                    .with_show_download_progress(false),
+            ).map_err(|e| format!("Failed to load embedding model: {}", e))?;
+            *guard = Some(model);
+        }
+        let model = guard.as_ref().ok_or_else(|| "Embedding model not loaded".to_string())?;
+        model.embed(texts, None).map_err(|e| format!("Embedding failed: {}", e))
+    }
+
+    pub fn embed_query(app_handle: &AppHandle, query: &str) -> Result<Vec<f32>, String> {
+        embed(app_handle, vec![format!("{}{}", QUERY_PREFIX, query)])?
+            .pop()
+            .ok_or_else(|| "Embedding model returned no vector".to_string())
+    }
+}
+
+#[cfg(not(feature = "semantic-search"))]
+mod embedder {
+    use tauri::AppHandle;
+
+    pub fn embed(_app_handle: &AppHandle, _texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        Err("Semantic search is not included in this build".to_string())
+    }
+
+    pub fn embed_query(_app_handle: &AppHandle, _query: &str) -> Result<Vec<f32>, String> {
+        Err("Semantic search is not included in this build".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_roundtrip_and_cosine() {
+        let v = normalize(vec![3.0, 4.0]);
+        assert_eq!(decode_vector(&encode_vector(&v)), v);
+        assert!((cosine(&v, &v) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine(&v, &[1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_hybrid_scores_blend_keyword_and_semantic() {
+        // bm25: lower is better; the second hit is the best keyword match
+        let scores = hybrid_scores(&[Some(-2.0), Some(-8.0), None], &[0.9, 0.1, 0.95]);
+        assert!((scores[0] + 0.45).abs() < 1e-6);
+        assert!((scores[1] + 0.55).abs() < 1e-6);
+        assert!((scores[2] + 0.475).abs() < 1e-6);
+        // a close semantic match found only by embeddings outranks a weak keyword match
+        assert!(scores[2] < scores[0]);
+    }
+
+    #[test]
+    fn test_document_text_strips_markup() {
+        let text = document_text(" Contract ", "<style>p{}</style><p>Signed&nbsp;copy</p>");
+        assert_eq!(text, "Contract\n\nSigned copy");
+    }
+}
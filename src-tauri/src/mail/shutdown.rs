@@ -7,6 +7,8 @@ pub static IDLE_TOKEN: Lazy<CancellationToken> = Lazy::new(|| APP_TOKEN.child_to
 pub static POLL_TOKEN: Lazy<CancellationToken> = Lazy::new(|| APP_TOKEN.child_token());
 pub static PREFETCH_TOKEN: Lazy<CancellationToken> = Lazy::new(|| APP_TOKEN.child_token());
 pub static TRAY_TOKEN: Lazy<CancellationToken> = Lazy::new(|| APP_TOKEN.child_token());
+pub static SNOOZE_TOKEN: Lazy<CancellationToken> = Lazy::new(|| APP_TOKEN.child_token());
+pub static PENDING_OPS_TOKEN: Lazy<CancellationToken> = Lazy::new(|| APP_TOKEN.child_token());
 
 pub fn trigger_shutdown() {
     log::info!("Global shutdown triggered");
@@ -0,0 +1,836 @@
+//! Translation between local rules and the subset of Sieve (RFC 5228) that can express them,
+//! so rules keep running on the server while the app is closed.
+//!
+//! Supported: `header`/`address :all`, `body`, `size` tests combined with `allof`/`anyof`/`not`,
+//! and the `fileinto`, `addflag`, `redirect`, `discard` and `stop` actions. Attachment and
+//! extracted-entity conditions, and notification actions, only exist on the client.
+
+use crate::auth::account::{Account, MailProvider};
+use crate::mail::folder::trash_mailbox;
+use crate::mail::mailbox_actions::{self, MoveTarget};
+use crate::mail::managesieve::ManageSieveClient;
+use crate::mail::rules::{self, MatchMode, Rule, RuleAction, RuleCondition, TextOp};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeSet;
+use tauri::AppHandle;
+
+/// Name of the script the app owns on the server.
+pub const DEFAULT_SCRIPT_NAME: &str = "orionmail";
+const RULE_COMMENT: &str = "# rule: ";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SieveExport {
+    pub script: String,
+    /// Rules, or parts of rules, that could not be expressed in Sieve.
+    pub warnings: Vec<String>,
+    /// Rules the script expresses in full, which the server can run instead of the app.
+    pub complete_rule_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SieveImport {
+    pub rules: Vec<Rule>,
+    /// Statements that were skipped because they fall outside the supported subset.
+    pub warnings: Vec<String>,
+}
+
+// --- Export ---
+
+fn sieve_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escapes `:matches` wildcards so the value is taken literally.
+fn escape_glob(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('*', "\\*").replace('?', "\\?")
+}
+
+fn text_test(test: &str, name: &str, op: TextOp, value: &str, required: &mut BTreeSet<&'static str>) -> String {
+    let (tag, key) = match op {
+        TextOp::Contains | TextOp::NotContains => (":contains", value.to_string()),
+        TextOp::Equals => (":is", value.to_string()),
+        TextOp::StartsWith => (":matches", format!("{}*", escape_glob(value))),
+        TextOp::EndsWith => (":matches", format!("*{}", escape_glob(value))),
+        TextOp::Matches => {
+            required.insert("regex");
+            (":regex", value.to_string())
+        }
+    };
+    let name_arg = if name.is_empty() { String::new() } else { format!(" {}", sieve_string(name)) };
+    let test = format!("{} {}{} {}", test, tag, name_arg, sieve_string(&key));
+    if op == TextOp::NotContains { format!("not {}", test) } else { test }
+}
+
+fn condition_to_sieve(condition: &RuleCondition, required: &mut BTreeSet<&'static str>) -> Option<String> {
+    Some(match condition {
+        RuleCondition::From { op, value } => text_test("header", "from", *op, value, required),
+        RuleCondition::To { op, value } => text_test("header", "to", *op, value, required),
+        RuleCondition::Subject { op, value } => text_test("header", "subject", *op, value, required),
+        RuleCondition::Header { name, op, value } => text_test("header", &name.to_lowercase(), *op, value, required),
+        RuleCondition::Body { op, value } => {
+            required.insert("body");
+            text_test("body :text", "", *op, value, required)
+        }
+        RuleCondition::SizeGreaterThan { bytes } => format!("size :over {}", bytes),
+        RuleCondition::SizeLessThan { bytes } => format!("size :under {}", bytes),
+        RuleCondition::HasAttachment { .. } | RuleCondition::EntityType { .. } => return None,
+    })
+}
+
+/// The server mailbox a Move action files into. Folder ids such as `junk` resolve to the
+/// provider's mailbox, as they do when the rules engine runs the move itself.
+fn fileinto_mailbox(mailbox: &str, provider: &MailProvider) -> String {
+    match MoveTarget::parse(mailbox.trim(), provider) {
+        MoveTarget::Folder(folder) => folder.to_imap_mailbox(provider).map(String::from).unwrap_or_else(|| mailbox.trim().to_string()),
+        _ => mailbox.trim().to_string(),
+    }
+}
+
+/// Translates enabled rules into one script, in rule order. Rules that depend on
+/// client-only conditions are left out entirely rather than run with weaker matching.
+pub fn rules_to_sieve(local_rules: &[Rule], provider: &MailProvider) -> SieveExport {
+    let mut required = BTreeSet::new();
+    let mut blocks = Vec::new();
+    let mut warnings = Vec::new();
+    let mut complete_rule_ids = Vec::new();
+
+    for rule in local_rules.iter().filter(|r| r.enabled) {
+        let tests: Option<Vec<String>> = rule.conditions.iter().map(|c| condition_to_sieve(c, &mut required)).collect();
+        let Some(tests) = tests else {
+            warnings.push(format!("\"{}\" uses attachment or entity conditions, which only run in the app", rule.name));
+            continue;
+        };
+        let test = match (tests.len(), rule.match_mode) {
+            (1, _) => tests[0].clone(),
+            (_, MatchMode::All) => format!("allof ({})", tests.join(", ")),
+            (_, MatchMode::Any) => format!("anyof ({})", tests.join(", ")),
+        };
+
+        // Flags are set first so that the copy filed by fileinto carries them
+        let mut flags = Vec::new();
+        let mut delivery = Vec::new();
+        let mut stop = false;
+        let mut complete = true;
+        for action in &rule.actions {
+            match action {
+                RuleAction::MarkRead => flags.push("\\Seen".to_string()),
+                RuleAction::Star => flags.push("\\Flagged".to_string()),
+                RuleAction::Label { label } => flags.push(rules::keyword_for_label(label)),
+                RuleAction::Forward { to } => {
+                    required.insert("copy");
+                    delivery.push(format!("redirect :copy {};", sieve_string(to.trim())));
+                }
+                RuleAction::Move { mailbox } => {
+                    required.insert("fileinto");
+                    delivery.push(format!("fileinto {};", sieve_string(&fileinto_mailbox(mailbox, provider))));
+                }
+                RuleAction::Delete => {
+                    required.insert("fileinto");
+                    delivery.push(format!("fileinto {};", sieve_string(trash_mailbox(provider))));
+                }
+                RuleAction::StopProcessing => stop = true,
+                RuleAction::Notify | RuleAction::SuppressNotification => {
+                    warnings.push(format!("\"{}\": notification settings only apply in the app", rule.name));
+                    complete = false;
+                }
+            }
+        }
+        if flags.is_empty() && delivery.is_empty() && !stop {
+            continue;
+        }
+
+        let mut body = Vec::new();
+        if !flags.is_empty() {
+            required.insert("imap4flags");
+            let list = flags.iter().map(|f| sieve_string(f)).collect::<Vec<_>>().join(", ");
+            body.push(format!("addflag [{}];", list));
+        }
+        body.extend(delivery);
+        if stop {
+            body.push("stop;".to_string());
+        }
+
+        let name = rule.name.replace(['\r', '\n'], " ");
+        blocks.push(format!("{}{}\nif {} {{\n    {}\n}}\n", RULE_COMMENT, name, test, body.join("\n    ")));
+        if complete {
+            complete_rule_ids.push(rule.id.clone());
+        }
+    }
+
+    let mut script = String::from("# Managed by orionmail. Edit rules in the app; changes here are overwritten.\n");
+    if !required.is_empty() {
+        let list = required.iter().map(|r| sieve_string(r)).collect::<Vec<_>>().join(", ");
+        script.push_str(&format!("require [{}];\n", list));
+    }
+    for block in blocks {
+        script.push('\n');
+        script.push_str(&block);
+    }
+
+    SieveExport { script, warnings, complete_rule_ids }
+}
+
+// --- Import ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Tag(String),
+    Str(String),
+    Num(u64),
+    RuleName(String),
+    Punct(char),
+}
+
+fn lex(script: &str) -> Result<Vec<Tok>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = script.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '#' => {
+                let end = chars[i..].iter().position(|&ch| ch == '\n').map(|p| i + p).unwrap_or(chars.len());
+                let comment: String = chars[i..end].iter().collect();
+                if let Some(name) = comment.strip_prefix(RULE_COMMENT) {
+                    tokens.push(Tok::RuleName(name.trim().to_string()));
+                }
+                i = end;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                let rest: String = chars[i + 2..].iter().collect();
+                let end = rest.find("*/").ok_or("Unterminated comment in Sieve script")?;
+                i += 2 + rest[..end].chars().count() + 2;
+            }
+            '"' => {
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('\\') => {
+                            s.extend(chars.get(i + 1));
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&ch) => {
+                            s.push(ch);
+                            i += 1;
+                        }
+                        None => return Err("Unterminated string in Sieve script".to_string()),
+                    }
+                }
+                tokens.push(Tok::Str(s));
+            }
+            '[' | ']' | '(' | ')' | '{' | '}' | ',' | ';' => {
+                tokens.push(Tok::Punct(c));
+                i += 1;
+            }
+            ':' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Tok::Tag(chars[start..i].iter().collect::<String>().to_lowercase()));
+            }
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let n: u64 = chars[start..i].iter().collect::<String>().parse().map_err(|_| "Number too large in Sieve script")?;
+                let multiplier = match chars.get(i).map(|c| c.to_ascii_uppercase()) {
+                    Some('K') => 1 << 10,
+                    Some('M') => 1 << 20,
+                    Some('G') => 1 << 30,
+                    _ => 1,
+                };
+                if multiplier > 1 {
+                    i += 1;
+                }
+                tokens.push(Tok::Num(n.checked_mul(multiplier).ok_or("Number too large in Sieve script")?));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let ident: String = chars[start..i].iter().collect::<String>().to_lowercase();
+                if ident == "text" && chars.get(i) == Some(&':') {
+                    return Err("Multi-line text: strings are not supported".to_string());
+                }
+                tokens.push(Tok::Ident(ident));
+            }
+            other => return Err(format!("Unexpected character '{}' in Sieve script", other)),
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    Tag(String),
+    Strings(Vec<String>),
+    Num(u64),
+}
+
+#[derive(Debug, Clone)]
+struct Test {
+    name: String,
+    args: Vec<Arg>,
+    tests: Vec<Test>,
+}
+
+#[derive(Debug, Clone)]
+struct Command {
+    name: String,
+    args: Vec<Arg>,
+    tests: Vec<Test>,
+    block: Option<Vec<Command>>,
+    label: Option<String>,
+}
+
+struct Parser {
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.next() {
+            Some(Tok::Punct(p)) if p == c => Ok(()),
+            other => Err(format!("Expected '{}' in Sieve script, found {:?}", c, other)),
+        }
+    }
+
+    fn commands(&mut self, nested: bool) -> Result<Vec<Command>, String> {
+        let mut commands = Vec::new();
+        let mut label = None;
+        loop {
+            match self.peek() {
+                None if !nested => return Ok(commands),
+                None => return Err("Unterminated block in Sieve script".to_string()),
+                Some(Tok::Punct('}')) if nested => {
+                    self.pos += 1;
+                    return Ok(commands);
+                }
+                Some(Tok::RuleName(_)) => {
+                    if let Some(Tok::RuleName(name)) = self.next() {
+                        label = Some(name);
+                    }
+                }
+                _ => {
+                    let mut command = self.command()?;
+                    command.label = label.take();
+                    commands.push(command);
+                }
+            }
+        }
+    }
+
+    fn command(&mut self) -> Result<Command, String> {
+        let name = match self.next() {
+            Some(Tok::Ident(name)) => name,
+            other => return Err(format!("Expected a command in Sieve script, found {:?}", other)),
+        };
+        let (args, tests) = self.arguments()?;
+        let block = match self.next() {
+            Some(Tok::Punct(';')) => None,
+            Some(Tok::Punct('{')) => Some(self.commands(true)?),
+            other => return Err(format!("Expected ';' or '{{' after {}, found {:?}", name, other)),
+        };
+        Ok(Command { name, args, tests, block, label: None })
+    }
+
+    fn arguments(&mut self) -> Result<(Vec<Arg>, Vec<Test>), String> {
+        let mut args = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Tok::Tag(_)) => if let Some(Tok::Tag(t)) = self.next() { args.push(Arg::Tag(t)) },
+                Some(Tok::Num(_)) => if let Some(Tok::Num(n)) = self.next() { args.push(Arg::Num(n)) },
+                Some(Tok::Str(_)) => if let Some(Tok::Str(s)) = self.next() { args.push(Arg::Strings(vec![s])) },
+                Some(Tok::Punct('[')) => {
+                    self.pos += 1;
+                    let mut list = Vec::new();
+                    loop {
+                        match self.next() {
+                            Some(Tok::Str(s)) => list.push(s),
+                            other => return Err(format!("Expected a string in list, found {:?}", other)),
+                        }
+                        match self.next() {
+                            Some(Tok::Punct(',')) => continue,
+                            Some(Tok::Punct(']')) => break,
+                            other => return Err(format!("Expected ',' or ']' in list, found {:?}", other)),
+                        }
+                    }
+                    args.push(Arg::Strings(list));
+                }
+                Some(Tok::Ident(_)) => return Ok((args, vec![self.test()?])),
+                Some(Tok::Punct('(')) => return Ok((args, self.test_list()?)),
+                _ => return Ok((args, Vec::new())),
+            }
+        }
+    }
+
+    fn test(&mut self) -> Result<Test, String> {
+        let name = match self.next() {
+            Some(Tok::Ident(name)) => name,
+            other => return Err(format!("Expected a test in Sieve script, found {:?}", other)),
+        };
+        let (args, tests) = self.arguments()?;
+        Ok(Test { name, args, tests })
+    }
+
+    fn test_list(&mut self) -> Result<Vec<Test>, String> {
+        self.expect('(')?;
+        let mut tests = vec![self.test()?];
+        loop {
+            match self.next() {
+                Some(Tok::Punct(',')) => tests.push(self.test()?),
+                Some(Tok::Punct(')')) => return Ok(tests),
+                other => return Err(format!("Expected ',' or ')' in test list, found {:?}", other)),
+            }
+        }
+    }
+}
+
+/// Converts a `:matches` pattern into the closest text operator, falling back to a regex.
+fn glob_to_op(pattern: &str) -> (TextOp, String) {
+    // Literal runs between unescaped `*`; any unescaped `?` forces the regex form
+    let mut segments = vec![String::new()];
+    let mut has_single = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => segments.last_mut().unwrap().extend(chars.next()),
+            '*' => segments.push(String::new()),
+            '?' => has_single = true,
+            _ => segments.last_mut().unwrap().push(c),
+        }
+    }
+
+    if !has_single {
+        match segments.as_slice() {
+            [only] => return (TextOp::Equals, only.clone()),
+            [head, tail] if !head.is_empty() && tail.is_empty() => return (TextOp::StartsWith, head.clone()),
+            [head, tail] if head.is_empty() && !tail.is_empty() => return (TextOp::EndsWith, tail.clone()),
+            [head, middle, tail] if head.is_empty() && tail.is_empty() && !middle.is_empty() => return (TextOp::Contains, middle.clone()),
+            _ => {}
+        }
+    }
+
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => regex.push_str(&regex::escape(&chars.next().map(String::from).unwrap_or_default())),
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    (TextOp::Matches, regex)
+}
+
+fn single_string(args: &[Arg], index: usize) -> Result<String, String> {
+    match args.iter().filter(|a| matches!(a, Arg::Strings(_))).nth(index) {
+        Some(Arg::Strings(list)) if list.len() == 1 => Ok(list[0].clone()),
+        Some(Arg::Strings(_)) => Err("string lists with several values are not supported".to_string()),
+        _ => Err("missing string argument".to_string()),
+    }
+}
+
+/// Match type and comparison tags of a test, skipping `:comparator` and its argument.
+fn match_op(args: &[Arg]) -> Result<(Option<&str>, Vec<Arg>), String> {
+    let mut op = None;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg {
+            Arg::Tag(t) if t == "comparator" => {
+                if let Some(Arg::Strings(c)) = iter.next() {
+                    if c.first().is_some_and(|c| c != "i;ascii-casemap") {
+                        return Err(format!("comparator {} is not supported", c[0]));
+                    }
+                }
+            }
+            Arg::Tag(t) if ["is", "contains", "matches", "regex"].contains(&t.as_str()) => op = Some(t.as_str()),
+            Arg::Tag(t) if ["all", "text"].contains(&t.as_str()) => {}
+            Arg::Tag(t) => return Err(format!(":{} is not supported", t)),
+            other => rest.push(other.clone()),
+        }
+    }
+    Ok((op, rest))
+}
+
+fn test_to_condition(test: &Test, negated: bool) -> Result<RuleCondition, String> {
+    match test.name.as_str() {
+        "not" if !negated && test.tests.len() == 1 => test_to_condition(&test.tests[0], true),
+        "header" | "address" | "body" => {
+            let (op, args) = match_op(&test.args)?;
+            let value_index = if test.name == "body" { 0 } else { 1 };
+            let raw = single_string(&args, value_index)?;
+            let (op, value) = match op {
+                Some("is") => (TextOp::Equals, raw),
+                Some("matches") => glob_to_op(&raw),
+                Some("regex") => (TextOp::Matches, raw),
+                _ => (TextOp::Contains, raw),
+            };
+            let op = match (negated, op) {
+                (false, op) => op,
+                (true, TextOp::Contains) => TextOp::NotContains,
+                (true, _) => return Err("only \"not ... :contains\" can be negated".to_string()),
+            };
+
+            if test.name == "body" {
+                return Ok(RuleCondition::Body { op, value });
+            }
+            let name = single_string(&args, 0)?.to_lowercase();
+            Ok(match name.as_str() {
+                "from" => RuleCondition::From { op, value },
+                "to" => RuleCondition::To { op, value },
+                "subject" if test.name == "header" => RuleCondition::Subject { op, value },
+                _ if test.name == "header" => RuleCondition::Header { name, op, value },
+                _ => return Err(format!("address test on {} is not supported", name)),
+            })
+        }
+        "size" if !negated => {
+            let bytes = test.args.iter().find_map(|a| match a { Arg::Num(n) => Some(*n), _ => None }).ok_or("size test without a limit")?;
+            match test.args.first() {
+                Some(Arg::Tag(t)) if t == "over" => Ok(RuleCondition::SizeGreaterThan { bytes }),
+                Some(Arg::Tag(t)) if t == "under" => Ok(RuleCondition::SizeLessThan { bytes }),
+                _ => Err("size test needs :over or :under".to_string()),
+            }
+        }
+        other => Err(format!("test \"{}\" is not supported", other)),
+    }
+}
+
+fn block_to_actions(block: &[Command], provider: &MailProvider, warnings: &mut Vec<String>, rule_name: &str) -> Result<Vec<RuleAction>, String> {
+    let mut actions = Vec::new();
+    for command in block {
+        match command.name.as_str() {
+            "fileinto" => {
+                if command.args.iter().any(|a| a == &Arg::Tag("copy".to_string())) {
+                    return Err("fileinto :copy is not supported".to_string());
+                }
+                let mailbox = single_string(&command.args, 0)?;
+                if mailbox == trash_mailbox(provider) {
+                    actions.push(RuleAction::Delete);
+                    continue;
+                }
+                // Synced folders come back as their ids, which is how the app names them
+                let mailbox = mailbox_actions::local_folder_for(&mailbox, provider).map_or(mailbox, |f| f.to_string());
+                actions.push(RuleAction::Move { mailbox });
+            }
+            "addflag" | "setflag" => {
+                let flags = command.args.iter()
+                    .filter_map(|a| match a { Arg::Strings(list) => Some(list.clone()), _ => None })
+                    .next_back()
+                    .unwrap_or_default();
+                for flag in flags.iter().flat_map(|f| f.split_whitespace()) {
+                    actions.push(match flag.to_lowercase().as_str() {
+                        "\\seen" => RuleAction::MarkRead,
+                        "\\flagged" => RuleAction::Star,
+                        _ if flag.starts_with('\\') => return Err(format!("flag {} is not supported", flag)),
+                        _ => RuleAction::Label { label: flag.to_string() },
+                    });
+                }
+            }
+            "redirect" => {
+                if !command.args.iter().any(|a| a == &Arg::Tag("copy".to_string())) {
+                    warnings.push(format!("\"{}\": redirect without :copy imported as a forward that keeps the message", rule_name));
+                }
+                actions.push(RuleAction::Forward { to: single_string(&command.args, 0)? });
+            }
+            "discard" => actions.push(RuleAction::Delete),
+            "stop" => actions.push(RuleAction::StopProcessing),
+            "keep" => {}
+            other => return Err(format!("action \"{}\" is not supported", other)),
+        }
+    }
+    Ok(actions)
+}
+
+/// Parses a script into rules. Each top-level `if` becomes one rule; anything outside the
+/// supported subset is reported in `warnings` and left out.
+pub fn sieve_to_rules(script: &str, provider: &MailProvider) -> Result<SieveImport, String> {
+    let commands = Parser { tokens: lex(script)?, pos: 0 }.commands(false)?;
+    let mut import = SieveImport::default();
+
+    for (index, command) in commands.iter().enumerate() {
+        let name = command.label.clone().unwrap_or_else(|| format!("Imported rule {}", import.rules.len() + 1));
+        match command.name.as_str() {
+            "require" => continue,
+            "if" => {}
+            "elsif" | "else" => {
+                import.warnings.push(format!("\"{}\": elsif/else branches are not supported", name));
+                continue;
+            }
+            other => {
+                import.warnings.push(format!("Top-level \"{}\" is not supported", other));
+                continue;
+            }
+        }
+        if commands.get(index + 1).is_some_and(|c| c.name == "elsif" || c.name == "else") {
+            import.warnings.push(format!("\"{}\": has elsif/else branches and was skipped", name));
+            continue;
+        }
+
+        let converted = (|| -> Result<Rule, String> {
+            let test = command.tests.first().ok_or("if without a test")?;
+            let (match_mode, tests) = match test.name.as_str() {
+                "allof" => (MatchMode::All, test.tests.clone()),
+                "anyof" => (MatchMode::Any, test.tests.clone()),
+                _ => (MatchMode::All, vec![test.clone()]),
+            };
+            let conditions = tests.iter().map(|t| test_to_condition(t, false)).collect::<Result<Vec<_>, _>>()?;
+            let actions = block_to_actions(command.block.as_deref().unwrap_or_default(), provider, &mut import.warnings, &name)?;
+            let rule = Rule { id: String::new(), name: name.clone(), enabled: true, match_mode, conditions, actions, position: 0, created_at: 0 };
+            rule.validate()?;
+            Ok(rule)
+        })();
+
+        match converted {
+            Ok(rule) => import.rules.push(rule),
+            Err(e) => import.warnings.push(format!("\"{}\" skipped: {}", name, e)),
+        }
+    }
+
+    Ok(import)
+}
+
+// --- Server ---
+
+fn with_client<R>(account: &Account, f: impl FnOnce(&mut ManageSieveClient) -> Result<R, String>) -> Result<R, String> {
+    let mut client = ManageSieveClient::connect(account)?;
+    let result = f(&mut client);
+    client.logout();
+    result
+}
+
+pub fn list_scripts(account: &Account) -> Result<Vec<crate::mail::managesieve::SieveScript>, String> {
+    with_client(account, |c| c.list_scripts())
+}
+
+pub fn get_script(account: &Account, name: &str) -> Result<String, String> {
+    with_client(account, |c| c.get_script(name))
+}
+
+pub fn check_script(account: &Account, script: &str) -> Result<Option<String>, String> {
+    with_client(account, |c| c.check_script(script))
+}
+
+/// Editing or replacing the app's script by hand means the server no longer runs the
+/// pushed rules as the app knows them, so they go back to running on sync.
+fn release_server_rules(app_handle: &AppHandle, account: &Account) -> Result<(), String> {
+    rules::set_server_rules(app_handle, &account.id, &[])
+}
+
+pub fn upload_script(app_handle: &AppHandle, account: &Account, name: &str, script: &str, activate: bool) -> Result<Option<String>, String> {
+    let warnings = with_client(account, |c| {
+        let warnings = c.put_script(name, script)?;
+        if activate {
+            c.set_active(name)?;
+        }
+        Ok(warnings)
+    })?;
+    if name == DEFAULT_SCRIPT_NAME || activate {
+        release_server_rules(app_handle, account)?;
+    }
+    Ok(warnings)
+}
+
+pub fn activate_script(app_handle: &AppHandle, account: &Account, name: &str) -> Result<(), String> {
+    with_client(account, |c| c.set_active(name))?;
+    if name != DEFAULT_SCRIPT_NAME {
+        release_server_rules(app_handle, account)?;
+    }
+    Ok(())
+}
+
+pub fn delete_script(app_handle: &AppHandle, account: &Account, name: &str) -> Result<(), String> {
+    with_client(account, |c| c.delete_script(name))?;
+    if name == DEFAULT_SCRIPT_NAME {
+        release_server_rules(app_handle, account)?;
+    }
+    Ok(())
+}
+
+pub fn export_rules(app_handle: &AppHandle, account: &Account) -> Result<SieveExport, String> {
+    let local_rules = rules::list_rules(app_handle)?;
+    Ok(rules_to_sieve(&local_rules, &account.provider))
+}
+
+/// Uploads the current rules as the app's script, validating it first. While the script is
+/// active, the rules it fully expresses are left to the server and skipped on sync.
+pub fn push_rules(app_handle: &AppHandle, account: &Account, activate: bool) -> Result<SieveExport, String> {
+    let mut export = export_rules(app_handle, account)?;
+    let active = with_client(account, |c| {
+        if let Some(warning) = c.check_script(&export.script)? {
+            export.warnings.push(warning);
+        }
+        c.put_script(DEFAULT_SCRIPT_NAME, &export.script)?;
+        if activate {
+            c.set_active(DEFAULT_SCRIPT_NAME)?;
+        }
+        Ok(c.list_scripts()?.iter().any(|s| s.name == DEFAULT_SCRIPT_NAME && s.active))
+    })?;
+    let server_side: &[String] = if active { &export.complete_rule_ids } else { &[] };
+    rules::set_server_rules(app_handle, &account.id, server_side)?;
+    Ok(export)
+}
+
+/// Parses `script` and saves every rule it yields as a new local rule.
+pub fn import_rules(app_handle: &AppHandle, account: &Account, script: &str) -> Result<SieveImport, String> {
+    let mut import = sieve_to_rules(script, &account.provider)?;
+    import.rules = import.rules.into_iter()
+        .map(|rule| rules::save_rule(app_handle, rule))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, match_mode: MatchMode, conditions: Vec<RuleCondition>, actions: Vec<RuleAction>) -> Rule {
+        Rule { id: String::new(), name: name.to_string(), enabled: true, match_mode, conditions, actions, position: 0, created_at: 0 }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rules = vec![
+            rule("Orders", MatchMode::All, vec![
+                RuleCondition::From { op: TextOp::EndsWith, value: "@shop.example".to_string() },
+                RuleCondition::SizeGreaterThan { bytes: 10_240 },
+            ], vec![RuleAction::MarkRead, RuleAction::Label { label: "Orders".to_string() }, RuleAction::Move { mailbox: "Shopping".to_string() }, RuleAction::StopProcessing]),
+            rule("Noise", MatchMode::Any, vec![
+                RuleCondition::Header { name: "List-Id".to_string(), op: TextOp::Contains, value: "deals".to_string() },
+                RuleCondition::Subject { op: TextOp::NotContains, value: "\"urgent\"".to_string() },
+            ], vec![RuleAction::Delete]),
+            rule("Boss", MatchMode::All, vec![
+                RuleCondition::Body { op: TextOp::Matches, value: r"invoice\s+\d+".to_string() },
+            ], vec![RuleAction::Star, RuleAction::Forward { to: "me@example.org".to_string() }]),
+        ];
+
+        let export = rules_to_sieve(&rules, &MailProvider::Google);
+        assert!(export.warnings.is_empty());
+        assert!(export.script.contains("require [\"body\", \"copy\", \"fileinto\", \"imap4flags\", \"regex\"];"));
+
+        let import = sieve_to_rules(&export.script, &MailProvider::Google).unwrap();
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+        assert_eq!(import.rules.len(), 3);
+
+        assert_eq!(import.rules[0].name, "Orders");
+        assert_eq!(import.rules[0].conditions, rules[0].conditions);
+        // Flags are emitted before delivery actions
+        assert_eq!(import.rules[0].actions, vec![RuleAction::MarkRead, RuleAction::Label { label: "Orders".to_string() }, RuleAction::Move { mailbox: "Shopping".to_string() }, RuleAction::StopProcessing]);
+
+        assert_eq!(import.rules[1].match_mode, MatchMode::Any);
+        assert_eq!(import.rules[1].conditions, vec![
+            RuleCondition::Header { name: "list-id".to_string(), op: TextOp::Contains, value: "deals".to_string() },
+            RuleCondition::Subject { op: TextOp::NotContains, value: "\"urgent\"".to_string() },
+        ]);
+        assert_eq!(import.rules[1].actions, vec![RuleAction::Delete]);
+
+        assert_eq!(import.rules[2].conditions, rules[2].conditions);
+        assert_eq!(import.rules[2].actions, rules[2].actions);
+    }
+
+    #[test]
+    fn test_folder_moves_use_provider_mailboxes() {
+        let rules = vec![
+            rule("Spam", MatchMode::All, vec![
+                RuleCondition::Subject { op: TextOp::Contains, value: "lottery".to_string() },
+            ], vec![RuleAction::Move { mailbox: "junk".to_string() }]),
+            rule("Mine", MatchMode::All, vec![
+                RuleCondition::From { op: TextOp::Contains, value: "me@example.org".to_string() },
+            ], vec![RuleAction::Move { mailbox: "sent".to_string() }]),
+        ];
+        let export = rules_to_sieve(&rules, &MailProvider::Google);
+        assert!(export.script.contains("fileinto \"[Gmail]/Spam\";"));
+        assert!(export.script.contains("fileinto \"[Gmail]/Sent Mail\";"));
+
+        let import = sieve_to_rules(&export.script, &MailProvider::Google).unwrap();
+        assert_eq!(import.rules[0].actions, rules[0].actions);
+        assert_eq!(import.rules[1].actions, rules[1].actions);
+    }
+
+    #[test]
+    fn test_client_only_rules_are_not_exported() {
+        let rules = vec![rule("Receipts", MatchMode::All, vec![
+            RuleCondition::EntityType { entity: "ReceiptReference".to_string() },
+        ], vec![RuleAction::Move { mailbox: "Receipts".to_string() }])];
+        let export = rules_to_sieve(&rules, &MailProvider::Google);
+        assert_eq!(export.warnings.len(), 1);
+        assert!(!export.script.contains("if "));
+    }
+
+    #[test]
+    fn test_partially_exported_rules_are_not_complete() {
+        let mut rules = vec![
+            rule("Orders", MatchMode::All, vec![
+                RuleCondition::From { op: TextOp::Contains, value: "shop".to_string() },
+            ], vec![RuleAction::MarkRead]),
+            rule("Alerts", MatchMode::All, vec![
+                RuleCondition::Subject { op: TextOp::Contains, value: "alert".to_string() },
+            ], vec![RuleAction::Star, RuleAction::Notify]),
+        ];
+        rules[0].id = "orders".to_string();
+        rules[1].id = "alerts".to_string();
+
+        let export = rules_to_sieve(&rules, &MailProvider::Google);
+        assert_eq!(export.complete_rule_ids, vec!["orders".to_string()]);
+        assert_eq!(export.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_oversized_numbers_are_rejected() {
+        assert!(sieve_to_rules("if size :over 17179869184G { discard; }", &MailProvider::Google).is_err());
+    }
+
+    #[test]
+    fn test_import_reports_unsupported_statements() {
+        let script = r#"
+            require ["fileinto", "vacation"];
+            /* hand written */
+            if address :domain :is "from" "example.com" { fileinto "Example"; }
+            if header :matches "subject" "Re: *report?" { fileinto "Reports"; }
+            vacation "Away";
+            if true { keep; }
+        "#;
+        let import = sieve_to_rules(script, &MailProvider::Google).unwrap();
+        assert_eq!(import.rules.len(), 1);
+        assert_eq!(import.rules[0].conditions, vec![
+            RuleCondition::Subject { op: TextOp::Matches, value: r"^Re: .*report.$".to_string() },
+        ]);
+        assert_eq!(import.warnings.len(), 3);
+    }
+
+    #[test]
+    fn test_glob_to_op() {
+        assert_eq!(glob_to_op("*@example.com"), (TextOp::EndsWith, "@example.com".to_string()));
+        assert_eq!(glob_to_op("News*"), (TextOp::StartsWith, "News".to_string()));
+        assert_eq!(glob_to_op("*sale*"), (TextOp::Contains, "sale".to_string()));
+        assert_eq!(glob_to_op("50\\* off*"), (TextOp::StartsWith, "50* off".to_string()));
+    }
+}
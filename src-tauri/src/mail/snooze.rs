@@ -0,0 +1,484 @@
+use crate::auth::account::Account;
+use crate::mail::database::{self, get_db_path};
+use crate::mail::folder::MailFolder;
+use crate::mail::imap_session::{execute_with_session, SessionKind};
+use crate::mail::mailbox_actions;
+use once_cell::sync::Lazy;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+/// Server mailbox that holds messages snoozed with server-side persistence.
+pub const SNOOZED_MAILBOX: &str = "Snoozed";
+const SNOOZED_KEYWORD: &str = "$Snoozed";
+/// Followed by the wake time in Unix seconds, so any device can tell when a snooze ends.
+const UNTIL_KEYWORD_PREFIX: &str = "$SnoozedUntil_";
+/// How often the Snoozed mailbox is checked for snoozes set or ended on other devices.
+const SERVER_SCAN_INTERVAL_SECS: i64 = 15 * 60;
+const MAX_SLEEP_SECS: i64 = 60;
+/// First retry after a failed wake or scan; doubles per failure up to the scan interval.
+const RETRY_BASE_SECS: i64 = 60;
+
+/// Hides locally snoozed rows from message lists and searches. `table` is the name or alias
+/// of the outer table, which needs `folder` and `uid` columns.
+pub fn not_snoozed_sql(table: &str) -> String {
+    format!("NOT EXISTS (SELECT 1 FROM snoozes s WHERE s.server_side = 0 AND s.folder = {0}.folder AND s.uid = {0}.uid)", table)
+}
+
+static SCHEDULER_RUNNING: AtomicBool = AtomicBool::new(false);
+static SCAN_REQUESTED: AtomicBool = AtomicBool::new(true);
+static SCHEDULER_WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnoozedMessage {
+    pub id: String,
+    /// Folder the message was snoozed from; server-side snoozes always return to the Inbox.
+    pub folder: String,
+    pub uid: u32,
+    pub message_id: Option<String>,
+    pub subject: String,
+    pub from: String,
+    pub date: i64,
+    pub until: i64,
+    pub server_side: bool,
+    /// UID in the Snoozed mailbox, for server-side snoozes.
+    pub mailbox_uid: Option<u32>,
+    pub created_at: i64,
+}
+
+pub fn init_snooze_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS snoozes (
+            id TEXT PRIMARY KEY,
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            message_id TEXT,
+            subject TEXT,
+            sender TEXT,
+            date INTEGER,
+            until INTEGER NOT NULL,
+            server_side INTEGER NOT NULL DEFAULT 0,
+            mailbox_uid INTEGER,
+            created_at INTEGER NOT NULL
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_snoozes_until ON snoozes(until)", ()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn open(app_handle: &AppHandle) -> Result<Connection, String> {
+    let db_path = get_db_path(app_handle)?;
+    Connection::open(db_path).map_err(|e| e.to_string())
+}
+
+fn row_to_snooze(row: &rusqlite::Row) -> rusqlite::Result<SnoozedMessage> {
+    Ok(SnoozedMessage {
+        id: row.get(0)?,
+        folder: row.get(1)?,
+        uid: row.get(2)?,
+        message_id: row.get(3)?,
+        subject: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+        from: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+        date: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
+        until: row.get(7)?,
+        server_side: row.get::<_, i32>(8)? != 0,
+        mailbox_uid: row.get(9)?,
+        created_at: row.get(10)?,
+    })
+}
+
+const SELECT_COLUMNS: &str = "SELECT id, folder, uid, message_id, subject, sender, date, until, server_side, mailbox_uid, created_at FROM snoozes";
+
+pub fn list_snoozed(app_handle: &AppHandle) -> Result<Vec<SnoozedMessage>, String> {
+    let conn = open(app_handle)?;
+    let mut stmt = conn.prepare(&format!("{} ORDER BY until ASC", SELECT_COLUMNS)).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], row_to_snooze).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+fn get_snooze(conn: &Connection, id: &str) -> Result<Option<SnoozedMessage>, String> {
+    conn.query_row(&format!("{} WHERE id = ?1", SELECT_COLUMNS), rusqlite::params![id], row_to_snooze)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+fn insert_snooze(conn: &Connection, snooze: &SnoozedMessage) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO snoozes (id, folder, uid, message_id, subject, sender, date, until, server_side, mailbox_uid, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        rusqlite::params![
+            snooze.id, snooze.folder, snooze.uid, snooze.message_id, snooze.subject, snooze.from,
+            snooze.date, snooze.until, snooze.server_side as i32, snooze.mailbox_uid, snooze.created_at
+        ],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn until_from_keywords(flags: &[imap::types::Flag]) -> Option<i64> {
+    flags.iter().find_map(|f| match f {
+        imap::types::Flag::Custom(name) => name.strip_prefix(UNTIL_KEYWORD_PREFIX).and_then(|ts| ts.parse().ok()),
+        _ => None,
+    })
+}
+
+fn source_mailbox(account: &Account, folder: &str) -> Result<String, String> {
+    MailFolder::from_str(folder).ok()
+        .and_then(|f| f.to_imap_mailbox(&account.provider))
+        .map(|mailbox| mailbox.into_owned())
+        .ok_or_else(|| format!("Cannot snooze messages in {}", folder))
+}
+
+/// Hides a message until `until`. With `server_side`, the message is moved to the Snoozed
+/// mailbox with keywords recording the wake time so other devices honour the snooze too;
+/// otherwise it only disappears from this device's lists.
+pub async fn snooze_message(app_handle: &AppHandle, account: &Account, folder: &str, uid: u32, until: i64, server_side: bool) -> Result<SnoozedMessage, String> {
+    let now = chrono::Utc::now().timestamp();
+    if until <= now {
+        return Err("Snooze time must be in the future".to_string());
+    }
+
+    let header = database::get_messages_by_uids(app_handle, folder, &[uid])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Message {} not found in {}", uid, folder))?;
+
+    let snooze = SnoozedMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        folder: folder.to_string(),
+        uid,
+        message_id: header.message_id.clone(),
+        subject: header.subject.clone(),
+        from: header.from.clone(),
+        date: header.date,
+        until,
+        server_side,
+        mailbox_uid: None,
+        created_at: now,
+    };
+
+    if server_side {
+        let mailbox = source_mailbox(account, folder)?;
+        let keywords = format!("{} {}{}", SNOOZED_KEYWORD, UNTIL_KEYWORD_PREFIX, until);
+        execute_with_session(account, SessionKind::Sync, move |session| {
+            // Fails harmlessly when the mailbox already exists
+            let _ = session.create(SNOOZED_MAILBOX);
+            session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+            session.uid_store(uid.to_string(), format!("+FLAGS.SILENT ({})", keywords)).map_err(|e| format!("IMAP Store Error: {}", e))?;
+            if let Err(e) = session.uid_mv(uid.to_string(), SNOOZED_MAILBOX) {
+                let _ = session.uid_store(uid.to_string(), format!("-FLAGS.SILENT ({})", keywords));
+                return Err(format!("IMAP Move Error: {}", e));
+            }
+            Ok(())
+        }).await?;
+        database::delete_message_local(app_handle, folder, uid)?;
+        SCAN_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    insert_snooze(&open(app_handle)?, &snooze)?;
+    SCHEDULER_WAKE.notify_one();
+
+    let _ = app_handle.emit("mail:updated", folder);
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    Ok(snooze)
+}
+
+/// Ends a snooze now instead of at its scheduled time.
+pub async fn unsnooze_message(app_handle: &AppHandle, account: &Account, id: &str) -> Result<(), String> {
+    let snooze = get_snooze(&open(app_handle)?, id)?.ok_or_else(|| "Snooze not found".to_string())?;
+    if snooze.server_side {
+        let uids: Vec<u32> = snooze.mailbox_uid.into_iter().collect();
+        if uids.is_empty() {
+            return Err("Snoozed message has not reached the server yet; try again shortly".to_string());
+        }
+        scan_server(app_handle, account, uids).await
+    } else {
+        wake_local(app_handle, account, vec![snooze]).await;
+        Ok(())
+    }
+}
+
+/// Marks a message unread and re-delivers it by moving it to the Snoozed mailbox and straight
+/// back, so it gets a new UID and returns to the top of its folder like new mail. Returns the
+/// new `(uid_validity, uid)` when the server reports it. While parked the message carries the
+/// snooze keywords with its past wake time, so a server scan returns it to the Inbox if it
+/// is stranded there.
+async fn redeliver(account: &Account, snooze: &SnoozedMessage) -> Result<Option<(u32, u32)>, String> {
+    let mailbox = source_mailbox(account, &snooze.folder)?;
+    let uid = snooze.uid;
+    let ids: HashMap<u32, String> = snooze.message_id.clone().map(|id| (uid, id)).into_iter().collect();
+    let keywords = format!("{} {}{}", SNOOZED_KEYWORD, UNTIL_KEYWORD_PREFIX, snooze.until);
+
+    execute_with_session(account, SessionKind::Sync, move |session| {
+        // Fails harmlessly when the mailbox already exists
+        let _ = session.create(SNOOZED_MAILBOX);
+        session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        session.uid_store(uid.to_string(), "-FLAGS.SILENT (\\Seen)").map_err(|e| format!("IMAP Store Error: {}", e))?;
+        session.uid_store(uid.to_string(), format!("+FLAGS.SILENT ({})", keywords)).map_err(|e| format!("IMAP Store Error: {}", e))?;
+
+        let parked = mailbox_actions::move_selected(session, &[uid], SNOOZED_MAILBOX, &ids)?;
+        if let Some((_, e)) = parked.failed.into_iter().next() {
+            let _ = session.uid_store(uid.to_string(), format!("-FLAGS.SILENT ({})", keywords));
+            return Err(e);
+        }
+        let Some((_, parked_uid)) = parked.dests.get(&uid).copied() else {
+            SCAN_REQUESTED.store(true, Ordering::SeqCst);
+            return Ok(None);
+        };
+
+        session.select(SNOOZED_MAILBOX).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        let parked_ids: HashMap<u32, String> = ids.values().map(|id| (parked_uid, id.clone())).collect();
+        let back = mailbox_actions::move_selected(session, &[parked_uid], &mailbox, &parked_ids)?;
+        if let Some((_, e)) = back.failed.into_iter().next() {
+            SCAN_REQUESTED.store(true, Ordering::SeqCst);
+            return Err(e);
+        }
+        let Some((validity, new_uid)) = back.dests.get(&parked_uid).copied() else { return Ok(None) };
+
+        session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        session.uid_store(new_uid.to_string(), format!("-FLAGS.SILENT ({})", keywords)).map_err(|e| format!("IMAP Store Error: {}", e))?;
+        Ok(Some((validity, new_uid)))
+    }).await
+}
+
+/// Brings locally snoozed messages back as unread at the top of their folder.
+async fn wake_local(app_handle: &AppHandle, account: &Account, snoozes: Vec<SnoozedMessage>) {
+    if snoozes.is_empty() {
+        return;
+    }
+    let Ok(conn) = open(app_handle) else { return };
+
+    let mut notif_batch = Vec::new();
+    for snooze in &snoozes {
+        let _ = conn.execute("DELETE FROM snoozes WHERE id = ?1", rusqlite::params![snooze.id]);
+        if let Err(e) = database::set_message_seen(app_handle, &snooze.folder, snooze.uid, false) {
+            log::warn!("[SNOOZE] Failed to mark {} unread locally: {}", snooze.uid, e);
+        }
+        let relocated = match redeliver(account, snooze).await {
+            Ok(Some((validity, new_uid))) => Some((snooze.folder.as_str(), new_uid, validity)),
+            // The old UID is gone; the next sync brings the message back under its new one
+            Ok(None) => None,
+            Err(e) => {
+                log::warn!("[SNOOZE] Failed to re-deliver {} on server: {}", snooze.uid, e);
+                notif_batch.push((snooze.from.clone(), snooze.subject.clone(), snooze.uid));
+                let _ = app_handle.emit("mail:updated", &snooze.folder);
+                continue;
+            }
+        };
+        if let Err(e) = database::move_messages_local(app_handle, &snooze.folder, &[(snooze.uid, relocated)]) {
+            log::warn!("[SNOOZE] Failed to update {} locally: {}", snooze.uid, e);
+        }
+        if relocated.is_none() {
+            if let Ok(folder) = MailFolder::from_str(&snooze.folder) {
+                crate::mail::sync_manager::enqueue_sync(app_handle.clone(), account.clone(), folder).await;
+            }
+        }
+        let uid = relocated.map(|(_, uid, _)| uid).unwrap_or(snooze.uid);
+        notif_batch.push((snooze.from.clone(), snooze.subject.clone(), uid));
+        let _ = app_handle.emit("mail:updated", &snooze.folder);
+    }
+
+    let _ = app_handle.emit("mail:snooze_woke", &snoozes);
+    crate::mail::notifications::show_new_emails(app_handle, &notif_batch);
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+}
+
+struct ServerSnooze {
+    mailbox_uid: u32,
+    until: i64,
+    header: Option<crate::mail::message_list::MessageHeader>,
+}
+
+/// Wakes due snoozes in the Snoozed mailbox (plus any in `force`) by moving them back to the
+/// Inbox unread, then mirrors the snoozes still pending into the local table.
+async fn scan_server(app_handle: &AppHandle, account: &Account, force: Vec<u32>) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp();
+
+    let (pending, woken) = execute_with_session(account, SessionKind::Sync, move |session| {
+        if session.select(SNOOZED_MAILBOX).is_err() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let uids = session.uid_search(format!("KEYWORD {}", SNOOZED_KEYWORD)).map_err(|e| format!("IMAP Search Error: {}", e))?;
+        if uids.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let uid_set = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+        let fetches = session.uid_fetch(&uid_set, "(UID FLAGS BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE TO MESSAGE-ID)])")
+            .map_err(|e| format!("IMAP Fetch Error: {}", e))?;
+
+        let mut pending = Vec::new();
+        let mut woken = Vec::new();
+        for fetch in fetches.iter() {
+            let Some(mailbox_uid) = fetch.uid else { continue };
+            // A snooze without a wake time is treated as due rather than hidden forever
+            let until = until_from_keywords(fetch.flags()).unwrap_or(0);
+            if until > now && !force.contains(&mailbox_uid) {
+                pending.push(ServerSnooze {
+                    mailbox_uid,
+                    until,
+                    header: crate::mail::sync::parse_header_to_message(fetch, 0, MailFolder::Inbox.to_string().as_str()),
+                });
+                continue;
+            }
+
+            let keywords = format!("{} {}{} \\Seen", SNOOZED_KEYWORD, UNTIL_KEYWORD_PREFIX, until);
+            session.uid_store(mailbox_uid.to_string(), format!("-FLAGS.SILENT ({})", keywords)).map_err(|e| format!("IMAP Store Error: {}", e))?;
+            session.uid_mv(mailbox_uid.to_string(), "INBOX").map_err(|e| format!("IMAP Move Error: {}", e))?;
+            woken.push(mailbox_uid);
+        }
+        Ok((pending, woken))
+    }).await?;
+
+    // The server is authoritative for server-side snoozes, including ones set elsewhere
+    let conn = open(app_handle)?;
+    let previous: Vec<SnoozedMessage> = list_snoozed(app_handle)?.into_iter().filter(|s| s.server_side).collect();
+    conn.execute("DELETE FROM snoozes WHERE server_side = 1", ()).map_err(|e| e.to_string())?;
+    for server in pending {
+        let header = server.header.as_ref();
+        let message_id = header.and_then(|h| h.message_id.clone());
+        let known = previous.iter().find(|p| {
+            p.mailbox_uid == Some(server.mailbox_uid) || (message_id.is_some() && p.message_id == message_id)
+        });
+        let snooze = SnoozedMessage {
+            id: known.map(|k| k.id.clone()).unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            folder: known.map(|k| k.folder.clone()).unwrap_or_else(|| MailFolder::Inbox.to_string()),
+            uid: known.map(|k| k.uid).unwrap_or(server.mailbox_uid),
+            message_id,
+            subject: header.map(|h| h.subject.clone()).unwrap_or_default(),
+            from: header.map(|h| h.from.clone()).unwrap_or_default(),
+            date: header.map(|h| h.date).unwrap_or(0),
+            until: server.until,
+            server_side: true,
+            mailbox_uid: Some(server.mailbox_uid),
+            created_at: known.map(|k| k.created_at).unwrap_or(now),
+        };
+        insert_snooze(&conn, &snooze)?;
+    }
+
+    if !woken.is_empty() {
+        log::info!("[SNOOZE] Returned {} snoozed messages to the Inbox", woken.len());
+        let woken_snoozes: Vec<&SnoozedMessage> = previous.iter()
+            .filter(|p| p.mailbox_uid.is_some_and(|uid| woken.contains(&uid)))
+            .collect();
+        let _ = app_handle.emit("mail:snooze_woke", &woken_snoozes);
+        // Woken messages arrive in the Inbox with new UIDs, so a sync picks them up as new mail
+        if let Ok(_guard) = crate::mail::sync::SYNC_LOCK.try_lock() {
+            if let Err(e) = crate::mail::sync::sync_inbox(app_handle, account.clone()).await {
+                log::warn!("[SNOOZE] Inbox sync after wake failed: {}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn due_local(app_handle: &AppHandle, now: i64) -> Result<Vec<SnoozedMessage>, String> {
+    let conn = open(app_handle)?;
+    let mut stmt = conn.prepare(&format!("{} WHERE server_side = 0 AND until <= ?1", SELECT_COLUMNS)).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params![now], row_to_snooze).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+fn next_due(app_handle: &AppHandle) -> Option<(i64, bool)> {
+    open(app_handle).ok()?.query_row(
+        "SELECT until, server_side FROM snoozes ORDER BY until ASC LIMIT 1",
+        [],
+        |row| Ok((row.get(0)?, row.get::<_, i32>(1)? != 0)),
+    ).optional().ok().flatten()
+}
+
+/// Starts the loop that wakes snoozed messages. It sleeps until the next snooze is due,
+/// at most a minute at a time, and is woken early whenever a snooze is added.
+pub fn spawn_scheduler(app: AppHandle) {
+    if SCHEDULER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        log::info!("[SNOOZE] Scheduler started");
+        let mut last_scan = 0;
+        // After a failure nothing is retried before `retry_at`, so a due snooze that keeps
+        // failing does not spin the loop once a second
+        let mut failures = 0;
+        let mut retry_at = 0;
+
+        loop {
+            let now = chrono::Utc::now().timestamp();
+            let next = next_due(&app);
+            let local_due = due_local(&app, now).unwrap_or_default();
+            let server_due = next.is_some_and(|(until, server_side)| server_side && until <= now);
+            let scan_due = server_due || SCAN_REQUESTED.load(Ordering::SeqCst) || now - last_scan >= SERVER_SCAN_INTERVAL_SECS;
+
+            if (!local_due.is_empty() || scan_due) && now >= retry_at {
+                let result = match crate::auth::bootstrap::ensure_active_account(&app).await {
+                    Ok(account) => {
+                        wake_local(&app, &account, local_due).await;
+                        if scan_due {
+                            SCAN_REQUESTED.store(false, Ordering::SeqCst);
+                            last_scan = now;
+                            scan_server(&app, &account, Vec::new()).await.map_err(|e| format!("Server scan failed: {}", e))
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    Err(e) => Err(format!("No usable account: {}", e)),
+                };
+                match result {
+                    Ok(()) => failures = 0,
+                    Err(e) => {
+                        failures += 1;
+                        retry_at = now + retry_delay(failures);
+                        log::warn!("[SNOOZE] {}; retrying in {}s", e, retry_at - now);
+                    }
+                }
+            }
+
+            let sleep_secs = next_due(&app)
+                .map(|(until, _)| until.max(retry_at) - chrono::Utc::now().timestamp())
+                .unwrap_or(MAX_SLEEP_SECS)
+                .clamp(1, MAX_SLEEP_SECS);
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(sleep_secs as u64)) => {}
+                _ = SCHEDULER_WAKE.notified() => {}
+                _ = crate::mail::shutdown::SNOOZE_TOKEN.cancelled() => {
+                    log::info!("[SNOOZE] Shutdown requested, exiting scheduler.");
+                    break;
+                }
+            }
+        }
+
+        SCHEDULER_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
+
+fn retry_delay(failures: u32) -> i64 {
+    RETRY_BASE_SECS.saturating_mul(1 << failures.saturating_sub(1).min(8)).min(SERVER_SCAN_INTERVAL_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use imap::types::Flag;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_until_from_keywords() {
+        let flags = vec![Flag::Seen, Flag::Custom(Cow::Borrowed("$Snoozed")), Flag::Custom(Cow::Borrowed("$SnoozedUntil_1767225600"))];
+        assert_eq!(until_from_keywords(&flags), Some(1_767_225_600));
+        assert_eq!(until_from_keywords(&[Flag::Custom(Cow::Borrowed("$SnoozedUntil_soon"))]), None);
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off() {
+        assert_eq!(retry_delay(1), 60);
+        assert_eq!(retry_delay(2), 120);
+        assert_eq!(retry_delay(4), 480);
+        assert_eq!(retry_delay(50), SERVER_SCAN_INTERVAL_SECS);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::auth::account::Account;
+use crate::contacts::sender_lists::{self, SenderList};
+use crate::mail::database;
+use crate::mail::folder::MailFolder;
+use crate::mail::mailbox_actions::{self, BulkResult, MessageRef, MoveTarget, SpecialUse};
+use crate::mail::message_list::MessageHeader;
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use tauri::AppHandle;
+
+const JUNK_KEYWORD: &str = "$Junk";
+const NOT_JUNK_KEYWORD: &str = "$NotJunk";
+
+/// Where sync puts new mail from blocked senders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockedDestination {
+    Junk,
+    Trash,
+}
+
+impl BlockedDestination {
+    fn target(&self) -> MoveTarget {
+        match self {
+            BlockedDestination::Junk => MoveTarget::Special(SpecialUse::Junk),
+            BlockedDestination::Trash => MoveTarget::Special(SpecialUse::Trash),
+        }
+    }
+}
+
+fn open(app_handle: &AppHandle) -> Result<Connection, String> {
+    let db_path = database::get_db_path(app_handle)?;
+    Connection::open(db_path).map_err(|e| e.to_string())
+}
+
+pub fn get_blocked_destination(app_handle: &AppHandle) -> BlockedDestination {
+    open(app_handle).map_or(BlockedDestination::Junk, |conn| blocked_destination(&conn))
+}
+
+fn blocked_destination(conn: &Connection) -> BlockedDestination {
+    match conn.query_row("SELECT value FROM app_metadata WHERE key = 'blocked_sender_destination'", [], |row| row.get::<_, String>(0)).as_deref() {
+        Ok("trash") => BlockedDestination::Trash,
+        _ => BlockedDestination::Junk,
+    }
+}
+
+pub fn set_blocked_destination(app_handle: &AppHandle, destination: BlockedDestination) -> Result<(), String> {
+    save_blocked_destination(&open(app_handle)?, destination)
+}
+
+fn save_blocked_destination(conn: &Connection, destination: BlockedDestination) -> Result<(), String> {
+    let value = match destination {
+        BlockedDestination::Junk => "junk",
+        BlockedDestination::Trash => "trash",
+    };
+    conn.execute(
+        "INSERT INTO app_metadata (key, value) VALUES ('blocked_sender_destination', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![value],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Sender addresses of `refs`, keyed by message.
+fn senders_of(app_handle: &AppHandle, refs: &[MessageRef]) -> HashMap<(String, u32), String> {
+    let mut senders = HashMap::new();
+    for (folder, uids) in mailbox_actions::group_by_folder(refs) {
+        let headers = database::get_messages_by_uids(app_handle, &folder, &uids).unwrap_or_default();
+        for header in headers {
+            if let Some(address) = database::parse_sender_address(&header.from) {
+                senders.insert((folder.clone(), header.uid), address);
+            }
+        }
+    }
+    senders
+}
+
+/// Sets `$Junk`/`$NotJunk` so the server's filter learns from the verdict. Best effort: a
+/// mailbox that does not accept keywords still gets the move.
+async fn mark_keywords(account: &Account, refs: &[MessageRef], junk: bool) {
+    let (add, remove) = if junk { (JUNK_KEYWORD, NOT_JUNK_KEYWORD) } else { (NOT_JUNK_KEYWORD, JUNK_KEYWORD) };
+    for (folder, uids) in mailbox_actions::group_by_folder(refs) {
+        if let Err(e) = crate::mail::tags::store_keywords(account, &folder, &uids, &[add.to_string()], &[remove.to_string()]).await {
+            log::warn!("Failed to set {} on {} messages in {}: {}", add, uids.len(), folder, e);
+        }
+    }
+}
+
+/// The senders of the messages that `result` moved.
+fn moved_senders(result: &BulkResult, senders: &HashMap<(String, u32), String>) -> HashSet<String> {
+    result.succeeded.iter()
+        .filter_map(|m| senders.get(&(m.folder.to_lowercase(), m.uid)).cloned())
+        .collect()
+}
+
+/// Marks messages as junk and moves them to the `\Junk` mailbox, optionally blocking their
+/// senders so later mail from them skips the Inbox.
+pub async fn report_spam(app_handle: &AppHandle, account: &Account, refs: &[MessageRef], block_sender: bool) -> BulkResult {
+    let senders = if block_sender { senders_of(app_handle, refs) } else { HashMap::new() };
+    mark_keywords(account, refs, true).await;
+    let result = mailbox_actions::move_messages(app_handle, account, refs, MoveTarget::Special(SpecialUse::Junk)).await;
+
+    for address in moved_senders(&result, &senders) {
+        if let Err(e) = sender_lists::add_entry(app_handle, SenderList::Block, &address) {
+            log::warn!("Failed to block {}: {}", address, e);
+        }
+    }
+    result
+}
+
+/// Marks messages as not junk, moves them back to the Inbox and takes their addresses off
+/// the block list. A blocked domain stays blocked.
+pub async fn not_spam(app_handle: &AppHandle, account: &Account, refs: &[MessageRef]) -> BulkResult {
+    let senders = senders_of(app_handle, refs);
+    mark_keywords(account, refs, false).await;
+    let result = mailbox_actions::move_messages(app_handle, account, refs, MoveTarget::Folder(MailFolder::Inbox)).await;
+
+    for address in moved_senders(&result, &senders) {
+        if let Err(e) = sender_lists::remove_entry(app_handle, SenderList::Block, &address) {
+            log::warn!("Failed to unblock {}: {}", address, e);
+        }
+    }
+    result
+}
+
+/// Applies the sender lists to newly synced mail: Inbox messages from blocked senders go
+/// to Junk or Trash, and Junk messages from allowed senders go back to the Inbox. Returns
+/// the UIDs to leave out of rules and notifications, which includes blocked messages whose
+/// move failed.
+pub(crate) async fn apply_sender_lists(app_handle: &AppHandle, account: &Account, folder: MailFolder, messages: &[MessageHeader]) -> HashSet<u32> {
+    let (wanted, target) = match folder {
+        MailFolder::Inbox => (SenderList::Block, get_blocked_destination(app_handle).target()),
+        MailFolder::Junk => (SenderList::Allow, MoveTarget::Folder(MailFolder::Inbox)),
+        _ => return HashSet::new(),
+    };
+    let lists = match sender_lists::load_sender_lists(app_handle) {
+        Ok(lists) if !lists.is_empty() => lists,
+        Ok(_) => return HashSet::new(),
+        Err(e) => {
+            log::warn!("Failed to load sender lists: {}", e);
+            return HashSet::new();
+        }
+    };
+    let refs: Vec<MessageRef> = messages.iter()
+        .filter(|m| database::parse_sender_address(&m.from).is_some_and(|a| lists.verdict(&a) == Some(wanted)))
+        .map(|m| MessageRef { folder: folder.to_string(), uid: m.uid })
+        .collect();
+    if refs.is_empty() {
+        return HashSet::new();
+    }
+
+    let result = mailbox_actions::move_messages(app_handle, account, &refs, target).await;
+    for failure in &result.failed {
+        log::warn!("Failed to move message {} by sender list: {}", failure.uid, failure.error);
+    }
+    match wanted {
+        SenderList::Block => refs.into_iter().map(|m| m.uid).collect(),
+        SenderList::Allow => result.succeeded.into_iter().map(|m| m.uid).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocked_destination_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE app_metadata (key TEXT PRIMARY KEY, value TEXT)", ()).unwrap();
+        assert_eq!(blocked_destination(&conn), BlockedDestination::Junk);
+
+        save_blocked_destination(&conn, BlockedDestination::Trash).unwrap();
+        assert_eq!(blocked_destination(&conn), BlockedDestination::Trash);
+        assert!(matches!(blocked_destination(&conn).target(), MoveTarget::Special(SpecialUse::Trash)));
+
+        save_blocked_destination(&conn, BlockedDestination::Junk).unwrap();
+        assert_eq!(blocked_destination(&conn), BlockedDestination::Junk);
+        assert!(matches!(blocked_destination(&conn).target(), MoveTarget::Special(SpecialUse::Junk)));
+    }
+
+    #[test]
+    fn test_moved_senders_skips_failed_moves() {
+        let senders = HashMap::from([
+            (("inbox".to_string(), 1), "spam@example.com".to_string()),
+            (("inbox".to_string(), 2), "friend@example.com".to_string()),
+            (("sent".to_string(), 3), "spam@example.com".to_string()),
+        ]);
+        let result = BulkResult {
+            succeeded: vec![
+                MessageRef { folder: "INBOX".to_string(), uid: 1 },
+                MessageRef { folder: "sent".to_string(), uid: 3 },
+                MessageRef { folder: "inbox".to_string(), uid: 9 },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(moved_senders(&result, &senders), HashSet::from(["spam@example.com".to_string()]));
+    }
+}
@@ -15,7 +15,7 @@ pub async fn sync_inbox(app_handle: &AppHandle, account: Account) -> Result<u32,
 }
 
 pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailFolder) -> Result<u32, String> {
-    let imap_mailbox = match folder.to_imap_mailbox(&account.provider) {
+    let mut imap_mailbox = match folder.to_imap_mailbox(&account.provider) {
         Some(mb) => mb.to_string(),
         None => {
             log::info!("Folder {} is virtual. Skipping IMAP sync.", folder);
@@ -27,6 +27,9 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
     let app_handle_clone = app_handle.clone();
     let folder_name_clone = folder_name.clone();
     let folder_clone = folder.clone();
+    // Gmail tags are labels; elsewhere they arrive as keywords in FLAGS
+    let collect_keywords = !matches!(account.provider, crate::auth::account::MailProvider::Google);
+    let provider = account.provider.clone();
 
     let new_messages_count_future = crate::mail::imap_session::execute_with_session(
         &account,
@@ -46,6 +49,11 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
                     last_error: None,
                 });
 
+            if folder_clone == MailFolder::Junk {
+                // Custom servers may name it "Spam"; the lookup also updates `to_imap_mailbox`
+                imap_mailbox = crate::mail::mailbox_actions::find_special_use(session, crate::mail::mailbox_actions::SpecialUse::Junk, &provider);
+            }
+
             let stored_validity = database::get_mailbox_validity(&app_handle_clone, &imap_mailbox).unwrap_or(None);
 
             let mailbox = if folder_clone == MailFolder::Inbox {
@@ -65,10 +73,50 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
                 sync_state.last_uid = 0;
             }
 
+            // Tags added or removed elsewhere only show in FLAGS, so refresh the cached messages' keywords
+            let server_keywords: Vec<String> = mailbox.flags.iter()
+                .filter_map(|f| match f {
+                    imap::types::Flag::Custom(k) => Some(k.to_string()),
+                    _ => None,
+                })
+                .collect();
+            let refresh = if collect_keywords && sync_state.last_uid > 0 && crate::mail::tags::has_tags(&app_handle_clone) {
+                // CONDSTORE lets the refresh skip messages whose flags have not changed
+                let condstore = session.capabilities().is_ok_and(|caps| caps.has_str("CONDSTORE"));
+                let highest_modseq = if condstore {
+                    session.run_command_and_read_response(format!("STATUS {} (HIGHESTMODSEQ)", crate::mail::mailbox_actions::imap_quote(&imap_mailbox)))
+                        .ok()
+                        .and_then(|response| crate::mail::tags::parse_highest_modseq(&response))
+                } else {
+                    None
+                };
+                crate::mail::tags::keyword_refresh(&app_handle_clone, &folder_name_clone, sync_state.last_uid, highest_modseq)
+            } else {
+                None
+            };
+            if let Some((set, items)) = refresh {
+                // A failed refresh leaves tags as they were rather than holding up new mail
+                let refreshed: Vec<(u32, Vec<String>)> = match session.uid_fetch(set, items) {
+                    Ok(fetches) => fetches.iter().filter_map(|msg| Some((msg.uid?, custom_flags(msg)))).collect(),
+                    Err(e) => {
+                        log::warn!("Failed to fetch keywords for {}: {}", folder_name_clone, e);
+                        Vec::new()
+                    }
+                };
+                match crate::mail::tags::save_synced_keywords(&app_handle_clone, &folder_name_clone, &server_keywords, &refreshed) {
+                    Ok(true) => {
+                        use tauri::Emitter;
+                        let _ = app_handle_clone.emit("mail:updated", &folder_name_clone);
+                    }
+                    Ok(false) => {}
+                    Err(e) => log::warn!("Failed to refresh tags for {}: {}", folder_name_clone, e),
+                }
+            }
+
             // 2. Fast Exit Check
             if uid_next <= sync_state.last_uid + 1 {
                 log::info!("{} already up to date.", folder_name_clone);
-                return Ok(0);
+                return Ok((0, Vec::new(), false));
             }
 
             // 3. Strategy based on is_bootstrap
@@ -80,7 +128,7 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
                 let end = uid_next.saturating_sub(1);
                 let start_uid = sync_state.last_uid + 1;
                 if start_uid > end {
-                    return Ok(0);
+                    return Ok((0, Vec::new(), false));
                 }
                 format!("{}:{}", start_uid, end)
             };
@@ -96,14 +144,26 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
 
             let fetch_results = session.uid_fetch(
                 &range,
-                "(UID FLAGS BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE TO CC REPLY-TO)])"
+                "(UID FLAGS RFC822.SIZE BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE TO CC REPLY-TO LIST-UNSUBSCRIBE LIST-UNSUBSCRIBE-POST)])"
             ).map_err(|e| format!("IMAP Fetch Error: {}", e))?;
 
             let mut messages = Vec::new();
             let mut raw_headers = Vec::new();
+            let mut sizes = Vec::new();
+            let mut keywords = Vec::new();
             let mut max_fetched_uid = sync_state.last_uid;
             
             for msg in fetch_results.iter() {
+                // Moved away on a server without UIDPLUS and waiting for an expunge
+                if msg.flags().contains(&imap::types::Flag::Deleted) {
+                    continue;
+                }
+                if let (Some(uid), Some(size)) = (msg.uid, msg.size) {
+                    sizes.push((uid, size));
+                }
+                if let Some(uid) = msg.uid.filter(|_| collect_keywords) {
+                    keywords.push((uid, custom_flags(msg)));
+                }
                 if let Some(header) = parse_header_to_message(msg, server_validity, &folder_name_clone) {
                     if header.uid > max_fetched_uid {
                         max_fetched_uid = header.uid;
@@ -129,6 +189,10 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
 
             log::info!("Grabbed {} new messages for {}!", num_new, folder_name_clone);
             database::insert_or_update_messages(&app_handle_clone, &messages).map_err(|e| e.to_string())?;
+            let _ = database::update_message_sizes(&app_handle_clone, &folder_name_clone, &sizes);
+            if let Err(e) = crate::mail::tags::save_synced_keywords(&app_handle_clone, &folder_name_clone, &server_keywords, &keywords) {
+                log::warn!("Failed to save tags for {}: {}", folder_name_clone, e);
+            }
             
             if !raw_headers.is_empty() {
                 if let Err(e) = crate::contacts::contact_indexer::extract_and_store_contacts(&app_handle_clone, &raw_headers) {
@@ -142,13 +206,12 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
                     log::error!("Failed to emit mail:updated event: {}", e);
                 }
 
-                if !is_bootstrap && folder_clone == MailFolder::Inbox {
-                    let mut notif_batch = Vec::new();
-                    for msg in &messages {
-                        notif_batch.push((msg.from.clone(), msg.subject.clone(), msg.uid));
+                if !is_bootstrap {
+                    if let Err(e) = crate::mail::saved_searches::check_new_matches(&app_handle_clone) {
+                        log::warn!("Failed to check saved searches for new matches: {}", e);
                     }
-                    notifications::show_new_emails(&app_handle_clone, &notif_batch);
                 }
+
             }
 
             if folder_clone == MailFolder::Inbox {
@@ -163,12 +226,12 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
             sync_state.last_error = None;
             let _ = database::update_folder_sync_state(&app_handle_clone, &sync_state);
 
-            Ok(num_new)
+            Ok((num_new, messages, is_bootstrap))
         }
     );
 
-    let new_messages_count = match tokio::time::timeout(std::time::Duration::from_secs(120), new_messages_count_future).await {
-        Ok(Ok(count)) => count,
+    let (new_messages_count, new_messages, is_bootstrap) = match tokio::time::timeout(std::time::Duration::from_secs(120), new_messages_count_future).await {
+        Ok(Ok(result)) => result,
         Ok(Err(e)) => {
             // Error handling
             let mut sync_state = database::get_folder_sync_state(&app_handle, &folder_name).unwrap_or(None).unwrap_or_default();
@@ -182,6 +245,46 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
         Err(_) => return Err("Sync Connection Timeout".to_string()),
     };
 
+    // A completed sync means the server is reachable again
+    crate::mail::pending_ops::wake();
+
+    if !new_messages.is_empty() {
+        let uids: Vec<u32> = new_messages.iter().map(|m| m.uid).collect();
+        if let Err(e) = crate::mail::gmail_labels::sync_labels(app_handle, &account, &folder_name, &uids).await {
+            log::warn!("Failed to fetch Gmail labels for {}: {}", folder_name, e);
+        }
+    }
+
+    // Sender lists apply before rules or notifications see the mail
+    let new_messages: Vec<MessageHeader> = if !is_bootstrap && !new_messages.is_empty() {
+        let moved = crate::mail::spam::apply_sender_lists(app_handle, &account, folder.clone(), &new_messages).await;
+        new_messages.into_iter().filter(|m| !moved.contains(&m.uid)).collect()
+    } else {
+        new_messages
+    };
+
+    // Rules run outside the sync session since their actions open sessions of their own
+    if !is_bootstrap && !new_messages.is_empty() {
+        let outcomes = crate::mail::rules::apply_rules(app_handle, &account, &folder_name, &new_messages, crate::mail::rules::RuleTrigger::Sync, None).await;
+
+        let notif_batch: Vec<(String, String, u32)> = new_messages.iter()
+            .filter(|msg| {
+                let outcome = outcomes.get(&msg.uid);
+                if outcome.is_some_and(|o| o.removed) {
+                    return false;
+                }
+                match outcome.and_then(|o| o.notify) {
+                    Some(notify) => notify,
+                    None => folder == MailFolder::Inbox,
+                }
+            })
+            .map(|msg| (msg.from.clone(), msg.subject.clone(), msg.uid))
+            .collect();
+        if !notif_batch.is_empty() {
+            notifications::show_new_emails(app_handle, &notif_batch);
+        }
+    }
+
     // Enqueue top 10 most recent UIDs for prefetching immediately after sync
     let uids = database::get_unfetched_recent_uids(app_handle, &folder_name, 10).unwrap_or_default();
     
@@ -204,7 +307,17 @@ pub async fn sync_folder(app_handle: &AppHandle, account: Account, folder: MailF
     Ok(new_messages_count)
 }
 
-fn parse_header_to_message(msg: &imap::types::Fetch, server_validity: u32, folder_name: &str) -> Option<MessageHeader> {
+/// Keywords among a fetched message's flags, e.g. tags stored as `$label1`.
+fn custom_flags(msg: &imap::types::Fetch) -> Vec<String> {
+    msg.flags().iter()
+        .filter_map(|f| match f {
+            imap::types::Flag::Custom(k) => Some(k.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn parse_header_to_message(msg: &imap::types::Fetch, server_validity: u32, folder_name: &str) -> Option<MessageHeader> {
     let actual_uid = msg.uid?;
     let body = msg.header()?;
 
@@ -218,6 +331,8 @@ fn parse_header_to_message(msg: &imap::types::Fetch, server_validity: u32, folde
     let mut to_recipient = String::new();
     let mut date = String::new();
     let mut message_id = None;
+    let mut list_unsubscribe = None;
+    let mut list_unsubscribe_post = None;
 
     for header in parsed.get_headers() {
         let key = header.get_key().to_lowercase();
@@ -229,6 +344,8 @@ fn parse_header_to_message(msg: &imap::types::Fetch, server_validity: u32, folde
             "to" => to_recipient = val,
             "date" => date = val,
             "message-id" => message_id = Some(val),
+            "list-unsubscribe" => list_unsubscribe = Some(val),
+            "list-unsubscribe-post" => list_unsubscribe_post = Some(val),
             _ => {}
         }
     }
@@ -271,5 +388,6 @@ fn parse_header_to_message(msg: &imap::types::Fetch, server_validity: u32, folde
         snippet,
         to: to_opt,
         message_id,
+        unsubscribe: list_unsubscribe.and_then(|value| crate::mail::unsubscribe::parse_list_unsubscribe(&value, list_unsubscribe_post.as_deref())),
     })
 }
@@ -44,6 +44,8 @@ pub async fn enqueue_sync(app_handle: AppHandle, account: Account, folder: MailF
             MailFolder::Inbox => 30, // Aggressive refresh (30 seconds)
             MailFolder::Sent => 300, // Lazy opportunistic refresh (5 minutes)
             MailFolder::Starred => 0, // Not synced from IMAP
+            MailFolder::AllMail => return, // Populated by everywhere searches, never bulk-synced
+            MailFolder::Junk => 1800, // Rarely looked at (30 minutes)
         };
 
         if elapsed < min_interval {
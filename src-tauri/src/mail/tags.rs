@@ -0,0 +1,607 @@
+//! User tags with colors. On Gmail a tag is a label; elsewhere it is stored on the server as
+//! an IMAP keyword when the mailbox's PERMANENTFLAGS allow it, and kept locally otherwise.
+//! Assignments share the `message_labels` table with Gmail labels.
+
+use crate::auth::account::{Account, MailProvider};
+use crate::mail::database::get_db_path;
+use crate::mail::gmail_labels;
+use crate::mail::imap_session::{execute_with_session, SessionKind};
+use crate::mail::mailbox_actions::{self, BulkResult, FolderOutcome, MessageRef};
+use crate::mail::message_list::MessageHeader;
+use crate::mail::saved_searches::{push_page_anchor, query_headers, PageAnchor, SPANNING_ORDER};
+use rusqlite::types::Value;
+use once_cell::sync::Lazy;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+/// Recent messages whose keywords every sync re-reads. Older messages are re-read while
+/// they carry a tag, and all of them once per `FULL_KEYWORD_REFRESH_SECS`.
+const RECENT_KEYWORD_WINDOW: u32 = 500;
+const FULL_KEYWORD_REFRESH_SECS: i64 = 6 * 60 * 60;
+
+/// HIGHESTMODSEQ at each folder's last keyword refresh, on CONDSTORE servers.
+static KEYWORD_MODSEQ: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// When each folder's keywords were last re-read across the whole mailbox.
+static FULL_KEYWORD_REFRESH: Lazy<Mutex<HashMap<String, i64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub name: String,
+    /// IMAP keyword stored on the server for non-Gmail accounts, e.g. `$label1`.
+    pub keyword: String,
+    pub color: Option<String>,
+    pub total: u32,
+    pub unread: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagUpdate {
+    pub result: BulkResult,
+    /// Folders whose server does not accept the keywords, where tags were only saved locally.
+    pub local_only: Vec<String>,
+}
+
+pub fn init_tags_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tags (
+            name TEXT PRIMARY KEY COLLATE NOCASE,
+            keyword TEXT NOT NULL,
+            color TEXT,
+            created_at INTEGER NOT NULL
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn validate_color(color: &str) -> Result<(), String> {
+    let hex = color.strip_prefix('#').unwrap_or("");
+    if matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(format!("Invalid tag color \"{}\", expected #rgb or #rrggbb", color))
+    }
+}
+
+/// Keywords must be IMAP atoms and cannot be system flags.
+fn validate_keyword(keyword: &str) -> Result<(), String> {
+    if keyword.is_empty() || keyword.starts_with('\\') || crate::mail::rules::keyword_for_label(keyword) != keyword {
+        return Err(format!("\"{}\" is not a valid IMAP keyword", keyword));
+    }
+    Ok(())
+}
+
+fn find_tag(conn: &Connection, name: &str) -> Result<Option<(String, String)>, String> {
+    conn.query_row("SELECT name, keyword FROM tags WHERE name = ?1", [name.trim()], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// Adds a tag, refusing names or keywords already taken. Derived keywords collide easily
+/// ("Work!" and "Work?" both become `Work_`), and synced keywords map back to a single tag.
+fn insert_tag(conn: &Connection, name: &str, keyword: &str, color: Option<&str>) -> Result<(), String> {
+    if find_tag(conn, name)?.is_some() {
+        return Err(format!("A tag named \"{}\" already exists", name));
+    }
+    let owner: Option<String> = conn.query_row("SELECT name FROM tags WHERE keyword = ?1 COLLATE NOCASE", [keyword], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(owner) = owner {
+        return Err(format!("Tag \"{}\" already uses the keyword {}", owner, keyword));
+    }
+    conn.execute(
+        "INSERT INTO tags (name, keyword, color, created_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![name, keyword, color, chrono::Utc::now().timestamp()],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn create_tag(app_handle: &AppHandle, name: &str, color: Option<&str>, keyword: Option<&str>) -> Result<Tag, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Tag name cannot be empty".to_string());
+    }
+    if let Some(color) = color {
+        validate_color(color)?;
+    }
+    let keyword = match keyword.map(str::trim).filter(|k| !k.is_empty()) {
+        Some(k) => {
+            validate_keyword(k)?;
+            k.to_string()
+        }
+        None => crate::mail::rules::keyword_for_label(name),
+    };
+
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    insert_tag(&conn, name, &keyword, color)?;
+
+    Ok(Tag { name: name.to_string(), keyword, color: color.map(String::from), total: 0, unread: 0 })
+}
+
+/// Returns `(name, keyword)` for each named tag. With `create`, missing tags are added with a
+/// derived keyword; otherwise they are skipped, since removing an unknown tag is a no-op.
+fn ensure_tags(conn: &Connection, names: &[String], create: bool) -> Result<Vec<(String, String)>, String> {
+    let mut tags = Vec::new();
+    for name in names.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        match find_tag(conn, name)? {
+            Some(tag) => tags.push(tag),
+            None if create => {
+                let keyword = crate::mail::rules::keyword_for_label(name);
+                insert_tag(conn, name, &keyword, None)?;
+                tags.push((name.to_string(), keyword));
+            }
+            None => {}
+        }
+    }
+    Ok(tags)
+}
+
+/// Every tag with message counts, counting each Gmail message once.
+pub fn list_tags(app_handle: &AppHandle) -> Result<Vec<Tag>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    tags_with_counts(&conn)
+}
+
+fn tags_with_counts(conn: &Connection) -> Result<Vec<Tag>, String> {
+    let tagged = format!(
+        "FROM message_labels l JOIN messages m ON m.folder = l.folder AND m.uid = l.uid WHERE l.label = t.name COLLATE NOCASE AND {}",
+        gmail_labels::unique_copy_sql("m")
+    );
+    let sql = format!(
+        "SELECT t.name, t.keyword, t.color, (SELECT COUNT(*) {tagged}), (SELECT COUNT(*) {tagged} AND m.seen = 0)
+         FROM tags t ORDER BY t.name COLLATE NOCASE",
+        tagged = tagged
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |row| Ok(Tag {
+        name: row.get(0)?,
+        keyword: row.get(1)?,
+        color: row.get(2)?,
+        total: row.get(3)?,
+        unread: row.get(4)?,
+    })).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Renames a tag and recolors it. The server keyword is kept, so messages tagged elsewhere
+/// still map to it; Gmail labels are renamed in Gmail itself.
+pub fn update_tag(app_handle: &AppHandle, account: &Account, name: &str, new_name: Option<&str>, color: Option<&str>) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let (current, _) = find_tag(&conn, name)?.ok_or_else(|| format!("Tag not found: {}", name))?;
+
+    if let Some(color) = color.filter(|c| !c.is_empty()) {
+        validate_color(color)?;
+    }
+    let new_name = new_name.map(str::trim).filter(|n| *n != current);
+    if let Some(new_name) = new_name {
+        if new_name.is_empty() {
+            return Err("Tag name cannot be empty".to_string());
+        }
+        if matches!(account.provider, MailProvider::Google) {
+            return Err("Gmail labels can only be renamed in Gmail".to_string());
+        }
+        if !new_name.eq_ignore_ascii_case(&current) && find_tag(&conn, new_name)?.is_some() {
+            return Err(format!("A tag named \"{}\" already exists", new_name));
+        }
+    }
+
+    apply_tag_update(&mut conn, &current, new_name, color)
+}
+
+fn apply_tag_update(conn: &mut Connection, current: &str, new_name: Option<&str>, color: Option<&str>) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    if let Some(color) = color {
+        // An empty color clears it
+        let color = (!color.is_empty()).then_some(color);
+        tx.execute("UPDATE tags SET color = ?1 WHERE name = ?2", rusqlite::params![color, current]).map_err(|e| e.to_string())?;
+    }
+    if let Some(new_name) = new_name {
+        tx.execute("UPDATE tags SET name = ?1 WHERE name = ?2", rusqlite::params![new_name, current]).map_err(|e| e.to_string())?;
+        tx.execute("UPDATE OR REPLACE message_labels SET label = ?1 WHERE label = ?2 COLLATE NOCASE", rusqlite::params![new_name, current]).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Removes a tag from every message carrying it, on the server too, then deletes it.
+pub async fn delete_tag(app_handle: &AppHandle, account: &Account, name: &str) -> Result<TagUpdate, String> {
+    let db_path = get_db_path(app_handle)?;
+    let refs: Vec<MessageRef> = {
+        let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare("SELECT folder, uid FROM message_labels WHERE label = ?1 COLLATE NOCASE").map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([name.trim()], |row| Ok(MessageRef { folder: row.get(0)?, uid: row.get(1)? })).map_err(|e| e.to_string())?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+
+    let update = tag_messages(app_handle, account, &refs, &[], &[name.to_string()]).await?;
+
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM message_labels WHERE label = ?1 COLLATE NOCASE", [name.trim()]).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM tags WHERE name = ?1", [name.trim()]).map_err(|e| e.to_string())?;
+    Ok(update)
+}
+
+fn keywords_allowed(mailbox: &imap::types::Mailbox, keywords: &[String]) -> bool {
+    keywords.iter().all(|keyword| mailbox.permanent_flags.iter().any(|flag| match flag {
+        imap::types::Flag::MayCreate => true,
+        imap::types::Flag::Custom(k) => k.eq_ignore_ascii_case(keyword),
+        _ => false,
+    }))
+}
+
+/// Stores keywords on one folder's messages. Returns `None` instead of an outcome when the
+/// folder is not on the server or its mailbox does not accept them.
+pub(crate) async fn store_keywords(account: &Account, folder: &str, uids: &[u32], add: &[String], remove: &[String]) -> Result<Option<FolderOutcome>, String> {
+    let Ok(mailbox) = mailbox_actions::source_mailbox(account, folder) else { return Ok(None) };
+    let (uids, add, remove) = (uids.to_vec(), add.to_vec(), remove.to_vec());
+    execute_with_session(account, SessionKind::Sync, move |session| {
+        let selected = session.select(&mailbox).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        let all: Vec<String> = add.iter().chain(remove.iter()).cloned().collect();
+        if !keywords_allowed(&selected, &all) {
+            return Ok(None);
+        }
+
+        let mut outcome = FolderOutcome { done: uids.clone(), ..Default::default() };
+        for (keywords, sign) in [(&add, '+'), (&remove, '-')] {
+            if keywords.is_empty() || outcome.done.is_empty() {
+                continue;
+            }
+            let step = mailbox_actions::store_selected(session, &outcome.done, &format!("{}FLAGS.SILENT ({})", sign, keywords.join(" ")))?;
+            outcome.failed.extend(step.failed);
+            outcome.done = step.done;
+        }
+        Ok(Some(outcome))
+    }).await
+}
+
+/// Adds and removes tags on messages from any number of folders, creating unknown tags that
+/// are added.
+pub async fn tag_messages(app_handle: &AppHandle, account: &Account, refs: &[MessageRef], add: &[String], remove: &[String]) -> Result<TagUpdate, String> {
+    let (add, remove) = {
+        let db_path = get_db_path(app_handle)?;
+        let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+        (ensure_tags(&conn, add, true)?, ensure_tags(&conn, remove, false)?)
+    };
+    let names = |tags: &[(String, String)]| tags.iter().map(|(n, _)| n.clone()).collect::<Vec<String>>();
+    let keywords = |tags: &[(String, String)]| tags.iter().map(|(_, k)| k.clone()).collect::<Vec<String>>();
+
+    if matches!(account.provider, MailProvider::Google) {
+        let result = gmail_labels::modify_labels(app_handle, account, refs, &names(&add), &names(&remove)).await?;
+        return Ok(TagUpdate { result, local_only: Vec::new() });
+    }
+
+    let mut update = TagUpdate::default();
+    for (folder, uids) in mailbox_actions::group_by_folder(refs) {
+        let outcome = match store_keywords(account, &folder, &uids, &keywords(&add), &keywords(&remove)).await {
+            Ok(Some(outcome)) => outcome,
+            Ok(None) => {
+                update.local_only.push(folder.clone());
+                FolderOutcome { done: uids.clone(), ..Default::default() }
+            }
+            Err(e) => FolderOutcome::all_failed(&uids, &e),
+        };
+        if let Err(e) = gmail_labels::save_changes(app_handle, &folder, &outcome.done, &names(&add), &names(&remove)) {
+            update.result.record(&folder, FolderOutcome::all_failed(&outcome.done, &e));
+            continue;
+        }
+        update.result.record(&folder, outcome);
+    }
+    Ok(update)
+}
+
+/// Makes the tags of synced messages match their keywords; unknown keywords such as
+/// `$Forwarded` are ignored. A tag is only taken off a message when its keyword is among the
+/// mailbox's `server_keywords` (its FLAGS), so tags kept locally on servers without keyword
+/// support survive. Returns whether any tag changed.
+pub(crate) fn save_synced_keywords(app_handle: &AppHandle, folder: &str, server_keywords: &[String], keywords: &[(u32, Vec<String>)]) -> Result<bool, String> {
+    if keywords.is_empty() {
+        return Ok(false);
+    }
+    let db_path = get_db_path(app_handle)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    apply_synced_keywords(&mut conn, folder, server_keywords, keywords)
+}
+
+fn apply_synced_keywords(conn: &mut Connection, folder: &str, server_keywords: &[String], keywords: &[(u32, Vec<String>)]) -> Result<bool, String> {
+    let tags: Vec<(String, String)> = {
+        let mut stmt = conn.prepare("SELECT name, keyword FROM tags").map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?.to_lowercase()))).map_err(|e| e.to_string())?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+    if tags.is_empty() {
+        return Ok(false);
+    }
+    let by_keyword: HashMap<&str, &str> = tags.iter().map(|(name, keyword)| (keyword.as_str(), name.as_str())).collect();
+    let on_server: HashSet<String> = server_keywords.iter().map(|k| k.to_lowercase()).collect();
+    let synced: HashSet<String> = tags.iter().filter(|(_, k)| on_server.contains(k)).map(|(name, _)| name.to_lowercase()).collect();
+
+    let mut current: HashMap<u32, Vec<String>> = HashMap::new();
+    {
+        let mut stmt = conn.prepare("SELECT l.uid, l.label FROM message_labels l JOIN tags t ON t.name = l.label COLLATE NOCASE WHERE l.folder = ?1")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([folder], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))).map_err(|e| e.to_string())?;
+        for (uid, label) in rows.filter_map(|r| r.ok()) {
+            current.entry(uid).or_default().push(label);
+        }
+    }
+
+    let mut changed = false;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut insert = tx.prepare("INSERT OR IGNORE INTO message_labels (folder, uid, label) VALUES (?1, ?2, ?3)").map_err(|e| e.to_string())?;
+        let mut delete = tx.prepare("DELETE FROM message_labels WHERE folder = ?1 AND uid = ?2 AND label = ?3").map_err(|e| e.to_string())?;
+        for (uid, list) in keywords {
+            let wanted: HashSet<&str> = list.iter().filter_map(|k| by_keyword.get(k.to_lowercase().as_str()).copied()).collect();
+            let had = current.get(uid).map(Vec::as_slice).unwrap_or_default();
+            for label in had {
+                if synced.contains(&label.to_lowercase()) && !wanted.iter().any(|w| w.eq_ignore_ascii_case(label)) {
+                    changed |= delete.execute(rusqlite::params![folder, uid, label]).map_err(|e| e.to_string())? > 0;
+                }
+            }
+            for name in wanted {
+                if !had.iter().any(|l| l.eq_ignore_ascii_case(name)) {
+                    changed |= insert.execute(rusqlite::params![folder, uid, name]).map_err(|e| e.to_string())? > 0;
+                }
+            }
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(changed)
+}
+
+/// Whether any tag exists, so syncs can skip refreshing keywords when none could match.
+pub(crate) fn has_tags(app_handle: &AppHandle) -> bool {
+    let Ok(db_path) = get_db_path(app_handle) else { return false };
+    let Ok(conn) = Connection::open(db_path) else { return false };
+    conn.query_row("SELECT EXISTS (SELECT 1 FROM tags)", [], |row| row.get(0)).unwrap_or(false)
+}
+
+/// The UID set and FETCH items a sync uses to re-read keywords for tag changes made
+/// elsewhere, or `None` when there is nothing to re-read. With the mailbox's
+/// HIGHESTMODSEQ from a CONDSTORE server only messages changed since the last sync are
+/// returned; otherwise the set is bounded as `refresh_set` describes.
+pub(crate) fn keyword_refresh(app_handle: &AppHandle, folder: &str, last_uid: u32, highest_modseq: Option<u64>) -> Option<(String, String)> {
+    let conn = Connection::open(get_db_path(app_handle).ok()?).ok()?;
+    if let Some(modseq) = highest_modseq {
+        let mut seen = KEYWORD_MODSEQ.lock().unwrap();
+        let previous = seen.get(folder).copied();
+        if previous == Some(modseq) {
+            return None;
+        }
+        let set = refresh_set(&conn, folder, last_uid, true)?;
+        seen.insert(folder.to_string(), modseq);
+        let items = previous.map_or("(UID FLAGS)".to_string(), |p| format!("(UID FLAGS) (CHANGEDSINCE {})", p));
+        return Some((set, items));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut refreshed = FULL_KEYWORD_REFRESH.lock().unwrap();
+    let full = refreshed.get(folder).map_or(true, |at| now - at >= FULL_KEYWORD_REFRESH_SECS);
+    let set = refresh_set(&conn, folder, last_uid, full)?;
+    if full {
+        refreshed.insert(folder.to_string(), now);
+    }
+    Some((set, "(UID FLAGS)".to_string()))
+}
+
+/// The HIGHESTMODSEQ value of a `STATUS` response.
+pub(crate) fn parse_highest_modseq(response: &[u8]) -> Option<u64> {
+    let text = String::from_utf8_lossy(response).to_ascii_uppercase();
+    let rest = &text[text.find("HIGHESTMODSEQ ")? + "HIGHESTMODSEQ ".len()..];
+    rest.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+/// The UIDs to re-read without CONDSTORE: everything up to `last_uid` when `full`,
+/// otherwise the recent window plus older messages carrying tags. Tags added elsewhere to
+/// older untagged messages show up at the next full refresh.
+fn refresh_set(conn: &Connection, folder: &str, last_uid: u32, full: bool) -> Option<String> {
+    let has_tags: bool = conn.query_row("SELECT EXISTS (SELECT 1 FROM tags)", [], |row| row.get(0)).unwrap_or(false);
+    if !has_tags || last_uid == 0 {
+        return None;
+    }
+    let window_start = last_uid.saturating_sub(RECENT_KEYWORD_WINDOW) + 1;
+    if full || window_start == 1 {
+        return Some(format!("1:{}", last_uid));
+    }
+
+    let tagged: Vec<u32> = conn.prepare(
+        "SELECT DISTINCT l.uid FROM message_labels l JOIN tags t ON t.name = l.label COLLATE NOCASE WHERE l.folder = ?1 AND l.uid < ?2"
+    ).and_then(|mut stmt| stmt.query_map(rusqlite::params![folder, window_start], |row| row.get(0))?.collect())
+        .unwrap_or_default();
+    let window = format!("{}:{}", window_start, last_uid);
+    Some(if tagged.is_empty() { window } else { format!("{},{}", mailbox_actions::uid_set(&tagged), window) })
+}
+
+/// Loads a page of `folder` (or Starred) restricted to messages carrying `tag`, newest first.
+/// Starred spans folders, so its pages are anchored on `(date, folder, uid)`.
+pub fn load_tagged_page(app_handle: &AppHandle, folder: &str, tag: &str, before: Option<PageAnchor>, limit: u32) -> Result<Vec<MessageHeader>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    tagged_page(&conn, folder, tag, before.as_ref(), limit)
+}
+
+fn tagged_page(conn: &Connection, folder: &str, tag: &str, before: Option<&PageAnchor>, limit: u32) -> Result<Vec<MessageHeader>, String> {
+    let mut scope = format!(
+        "EXISTS (SELECT 1 FROM message_labels l WHERE l.folder = m.folder AND l.uid = m.uid AND l.label = ? COLLATE NOCASE) AND {}",
+        crate::mail::snooze::not_snoozed_sql("m")
+    );
+    let mut scope_params = vec![Value::Text(tag.trim().to_string())];
+    if folder == "starred" {
+        scope.push_str(" AND m.flagged = 1 AND ");
+        scope.push_str(&gmail_labels::unique_copy_sql("m"));
+    } else {
+        scope.push_str(" AND m.folder = ?");
+        scope_params.push(Value::Text(folder.to_string()));
+    }
+
+    let mut sql = format!(
+        "SELECT m.uid, m.uid_validity, m.subject, m.sender, m.date, m.seen, m.flagged, m.snippet, m.folder, m.has_attachments, m.thread_id, m.recipient, m.message_id, m.unsubscribe
+         FROM messages m
+         WHERE {}",
+        scope
+    );
+    let mut params = scope_params.clone();
+
+    if folder == "starred" {
+        if let Some(anchor) = before {
+            push_page_anchor(conn, &mut sql, &mut params, &scope, &scope_params, anchor)?;
+        }
+        sql.push_str(SPANNING_ORDER);
+    } else {
+        if let Some(anchor) = before {
+            sql.push_str(" AND m.uid < ?");
+            params.push(Value::Integer(anchor.uid as i64));
+        }
+        sql.push_str(" ORDER BY m.uid DESC");
+    }
+    sql.push_str(" LIMIT ?");
+    params.push(Value::Integer(limit as i64));
+
+    query_headers(conn, &sql, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (
+                folder TEXT NOT NULL, uid INTEGER NOT NULL, uid_validity INTEGER, subject TEXT, sender TEXT,
+                recipient TEXT, date INTEGER NOT NULL, snippet TEXT, seen INTEGER DEFAULT 0, flagged INTEGER DEFAULT 0,
+                has_attachments INTEGER DEFAULT 0, thread_id TEXT, message_id TEXT, unsubscribe TEXT, gm_msgid INTEGER,
+                PRIMARY KEY (folder, uid)
+            );"
+        ).unwrap();
+        gmail_labels::init_labels_table(&conn).unwrap();
+        crate::mail::snooze::init_snooze_table(&conn).unwrap();
+        init_tags_table(&conn).unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, folder: &str, uid: u32, date: i64, seen: bool, labels: &[&str]) {
+        conn.execute(
+            "INSERT INTO messages (folder, uid, uid_validity, subject, sender, date, seen, flagged) VALUES (?1, ?2, 1, 'Hi', 'a@example.com', ?3, ?4, 1)",
+            rusqlite::params![folder, uid, date, seen as i32],
+        ).unwrap();
+        for label in labels {
+            conn.execute("INSERT INTO message_labels (folder, uid, label) VALUES (?1, ?2, ?3)", rusqlite::params![folder, uid, label]).unwrap();
+        }
+    }
+
+    fn labels(conn: &Connection, uid: u32) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT label FROM message_labels WHERE folder = 'inbox' AND uid = ?1 ORDER BY label").unwrap();
+        let rows = stmt.query_map([uid], |row| row.get(0)).unwrap();
+        rows.map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_synced_keywords_replace_server_tags() {
+        let mut conn = test_db();
+        insert_tag(&conn, "Work", "Work", None).unwrap();
+        insert_tag(&conn, "Home", "$home", None).unwrap();
+        insert_tag(&conn, "Local", "Local", None).unwrap();
+        insert(&conn, "inbox", 1, 1_000, false, &["Work", "Local"]);
+        insert(&conn, "inbox", 2, 2_000, false, &[]);
+
+        let server = vec!["Work".to_string(), "$Home".to_string()];
+        let keywords = vec![(1, vec!["$HOME".to_string()]), (2, vec!["work".to_string(), "$Forwarded".to_string()])];
+        assert!(apply_synced_keywords(&mut conn, "inbox", &server, &keywords).unwrap());
+        // Local's keyword is unknown to the server, so the locally kept tag stays
+        assert_eq!(labels(&conn, 1), vec!["Home", "Local"]);
+        assert_eq!(labels(&conn, 2), vec!["Work"]);
+        assert!(!apply_synced_keywords(&mut conn, "inbox", &server, &keywords).unwrap());
+    }
+
+    #[test]
+    fn test_tag_counts_follow_rename() {
+        let mut conn = test_db();
+        insert_tag(&conn, "Work", "Work", None).unwrap();
+        insert(&conn, "inbox", 1, 1_000, false, &["Work"]);
+        insert(&conn, "inbox", 2, 2_000, true, &["work"]);
+        insert(&conn, "inbox", 3, 3_000, false, &[]);
+
+        let tags = tags_with_counts(&conn).unwrap();
+        assert_eq!((tags[0].name.as_str(), tags[0].total, tags[0].unread), ("Work", 2, 1));
+
+        apply_tag_update(&mut conn, "Work", Some("Office"), Some("#0a0")).unwrap();
+        let tags = tags_with_counts(&conn).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!((tags[0].name.as_str(), tags[0].keyword.as_str(), tags[0].color.as_deref()), ("Office", "Work", Some("#0a0")));
+        assert_eq!((tags[0].total, tags[0].unread), (2, 1));
+        assert_eq!(labels(&conn, 2), vec!["Office"]);
+    }
+
+    #[test]
+    fn test_tags_need_distinct_keywords_and_removal_creates_none() {
+        let conn = test_db();
+        assert_eq!(ensure_tags(&conn, &["Work!".to_string()], true).unwrap(), vec![("Work!".to_string(), "Work_".to_string())]);
+        assert!(ensure_tags(&conn, &["Work?".to_string()], true).is_err());
+        assert!(insert_tag(&conn, "work!", "Other", None).is_err());
+
+        assert!(ensure_tags(&conn, &["typo".to_string()], false).unwrap().is_empty());
+        assert!(find_tag(&conn, "typo").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_tagged_starred_pages_do_not_skip_equal_dates() {
+        let conn = test_db();
+        insert(&conn, "inbox", 5, 1_000, false, &["Work"]);
+        insert(&conn, "sent", 5, 1_000, false, &["Work"]);
+        insert(&conn, "inbox", 4, 1_000, false, &["Work"]);
+
+        let mut seen = Vec::new();
+        let mut before: Option<PageAnchor> = None;
+        while let Some(last) = tagged_page(&conn, "starred", "work", before.as_ref(), 1).unwrap().pop() {
+            seen.push((last.folder.clone(), last.uid));
+            before = Some(PageAnchor { uid: last.uid, folder: Some(last.folder) });
+        }
+        assert_eq!(seen, vec![("sent".to_string(), 5), ("inbox".to_string(), 5), ("inbox".to_string(), 4)]);
+    }
+
+    #[test]
+    fn test_validate_tag_fields() {
+        assert!(validate_color("#fa0").is_ok());
+        assert!(validate_color("#12ab9F").is_ok());
+        assert!(validate_color("red").is_err());
+        assert!(validate_color("#12345").is_err());
+
+        assert!(validate_keyword("$label1").is_ok());
+        assert!(validate_keyword("Work_2").is_ok());
+        assert!(validate_keyword("\\Seen").is_err());
+        assert!(validate_keyword("two words").is_err());
+    }
+
+    #[test]
+    fn test_refresh_set_is_bounded_between_full_refreshes() {
+        let conn = test_db();
+        assert_eq!(refresh_set(&conn, "inbox", 2_000, false), None);
+
+        insert_tag(&conn, "Work", "Work", None).unwrap();
+        insert(&conn, "inbox", 12, 1_000, true, &["Work"]);
+        insert(&conn, "inbox", 40, 1_000, true, &["Work"]);
+        insert(&conn, "inbox", 41, 1_000, true, &["Work"]);
+        insert(&conn, "inbox", 1_900, 1_000, true, &["Work"]);
+        insert(&conn, "sent", 7, 1_000, true, &["Work"]);
+
+        assert_eq!(refresh_set(&conn, "inbox", 2_000, false).as_deref(), Some("12,40:41,1501:2000"));
+        assert_eq!(refresh_set(&conn, "inbox", 2_000, true).as_deref(), Some("1:2000"));
+        assert_eq!(refresh_set(&conn, "inbox", 300, false).as_deref(), Some("1:300"));
+    }
+
+    #[test]
+    fn test_parse_highest_modseq() {
+        assert_eq!(parse_highest_modseq(b"* STATUS INBOX (HIGHESTMODSEQ 7011231777)\r\n"), Some(7011231777));
+        assert_eq!(parse_highest_modseq(b"* STATUS \"Spam\" (highestmodseq 12)\r\n"), Some(12));
+        assert_eq!(parse_highest_modseq(b"* STATUS INBOX ()\r\n"), None);
+    }
+}
@@ -0,0 +1,512 @@
+use crate::auth::account::Account;
+use crate::mail::database::{self, get_db_path};
+use crate::mail::imap_session::{execute_with_imap_session, execute_with_session, Session, SessionKind};
+use crate::mail::mailbox_actions::{self, BulkResult, FolderOutcome, MessageFlag, MoveResult, SpecialUse};
+use crate::mail::message_list::MessageHeader;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use tauri::AppHandle;
+
+const DEFAULT_WINDOW_SECS: i64 = 30;
+const MAX_WINDOW_SECS: i64 = 24 * 60 * 60;
+/// Older entries are pruned; only the newest is ever undoable anyway.
+const JOURNAL_LIMIT: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionKind {
+    Delete,
+    Archive,
+    Move,
+    MarkRead,
+    MarkUnread,
+    Star,
+    Unstar,
+}
+
+/// What undoing needs to know about one message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalMessage {
+    /// UID in the source folder before the action.
+    pub uid: u32,
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// UID in the destination mailbox after a move, from COPYUID or a Message-ID lookup.
+    #[serde(default)]
+    pub dest_uid: Option<u32>,
+    /// UIDVALIDITY of the destination mailbox `dest_uid` belongs to.
+    #[serde(default)]
+    pub dest_validity: Option<u32>,
+    /// Flag value before a mark action.
+    #[serde(default)]
+    pub previous: Option<bool>,
+}
+
+/// One folder's share of a journaled action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UndoStep {
+    /// Messages moved from `folder` (`source_mailbox` on the server) into `mailbox`.
+    #[serde(rename_all = "camelCase")]
+    Move { folder: String, source_mailbox: String, mailbox: String, messages: Vec<JournalMessage> },
+    /// Inbox messages whose Gmail Inbox label was removed.
+    GmailArchive { folder: String, messages: Vec<JournalMessage> },
+    /// A flag change; `on_server` is false when only the cache was updated.
+    #[serde(rename_all = "camelCase")]
+    Flag { folder: String, flag: MessageFlag, on_server: bool, messages: Vec<JournalMessage> },
+    /// A delete still waiting in the offline queue; replaced by its `Move` once replayed.
+    QueuedDelete { folder: String, messages: Vec<JournalMessage> },
+}
+
+impl UndoStep {
+    /// Keeps only the messages in `uids`, or drops the step when none remain.
+    pub(crate) fn retain_uids(mut self, uids: &[u32]) -> Option<UndoStep> {
+        let messages = match &mut self {
+            UndoStep::Move { messages, .. } | UndoStep::GmailArchive { messages, .. }
+            | UndoStep::Flag { messages, .. } | UndoStep::QueuedDelete { messages, .. } => messages,
+        };
+        messages.retain(|m| uids.contains(&m.uid));
+        (!messages.is_empty()).then_some(self)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoResult {
+    pub kind: ActionKind,
+    /// Where the messages are now, or why they could not be restored.
+    pub result: BulkResult,
+}
+
+pub fn init_undo_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS undo_journal (
+            id TEXT PRIMARY KEY,
+            kind TEXT NOT NULL,
+            steps TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            undone_at INTEGER
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn open(app_handle: &AppHandle) -> Result<Connection, String> {
+    let db_path = get_db_path(app_handle)?;
+    Connection::open(db_path).map_err(|e| e.to_string())
+}
+
+/// How long after an action `undo_last_action` may reverse it, in seconds.
+pub fn get_window_secs(app_handle: &AppHandle) -> i64 {
+    let Ok(conn) = open(app_handle) else { return DEFAULT_WINDOW_SECS };
+    conn.query_row("SELECT value FROM app_metadata WHERE key = 'undo_window_secs'", [], |row| row.get::<_, String>(0))
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_WINDOW_SECS)
+}
+
+pub fn set_window_secs(app_handle: &AppHandle, secs: i64) -> Result<(), String> {
+    if !(0..=MAX_WINDOW_SECS).contains(&secs) {
+        return Err(format!("Undo window must be between 0 and {} seconds", MAX_WINDOW_SECS));
+    }
+    let conn = open(app_handle)?;
+    conn.execute(
+        "INSERT INTO app_metadata (key, value) VALUES ('undo_window_secs', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![secs.to_string()],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Journals an action and returns its entry id, or `None` when nothing was changed.
+pub fn record(app_handle: &AppHandle, kind: ActionKind, steps: Vec<UndoStep>) -> Result<Option<String>, String> {
+    if steps.is_empty() {
+        return Ok(None);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    let kind_json = serde_json::to_string(&kind).map_err(|e| e.to_string())?;
+    let steps_json = serde_json::to_string(&steps).map_err(|e| e.to_string())?;
+
+    let conn = open(app_handle)?;
+    conn.execute(
+        "INSERT INTO undo_journal (id, kind, steps, created_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![id, kind_json, steps_json, chrono::Utc::now().timestamp()],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM undo_journal WHERE id NOT IN (SELECT id FROM undo_journal ORDER BY created_at DESC, rowid DESC LIMIT ?1)",
+        rusqlite::params![JOURNAL_LIMIT],
+    ).map_err(|e| e.to_string())?;
+    Ok(Some(id))
+}
+
+/// Journals an action for commands; a journal failure is logged rather than failing the action.
+pub fn journal(app_handle: &AppHandle, kind: ActionKind, steps: Vec<UndoStep>) -> Option<String> {
+    record(app_handle, kind, steps).unwrap_or_else(|e| {
+        log::warn!("Failed to journal {:?} for undo: {}", kind, e);
+        None
+    })
+}
+
+pub fn journal_bulk(app_handle: &AppHandle, kind: ActionKind, result: &mut BulkResult) {
+    result.undo_id = journal(app_handle, kind, std::mem::take(&mut result.undo));
+}
+
+pub fn journal_move(app_handle: &AppHandle, kind: ActionKind, result: &mut MoveResult) {
+    result.undo_id = journal(app_handle, kind, result.undo.take().into_iter().collect());
+}
+
+/// Rewrites an entry's steps, e.g. once a queued delete has been replayed on the server. The
+/// entry keeps its time, so the undo window still runs from the original action.
+pub(crate) fn replace_steps(app_handle: &AppHandle, id: &str, steps: Vec<UndoStep>) -> Result<(), String> {
+    let steps_json = serde_json::to_string(&steps).map_err(|e| e.to_string())?;
+    let conn = open(app_handle)?;
+    conn.execute("UPDATE undo_journal SET steps = ?1 WHERE id = ?2", rusqlite::params![steps_json, id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn entry_steps(app_handle: &AppHandle, id: &str) -> Result<Vec<UndoStep>, String> {
+    let conn = open(app_handle)?;
+    let steps: String = conn.query_row("SELECT steps FROM undo_journal WHERE id = ?1", rusqlite::params![id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&steps).map_err(|e| e.to_string())
+}
+
+/// Captures the current value of `flag` for `uids` before it is changed.
+pub fn flag_step(app_handle: &AppHandle, folder: &str, uids: &[u32], flag: MessageFlag, on_server: bool) -> Option<UndoStep> {
+    let headers = database::get_messages_by_uids(app_handle, folder, uids).ok()?;
+    let messages: Vec<JournalMessage> = headers.into_iter()
+        .map(|h| JournalMessage {
+            uid: h.uid,
+            previous: Some(match flag { MessageFlag::Seen => h.seen, MessageFlag::Flagged => h.flagged }),
+            message_id: h.message_id,
+            dest_uid: None,
+            dest_validity: None,
+        })
+        .collect();
+    (!messages.is_empty()).then(|| UndoStep::Flag { folder: folder.to_string(), flag, on_server, messages })
+}
+
+/// Takes the newest entry that has not been undone, marking it undone before anything touches
+/// the server so a second click cannot replay it. `release_claim` hands it back if every step fails.
+fn claim_last(app_handle: &AppHandle) -> Result<(String, ActionKind, Vec<UndoStep>), String> {
+    let conn = open(app_handle)?;
+    let entry: Option<(String, String, String, i64)> = conn.query_row(
+        "SELECT id, kind, steps, created_at FROM undo_journal WHERE undone_at IS NULL ORDER BY created_at DESC, rowid DESC LIMIT 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    ).optional().map_err(|e| e.to_string())?;
+    let Some((id, kind, steps, created_at)) = entry else {
+        return Err("Nothing to undo".to_string());
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if now - created_at > get_window_secs(app_handle) {
+        return Err("The last action can no longer be undone".to_string());
+    }
+    let kind: ActionKind = serde_json::from_str(&kind).map_err(|e| e.to_string())?;
+    let steps: Vec<UndoStep> = serde_json::from_str(&steps).map_err(|e| e.to_string())?;
+
+    let claimed = conn.execute(
+        "UPDATE undo_journal SET undone_at = ?1 WHERE id = ?2 AND undone_at IS NULL",
+        rusqlite::params![now, id],
+    ).map_err(|e| e.to_string())?;
+    if claimed == 0 {
+        return Err("Nothing to undo".to_string());
+    }
+    Ok((id, kind, steps))
+}
+
+/// Hands a claimed entry back when none of its steps reached the server, so it can be retried.
+fn release_claim(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let conn = open(app_handle)?;
+    conn.execute("UPDATE undo_journal SET undone_at = NULL WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Reverses the newest journaled action if it is still within the undo window.
+pub async fn undo_last(app_handle: &AppHandle, account: &Account) -> Result<UndoResult, String> {
+    let (id, kind, steps) = claim_last(app_handle)?;
+
+    let mut result = BulkResult::default();
+    let mut any_applied = false;
+    for step in steps {
+        let uids: Vec<u32> = match &step {
+            UndoStep::Move { messages, .. } | UndoStep::GmailArchive { messages, .. }
+            | UndoStep::Flag { messages, .. } | UndoStep::QueuedDelete { messages, .. } => messages.iter().map(|m| m.uid).collect(),
+        };
+        let (folder, outcome) = match step {
+            UndoStep::Move { folder, source_mailbox, mailbox, messages } => {
+                let outcome = undo_move(app_handle, account, &folder, &source_mailbox, &mailbox, messages).await;
+                (folder, outcome)
+            }
+            UndoStep::GmailArchive { folder, messages } => {
+                let outcome = undo_gmail_archive(app_handle, account, &folder, messages).await;
+                (folder, outcome)
+            }
+            UndoStep::Flag { folder, flag, on_server, messages } => {
+                let outcome = undo_flag(app_handle, &folder, flag, on_server, messages);
+                (folder, outcome)
+            }
+            UndoStep::QueuedDelete { folder, messages } => {
+                let outcome = undo_queued_delete(app_handle, account, &id, &folder, messages).await;
+                (folder, outcome)
+            }
+        };
+        any_applied |= outcome.is_ok();
+        result.record(&folder, outcome.unwrap_or_else(|e| FolderOutcome::all_failed(&uids, &e)));
+    }
+    if !any_applied {
+        if let Err(e) = release_claim(app_handle, &id) {
+            log::warn!("Failed to release undo entry {}: {}", id, e);
+        }
+    }
+
+    crate::tray_state::refresh_unread_count_from_db(app_handle);
+    Ok(UndoResult { kind, result })
+}
+
+fn find_by_message_id(session: &mut Session, message_id: &str) -> Result<Option<u32>, String> {
+    let uids = session.uid_search(format!("HEADER Message-ID {}", mailbox_actions::imap_quote(message_id)))
+        .map_err(|e| format!("IMAP Search Error: {}", e))?;
+    Ok(uids.into_iter().max())
+}
+
+/// Fetches list headers for messages restored into `mailbox`, so they can be cached
+/// without the next sync treating them as new mail.
+fn fetch_restored(session: &mut Session, mailbox: &str, folder: &str, uids: &[u32]) -> Result<Vec<MessageHeader>, String> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let selected = session.examine(mailbox).map_err(|e| format!("IMAP Examine Error: {}", e))?;
+    let validity = selected.uid_validity.unwrap_or(0);
+    let fetches = session.uid_fetch(mailbox_actions::uid_set(uids), "(UID FLAGS BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE TO CC REPLY-TO MESSAGE-ID)])")
+        .map_err(|e| format!("IMAP Fetch Error: {}", e))?;
+    Ok(fetches.iter().filter_map(|f| crate::mail::sync::parse_header_to_message(f, validity, folder)).collect())
+}
+
+/// Caches restored messages and, when they are the next UIDs the folder's sync would fetch,
+/// advances its sync state past them so they are not announced as new mail.
+fn cache_restored(app_handle: &AppHandle, folder: &str, headers: &[MessageHeader]) -> Result<(), String> {
+    database::insert_or_update_messages(app_handle, headers)?;
+    let (Some(first), Some(last)) = (headers.iter().map(|h| h.uid).min(), headers.iter().map(|h| h.uid).max()) else {
+        return Ok(());
+    };
+    if let Some(mut state) = database::get_folder_sync_state(app_handle, folder)? {
+        if state.last_uid + 1 == first {
+            state.last_uid = last;
+            database::update_folder_sync_state(app_handle, &state)?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves messages back from `mailbox` to where they came from.
+async fn undo_move(app_handle: &AppHandle, account: &Account, folder: &str, source_mailbox: &str, mailbox: &str, messages: Vec<JournalMessage>) -> Result<FolderOutcome, String> {
+    let source = source_mailbox.to_string();
+    let dest = mailbox.to_string();
+    let local_folder = folder.to_string();
+
+    let (restored, lookup_failed, headers, by_dest) = execute_with_session(account, SessionKind::Sync, move |session| {
+        let selected = session.select(&dest).map_err(|e| format!("IMAP Select Error: {}", e))?;
+        let validity = selected.uid_validity.filter(|v| *v != 0);
+
+        // A journaled UID is only trusted while the mailbox keeps its UIDVALIDITY; otherwise
+        // the message is looked up again by Message-ID
+        let mut by_dest: HashMap<u32, u32> = HashMap::new();
+        let mut ids: HashMap<u32, String> = HashMap::new();
+        let mut lookup_failed = Vec::new();
+        for m in &messages {
+            let found = match (m.dest_uid, &m.message_id) {
+                (Some(dest_uid), _) if validity.is_some() && m.dest_validity == validity => Some(dest_uid),
+                (_, Some(id)) => find_by_message_id(session, id)?,
+                (_, None) => None,
+            };
+            match found {
+                Some(dest_uid) => {
+                    by_dest.insert(dest_uid, m.uid);
+                    if let Some(id) = &m.message_id {
+                        ids.insert(dest_uid, id.clone());
+                    }
+                }
+                None => lookup_failed.push((m.uid, format!("Message could not be found in {}", dest))),
+            }
+        }
+
+        let dest_uids: Vec<u32> = by_dest.keys().copied().collect();
+        let restored = mailbox_actions::move_selected(session, &dest_uids, &source, &ids)?;
+        let new_uids: Vec<u32> = restored.dests.values().map(|(_, uid)| *uid).collect();
+        let headers = fetch_restored(session, &source, &local_folder, &new_uids)?;
+        Ok((restored, lookup_failed, headers, by_dest))
+    }).await?;
+
+    // Rows that followed the message into a synced folder move back with their cached bodies
+    if let Some(dest_folder) = mailbox_actions::local_folder_for(mailbox, &account.provider) {
+        let moves: Vec<(u32, Option<(&str, u32, u32)>)> = restored.done.iter()
+            .map(|dest_uid| (*dest_uid, restored.dests.get(dest_uid).map(|(validity, uid)| (folder, *uid, *validity))))
+            .collect();
+        database::move_messages_local(app_handle, &dest_folder.to_string(), &moves)?;
+    }
+    cache_restored(app_handle, folder, &headers)?;
+
+    let mut outcome = FolderOutcome { failed: lookup_failed, ..Default::default() };
+    for dest_uid in restored.done {
+        match restored.dests.get(&dest_uid) {
+            Some((_, uid)) => outcome.done.push(*uid),
+            // Restored, but the new UID is unknown until the next sync picks it up
+            None => outcome.failed.push((by_dest[&dest_uid], "Restored; it will reappear after the next sync".to_string())),
+        }
+    }
+    outcome.failed.extend(restored.failed.into_iter().map(|(dest_uid, e)| (by_dest[&dest_uid], e)));
+    Ok(outcome)
+}
+
+/// Puts Gmail's Inbox label back on archived messages, found in All Mail by Message-ID.
+async fn undo_gmail_archive(app_handle: &AppHandle, account: &Account, folder: &str, messages: Vec<JournalMessage>) -> Result<FolderOutcome, String> {
+    let provider = account.provider.clone();
+    let local_folder = folder.to_string();
+
+    let (all_mail, mut outcome, found) = execute_with_session(account, SessionKind::Sync, move |session| {
+        let all_mail = mailbox_actions::find_special_use(session, SpecialUse::All, &provider);
+        session.examine(&all_mail).map_err(|e| format!("IMAP Examine Error: {}", e))?;
+
+        let mut outcome = FolderOutcome::default();
+        let mut found = Vec::new();
+        for m in &messages {
+            match &m.message_id {
+                Some(id) => match find_by_message_id(session, id)? {
+                    Some(all_uid) => found.push((m.uid, id.clone(), all_uid)),
+                    None => outcome.failed.push((m.uid, format!("Message could not be found in {}", all_mail))),
+                },
+                None => outcome.failed.push((m.uid, "Message has no Message-ID to find it by".to_string())),
+            }
+        }
+        Ok((all_mail, outcome, found))
+    }).await?;
+
+    let all_uids: Vec<u32> = found.iter().map(|(_, _, u)| *u).collect();
+    let labeled = if all_uids.is_empty() {
+        FolderOutcome::default()
+    } else {
+        execute_with_imap_session(account, SessionKind::Sync, move |imap| {
+            imap.session.select(&all_mail).map_err(|e| format!("IMAP Select Error: {}", e))?;
+            crate::mail::gmail_labels::store_labels(imap, &all_uids, &["\\Inbox".to_string()], true)
+        }).await?
+    };
+
+    let mut restored = Vec::new();
+    for (uid, id, all_uid) in found {
+        match labeled.failed.iter().find(|(u, _)| *u == all_uid) {
+            Some((_, e)) => outcome.failed.push((uid, e.clone())),
+            None => restored.push((uid, id)),
+        }
+    }
+
+    let (inbox_uids, missing, headers) = execute_with_session(account, SessionKind::Sync, move |session| {
+        session.examine("INBOX").map_err(|e| format!("IMAP Examine Error: {}", e))?;
+        let mut inbox_uids = Vec::new();
+        let mut missing = Vec::new();
+        for (uid, id) in &restored {
+            match find_by_message_id(session, id)? {
+                Some(inbox_uid) => inbox_uids.push(inbox_uid),
+                None => missing.push(*uid),
+            }
+        }
+        let headers = fetch_restored(session, "INBOX", &local_folder, &inbox_uids)?;
+        Ok((inbox_uids, missing, headers))
+    }).await?;
+    outcome.failed.extend(missing.into_iter().map(|uid| (uid, "Restored; it will reappear after the next sync".to_string())));
+    outcome.done = inbox_uids;
+
+    cache_restored(app_handle, folder, &headers)?;
+    let restored_uids: Vec<u32> = headers.iter().map(|h| h.uid).collect();
+    if let Err(e) = crate::mail::gmail_labels::sync_labels(app_handle, account, folder, &restored_uids).await {
+        log::warn!("Failed to fetch labels for restored messages: {}", e);
+    }
+    Ok(outcome)
+}
+
+/// Cancels deletes that are still queued. Ones that reached the server meanwhile have had
+/// their step replaced by the real move, which is undone instead.
+async fn undo_queued_delete(app_handle: &AppHandle, account: &Account, id: &str, folder: &str, messages: Vec<JournalMessage>) -> Result<FolderOutcome, String> {
+    let mut outcome = FolderOutcome::default();
+    let mut replayed = Vec::new();
+    for m in messages {
+        if crate::mail::pending_ops::cancel_delete(app_handle, folder, m.uid).await? {
+            outcome.done.push(m.uid);
+        } else {
+            replayed.push(m.uid);
+        }
+    }
+    if replayed.is_empty() {
+        return Ok(outcome);
+    }
+
+    for step in entry_steps(app_handle, id)? {
+        let Some(UndoStep::Move { folder, source_mailbox, mailbox, messages }) = step.retain_uids(&replayed) else { continue };
+        replayed.retain(|uid| !messages.iter().any(|m| m.uid == *uid));
+        let moved = undo_move(app_handle, account, &folder, &source_mailbox, &mailbox, messages).await?;
+        outcome.done.extend(moved.done);
+        outcome.failed.extend(moved.failed);
+    }
+    outcome.failed.extend(replayed.into_iter().map(|uid| (uid, "The delete could not be cancelled".to_string())));
+    Ok(outcome)
+}
+
+/// Restores each message's previous flag value in the cache and queues the same change for
+/// the server, so it cannot overtake a queued change it is reversing.
+fn undo_flag(app_handle: &AppHandle, folder: &str, flag: MessageFlag, on_server: bool, messages: Vec<JournalMessage>) -> Result<FolderOutcome, String> {
+    let (was_set, was_clear): (Vec<&JournalMessage>, Vec<&JournalMessage>) = messages.iter().partition(|m| m.previous.unwrap_or(false));
+    let uids = |list: &[&JournalMessage]| list.iter().map(|m| m.uid).collect::<Vec<u32>>();
+    flag.save_local(app_handle, folder, &uids(&was_set), true)?;
+    flag.save_local(app_handle, folder, &uids(&was_clear), false)?;
+
+    let mut outcome = FolderOutcome::default();
+    for m in &messages {
+        let previous = m.previous.unwrap_or(false);
+        let queued = if on_server {
+            crate::mail::pending_ops::enqueue(app_handle, folder, m.uid, flag.into(), previous, None).map(|_| ())
+        } else {
+            Ok(())
+        };
+        match queued {
+            Ok(()) => outcome.done.push(m.uid),
+            Err(e) => outcome.failed.push((m.uid, format!("Restored locally but not queued for the server: {}", e))),
+        }
+    }
+    if on_server {
+        crate::mail::pending_ops::wake();
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mail::folder::MailFolder;
+
+    #[test]
+    fn test_step_round_trip_and_retain() {
+        let step = UndoStep::Flag {
+            folder: MailFolder::Inbox.to_string(),
+            flag: MessageFlag::Seen,
+            on_server: true,
+            messages: vec![
+                JournalMessage { uid: 4, message_id: None, dest_uid: None, dest_validity: None, previous: Some(false) },
+                JournalMessage { uid: 9, message_id: None, dest_uid: None, dest_validity: None, previous: Some(true) },
+            ],
+        };
+        let json = serde_json::to_string(&step).unwrap();
+        assert!(json.contains("\"type\":\"flag\"") && json.contains("\"onServer\":true"));
+
+        let parsed: UndoStep = serde_json::from_str(&json).unwrap();
+        match parsed.clone().retain_uids(&[9]) {
+            Some(UndoStep::Flag { messages, .. }) => assert_eq!(messages.iter().map(|m| m.uid).collect::<Vec<_>>(), vec![9]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(parsed.retain_uids(&[1]).is_none());
+    }
+}
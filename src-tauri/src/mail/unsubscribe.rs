@@ -0,0 +1,321 @@
+use crate::auth::account::Account;
+use crate::mail::auth_results::AuthStatus;
+use crate::mail::database::{self, get_db_path};
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+use tauri::AppHandle;
+
+/// The unsubscribe options a message advertises in `List-Unsubscribe` (RFC 2369).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unsubscribe {
+    /// Web link; POSTed to when `one_click` is set, otherwise only a page to open.
+    pub url: Option<String>,
+    pub mailto: Option<String>,
+    /// The sender supports RFC 8058 one-click unsubscribe at `url`.
+    pub one_click: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnsubscribeMethod {
+    OneClick,
+    Mailto,
+    /// Only a web page was offered, which the user has to open themselves.
+    Browser,
+}
+
+impl UnsubscribeMethod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UnsubscribeMethod::OneClick => "oneClick",
+            UnsubscribeMethod::Mailto => "mailto",
+            UnsubscribeMethod::Browser => "browser",
+        }
+    }
+
+    fn from_db(value: &str) -> UnsubscribeMethod {
+        match value {
+            "oneClick" => UnsubscribeMethod::OneClick,
+            "mailto" => UnsubscribeMethod::Mailto,
+            _ => UnsubscribeMethod::Browser,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsubscribeOutcome {
+    pub sender: String,
+    pub method: UnsubscribeMethod,
+    /// The URL posted to, the address mailed, or the page to open.
+    pub target: String,
+    pub succeeded: bool,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+pub fn init_unsubscribe_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS unsubscribes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            sender TEXT NOT NULL,
+            method TEXT NOT NULL,
+            target TEXT NOT NULL,
+            succeeded INTEGER NOT NULL,
+            error TEXT,
+            created_at INTEGER NOT NULL
+        )",
+        (),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Parses `List-Unsubscribe`, keeping the first mailto URI and the first web URI, preferring
+/// https over http. One-click needs
+/// `List-Unsubscribe-Post: List-Unsubscribe=One-Click` and an https link (RFC 8058).
+pub fn parse_list_unsubscribe(value: &str, post: Option<&str>) -> Option<Unsubscribe> {
+    let mut url = None;
+    let mut mailto = None;
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start + 1..].find('>') else { break };
+        // Folded headers can leave whitespace inside the brackets
+        let uri: String = rest[start + 1..start + 1 + len].split_whitespace().collect();
+        rest = &rest[start + 2 + len..];
+
+        let lower = uri.to_ascii_lowercase();
+        if lower.starts_with("mailto:") {
+            mailto = mailto.or(Some(uri));
+        } else if lower.starts_with("https://") {
+            if !url.as_deref().is_some_and(is_https) {
+                url = Some(uri);
+            }
+        } else if lower.starts_with("http://") {
+            url = url.or(Some(uri));
+        }
+    }
+    if url.is_none() && mailto.is_none() {
+        return None;
+    }
+
+    let one_click = url.as_deref().is_some_and(is_https)
+        && post.is_some_and(|p| p.split_whitespace().collect::<String>().eq_ignore_ascii_case("List-Unsubscribe=One-Click"));
+    Some(Unsubscribe { url, mailto, one_click })
+}
+
+fn is_https(url: &str) -> bool {
+    url.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))
+}
+
+pub(crate) fn from_column(value: Option<String>) -> Option<Unsubscribe> {
+    value.and_then(|json| serde_json::from_str(&json).ok())
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// A `mailto:` URI split into recipients, subject and body (RFC 6068).
+#[derive(Debug, PartialEq, Eq)]
+struct MailtoRequest {
+    to: Vec<String>,
+    subject: Option<String>,
+    body: Option<String>,
+}
+
+fn parse_mailto(uri: &str) -> Option<MailtoRequest> {
+    let rest = uri.get(..7).filter(|s| s.eq_ignore_ascii_case("mailto:")).map(|_| &uri[7..])?;
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let mut request = MailtoRequest { to: Vec::new(), subject: None, body: None };
+    request.to.extend(path.split(',').map(percent_decode));
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value);
+        match key.to_ascii_lowercase().as_str() {
+            "to" => request.to.extend(value.split(',').map(str::to_string)),
+            "subject" => request.subject = Some(value),
+            "body" => request.body = Some(value),
+            _ => {}
+        }
+    }
+    request.to = request.to.into_iter().map(|a| a.trim().to_string()).filter(|a| a.contains('@')).collect();
+    (!request.to.is_empty()).then_some(request)
+}
+
+/// One-click is only trusted when a valid DKIM signature covers both list headers (RFC 8058 §4),
+/// so a forged header cannot make us POST to an arbitrary URL.
+async fn check_one_click_signed(account: &Account, folder: &str, uid: u32) -> Result<(), String> {
+    let raw = crate::mail::raw_message::fetch_raw_message(account, folder, uid).await?;
+    let verdict = crate::mail::dkim::verify_dkim_covering(
+        &raw,
+        &crate::mail::dkim::SystemKeyResolver,
+        &["list-unsubscribe", "list-unsubscribe-post"],
+    ).await;
+    if verdict.status != AuthStatus::Pass {
+        return Err(verdict.detail.unwrap_or_else(|| "List headers are not DKIM signed".to_string()));
+    }
+    Ok(())
+}
+
+/// Sends the RFC 8058 one-click POST. No cookies or credentials go with it, and redirects
+/// are not followed.
+async fn post_one_click(url: &str) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(15))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| format!("HTTP client error: {}", e))?;
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .map_err(|e| format!("Unsubscribe request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Unsubscribe request failed with HTTP {}", response.status()));
+    }
+    Ok(())
+}
+
+async fn send_mailto(app_handle: &AppHandle, account: &mut Account, uri: &str) -> Result<(), String> {
+    let request = parse_mailto(uri).ok_or_else(|| format!("Invalid unsubscribe address: {}", uri))?;
+    let subject = request.subject.filter(|s| !s.trim().is_empty()).unwrap_or_else(|| "unsubscribe".to_string());
+    let body = request.body.unwrap_or_else(|| "unsubscribe".to_string());
+    crate::mail::smtp_client::send_email(app_handle, account, request.to, Vec::new(), Vec::new(), None, &subject, &body, &body, Vec::new())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn record(app_handle: &AppHandle, folder: &str, uid: u32, outcome: &UnsubscribeOutcome) -> Result<(), String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO unsubscribes (folder, uid, sender, method, target, succeeded, error, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![folder, uid, outcome.sender, outcome.method.as_str(), outcome.target, outcome.succeeded, outcome.error, outcome.created_at],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Unsubscribes from the list a message came from: one-click POST when offered and signed, falling
+/// back to the mailto address. A sender with only a web page gets a `Browser` outcome with
+/// the link to open. Every attempt is recorded.
+pub async fn unsubscribe(app_handle: &AppHandle, mut account: Account, folder: &str, uid: u32) -> Result<UnsubscribeOutcome, String> {
+    let header = database::get_messages_by_uids(app_handle, folder, &[uid])?
+        .pop()
+        .ok_or_else(|| "Message not found".to_string())?;
+    let options = header.unsubscribe.ok_or_else(|| "This message has no unsubscribe option".to_string())?;
+
+    let one_click = match options.url.as_ref().filter(|_| options.one_click) {
+        Some(url) => match check_one_click_signed(&account, folder, uid).await {
+            Ok(()) => Some(post_one_click(url).await),
+            Err(e) => {
+                log::warn!("Not using one-click unsubscribe for UID {}: {}", uid, e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let (method, target, error) = match (one_click, &options.mailto, &options.url) {
+        (Some(Ok(())), _, Some(url)) => (UnsubscribeMethod::OneClick, url.clone(), None),
+        (_, Some(mailto), _) => (UnsubscribeMethod::Mailto, mailto.clone(), send_mailto(app_handle, &mut account, mailto).await.err()),
+        (Some(Err(e)), None, Some(url)) => (UnsubscribeMethod::OneClick, url.clone(), Some(e)),
+        (None, None, Some(url)) => (UnsubscribeMethod::Browser, url.clone(), Some("Open the link to finish unsubscribing".to_string())),
+        _ => return Err("This message has no unsubscribe option".to_string()),
+    };
+
+    let outcome = UnsubscribeOutcome {
+        sender: header.from,
+        method,
+        target,
+        succeeded: error.is_none(),
+        error,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    if let Err(e) = record(app_handle, folder, uid, &outcome) {
+        log::warn!("Failed to record unsubscribe from {}: {}", outcome.sender, e);
+    }
+    Ok(outcome)
+}
+
+/// Recent unsubscribe attempts, newest first.
+pub fn list_unsubscribes(app_handle: &AppHandle, limit: u32) -> Result<Vec<UnsubscribeOutcome>, String> {
+    let db_path = get_db_path(app_handle)?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT sender, method, target, succeeded, error, created_at FROM unsubscribes ORDER BY created_at DESC, id DESC LIMIT ?1"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([limit], |row| {
+        Ok(UnsubscribeOutcome {
+            sender: row.get(0)?,
+            method: UnsubscribeMethod::from_db(&row.get::<_, String>(1)?),
+            target: row.get(2)?,
+            succeeded: row.get(3)?,
+            error: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    }).map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_unsubscribe() {
+        let parsed = parse_list_unsubscribe(
+            "<mailto:leave@lists.example.com?subject=unsubscribe>, <https://example.com/unsub?id=42>",
+            Some("List-Unsubscribe=One-Click"),
+        ).unwrap();
+        assert_eq!(parsed.url.as_deref(), Some("https://example.com/unsub?id=42"));
+        assert_eq!(parsed.mailto.as_deref(), Some("mailto:leave@lists.example.com?subject=unsubscribe"));
+        assert!(parsed.one_click);
+
+        let no_post = parse_list_unsubscribe("<https://example.com/unsub>", None).unwrap();
+        assert!(!no_post.one_click);
+        let plain_http = parse_list_unsubscribe("<http://example.com/unsub>", Some("List-Unsubscribe=One-Click")).unwrap();
+        assert!(!plain_http.one_click);
+        let mixed = parse_list_unsubscribe("<http://example.com/a>, <HTTPS://example.com/b>, <https://example.com/c>", None).unwrap();
+        assert_eq!(mixed.url.as_deref(), Some("HTTPS://example.com/b"));
+        let folded = parse_list_unsubscribe("<https://example.com/\r\n a>", None).unwrap();
+        assert_eq!(folded.url.as_deref(), Some("https://example.com/a"));
+        assert_eq!(parse_list_unsubscribe("<ftp://example.com>", None), None);
+        assert_eq!(parse_list_unsubscribe("", None), None);
+    }
+
+    #[test]
+    fn test_parse_mailto() {
+        assert_eq!(
+            parse_mailto("mailto:leave+123@example.com?subject=Remove%20me&body=stop"),
+            Some(MailtoRequest { to: vec!["leave+123@example.com".to_string()], subject: Some("Remove me".to_string()), body: Some("stop".to_string()) }),
+        );
+        assert_eq!(
+            parse_mailto("MAILTO:a%40example.com?to=b@example.com").map(|r| r.to),
+            Some(vec!["a@example.com".to_string(), "b@example.com".to_string()]),
+        );
+        assert_eq!(parse_mailto("mailto:?subject=x"), None);
+        assert_eq!(parse_mailto("https://example.com"), None);
+    }
+}